@@ -2,8 +2,16 @@
 Description=Bifrost Bridge
 After=network.target
 
+# Optional: let systemd open port 80 + 443 for bifrost (see bifrost.socket.ex)
+#Requires=bifrost.socket
+#After=bifrost.socket
+
 [Service]
-Type=simple
+# Bifrost tells systemd when it is ready to serve requests
+Type=notify
+
+# If bifrost stops responding for this long, systemd will restart it
+WatchdogSec=60s
 
 # Make it possible for unprivileged processes to bind to low ports (< 1024)
 # This is needed to run port 80 + 443 without being root.
@@ -0,0 +1,16 @@
+[Unit]
+Description=Bifrost Bridge sockets
+
+# With socket activation, systemd opens the listening sockets and hands them
+# to bifrost on startup. This makes it possible to use port 80 + 443 without
+# root, or any extra capabilities, and keeps the ports open while bifrost is
+# restarting.
+#
+# The ports must match http_port and https_port in the bifrost config.
+[Socket]
+ListenStream=80
+ListenStream=443
+Service=bifrost.service
+
+[Install]
+WantedBy=sockets.target
@@ -6,18 +6,26 @@ use mac_address::MacAddress;
 use p256::pkcs8::EncodePrivateKey;
 use rand_core::OsRng;
 
-use bifrost::{error::ApiResult, server::certificate};
+use bifrost::error::ApiResult;
+use bifrost::server::certificate::{self, BridgeId};
 
 #[derive(Debug, Parser)]
 struct Cli {
     mac: MacAddress,
+    /// Bridge id to use, instead of deriving it from the mac address
+    #[arg(long)]
+    bridge_id: Option<BridgeId>,
 }
 
 fn main() -> ApiResult<()> {
     let args = Cli::parse();
 
     let secret_key = p256::SecretKey::random(&mut OsRng);
-    let cert = certificate::generate(&secret_key, args.mac)?;
+    let cert = certificate::generate(
+        &secret_key,
+        args.bridge_id
+            .unwrap_or_else(|| BridgeId::from_mac(args.mac)),
+    )?;
 
     let mut out = stdout().lock();
 
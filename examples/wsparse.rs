@@ -44,6 +44,12 @@ async fn main() -> ApiResult<()> {
             Message::BridgeEvent(ref obj) => {
                 println!("{obj:#?}");
             },
+            Message::BridgeNetworkMap(ref obj) => {
+                println!("{obj:#?}");
+            },
+            Message::BridgeInstallCode(ref obj) => {
+                println!("{obj:#?}");
+            },
         }
     }
 
@@ -1,46 +1,596 @@
-use std::{collections::HashMap, net::Ipv4Addr};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::Ipv4Addr;
 
 use camino::{Utf8Path, Utf8PathBuf};
 use config::{Config, ConfigError};
+use log::LevelFilter;
 use mac_address::MacAddress;
 use serde::{Deserialize, Serialize};
 
-use crate::hue::api::RoomArchetype;
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{RType, RoomArchetype, SceneAction};
+use crate::hue::best_guess_timezone;
+use crate::hue::locale::Locale;
+use crate::model::units;
+use crate::server::certificate::BridgeId;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BridgeConfig {
     pub name: String,
     pub mac: MacAddress,
+    /* bridge id to use instead of the one derived from the mac address */
+    pub bridge_id: Option<BridgeId>,
     pub ipaddress: Ipv4Addr,
+    /* network interface to follow the address of, instead of ipaddress */
+    pub interface: Option<String>,
     pub http_port: u16,
     pub https_port: u16,
+    /* set to false to disable the http listener */
+    #[serde(default = "BridgeConfig::default_enabled")]
+    pub http_enabled: bool,
+    /* set to false to disable the https listener (e.g., behind a reverse proxy) */
+    #[serde(default = "BridgeConfig::default_enabled")]
+    pub https_enabled: bool,
+    /* ports to use instead, if http_port/https_port cannot be used */
+    pub http_port_fallback: Option<u16>,
+    pub https_port_fallback: Option<u16>,
     pub netmask: Ipv4Addr,
     pub gateway: Ipv4Addr,
+    #[serde(default = "best_guess_timezone")]
     pub timezone: String,
+    /* language of the names given to generated resources */
+    #[serde(default)]
+    pub locale: Locale,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub http_limits: HttpLimitsConfig,
+    /* answer ssdp searches (needed for discovery by echo devices) */
+    #[serde(default)]
+    pub ssdp: bool,
+    /* where the bridge is, for sunrise and sunset times */
+    pub location: Option<LocationConfig>,
+}
+
+impl BridgeConfig {
+    const fn default_enabled() -> bool {
+        true
+    }
+
+    /// The bridge id reported to clients (and used in the certificate)
+    #[must_use]
+    pub fn id(&self) -> BridgeId {
+        self.bridge_id
+            .unwrap_or_else(|| BridgeId::from_mac(self.mac))
+    }
+}
+
+/// Location of the bridge, for computing sunrise and sunset. The offsets
+/// (in minutes) shift when the daylight sensor reports daylight.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocationConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub sunrise_offset: i32,
+    #[serde(default)]
+    pub sunset_offset: i32,
+}
+
+/// Resource limits of the emulated bridge. The defaults match a real hue
+/// bridge, which is what most clients expect.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub scenes_per_group: u32,
+    pub rules: u32,
+    pub entertainment_channels: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            scenes_per_group: 200,
+            rules: 255,
+            entertainment_channels: 20,
+        }
+    }
+}
+
+/// Limits protecting the http and https listeners from misbehaving (or very
+/// slow) clients. All timeouts are in seconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpLimitsConfig {
+    /* largest accepted request body, in bytes */
+    pub max_body_size: usize,
+    /* time allowed for handling a request, including reading its body */
+    pub request_timeout: u64,
+    /* time allowed for a client to send the request headers */
+    pub header_timeout: u64,
+    /* time allowed for a client to complete the tls handshake */
+    pub handshake_timeout: u64,
+}
+
+impl Default for HttpLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_size: 2 * 1024 * 1024,
+            request_timeout: 30,
+            header_timeout: 10,
+            handshake_timeout: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateFileMode {
+    /* Always write state file, even if it was changed by someone else */
+    #[default]
+    Overwrite,
+    /* Reload state file, when changed by someone else */
+    Reload,
+    /* Never overwrite a state file that was changed by someone else */
+    Locked,
+}
+
+/// What an application key is allowed to do. Each scope includes the ones
+/// before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyScope {
+    /* read resources, and follow the event stream */
+    Read,
+    /* also update resources (e.g., switch lights, recall scenes) */
+    Control,
+    /* also create and delete resources */
+    #[default]
+    Admin,
+}
+
+/// Scopes of application keys (the "username" of the v1 api, and the
+/// hue-application-key header of the v2 api)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionsConfig {
+    /* scope of paired applications not listed below (and, while the
+     * whitelist is not enforced, of any key, or no key) */
+    pub default: KeyScope,
+    pub keys: HashMap<String, KeyScope>,
+}
+
+impl PermissionsConfig {
+    /// The scope of `key`, or None if the key is not allowed at all.
+    ///
+    /// Keys listed in the config always have their listed scope. Keys of
+    /// paired applications (see [`LinkButtonConfig`]) have the default
+    /// scope. Once the whitelist is `enforced`, any other key is refused.
+    #[must_use]
+    pub fn scope(&self, key: Option<&str>, paired: bool, enforced: bool) -> Option<KeyScope> {
+        match key.and_then(|key| self.keys.get(key)) {
+            Some(scope) => Some(*scope),
+            None if paired || !enforced => Some(self.default),
+            None => None,
+        }
+    }
+}
+
+/// Pairing new applications (POST /api). Like on a real bridge, this can
+/// require pressing the link button first, which is done through the admin
+/// api, or happens automatically for a while after startup.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkButtonConfig {
+    /* without this, the link button is always pressed (and keys of
+     * unpaired applications are accepted, as long as none are paired) */
+    pub required: bool,
+    /* seconds after startup during which the button counts as pressed */
+    pub startup_window: u64,
+    /* seconds the button counts as pressed, after pressing it */
+    pub press_duration: u64,
+}
+
+impl Default for LinkButtonConfig {
+    fn default() -> Self {
+        Self {
+            required: true,
+            startup_window: 300,
+            press_duration: 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcmeChallenge {
+    /* Serve the challenge response on the http port */
+    #[default]
+    Http01,
+    /* Publish the challenge response as a DNS TXT record, using `dns_hook` */
+    Dns01,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AcmeConfig {
+    pub hostname: String,
+    pub email: Option<String>,
+    #[serde(default = "AcmeConfig::default_directory")]
+    pub directory: String,
+    #[serde(default)]
+    pub challenge: AcmeChallenge,
+    pub dns_hook: Option<Utf8PathBuf>,
+    #[serde(default = "AcmeConfig::default_dns_delay")]
+    pub dns_delay: u64,
+    #[serde(default = "AcmeConfig::default_cert_file")]
+    pub cert_file: Utf8PathBuf,
+    #[serde(default = "AcmeConfig::default_account_file")]
+    pub account_file: Utf8PathBuf,
+    #[serde(default = "AcmeConfig::default_renew_days")]
+    pub renew_days: u64,
+}
+
+impl AcmeConfig {
+    fn default_directory() -> String {
+        "https://acme-v02.api.letsencrypt.org/directory".to_string()
+    }
+
+    const fn default_dns_delay() -> u64 {
+        30
+    }
+
+    fn default_cert_file() -> Utf8PathBuf {
+        "acme-cert.pem".into()
+    }
+
+    fn default_account_file() -> Utf8PathBuf {
+        "acme-account.json".into()
+    }
+
+    const fn default_renew_days() -> u64 {
+        30
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BifrostConfig {
     pub state_file: Utf8PathBuf,
+    #[serde(default)]
+    pub state_file_mode: StateFileMode,
     pub cert_file: Utf8PathBuf,
+    pub cert_reload_interval: Option<u64>,
+    pub acme: Option<AcmeConfig>,
+    pub fade_file: Utf8PathBuf,
+    pub admin_token: Option<String>,
+    /* if set, admin endpoints require one of these (pem) client certificates */
+    #[serde(default)]
+    pub client_certificates: Vec<Utf8PathBuf>,
+    /* seconds without stream data, before an entertainment session is stopped */
+    pub entertainment_timeout: Option<u64>,
+    /* emulate candle and fire effects on lights without native support */
+    #[serde(default)]
+    pub effect_emulation: bool,
+    #[serde(default)]
+    pub features: FeaturesConfig,
+    pub otlp: Option<OtlpConfig>,
+    pub failover: Option<FailoverConfig>,
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+    #[serde(default)]
+    pub link_button: LinkButtonConfig,
+    /* decimals of brightness percentages reported to clients (global to the
+     * process, see `brightness_precision()`) */
+    pub brightness_precision: Option<u32>,
+    #[serde(default)]
+    pub quirks: QuirksConfig,
+    pub night_mode: Option<NightModeConfig>,
+}
+
+impl BifrostConfig {
+    #[must_use]
+    pub fn brightness_precision(&self) -> u32 {
+        self.brightness_precision
+            .unwrap_or(units::DEFAULT_PRECISION)
+    }
+}
+
+/// Adjustments of the v1 api for clients that validate bridge metadata
+/// strictly. Everything is disabled by default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuirksConfig {
+    /* report bridge, light and sensor metadata the way homebridge-hue (and
+     * homekit, through it) expects it */
+    pub homebridge: bool,
+}
+
+/// Quiet hours. While active, lights in the listed rooms (and zones) are
+/// kept dim and warm, whatever clients ask for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NightModeConfig {
+    /* local time of the bridge, e.g., "22:30", or relative to the sun, e.g., "sunset+1h" */
+    pub start: String,
+    pub end: String,
+    /* room and zone names */
+    pub rooms: Vec<String>,
+    /* highest brightness, in percent */
+    #[serde(default = "NightModeConfig::default_max_brightness")]
+    pub max_brightness: f64,
+    /* color temperature (in mirek) lights are set to */
+    #[serde(default = "NightModeConfig::default_mirek")]
+    pub mirek: u32,
+}
+
+impl NightModeConfig {
+    const fn default_max_brightness() -> f64 {
+        20.0
+    }
+
+    const fn default_mirek() -> u32 {
+        454
+    }
+}
+
+/// Export of traces and metrics to an OpenTelemetry collector (otlp over
+/// http, json encoded)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OtlpConfig {
+    /* e.g., "http://tempo:4318" */
+    pub endpoint: String,
+    #[serde(default = "OtlpConfig::default_service_name")]
+    pub service_name: String,
+    /* seconds between exports */
+    #[serde(default = "OtlpConfig::default_interval")]
+    pub interval: u64,
+}
+
+impl OtlpConfig {
+    fn default_service_name() -> String {
+        "bifrost".to_string()
+    }
+
+    const fn default_interval() -> u64 {
+        10
+    }
 }
 
+/// Run as a standby instance, replicating the state of a primary bifrost
+/// instance, and taking over (i.e., announcing the bridge) when it fails.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FailoverConfig {
+    /* http address of the primary, e.g., "10.0.0.12:80" */
+    pub primary: String,
+    /* admin token of the primary, needed to fetch its state (sent over
+     * plain http) */
+    pub token: String,
+    /* seconds between health checks of the primary */
+    #[serde(default = "FailoverConfig::default_check_interval")]
+    pub check_interval: u64,
+    /* failed health checks in a row before taking over */
+    #[serde(default = "FailoverConfig::default_failures")]
+    pub failures: u32,
+}
+
+impl FailoverConfig {
+    const fn default_check_interval() -> u64 {
+        5
+    }
+
+    const fn default_failures() -> u32 {
+        3
+    }
+}
+
+/// Bifrost features that can be turned off. Everything is enabled by
+/// default. Enabled features are listed by the /bifrost/features endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FeaturesConfig {
+    /* hue api v1 (pairing and bridge discovery still work without it) */
+    pub v1_api: bool,
+    pub entertainment: bool,
+    pub behaviors: bool,
+    /* motion, light level and temperature sensors */
+    pub sensors: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            v1_api: true,
+            entertainment: true,
+            behaviors: true,
+            sensors: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Z2mConfig {
     #[serde(flatten)]
     pub servers: HashMap<String, Z2mServer>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Z2mServer {
     pub url: String,
     pub group_prefix: Option<String>,
+    pub poll_interval: Option<u64>,
+    #[serde(default)]
+    pub device_options: HashMap<String, DeviceOptions>,
+    #[serde(default)]
+    pub zone_groups: bool,
+    /* maximum number of messages per second sent to z2m (default: 20,
+     * unlimited if 0) */
+    pub max_rate: Option<f64>,
+    /* seconds a command may wait in the queue (e.g., while z2m is offline),
+     * before it is dropped */
+    pub max_command_age: Option<f64>,
+    /* devices with a lower link quality are reported as having connectivity issues */
+    pub min_linkquality: Option<u8>,
+    /* devices not seen for this many seconds are reported as disconnected */
+    pub unreachable_after: Option<u64>,
+    /* times a command is sent again, if the target does not report a new state */
+    #[serde(default)]
+    pub retries: u32,
+    /* seconds to wait for a state update, before a command is sent again */
+    pub retry_timeout: Option<f64>,
+    /* fault injection, for testing only */
+    pub chaos: Option<ChaosConfig>,
+}
+
+impl Z2mServer {
+    pub const DEFAULT_RETRY_TIMEOUT: f64 = 3.0;
+    pub const DEFAULT_MAX_COMMAND_AGE: f64 = 30.0;
+
+    /// Time a command may be queued, before it is dropped
+    #[must_use]
+    pub fn max_command_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(
+            self.max_command_age
+                .unwrap_or(Self::DEFAULT_MAX_COMMAND_AGE)
+                .max(0.1),
+        )
+    }
+
+    /// Time to wait for a state update before retrying a command
+    #[must_use]
+    pub fn retry_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(
+            self.retry_timeout
+                .unwrap_or(Self::DEFAULT_RETRY_TIMEOUT)
+                .max(0.1),
+        )
+    }
+}
+
+/// Faults injected into a z2m connection, for testing how bifrost copes
+/// with slow and unreliable networks. Never enable this in normal use.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /* outgoing messages are held back by up to this many seconds */
+    pub delay: f64,
+    /* share (0.0 - 1.0) of outgoing commands that are silently dropped */
+    pub drop: f64,
+    /* share (0.0 - 1.0) of incoming device messages that are silently dropped */
+    pub drop_incoming: f64,
+    /* mean number of seconds between forced disconnects */
+    pub reconnect: Option<f64>,
+}
+
+/// Per-device options, as understood by zigbee2mqtt. Options that are not
+/// set are left unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_sync: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legacy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupancy_timeout: Option<u32>,
+}
+
+/// How a zigbee2mqtt group is presented to hue clients
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupKind {
+    #[default]
+    Room,
+    Zone,
+    Skip,
+}
+
+impl GroupKind {
+    /// The resource type groups of this kind are exposed as, if any
+    #[must_use]
+    pub const fn rtype(self) -> Option<RType> {
+        match self {
+            Self::Room => Some(RType::Room),
+            Self::Zone => Some(RType::Zone),
+            Self::Skip => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct RoomConfig {
+    #[serde(default)]
+    pub kind: GroupKind,
     pub name: Option<String>,
     pub icon: Option<RoomArchetype>,
+    pub transition: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct LightConfig {
+    pub transition: Option<f64>,
+    /* lowest usable brightness (in percent); hue brightness is scaled above it */
+    pub min_brightness: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct SensorConfig {
+    /* seconds motion is reported after the last detection */
+    pub hold: Option<f64>,
+    /* seconds after motion ended, during which new motion is ignored */
+    pub retrigger_delay: Option<f64>,
+}
+
+/// Rooms, zones and scenes declared in the config. They are created (or
+/// updated to match) at startup, and whenever z2m reports its devices, but
+/// never deleted.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub rooms: Vec<LayoutRoom>,
+    pub zones: Vec<LayoutZone>,
+    pub scenes: Vec<LayoutScene>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutRoom {
+    pub name: String,
+    pub icon: Option<RoomArchetype>,
+    /* device names */
+    #[serde(default)]
+    pub devices: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutZone {
+    pub name: String,
+    pub icon: Option<RoomArchetype>,
+    /* light (or device) names */
+    #[serde(default)]
+    pub lights: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutScene {
+    pub name: String,
+    /* name of a room or zone */
+    pub group: String,
+    #[serde(default)]
+    pub actions: Vec<LayoutAction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutAction {
+    /* light (or device) name */
+    pub light: String,
+    #[serde(flatten)]
+    pub action: SceneAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct LoggingConfig {
+    pub level: Option<LevelFilter>,
+    #[serde(default)]
+    pub targets: BTreeMap<String, LevelFilter>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,15 +600,145 @@ pub struct AppConfig {
     pub bifrost: BifrostConfig,
     #[serde(default)]
     pub rooms: HashMap<String, RoomConfig>,
+    #[serde(default)]
+    pub lights: HashMap<String, LightConfig>,
+    #[serde(default)]
+    pub sensors: HashMap<String, SensorConfig>,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub homes: BTreeMap<String, HomeConfig>,
+}
+
+/// Additional virtual bridge, hosted by the same bifrost process. Each home
+/// has its own bridge identity, certificate, state file and z2m servers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HomeConfig {
+    pub bridge: BridgeConfig,
+    pub bifrost: BifrostConfig,
+    #[serde(default)]
+    pub z2m: Z2mConfig,
+    #[serde(default)]
+    pub rooms: HashMap<String, RoomConfig>,
+    #[serde(default)]
+    pub lights: HashMap<String, LightConfig>,
+    #[serde(default)]
+    pub sensors: HashMap<String, SensorConfig>,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+}
+
+impl AppConfig {
+    /// Split the config into one config per home. The top-level config is the
+    /// first home, followed by any additional homes.
+    pub fn homes(&self) -> ApiResult<Vec<(String, Self)>> {
+        let primary = Self {
+            homes: BTreeMap::new(),
+            ..self.clone()
+        };
+
+        let mut homes = vec![("default".to_string(), primary)];
+        for (name, home) in &self.homes {
+            let conf = Self {
+                bridge: home.bridge.clone(),
+                z2m: home.z2m.clone(),
+                bifrost: home.bifrost.clone(),
+                rooms: home.rooms.clone(),
+                lights: home.lights.clone(),
+                sensors: home.sensors.clone(),
+                layout: home.layout.clone(),
+                logging: self.logging.clone(),
+                homes: BTreeMap::new(),
+            };
+            homes.push((name.clone(), conf));
+        }
+
+        /* homes must not share identities, files, or listening addresses */
+        let mut seen = HashSet::new();
+        for (name, conf) in &homes {
+            /* brightness precision (like logging) is global to the process */
+            let global = self.bifrost.brightness_precision();
+            if let Some(precision) = conf.bifrost.brightness_precision {
+                if precision != global {
+                    return Err(ApiError::InvalidConfig(format!(
+                        "home {name:?} sets brightness_precision {precision}, but all homes share the top-level setting ({global})"
+                    )));
+                }
+            }
+
+            let bridge = &conf.bridge;
+            if !bridge.http_enabled && !bridge.https_enabled {
+                return Err(ApiError::InvalidConfig(format!(
+                    "home {name:?} has both http and https disabled"
+                )));
+            }
+
+            let mut unique = vec![
+                format!("mac {}", bridge.mac),
+                format!("bridge id {}", bridge.id()),
+                format!("state_file {}", conf.bifrost.state_file),
+                format!("cert_file {}", conf.bifrost.cert_file),
+                format!("fade_file {}", conf.bifrost.fade_file),
+            ];
+            if bridge.http_enabled {
+                unique.push(format!("address {}:{}", bridge.ipaddress, bridge.http_port));
+            }
+            if bridge.https_enabled {
+                unique.push(format!(
+                    "address {}:{}",
+                    bridge.ipaddress, bridge.https_port
+                ));
+            }
+            for key in unique {
+                if !seen.insert(key.clone()) {
+                    return Err(ApiError::InvalidConfig(format!(
+                        "home {name:?} reuses {key}"
+                    )));
+                }
+            }
+        }
+
+        Ok(homes)
+    }
+}
+
+/// Settings given on the command line (or through the environment), which
+/// take precedence over the config file. Additional homes are not affected.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigOverrides {
+    pub state_file: Option<Utf8PathBuf>,
+    pub cert_file: Option<Utf8PathBuf>,
+    pub http_port: Option<u16>,
+    pub https_port: Option<u16>,
 }
 
 pub fn parse(filename: &Utf8Path) -> Result<AppConfig, ConfigError> {
+    parse_with_overrides(filename, &ConfigOverrides::default())
+}
+
+pub fn parse_with_overrides(
+    filename: &Utf8Path,
+    overrides: &ConfigOverrides,
+) -> Result<AppConfig, ConfigError> {
     let settings = Config::builder()
         .set_default("bifrost.state_file", "state.yaml")?
         .set_default("bifrost.cert_file", "cert.pem")?
+        .set_default("bifrost.fade_file", "fades.yaml")?
         .set_default("bridge.http_port", 80)?
         .set_default("bridge.https_port", 443)?
         .add_source(config::File::with_name(filename.as_str()))
+        .set_override_option(
+            "bifrost.state_file",
+            overrides.state_file.as_ref().map(|path| path.as_str()),
+        )?
+        .set_override_option(
+            "bifrost.cert_file",
+            overrides.cert_file.as_ref().map(|path| path.as_str()),
+        )?
+        .set_override_option("bridge.http_port", overrides.http_port)?
+        .set_override_option("bridge.https_port", overrides.https_port)?
         .build()?;
 
     settings.try_deserialize()
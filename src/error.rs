@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use camino::Utf8PathBuf;
@@ -6,12 +7,14 @@ use tokio::task::JoinError;
 use uuid::Uuid;
 
 use crate::{
+    config::KeyScope,
     hue::{
-        api::{RType, ResourceLink},
+        api::{LightEffect, RType, ResourceLink},
         event::EventBlock,
         legacy_api::ApiResourceType,
     },
-    z2m::request::ClientRequest,
+    model::networkmap::NetworkMapType,
+    z2m::request::TaggedRequest,
 };
 
 #[derive(Error, Debug)]
@@ -42,7 +45,7 @@ pub enum ApiError {
     SendErrorHue(#[from] tokio::sync::broadcast::error::SendError<EventBlock>),
 
     #[error(transparent)]
-    SendErrorZ2m(#[from] tokio::sync::broadcast::error::SendError<Arc<ClientRequest>>),
+    SendErrorZ2m(#[from] tokio::sync::broadcast::error::SendError<Arc<TaggedRequest>>),
 
     #[error(transparent)]
     SetLoggerError(#[from] log::SetLoggerError),
@@ -74,6 +77,10 @@ pub enum ApiError {
     #[error(transparent)]
     P256Pkcs8Error(#[from] p256::pkcs8::Error),
 
+    #[cfg(feature = "server-acme")]
+    #[error(transparent)]
+    AcmeError(Box<instant_acme::Error>),
+
     /* zigbee2mqtt errors */
     #[error("Unexpected eof on z2m socket")]
     UnexpectedZ2mEof,
@@ -101,7 +108,72 @@ pub enum ApiError {
     #[error("Cannot allocate any more {0:?}")]
     Full(RType),
 
+    /// Same as hue error 11 ("too many items in list")
+    #[error("Too many items in list: at most {1} of {0:?} allowed")]
+    TooManyItems(RType, u32),
+
+    #[error("Light {0} is in use by an entertainment session")]
+    LightStreaming(Uuid),
+
+    #[error("Light {0} does not support effect {1:?}")]
+    EffectUnsupported(Uuid, LightEffect),
+
+    #[error("Invalid resource: {}", .0.join(", "))]
+    InvalidResource(Vec<String>),
+
+    /// The update was applied to the listed targets, but failed for others
+    #[error("Update failed for some targets: {}", .1.join(", "))]
+    PartialFailure(Vec<ResourceLink>, Vec<String>),
+
     /* bifrost errors */
+    #[error("Admin access denied")]
+    AdminDenied,
+
+    #[error("Client certificate required")]
+    ClientCertRequired,
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid timezone: {0:?}")]
+    InvalidTimezone(String),
+
+    #[error("Zigbee2mqtt server {0:?} not found")]
+    Z2mServerNotFound(String),
+
+    #[error("Invalid zigbee2mqtt server: {0}")]
+    InvalidZ2mServer(String),
+
+    #[error("No {1:?} network map from zigbee2mqtt server {0:?} (yet)")]
+    NetworkMapNotFound(String, NetworkMapType),
+
+    #[error("Invalid install code: {0}")]
+    InvalidInstallCode(String),
+
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+
+    #[error("Invalid entertainment stream frame: {0}")]
+    InvalidStreamFrame(String),
+
+    #[error("Cannot listen on {1} for {0}: {2}")]
+    BindFailed(String, SocketAddr, String),
+
+    #[error("Feature disabled: {0}")]
+    FeatureDisabled(&'static str),
+
+    #[error("Application key does not have {0:?} permission")]
+    ScopeDenied(KeyScope),
+
+    #[error("Unauthorized user")]
+    UnauthorizedUser,
+
+    #[error("Primary bifrost instance at {0} failed: {1}")]
+    PrimaryFailed(String, String),
+
     #[error("Missing auxiliary data resource {0:?}")]
     AuxNotFound(ResourceLink),
 
@@ -110,6 +182,20 @@ pub enum ApiError {
 
     #[error("Cannot parse certificate: {0:?}")]
     CertificateInvalid(Utf8PathBuf),
+
+    #[error("Cannot obtain certificate for {0}: {1}")]
+    AcmeFailed(String, String),
+
+    #[error("Entertainment stream error: {0}")]
+    Dtls(String),
 }
 
 pub type ApiResult<T> = Result<T, ApiError>;
+
+/* boxed, since instant_acme::Error is much larger than any other variant */
+#[cfg(feature = "server-acme")]
+impl From<instant_acme::Error> for ApiError {
+    fn from(err: instant_acme::Error) -> Self {
+        Self::AcmeError(Box::new(err))
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::history::{AutomationEvent, AutomationHistory, AutomationKind, AutomationOutcome};
+use crate::hue::api::{BehaviorInstance, GroupedLight, Light, RType, ResourceLink, Room, Zone};
+use crate::hue::date_format;
+use crate::model::units;
+use crate::resource::Resources;
+use crate::timezone;
+use crate::z2m::request::ClientRequest;
+use crate::z2m::update::DeviceUpdate;
+
+/// A single segment of a long-running fade.
+///
+/// Each stage ramps linearly from where the previous stage ended (or from the
+/// starting point of the fade) to the given brightness and color temperature.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FadeStage {
+    /// Duration of this stage, in seconds
+    pub duration: u32,
+
+    /// Brightness (in percent) at the end of this stage. A fade ending at 0.0
+    /// turns the light off when it completes.
+    pub brightness: f64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirek: Option<u32>,
+}
+
+/// Request for a new fade, as submitted to the fade endpoint.
+///
+/// If the starting brightness is omitted, the current brightness of the target
+/// is used.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FadeRequest {
+    pub target: ResourceLink,
+    #[serde(default)]
+    pub brightness: Option<f64>,
+    #[serde(default)]
+    pub mirek: Option<u32>,
+    pub stages: Vec<FadeStage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FadeJob {
+    pub target: ResourceLink,
+    #[serde(with = "date_format::utc")]
+    pub started: DateTime<Utc>,
+    pub brightness: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirek: Option<u32>,
+    pub stages: Vec<FadeStage>,
+}
+
+#[derive(Serialize)]
+pub struct FadeRecord<'a> {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub job: &'a FadeJob,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FadePoint {
+    pub brightness: f64,
+    pub mirek: Option<u32>,
+    pub done: bool,
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn lerp_mirek(from: Option<u32>, to: Option<u32>, t: f64) -> Option<u32> {
+    match (from, to) {
+        (Some(a), Some(b)) => Some(
+            (f64::from(b) - f64::from(a))
+                .mul_add(t, f64::from(a))
+                .round() as u32,
+        ),
+        (a, b) => b.or(a),
+    }
+}
+
+impl FadeJob {
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.stages.iter().map(|s| i64::from(s.duration)).sum())
+    }
+
+    /// Calculate the desired state of the target at the given time
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn state_at(&self, now: DateTime<Utc>) -> FadePoint {
+        let mut elapsed = (now - self.started).num_milliseconds().max(0) as f64 / 1000.0;
+        let mut brightness = self.brightness;
+        let mut mirek = self.mirek;
+
+        for stage in &self.stages {
+            let duration = f64::from(stage.duration);
+            if elapsed < duration {
+                let t = elapsed / duration;
+                return FadePoint {
+                    brightness: (stage.brightness - brightness).mul_add(t, brightness),
+                    mirek: lerp_mirek(mirek, stage.mirek, t),
+                    done: false,
+                };
+            }
+            elapsed -= duration;
+            brightness = stage.brightness;
+            mirek = stage.mirek.or(mirek);
+        }
+
+        FadePoint {
+            brightness,
+            mirek,
+            done: true,
+        }
+    }
+
+    fn apply(&self, res: &Resources, point: &FadePoint, transition: f64) -> ApiResult<()> {
+        let on = !(point.done && point.brightness <= 0.0);
+
+        let mut upd = DeviceUpdate::default()
+            .with_state(Some(on))
+            .with_transition(Some(transition));
+
+        if on {
+            upd = upd
+                .with_brightness(Some(units::percent_to_device(point.brightness)))
+                .with_color_temp(point.mirek);
+        }
+
+        let req = match self.target.rtype {
+            RType::GroupedLight => ClientRequest::group_update(self.target, upd),
+            _ => ClientRequest::light_update(self.target, upd),
+        };
+
+        res.z2m_request(req)
+    }
+}
+
+/// Map a fade target to something we can send updates to. Rooms and zones are
+/// faded through their grouped light.
+fn resolve_target(res: &Resources, link: &ResourceLink) -> ApiResult<ResourceLink> {
+    let services = match link.rtype {
+        RType::Light | RType::GroupedLight => return Ok(*link),
+        RType::Room => &res.get::<Room>(link)?.services,
+        RType::Zone => &res.get::<Zone>(link)?.services,
+        rtype => return Err(ApiError::WrongType(RType::Light, rtype)),
+    };
+
+    services
+        .iter()
+        .find(|rl| rl.rtype == RType::GroupedLight)
+        .copied()
+        .ok_or(ApiError::NotFound(link.rid))
+}
+
+fn current_brightness(res: &Resources, link: &ResourceLink) -> ApiResult<f64> {
+    if link.rtype == RType::GroupedLight {
+        let glight = res.get::<GroupedLight>(link)?;
+        if glight.on.is_some_and(|on| on.on) {
+            return Ok(glight.as_brightness_opt().unwrap_or(100.0));
+        }
+    } else {
+        let light = res.get::<Light>(link)?;
+        if light.on.on {
+            return Ok(light.dimming.map_or(100.0, |dim| dim.brightness));
+        }
+    }
+
+    Ok(0.0)
+}
+
+pub struct FadeEngine {
+    filename: Utf8PathBuf,
+    jobs: BTreeMap<Uuid, FadeJob>,
+    history: AutomationHistory,
+}
+
+impl FadeEngine {
+    /// How often fades are advanced. Each step is sent with a matching
+    /// transition time, so the light moves smoothly between steps.
+    pub const STEP: std::time::Duration = std::time::Duration::from_secs(10);
+
+    pub fn new(filename: &Utf8Path) -> ApiResult<Self> {
+        let jobs = if let Ok(fd) = File::open(filename) {
+            log::debug!("Existing fade file found, resuming fades..");
+            serde_yaml::from_reader(fd)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            filename: filename.to_owned(),
+            jobs,
+            history: AutomationHistory::default(),
+        })
+    }
+
+    fn save(&self) -> ApiResult<()> {
+        let tmp = self.filename.with_extension("tmp");
+        let mut fd = File::create(&tmp)?;
+        fd.write_all(serde_yaml::to_string(&self.jobs)?.as_bytes())?;
+        std::fs::rename(&tmp, &self.filename)?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn jobs(&self) -> Vec<FadeRecord<'_>> {
+        self.jobs
+            .iter()
+            .map(|(id, job)| FadeRecord { id: *id, job })
+            .collect()
+    }
+
+    /// Recent automation events (fades, and the behaviors starting them)
+    #[must_use]
+    pub fn history(&self) -> Vec<&AutomationEvent> {
+        self.history.events()
+    }
+
+    pub fn record(&mut self, event: AutomationEvent) {
+        self.history.record(event);
+    }
+
+    /// Start a new fade. Any existing fade on the same target is replaced.
+    pub fn start(&mut self, res: &Resources, req: FadeRequest) -> ApiResult<Uuid> {
+        let target = resolve_target(res, &req.target)?;
+
+        let brightness = match req.brightness {
+            Some(brightness) => brightness,
+            None => current_brightness(res, &target)?,
+        };
+
+        let job = FadeJob {
+            target,
+            started: Utc::now(),
+            brightness,
+            mirek: req.mirek,
+            stages: req.stages,
+        };
+
+        self.jobs.retain(|_, old| old.target != target);
+
+        let id = Uuid::new_v4();
+        log::info!(
+            "Starting fade {id} on {target:?} ({} seconds)",
+            job.duration().num_seconds()
+        );
+        self.jobs.insert(id, job);
+        self.save()?;
+
+        self.record(
+            AutomationEvent::new(AutomationKind::Fade, id, AutomationOutcome::Triggered)
+                .with_target(target),
+        );
+
+        Ok(id)
+    }
+
+    pub fn cancel(&mut self, id: &Uuid) -> ApiResult<FadeJob> {
+        let job = self.jobs.remove(id).ok_or(ApiError::NotFound(*id))?;
+        log::info!("Cancelled fade {id} on {:?}", job.target);
+        self.save()?;
+        self.record(
+            AutomationEvent::new(AutomationKind::Fade, *id, AutomationOutcome::Cancelled)
+                .with_target(job.target),
+        );
+        Ok(job)
+    }
+
+    fn step(&mut self, res: &Resources) -> ApiResult<()> {
+        let now = Utc::now();
+        let transition = Self::STEP.as_secs_f64();
+
+        let mut finished = vec![];
+        for (id, job) in &self.jobs {
+            let point = job.state_at(now);
+            let evt = AutomationEvent::new(AutomationKind::Fade, *id, AutomationOutcome::Completed)
+                .with_target(job.target);
+            if let Err(err) = job.apply(res, &point, transition) {
+                log::warn!("Fade {id} on {:?} failed: {err}", job.target);
+                finished.push(AutomationEvent {
+                    outcome: AutomationOutcome::Failed,
+                    ..evt.with_message(err.to_string())
+                });
+            } else if point.done {
+                log::info!("Fade {id} on {:?} completed", job.target);
+                finished.push(evt);
+            }
+        }
+
+        if !finished.is_empty() {
+            self.jobs
+                .retain(|id, _| !finished.iter().any(|evt| &evt.id == id));
+            for evt in finished {
+                self.record(evt);
+            }
+            self.save()?;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn fade_runner(
+    res: Arc<Mutex<Resources>>,
+    fades: Arc<Mutex<FadeEngine>>,
+) -> ApiResult<()> {
+    let mut interval = tokio::time::interval(FadeEngine::STEP);
+
+    loop {
+        interval.tick().await;
+
+        if fades.lock().await.jobs.is_empty() {
+            continue;
+        }
+
+        /* always lock resources before fades, like the api and behaviors do */
+        let lock = res.lock().await;
+        let mut engine = fades.lock().await;
+
+        /* a failed step is retried on the next tick */
+        if let Err(err) = engine.step(&lock) {
+            log::error!("Fade step failed: {err}");
+        }
+    }
+}
+
+/* Configuration format of the built-in "Wake up" and "Go to sleep" behavior scripts */
+
+#[derive(Debug, Deserialize)]
+struct BehaviorDuration {
+    seconds: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct BehaviorTime {
+    hour: u32,
+    #[serde(default)]
+    minute: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct BehaviorTimePoint {
+    time: BehaviorTime,
+}
+
+#[derive(Debug, Deserialize)]
+struct BehaviorWhen {
+    #[serde(default)]
+    recurrence_days: Vec<Weekday>,
+    time_point: BehaviorTimePoint,
+}
+
+#[derive(Debug, Deserialize)]
+struct BehaviorWhere {
+    group: ResourceLink,
+}
+
+#[derive(Debug, Deserialize)]
+struct WakeUpConfiguration {
+    #[serde(default = "WakeUpConfiguration::default_end_brightness")]
+    end_brightness: f64,
+    fade_in_duration: BehaviorDuration,
+    #[serde(default)]
+    style: Option<String>,
+    when: BehaviorWhen,
+    #[serde(rename = "where")]
+    targets: Vec<BehaviorWhere>,
+}
+
+impl WakeUpConfiguration {
+    const fn default_end_brightness() -> f64 {
+        100.0
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GoToSleepConfiguration {
+    fade_out_duration: BehaviorDuration,
+    when: BehaviorWhen,
+    #[serde(rename = "where")]
+    targets: Vec<BehaviorWhere>,
+}
+
+/// A fade derived from a behavior instance, along with the local time of day
+/// it should start at.
+struct BehaviorFade {
+    start: NaiveTime,
+    end: NaiveTime,
+    days: Vec<Weekday>,
+    targets: Vec<ResourceLink>,
+    brightness: Option<f64>,
+    mirek: Option<u32>,
+    stages: Vec<FadeStage>,
+}
+
+impl BehaviorFade {
+    /* Mirek range used for the "sunrise" wake up style */
+    const SUNRISE_MIREK: (u32, u32) = (454, 233);
+
+    fn from_behavior(bi: &BehaviorInstance) -> Option<Self> {
+        if bi.script_id == BehaviorInstance::WAKE_UP {
+            let conf: WakeUpConfiguration = serde_json::from_value(bi.configuration.clone())
+                .map_err(|err| log::warn!("Cannot parse wake up configuration: {err}"))
+                .ok()?;
+
+            let time = &conf.when.time_point.time;
+            let end = NaiveTime::from_hms_opt(time.hour, time.minute, 0)?;
+            let duration = Duration::seconds(i64::from(conf.fade_in_duration.seconds));
+            let sunrise = conf.style.as_deref() == Some("sunrise");
+
+            Some(Self {
+                start: end - duration,
+                end,
+                days: conf.when.recurrence_days,
+                targets: conf.targets.into_iter().map(|w| w.group).collect(),
+                brightness: Some(0.0),
+                mirek: sunrise.then_some(Self::SUNRISE_MIREK.0),
+                stages: vec![FadeStage {
+                    duration: conf.fade_in_duration.seconds,
+                    brightness: conf.end_brightness,
+                    mirek: sunrise.then_some(Self::SUNRISE_MIREK.1),
+                }],
+            })
+        } else if bi.script_id == BehaviorInstance::GO_TO_SLEEP {
+            let conf: GoToSleepConfiguration = serde_json::from_value(bi.configuration.clone())
+                .map_err(|err| log::warn!("Cannot parse go to sleep configuration: {err}"))
+                .ok()?;
+
+            let time = &conf.when.time_point.time;
+            let start = NaiveTime::from_hms_opt(time.hour, time.minute, 0)?;
+            let duration = Duration::seconds(i64::from(conf.fade_out_duration.seconds));
+
+            Some(Self {
+                start,
+                end: start + duration,
+                days: conf.when.recurrence_days,
+                targets: conf.targets.into_iter().map(|w| w.group).collect(),
+                brightness: None,
+                mirek: None,
+                stages: vec![FadeStage {
+                    duration: conf.fade_out_duration.seconds,
+                    brightness: 0.0,
+                    mirek: None,
+                }],
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns true, if the given (bridge local) time is the start time of
+    /// this fade, regardless of the day.
+    fn is_start_time(&self, now: &DateTime<Tz>) -> bool {
+        now.hour() == self.start.hour() && now.minute() == self.start.minute()
+    }
+
+    /// Returns true, if this fade should run on the day of the given (bridge
+    /// local) time.
+    ///
+    /// Recurrence days refer to the day the behavior completes on, which
+    /// might be the day after the fade starts (for fades crossing midnight).
+    fn runs_on(&self, now: &DateTime<Tz>) -> bool {
+        let end_day = if self.end < self.start {
+            now.weekday().succ()
+        } else {
+            now.weekday()
+        };
+
+        self.days.is_empty() || self.days.contains(&end_day)
+    }
+}
+
+/// Watch enabled "Wake up" and "Go to sleep" behavior instances, and start the
+/// corresponding fades when they are due.
+pub async fn behavior_runner(
+    res: Arc<Mutex<Resources>>,
+    fades: Arc<Mutex<FadeEngine>>,
+    tz: Tz,
+) -> ApiResult<()> {
+    const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+    /* Remember when each behavior last fired, to only trigger once per day */
+    let mut fired: HashMap<Uuid, NaiveDate> = HashMap::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let now = timezone::now(tz);
+        let lock = res.lock().await;
+
+        for rr in lock.get_resources_by_type(RType::BehaviorInstance) {
+            let bi: BehaviorInstance = rr.obj.try_into()?;
+
+            let Some(bfade) = BehaviorFade::from_behavior(&bi) else {
+                continue;
+            };
+
+            if !bfade.is_start_time(&now) || fired.get(&rr.id) == Some(&now.date_naive()) {
+                continue;
+            }
+
+            fired.insert(rr.id, now.date_naive());
+
+            let evt = AutomationEvent::new(
+                AutomationKind::Behavior,
+                rr.id,
+                AutomationOutcome::Triggered,
+            );
+            let skipped = AutomationEvent {
+                outcome: AutomationOutcome::Skipped,
+                ..evt.clone()
+            };
+
+            let mut engine = fades.lock().await;
+
+            if !bi.enabled {
+                engine.record(skipped.with_message("Behavior is disabled"));
+                continue;
+            }
+
+            if !bfade.runs_on(&now) {
+                engine.record(skipped.with_message("Not scheduled for today"));
+                continue;
+            }
+
+            log::info!("Behavior {} ({}) triggered", rr.id, bi.metadata.name);
+            engine.record(evt.clone().with_message(bi.metadata.name.clone()));
+
+            for target in &bfade.targets {
+                let req = FadeRequest {
+                    target: *target,
+                    brightness: bfade.brightness,
+                    mirek: bfade.mirek,
+                    stages: bfade.stages.clone(),
+                };
+                if let Err(err) = engine.start(&lock, req) {
+                    log::error!("Behavior {} cannot fade {target:?}: {err}", rr.id);
+                    engine.record(
+                        AutomationEvent {
+                            outcome: AutomationOutcome::Failed,
+                            ..evt.clone()
+                        }
+                        .with_target(*target)
+                        .with_message(err.to_string()),
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::hue::api::ResourceLink;
+use crate::hue::date_format;
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationKind {
+    Behavior,
+    Fade,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationOutcome {
+    /// The automation was due, and has been started
+    Triggered,
+    /// The automation was due, but did not run (see message)
+    Skipped,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AutomationEvent {
+    #[serde(with = "date_format::utc")]
+    pub time: DateTime<Utc>,
+    pub kind: AutomationKind,
+    pub id: Uuid,
+    pub outcome: AutomationOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<ResourceLink>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl AutomationEvent {
+    #[must_use]
+    pub fn new(kind: AutomationKind, id: Uuid, outcome: AutomationOutcome) -> Self {
+        Self {
+            time: Utc::now(),
+            kind,
+            id,
+            outcome,
+            target: None,
+            message: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_target(self, target: ResourceLink) -> Self {
+        Self {
+            target: Some(target),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_message(self, message: impl Into<String>) -> Self {
+        Self {
+            message: Some(message.into()),
+            ..self
+        }
+    }
+}
+
+/// Bounded, in-memory history of automation executions (behaviors and fades),
+/// to help figuring out why an automation did (or did not) run.
+#[derive(Debug)]
+pub struct AutomationHistory {
+    events: VecDeque<AutomationEvent>,
+    capacity: usize,
+}
+
+impl AutomationHistory {
+    pub const DEFAULT_CAPACITY: usize = 200;
+
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, event: AutomationEvent) {
+        if self.events.len() >= self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// All recorded events, newest first
+    #[must_use]
+    pub fn events(&self) -> Vec<&AutomationEvent> {
+        self.events.iter().rev().collect()
+    }
+}
+
+impl Default for AutomationHistory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
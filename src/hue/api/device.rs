@@ -17,6 +17,37 @@ impl Device {
     }
 }
 
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceIdentifyAction {
+    Identify,
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceIdentify {
+    pub action: DeviceIdentifyAction,
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceRestartAction {
+    Restart,
+}
+
+/// Restart request. Only supported by zigbee2mqtt coordinators.
+#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceRestart {
+    pub action: DeviceRestartAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DeviceUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identify: Option<DeviceIdentify>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<DeviceRestart>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeviceProductData {
     pub model_id: String,
@@ -29,6 +60,8 @@ pub struct DeviceProductData {
 
 impl DeviceProductData {
     const SIGNIFY_MANUFACTURER_NAME: &'static str = "Signify Netherlands B.V.";
+    const Z2M_MANUFACTURER_NAME: &'static str = "zigbee2mqtt";
+    const Z2M_COORDINATOR_NAME: &'static str = "Zigbee2mqtt coordinator";
 
     #[must_use]
     pub fn hue_bridge_v2() -> Self {
@@ -42,6 +75,34 @@ impl DeviceProductData {
         }
     }
 
+    /// Product data for a zigbee2mqtt coordinator. The software version is the
+    /// coordinator firmware revision, if reported, or the z2m version otherwise.
+    #[must_use]
+    pub fn from_coordinator(info: &z2m::api::BridgeInfo) -> Self {
+        let software_version = info
+            .coordinator
+            .meta
+            .get("revision")
+            .map_or_else(|| info.version.clone(), ToString::to_string)
+            .trim_matches('"')
+            .to_string();
+
+        Self {
+            model_id: info.coordinator.coordinator_type.clone(),
+            manufacturer_name: Self::Z2M_MANUFACTURER_NAME.to_string(),
+            product_name: Self::Z2M_COORDINATOR_NAME.to_string(),
+            product_archetype: DeviceArchetype::UnknownArchetype,
+            certified: false,
+            software_version,
+        }
+    }
+
+    #[must_use]
+    pub fn is_coordinator(&self) -> bool {
+        self.manufacturer_name == Self::Z2M_MANUFACTURER_NAME
+            && self.product_name == Self::Z2M_COORDINATOR_NAME
+    }
+
     #[must_use]
     pub fn guess_from_device(dev: &z2m::api::Device) -> Self {
         fn str_or_unknown(name: &Option<String>) -> String {
@@ -67,7 +128,7 @@ impl DeviceProductData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum DeviceArchetype {
     BridgeV2,
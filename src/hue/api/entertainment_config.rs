@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::hue::api::ResourceLink;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntertainmentConfigurationMetadata {
+    pub name: String,
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntertainmentConfigurationType {
+    Screen,
+    Monitor,
+    Music,
+    #[serde(rename = "3dspace")]
+    Space3D,
+    Other,
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntertainmentConfigurationStatus {
+    Active,
+    #[default]
+    Inactive,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntertainmentConfiguration {
+    pub metadata: EntertainmentConfigurationMetadata,
+    pub configuration_type: EntertainmentConfigurationType,
+    #[serde(default)]
+    pub status: EntertainmentConfigurationStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_streamer: Option<ResourceLink>,
+    #[serde(default)]
+    pub light_services: Vec<ResourceLink>,
+    #[serde(default)]
+    pub channels: Vec<Value>,
+    #[serde(default)]
+    pub locations: Value,
+}
+
+impl EntertainmentConfiguration {
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.status == EntertainmentConfigurationStatus::Active
+    }
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntertainmentConfigurationAction {
+    Start,
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EntertainmentConfigurationUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<EntertainmentConfigurationAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<EntertainmentConfigurationMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<EntertainmentConfigurationStatus>,
+}
+
+impl EntertainmentConfigurationUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_metadata(self, metadata: EntertainmentConfigurationMetadata) -> Self {
+        Self {
+            metadata: Some(metadata),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_status(self, status: EntertainmentConfigurationStatus) -> Self {
+        Self {
+            status: Some(status),
+            ..self
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::hue::api::{ColorTemperatureUpdate, ColorUpdate, DimmingUpdate, On, ResourceLink};
+use crate::hue::api::{
+    AlertEffect, AlertUpdate, ColorTemperatureUpdate, ColorUpdate, DimmingUpdate, LightAlert,
+    LightDynamicsUpdate, On, ResourceLink,
+};
 use crate::model::types::XY;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupedLight {
-    pub alert: Value,
+    pub alert: Option<LightAlert>,
     pub dimming: Option<DimmingUpdate>,
     pub on: Option<On>,
     pub owner: ResourceLink,
@@ -15,9 +18,11 @@ pub struct GroupedLight {
 
 impl GroupedLight {
     #[must_use]
-    pub const fn new(room: ResourceLink) -> Self {
+    pub fn new(room: ResourceLink) -> Self {
         Self {
-            alert: Value::Null,
+            alert: Some(LightAlert {
+                action_values: vec![AlertEffect::Breathe],
+            }),
             dimming: None,
             on: None,
             owner: room,
@@ -41,6 +46,10 @@ pub struct GroupedLightUpdate {
     pub color: Option<ColorUpdate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color_temperature: Option<ColorTemperatureUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<AlertUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamics: Option<LightDynamicsUpdate>,
 }
 
 impl GroupedLightUpdate {
@@ -1,9 +1,9 @@
 use std::ops::{AddAssign, Sub};
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 
-use crate::hue::api::{Metadata, ResourceLink};
+use crate::hue::api::{Metadata, ResourceLink, SceneAction};
 use crate::model::types::XY;
 use crate::z2m::api::Expose;
 
@@ -61,6 +61,26 @@ impl Light {
     pub fn as_color_opt(&self) -> Option<XY> {
         self.color.as_ref().map(|col| col.xy)
     }
+
+    /// The current state of this light, as a scene action
+    #[must_use]
+    pub fn as_scene_action(&self) -> SceneAction {
+        /* a light is in either color temperature or color (xy) mode */
+        let color_temperature = self
+            .as_mirek_opt()
+            .map(|mirek| ColorTemperatureUpdate { mirek });
+        let color = self
+            .as_color_opt()
+            .filter(|_| color_temperature.is_none())
+            .map(|xy| ColorUpdate { xy });
+
+        SceneAction {
+            color,
+            color_temperature,
+            dimming: self.as_dimming_opt(),
+            on: Some(self.on),
+        }
+    }
 }
 
 impl AddAssign<LightUpdate> for Light {
@@ -75,8 +95,10 @@ impl AddAssign<LightUpdate> for Light {
             }
         }
 
-        if let Some(ct) = &mut self.color_temperature {
-            ct.mirek = upd.color_temperature.map(|c| c.mirek);
+        /* the color temperature is only valid in color temperature mode */
+        if let (Some(ct), Some(upd_ct)) = (&mut self.color_temperature, &upd.color_temperature) {
+            ct.mirek = Some(upd_ct.mirek);
+            ct.mirek_valid = true;
         }
 
         if let Some(col) = upd.color {
@@ -85,6 +107,7 @@ impl AddAssign<LightUpdate> for Light {
             }
             if let Some(ct) = &mut self.color_temperature {
                 ct.mirek = None;
+                ct.mirek_valid = false;
             }
         }
     }
@@ -99,8 +122,15 @@ impl Sub<&Light> for &Light {
             dimming: None,
             color: None,
             color_temperature: None,
+            dynamics: None,
+            effects: None,
+            mode: None,
         };
 
+        if self.mode != rhs.mode {
+            upd.mode = Some(rhs.mode);
+        }
+
         if self.on != rhs.on {
             upd.on = Some(rhs.on);
         }
@@ -121,7 +151,7 @@ impl Sub<&Light> for &Light {
     }
 }
 
-#[derive(Copy, Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum LightMode {
     #[default]
@@ -145,6 +175,22 @@ pub struct LightPowerup {
     pub data: Value,
 }
 
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEffect {
+    Breathe,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LightAlert {
+    pub action_values: Vec<AlertEffect>,
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+pub struct AlertUpdate {
+    pub action: AlertEffect,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LightSignaling {
     pub signal_values: Vec<LightSignal>,
@@ -176,6 +222,15 @@ pub struct LightDynamics {
     pub speed_valid: bool,
 }
 
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LightEffect {
+    #[default]
+    NoEffect,
+    Candle,
+    Fire,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LightEffects {
     pub status_values: Value,
@@ -183,6 +238,30 @@ pub struct LightEffects {
     pub effect_values: Value,
 }
 
+impl LightEffects {
+    #[must_use]
+    pub fn new(values: &[LightEffect]) -> Self {
+        Self {
+            status_values: json!(values),
+            status: json!(LightEffect::NoEffect),
+            effect_values: json!(values),
+        }
+    }
+
+    #[must_use]
+    pub fn with_status(self, status: LightEffect) -> Self {
+        Self {
+            status: json!(status),
+            ..self
+        }
+    }
+}
+
+#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+pub struct LightEffectsUpdate {
+    pub effect: LightEffect,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LightTimedEffects {
     pub status_values: Value,
@@ -200,6 +279,30 @@ pub struct LightUpdate {
     pub color: Option<ColorUpdate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color_temperature: Option<ColorTemperatureUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamics: Option<LightDynamicsUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effects: Option<LightEffectsUpdate>,
+    /* only reported in events, cannot be changed by clients */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<LightMode>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LightDynamicsUpdate {
+    /* transition time, in milliseconds */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+}
+
+impl LightDynamicsUpdate {
+    /// Transition time in seconds, if any
+    #[must_use]
+    pub fn transition(&self) -> Option<f64> {
+        self.duration.map(|ms| f64::from(ms) / 1000.0)
+    }
 }
 
 impl LightUpdate {
@@ -208,6 +311,16 @@ impl LightUpdate {
         Self::default()
     }
 
+    /// Returns true, if this update changes the visible light state
+    #[must_use]
+    pub const fn changes_state(&self) -> bool {
+        self.on.is_some()
+            || self.dimming.is_some()
+            || self.color.is_some()
+            || self.color_temperature.is_some()
+            || self.effects.is_some()
+    }
+
     #[must_use]
     pub fn with_brightness(self, dim: Option<impl Into<f64>>) -> Self {
         Self {
@@ -239,6 +352,14 @@ impl LightUpdate {
             ..self
         }
     }
+
+    #[must_use]
+    pub fn with_mode(self, mode: impl Into<Option<LightMode>>) -> Self {
+        Self {
+            mode: mode.into(),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
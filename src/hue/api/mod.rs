@@ -1,29 +1,46 @@
 mod device;
+mod entertainment_config;
 mod grouped_light;
 mod light;
 mod resource;
 mod room;
 mod scene;
+mod sensor;
 mod stubs;
 mod update;
 
-pub use device::{Device, DeviceArchetype, DeviceProductData};
+pub use device::{
+    Device, DeviceArchetype, DeviceIdentify, DeviceIdentifyAction, DeviceProductData,
+    DeviceRestart, DeviceRestartAction, DeviceUpdate,
+};
+pub use entertainment_config::{
+    EntertainmentConfiguration, EntertainmentConfigurationAction,
+    EntertainmentConfigurationMetadata, EntertainmentConfigurationStatus,
+    EntertainmentConfigurationType, EntertainmentConfigurationUpdate,
+};
 pub use grouped_light::{GroupedLight, GroupedLightUpdate};
 pub use light::{
-    ColorGamut, ColorTemperature, ColorTemperatureUpdate, ColorUpdate, Delta, Dimming,
-    DimmingUpdate, GamutType, Light, LightColor, LightUpdate, MirekSchema, On,
+    AlertEffect, AlertUpdate, ColorGamut, ColorTemperature, ColorTemperatureUpdate, ColorUpdate,
+    Delta, Dimming, DimmingUpdate, GamutType, Light, LightAlert, LightColor, LightDynamicsUpdate,
+    LightEffect, LightEffects, LightEffectsUpdate, LightMode, LightUpdate, MirekSchema, On,
 };
-pub use resource::{RType, ResourceLink, ResourceRecord};
-pub use room::{Room, RoomArchetype, RoomMetadata};
+pub use resource::{RType, ResourceLink, ResourceRecord, SharedRecord};
+pub use room::{Room, RoomArchetype, RoomMetadata, RoomMetadataUpdate, RoomUpdate};
 pub use scene::{
     Scene, SceneAction, SceneActionElement, SceneMetadata, SceneRecall, SceneStatus,
     SceneStatusUpdate, SceneUpdate,
 };
+pub use sensor::{
+    LightLevel, LightLevelData, LightLevelReport, LightLevelUpdate, Motion, MotionData,
+    MotionReport, MotionSensitivity, MotionSensitivityUpdate, MotionUpdate, Temperature,
+    TemperatureData, TemperatureReport, TemperatureUpdate,
+};
 pub use stubs::{
-    BehaviorInstance, BehaviorScript, Bridge, BridgeHome, Button, ButtonData, ButtonMetadata,
-    ButtonReport, DollarRef, Entertainment, EntertainmentSegment, EntertainmentSegments,
-    GeofenceClient, Geolocation, Homekit, Matter, Metadata, PublicImage, SmartScene, TimeZone,
-    ZigbeeConnectivity, ZigbeeConnectivityStatus, ZigbeeDeviceDiscovery, Zone,
+    BehaviorInstance, BehaviorInstanceMetadata, BehaviorScript, Bridge, BridgeHome, Button,
+    ButtonData, ButtonMetadata, ButtonReport, ButtonUpdate, DollarRef, Entertainment,
+    EntertainmentSegment, EntertainmentSegments, GeofenceClient, Geolocation, Homekit, Matter,
+    Metadata, PublicImage, SmartScene, TimeZone, ZigbeeConnectivity, ZigbeeConnectivityStatus,
+    ZigbeeConnectivityUpdate, ZigbeeDeviceDiscovery, ZigbeeDeviceDiscoveryUpdate, Zone,
 };
 pub use update::{Update, UpdateRecord};
 
@@ -44,16 +61,20 @@ pub enum Resource {
     Button(Button),
     Device(Device),
     Entertainment(Entertainment),
+    EntertainmentConfiguration(EntertainmentConfiguration),
     GeofenceClient(GeofenceClient),
     Geolocation(Geolocation),
     GroupedLight(GroupedLight),
     Homekit(Homekit),
     Light(Light),
+    LightLevel(LightLevel),
     Matter(Matter),
+    Motion(Motion),
     PublicImage(PublicImage),
     Room(Room),
     Scene(Scene),
     SmartScene(SmartScene),
+    Temperature(Temperature),
     ZigbeeConnectivity(ZigbeeConnectivity),
     ZigbeeDeviceDiscovery(ZigbeeDeviceDiscovery),
     Zone(Zone),
@@ -70,16 +91,20 @@ impl Resource {
             Self::Button(_) => RType::Button,
             Self::Device(_) => RType::Device,
             Self::Entertainment(_) => RType::Entertainment,
+            Self::EntertainmentConfiguration(_) => RType::EntertainmentConfiguration,
             Self::GeofenceClient(_) => RType::GeofenceClient,
             Self::Geolocation(_) => RType::Geolocation,
             Self::GroupedLight(_) => RType::GroupedLight,
             Self::Homekit(_) => RType::Homekit,
             Self::Light(_) => RType::Light,
+            Self::LightLevel(_) => RType::LightLevel,
             Self::Matter(_) => RType::Matter,
+            Self::Motion(_) => RType::Motion,
             Self::PublicImage(_) => RType::PublicImage,
             Self::Room(_) => RType::Room,
             Self::Scene(_) => RType::Scene,
             Self::SmartScene(_) => RType::SmartScene,
+            Self::Temperature(_) => RType::Temperature,
             Self::ZigbeeConnectivity(_) => RType::ZigbeeConnectivity,
             Self::ZigbeeDeviceDiscovery(_) => RType::ZigbeeDeviceDiscovery,
             Self::Zone(_) => RType::Zone,
@@ -95,16 +120,20 @@ impl Resource {
             RType::Button => Self::Button(from_value(obj)?),
             RType::Device => Self::Device(from_value(obj)?),
             RType::Entertainment => Self::Entertainment(from_value(obj)?),
+            RType::EntertainmentConfiguration => Self::EntertainmentConfiguration(from_value(obj)?),
             RType::GeofenceClient => Self::GeofenceClient(from_value(obj)?),
             RType::Geolocation => Self::Geolocation(from_value(obj)?),
             RType::GroupedLight => Self::GroupedLight(from_value(obj)?),
             RType::Homekit => Self::Homekit(from_value(obj)?),
             RType::Light => Self::Light(from_value(obj)?),
+            RType::LightLevel => Self::LightLevel(from_value(obj)?),
             RType::Matter => Self::Matter(from_value(obj)?),
+            RType::Motion => Self::Motion(from_value(obj)?),
             RType::PublicImage => Self::PublicImage(from_value(obj)?),
             RType::Room => Self::Room(from_value(obj)?),
             RType::Scene => Self::Scene(from_value(obj)?),
             RType::SmartScene => Self::SmartScene(from_value(obj)?),
+            RType::Temperature => Self::Temperature(from_value(obj)?),
             RType::ZigbeeConnectivity => Self::ZigbeeConnectivity(from_value(obj)?),
             RType::ZigbeeDeviceDiscovery => Self::ZigbeeDeviceDiscovery(from_value(obj)?),
             RType::Zone => Self::Zone(from_value(obj)?),
@@ -167,16 +196,20 @@ resource_conversion_impl!(BridgeHome);
 resource_conversion_impl!(Button);
 resource_conversion_impl!(Device);
 resource_conversion_impl!(Entertainment);
+resource_conversion_impl!(EntertainmentConfiguration);
 resource_conversion_impl!(GeofenceClient);
 resource_conversion_impl!(Geolocation);
 resource_conversion_impl!(GroupedLight);
 resource_conversion_impl!(Homekit);
 resource_conversion_impl!(Light);
+resource_conversion_impl!(LightLevel);
 resource_conversion_impl!(Matter);
+resource_conversion_impl!(Motion);
 resource_conversion_impl!(PublicImage);
 resource_conversion_impl!(Room);
 resource_conversion_impl!(Scene);
 resource_conversion_impl!(SmartScene);
+resource_conversion_impl!(Temperature);
 resource_conversion_impl!(ZigbeeConnectivity);
 resource_conversion_impl!(ZigbeeDeviceDiscovery);
 resource_conversion_impl!(Zone);
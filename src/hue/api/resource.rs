@@ -1,5 +1,6 @@
 use std::fmt::{self, Debug};
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -16,16 +17,20 @@ pub enum RType {
     Button,
     Device,
     Entertainment,
+    EntertainmentConfiguration,
     GeofenceClient,
     Geolocation,
     GroupedLight,
     Homekit,
     Light,
+    LightLevel,
     Matter,
+    Motion,
     PublicImage,
     Room,
     Scene,
     SmartScene,
+    Temperature,
     ZigbeeConnectivity,
     ZigbeeDeviceDiscovery,
     Zone,
@@ -79,6 +84,27 @@ impl ResourceRecord {
     }
 }
 
+/// A resource record sharing its resource with the resource store, so it
+/// can be serialized without a deep copy
+#[derive(Clone, Debug, Serialize)]
+pub struct SharedRecord {
+    pub id: Uuid,
+    id_v1: String,
+    #[serde(flatten)]
+    pub obj: Arc<Resource>,
+}
+
+impl SharedRecord {
+    #[must_use]
+    pub fn new(id: Uuid, obj: Arc<Resource>) -> Self {
+        Self {
+            id,
+            id_v1: format!("/legacy/{}", id.as_simple()),
+            obj,
+        }
+    }
+}
+
 #[derive(Copy, Hash, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ResourceLink {
     pub rid: Uuid,
@@ -25,7 +25,7 @@ impl Room {
     }
 }
 
-#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RoomArchetype {
     LivingRoom,
@@ -78,4 +78,44 @@ impl RoomMetadata {
             name: name.to_string(),
         }
     }
+
+    pub fn apply(&mut self, upd: RoomMetadataUpdate) {
+        if let Some(name) = upd.name {
+            self.name = name;
+        }
+        if let Some(archetype) = upd.archetype {
+            self.archetype = archetype;
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RoomMetadataUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archetype: Option<RoomArchetype>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RoomUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RoomMetadataUpdate>,
+}
+
+impl RoomUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_metadata(self, metadata: RoomMetadata) -> Self {
+        Self {
+            metadata: Some(RoomMetadataUpdate {
+                name: Some(metadata.name),
+                archetype: Some(metadata.archetype),
+            }),
+        }
+    }
 }
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::hue::api::{ColorTemperatureUpdate, ColorUpdate, DimmingUpdate, On, ResourceLink};
+use crate::hue::api::{
+    ColorTemperatureUpdate, ColorUpdate, DimmingUpdate, Light, On, ResourceLink,
+};
 
 #[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(tag = "active", rename_all = "snake_case")]
@@ -46,6 +48,16 @@ pub struct Scene {
     pub status: Option<SceneStatus>,
 }
 
+impl Scene {
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            Some(SceneStatus::Static | SceneStatus::DynamicPalette)
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SceneAction {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -58,6 +70,49 @@ pub struct SceneAction {
     pub on: Option<On>,
 }
 
+impl SceneAction {
+    /// Tolerances for comparing light state, since values are rounded by z2m
+    const BRIGHTNESS_MARGIN: f64 = 1.0;
+    const MIREK_MARGIN: u32 = 5;
+    const XY_MARGIN: f64 = 0.01;
+
+    /// Returns true, if `light` is (still) in the state set by this action
+    #[must_use]
+    pub fn matches(&self, light: &Light) -> bool {
+        if let Some(on) = self.on {
+            if on.on != light.on.on {
+                return false;
+            }
+            /* other attributes are irrelevant for lights turned off */
+            if !on.on {
+                return true;
+            }
+        }
+
+        if let (Some(dim), Some(current)) = (&self.dimming, &light.dimming) {
+            if (dim.brightness - current.brightness).abs() > Self::BRIGHTNESS_MARGIN {
+                return false;
+            }
+        }
+
+        if let (Some(ct), Some(mirek)) = (&self.color_temperature, light.as_mirek_opt()) {
+            if ct.mirek.abs_diff(mirek) > Self::MIREK_MARGIN {
+                return false;
+            }
+        }
+
+        if let (Some(col), Some(current)) = (&self.color, &light.color) {
+            if (col.xy.x - current.xy.x).abs() > Self::XY_MARGIN
+                || (col.xy.y - current.xy.y).abs() > Self::XY_MARGIN
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SceneActionElement {
     pub action: SceneAction,
@@ -88,6 +143,11 @@ impl SceneUpdate {
         Self::default()
     }
 
+    #[must_use]
+    pub fn with_metadata(self, metadata: Option<SceneMetadata>) -> Self {
+        Self { metadata, ..self }
+    }
+
     #[must_use]
     pub fn with_actions(self, actions: Option<Vec<SceneActionElement>>) -> Self {
         Self { actions, ..self }
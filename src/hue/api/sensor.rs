@@ -0,0 +1,250 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::hue::api::ResourceLink;
+use crate::hue::date_format;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Motion {
+    pub owner: ResourceLink,
+    pub enabled: bool,
+    pub motion: MotionData,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<MotionSensitivity>,
+}
+
+impl Motion {
+    #[must_use]
+    pub const fn new(owner: ResourceLink) -> Self {
+        Self {
+            owner,
+            enabled: true,
+            motion: MotionData {
+                motion: false,
+                motion_valid: true,
+                motion_report: None,
+            },
+            sensitivity: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MotionData {
+    pub motion: bool,
+    pub motion_valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motion_report: Option<MotionReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MotionReport {
+    #[serde(with = "date_format::utc")]
+    pub changed: DateTime<Utc>,
+    pub motion: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MotionSensitivity {
+    pub status: String,
+    pub sensitivity: u32,
+    pub sensitivity_max: u32,
+}
+
+impl MotionSensitivity {
+    #[must_use]
+    pub fn new(sensitivity: u32, sensitivity_max: u32) -> Self {
+        Self {
+            status: "set".to_string(),
+            sensitivity,
+            sensitivity_max,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MotionSensitivityUpdate {
+    pub sensitivity: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MotionUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motion: Option<MotionData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<MotionSensitivityUpdate>,
+    /* bifrost extension: seconds until zigbee2mqtt clears occupancy */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupancy_timeout: Option<u32>,
+}
+
+impl MotionUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub const fn with_enabled(self, enabled: bool) -> Self {
+        Self {
+            enabled: Some(enabled),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_motion(self, motion: MotionData) -> Self {
+        Self {
+            motion: Some(motion),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_sensitivity(self, sensitivity: Option<&MotionSensitivity>) -> Self {
+        Self {
+            sensitivity: sensitivity.map(|sens| MotionSensitivityUpdate {
+                sensitivity: sens.sensitivity,
+            }),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LightLevel {
+    pub owner: ResourceLink,
+    pub enabled: bool,
+    pub light: LightLevelData,
+}
+
+impl LightLevel {
+    #[must_use]
+    pub const fn new(owner: ResourceLink) -> Self {
+        Self {
+            owner,
+            enabled: true,
+            light: LightLevelData {
+                light_level: 0,
+                light_level_valid: true,
+                light_level_report: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LightLevelData {
+    pub light_level: u32,
+    pub light_level_valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light_level_report: Option<LightLevelReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LightLevelReport {
+    #[serde(with = "date_format::utc")]
+    pub changed: DateTime<Utc>,
+    pub light_level: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LightLevelUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightLevelData>,
+}
+
+impl LightLevelUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub const fn with_enabled(self, enabled: bool) -> Self {
+        Self {
+            enabled: Some(enabled),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_light(self, light: LightLevelData) -> Self {
+        Self {
+            light: Some(light),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Temperature {
+    pub owner: ResourceLink,
+    pub enabled: bool,
+    pub temperature: TemperatureData,
+}
+
+impl Temperature {
+    #[must_use]
+    pub const fn new(owner: ResourceLink) -> Self {
+        Self {
+            owner,
+            enabled: true,
+            temperature: TemperatureData {
+                temperature: 0.0,
+                temperature_valid: true,
+                temperature_report: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemperatureData {
+    pub temperature: f64,
+    pub temperature_valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature_report: Option<TemperatureReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemperatureReport {
+    #[serde(with = "date_format::utc")]
+    pub changed: DateTime<Utc>,
+    pub temperature: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TemperatureUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<TemperatureData>,
+}
+
+impl TemperatureUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub const fn with_enabled(self, enabled: bool) -> Self {
+        Self {
+            enabled: Some(enabled),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_temperature(self, temperature: TemperatureData) -> Self {
+        Self {
+            temperature: Some(temperature),
+            ..self
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use uuid::{uuid, Uuid};
 
-use crate::hue::api::{DeviceArchetype, ResourceLink, SceneMetadata};
+use crate::hue::api::{DeviceArchetype, ResourceLink, RoomMetadata, SceneMetadata};
 use crate::hue::{best_guess_timezone, date_format};
+use crate::model::pairing::PairingProgress;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Bridge {
@@ -32,6 +34,8 @@ pub struct ButtonMetadata {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ButtonData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub button_report: Option<ButtonReport>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -40,6 +44,11 @@ pub struct ButtonData {
     pub event_values: Option<Value>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ButtonUpdate {
+    pub button: ButtonData,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ButtonReport {
     #[serde(with = "date_format::utc")]
@@ -65,8 +74,39 @@ pub struct BehaviorScript {
     pub version: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct BehaviorInstance {}
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BehaviorInstance {
+    #[serde(default)]
+    pub configuration: Value,
+    #[serde(default)]
+    pub dependees: Vec<Value>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub metadata: BehaviorInstanceMetadata,
+    #[serde(default)]
+    pub script_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BehaviorInstanceMetadata {
+    #[serde(default)]
+    pub name: String,
+}
+
+impl BehaviorInstance {
+    /* Script id of the built-in "Wake up" behavior */
+    pub const WAKE_UP: Uuid = uuid!("ff8957e3-2eb9-4699-a0c8-ad2cb3ede704");
+
+    /* Script id of the built-in "Go to sleep" behavior */
+    pub const GO_TO_SLEEP: Uuid = uuid!("7e571ac6-f363-42e1-809a-4cbf6523ed72");
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Entertainment {
@@ -143,9 +183,11 @@ pub struct SmartScene {
     pub week_timeslots: Value,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Copy, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ZigbeeConnectivityStatus {
+    Connected,
+    Disconnected,
     ConnectivityIssue,
 }
 
@@ -156,6 +198,39 @@ pub struct ZigbeeConnectivity {
     pub mac_address: String,
     pub owner: ResourceLink,
     pub status: ZigbeeConnectivityStatus,
+
+    /* diagnostics reported by zigbee2mqtt (not part of the hue api) */
+    #[serde(
+        default,
+        with = "date_format::utc_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_seen: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linkquality: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+}
+
+impl ZigbeeConnectivity {
+    /// Keep the diagnostics of a previous version of this service
+    #[must_use]
+    pub fn with_diagnostics(self, old: Option<&Self>) -> Self {
+        match old {
+            Some(old) => Self {
+                last_seen: old.last_seen,
+                linkquality: old.linkquality,
+                rssi: old.rssi,
+                ..self
+            },
+            None => self,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ZigbeeConnectivityUpdate {
+    pub status: ZigbeeConnectivityStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -164,9 +239,17 @@ pub struct ZigbeeDeviceDiscovery {
     pub status: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ZigbeeDeviceDiscoveryUpdate {
+    pub status: String,
+    /* bifrost extension: progress of a device being paired */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairing: Option<PairingProgress>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Zone {
-    pub metadata: Metadata,
+    pub metadata: RoomMetadata,
     pub children: Vec<ResourceLink>,
     #[serde(default)]
     pub services: Vec<ResourceLink>,
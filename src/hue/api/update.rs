@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::hue::api::{GroupedLightUpdate, LightUpdate, RType, SceneUpdate};
+use crate::hue::api::{
+    ButtonUpdate, EntertainmentConfigurationUpdate, GroupedLightUpdate, LightLevelUpdate,
+    LightUpdate, MotionUpdate, RType, RoomUpdate, SceneUpdate, TemperatureUpdate,
+    ZigbeeConnectivityUpdate, ZigbeeDeviceDiscoveryUpdate,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -10,30 +14,44 @@ pub enum Update {
     /* BehaviorInstance(BehaviorInstanceUpdate), */
     /* Bridge(BridgeUpdate), */
     /* BridgeHome(BridgeHomeUpdate), */
+    Button(ButtonUpdate),
     /* Device(DeviceUpdate), */
     /* Entertainment(EntertainmentUpdate), */
+    EntertainmentConfiguration(EntertainmentConfigurationUpdate),
     /* GeofenceClient(GeofenceClientUpdate), */
     /* Geolocation(GeolocationUpdate), */
     GroupedLight(GroupedLightUpdate),
     /* Homekit(HomekitUpdate), */
     Light(LightUpdate),
+    LightLevel(LightLevelUpdate),
     /* Matter(MatterUpdate), */
+    Motion(MotionUpdate),
     /* PublicImage(PublicImageUpdate), */
-    /* Room(RoomUpdate), */
+    Room(RoomUpdate),
     Scene(SceneUpdate),
     /* SmartScene(SmartSceneUpdate), */
-    /* ZigbeeConnectivity(ZigbeeConnectivityUpdate), */
-    /* ZigbeeDeviceDiscovery(ZigbeeDeviceDiscoveryUpdate), */
-    /* Zone(ZoneUpdate), */
+    Temperature(TemperatureUpdate),
+    ZigbeeConnectivity(ZigbeeConnectivityUpdate),
+    ZigbeeDeviceDiscovery(ZigbeeDeviceDiscoveryUpdate),
+    Zone(RoomUpdate),
 }
 
 impl Update {
     #[must_use]
     pub const fn rtype(&self) -> RType {
         match self {
+            Self::Button(_) => RType::Button,
+            Self::EntertainmentConfiguration(_) => RType::EntertainmentConfiguration,
             Self::GroupedLight(_) => RType::GroupedLight,
             Self::Light(_) => RType::Light,
+            Self::LightLevel(_) => RType::LightLevel,
+            Self::Motion(_) => RType::Motion,
+            Self::Room(_) => RType::Room,
             Self::Scene(_) => RType::Scene,
+            Self::Temperature(_) => RType::Temperature,
+            Self::ZigbeeConnectivity(_) => RType::ZigbeeConnectivity,
+            Self::ZigbeeDeviceDiscovery(_) => RType::ZigbeeDeviceDiscovery,
+            Self::Zone(_) => RType::Zone,
         }
     }
 }
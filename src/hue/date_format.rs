@@ -22,26 +22,50 @@ pub mod utc {
     }
 }
 
+pub mod utc_opt {
+    use chrono::{DateTime, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => super::utc::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::utc")] DateTime<Utc>);
+
+        let date = Option::<Wrapper>::deserialize(deserializer)?;
+        Ok(date.map(|Wrapper(date)| date))
+    }
+}
+
+/* local time is sent without timezone information, like a real bridge does */
 pub mod local {
-    use chrono::{DateTime, Local, NaiveDateTime};
+    use chrono::NaiveDateTime;
     use serde::{self, de::Error, Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(date: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let s = format!("{}Z", date.format(super::FORMAT));
+        let s = date.format(super::FORMAT).to_string();
         serializer.serialize_str(&s)
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        let dt = NaiveDateTime::parse_from_str(&s, super::FORMAT).map_err(Error::custom)?;
-        dt.and_local_timezone(Local)
-            .single()
-            .ok_or_else(|| Error::custom("Localtime conversion failed"))
+        NaiveDateTime::parse_from_str(s.trim_end_matches('Z'), super::FORMAT).map_err(Error::custom)
     }
 }
@@ -1,3 +1,5 @@
+use std::fmt::{self, Display};
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -26,6 +28,42 @@ pub struct EventBlock {
     pub id: Uuid,
     #[serde(flatten)]
     pub event: Event,
+    #[serde(skip)]
+    pub seq: EventSequence,
+    /// Application owning the affected resource, if it is private to one
+    #[serde(skip)]
+    pub app_id: Option<String>,
+}
+
+/// Position of an event in the event stream, used as the SSE event id.
+///
+/// This is persisted in the state file, so event ids keep increasing across
+/// restarts, even if the system clock is behind the last seen event.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventSequence {
+    pub timestamp: i64,
+    pub index: u32,
+}
+
+impl EventSequence {
+    /// Advance to the next position, based on the current time
+    #[must_use]
+    pub fn advance(&mut self) -> Self {
+        let now = Utc::now().timestamp();
+        if now > self.timestamp {
+            self.timestamp = now;
+            self.index = 0;
+        } else {
+            self.index += 1;
+        }
+        *self
+    }
+}
+
+impl Display for EventSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.timestamp, self.index)
+    }
 }
 
 impl EventBlock {
@@ -35,6 +73,8 @@ impl EventBlock {
             creationtime: Utc::now(),
             id: Uuid::new_v4(),
             event: Event::Add(Add { data: vec![data] }),
+            seq: EventSequence::default(),
+            app_id: None,
         }
     }
 
@@ -45,9 +85,25 @@ impl EventBlock {
             event: Event::Update(Update {
                 data: vec![serde_json::to_value(api::UpdateRecord::new(id, data))?],
             }),
+            seq: EventSequence::default(),
+            app_id: None,
         })
     }
 
+    #[must_use]
+    pub fn with_app_id(self, app_id: Option<String>) -> Self {
+        Self { app_id, ..self }
+    }
+
+    /// Returns true, if this event should be sent to the given application.
+    /// Events for private resources are never sent to anonymous clients.
+    #[must_use]
+    pub fn visible_to(&self, app_id: Option<&str>) -> bool {
+        self.app_id
+            .as_deref()
+            .map_or(true, |owner| app_id == Some(owner))
+    }
+
     pub fn delete(link: &ResourceLink) -> ApiResult<Self> {
         Ok(Self {
             creationtime: Utc::now(),
@@ -59,6 +115,8 @@ impl EventBlock {
                     "type": link.rtype,
                 })],
             }),
+            seq: EventSequence::default(),
+            app_id: None,
         })
     }
 }
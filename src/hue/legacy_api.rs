@@ -1,12 +1,14 @@
 use std::{collections::HashMap, net::Ipv4Addr};
 
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use mac_address::MacAddress;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use uuid::Uuid;
+use uuid::{uuid, Uuid};
 
+use crate::config::LimitsConfig;
 use crate::hue::{api, best_guess_timezone};
+use crate::model::units;
 
 use super::date_format;
 
@@ -18,6 +20,26 @@ pub struct HueError {
     description: String,
 }
 
+impl HueError {
+    #[must_use]
+    pub fn link_button_not_pressed() -> Self {
+        Self {
+            typ: 101,
+            address: String::new(),
+            description: "link button not pressed".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn unauthorized_user(address: &str) -> Self {
+        Self {
+            typ: 1,
+            address: address.to_string(),
+            description: "unauthorized user".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HueResult<T> {
@@ -56,6 +78,55 @@ impl Default for ApiShortConfig {
     }
 }
 
+impl ApiShortConfig {
+    /// Api version of a current hue bridge (for the homebridge quirk)
+    pub const QUIRKS_API_VERSION: &'static str = "1.67.0";
+
+    /// Firmware version of a current hue bridge (for the homebridge quirk)
+    pub const QUIRKS_SW_VERSION: &'static str = "1967054020";
+
+    /// Report the versions of a current bridge, since some clients refuse
+    /// bridges with an outdated api version
+    #[must_use]
+    pub fn with_quirks(mut self) -> Self {
+        self.apiversion = Self::QUIRKS_API_VERSION.to_string();
+        self.swversion = Self::QUIRKS_SW_VERSION.to_string();
+        self
+    }
+}
+
+/// A firmware version in "major.minor.patch" form.
+///
+/// Homekit only accepts (up to) three numeric components. Versions reported by z2m vary between
+/// vendors (e.g., "1.2.3", "v2.1", "20230918", or nothing at all).
+#[must_use]
+pub fn quirks_firmware_version(version: &str) -> String {
+    let parts: Vec<u32> = version
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .take_while(|part| !part.is_empty())
+        .filter_map(|part| part.parse().ok())
+        .take(3)
+        .collect();
+
+    let part = |idx: usize| parts.get(idx).copied().unwrap_or_default();
+    format!("{}.{}.{}", part(0), part(1), part(2))
+}
+
+/// A v1 unique id in the form of a real bridge.
+///
+/// That is a (zigbee) mac address, followed by an endpoint and (optionally)
+/// a cluster, e.g., "00:17:88:01:02:03:04:05-0b". Bifrost has no zigbee addresses to offer,
+/// so the mac address is taken from the resource id.
+#[must_use]
+pub fn quirks_uniqueid(uuid: &Uuid, suffix: &str) -> String {
+    let mac: Vec<String> = uuid.as_bytes()[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("{}-{suffix}", mac.join(":"))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ApiResourceType {
@@ -76,10 +147,39 @@ pub struct NewUser {
     generateclientkey: Option<bool>,
 }
 
+impl NewUser {
+    #[must_use]
+    pub fn devicetype(&self) -> &str {
+        &self.devicetype
+    }
+
+    #[must_use]
+    pub fn wants_clientkey(&self) -> bool {
+        self.generateclientkey.unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewUserReply {
     pub username: Uuid,
-    pub clientkey: Uuid,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_clientkey"
+    )]
+    pub clientkey: Option<Uuid>,
+}
+
+/// Client keys are handed out as 32 uppercase hex digits, which clients
+/// decode into the 16-byte pre-shared key for entertainment streaming
+#[allow(clippy::ref_option)]
+fn serialize_clientkey<S>(clientkey: &Option<Uuid>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match clientkey {
+        Some(key) => serializer.serialize_str(&format!("{:X}", key.simple())),
+        None => serializer.serialize_none(),
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -196,9 +296,9 @@ impl SoftwareUpdate2 {
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Whitelist {
-    #[serde(with = "date_format::utc")]
+    #[serde(with = "date_format::utc", rename = "create date")]
     pub create_date: DateTime<Utc>,
-    #[serde(with = "date_format::utc")]
+    #[serde(with = "date_format::utc", rename = "last use date")]
     pub last_use_date: DateTime<Utc>,
     pub name: String,
 }
@@ -227,7 +327,7 @@ pub struct ApiConfig {
     #[serde(with = "date_format::utc", rename = "UTC")]
     pub utc: DateTime<Utc>,
     #[serde(with = "date_format::local")]
-    pub localtime: DateTime<Local>,
+    pub localtime: NaiveDateTime,
     pub whitelist: HashMap<Uuid, Whitelist>,
 }
 
@@ -288,7 +388,7 @@ impl ApiGroup {
                 on: glight.on.is_some_and(|on| on.on),
                 bri: glight
                     .dimming
-                    .map(|dim| (dim.brightness * 2.54) as u32)
+                    .map(|dim| units::percent_to_v1(dim.brightness))
                     .unwrap_or_default(),
                 hue: 0,
                 sat: 0,
@@ -317,17 +417,27 @@ pub enum LightColorMode {
     Xy,
 }
 
+/// State of a v1 light. Like on a real bridge, only the fields that match
+/// the type of the light are present, since clients (e.g., voice
+/// assistants) decide what a light can do from them.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiLightState {
     on: bool,
-    bri: u32,
-    hue: u32,
-    sat: u32,
-    effect: String,
-    xy: [f64; 2],
-    ct: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bri: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hue: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sat: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effect: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xy: Option<[f64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ct: Option<u32>,
     alert: String,
-    colormode: LightColorMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colormode: Option<LightColorMode>,
     mode: String,
     reachable: bool,
 }
@@ -338,6 +448,10 @@ pub struct ApiLightStateUpdate {
     pub on: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bri: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub hue: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sat: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub xy: Option<[f64; 2]>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -349,13 +463,46 @@ impl From<api::SceneAction> for ApiLightStateUpdate {
     fn from(action: api::SceneAction) -> Self {
         Self {
             on: action.on.map(|on| on.on),
-            bri: action.dimming.map(|dim| (dim.brightness * 2.54) as u32),
+            bri: action
+                .dimming
+                .map(|dim| units::percent_to_v1(dim.brightness)),
+            hue: None,
+            sat: None,
             xy: action.color.map(|col| col.xy.into()),
             ct: action.color_temperature.map(|ct| ct.mirek),
         }
     }
 }
 
+impl ApiLightStateUpdate {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.on.is_none()
+            && self.bri.is_none()
+            && self.hue.is_none()
+            && self.sat.is_none()
+            && self.xy.is_none()
+            && self.ct.is_none()
+    }
+}
+
+/// Group action: a state update for all lights in the group, and/or a
+/// scene to recall
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiGroupActionUpdate {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scene: Option<Uuid>,
+    #[serde(flatten)]
+    pub state: ApiLightStateUpdate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ApiLightSearch {
+    /* Serial numbers (or install codes) of lights to search for */
+    #[serde(default)]
+    pub deviceid: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiLight {
     state: ApiLightState,
@@ -375,31 +522,106 @@ pub struct ApiLight {
 }
 
 impl ApiLight {
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[must_use]
+    pub const fn with_reachable(mut self, reachable: bool) -> Self {
+        self.state.reachable = reachable;
+        self
+    }
+
+    /// Report metadata the way homebridge-hue expects it (see
+    /// [`quirks_firmware_version`] and [`quirks_uniqueid`])
+    #[must_use]
+    pub fn with_quirks(mut self, uuid: &Uuid) -> Self {
+        self.swversion = quirks_firmware_version(&self.swversion);
+        self.uniqueid = quirks_uniqueid(uuid, "0b");
+        self
+    }
+
+    /// The v1 type of a light (and a matching product name), from the
+    /// features it has
+    const fn light_type(light: &api::Light) -> (&'static str, &'static str) {
+        match (
+            light.color.is_some(),
+            light.color_temperature.is_some(),
+            light.dimming.is_some(),
+        ) {
+            (true, true, _) => ("Extended color light", "Hue color lamp"),
+            (true, false, _) => ("Color light", "Hue color lamp"),
+            (false, true, _) => ("Color temperature light", "Hue ambiance lamp"),
+            (false, false, true) => ("Dimmable light", "Hue white lamp"),
+            (false, false, false) => ("On/Off plug-in unit", "Hue smart plug"),
+        }
+    }
+
+    fn capabilities(light: &api::Light) -> Value {
+        let mut control = json!({});
+
+        if light.dimming.is_some() {
+            control["maxlumen"] = json!(300);
+            control["mindimlevel"] = json!(200);
+        }
+
+        if let Some(color) = &light.color {
+            let gamut = color.gamut.as_ref().unwrap_or(&api::ColorGamut::GAMUT_C);
+            control["colorgamut"] = json!([
+                [gamut.red.x, gamut.red.y],
+                [gamut.green.x, gamut.green.y],
+                [gamut.blue.x, gamut.blue.y],
+            ]);
+            control["colorgamuttype"] = json!(color.gamut_type);
+        }
+
+        if let Some(ct) = &light.color_temperature {
+            control["ct"] = json!({
+                "min": ct.mirek_schema.mirek_minimum,
+                "max": ct.mirek_schema.mirek_maximum,
+            });
+        }
+
+        json!({
+            "certified": true,
+            "control": control,
+            "streaming": {
+                "proxy": true,
+                "renderer": light.color.is_some(),
+            }
+        })
+    }
+
     #[must_use]
     pub fn from_dev_and_light(uuid: &Uuid, dev: api::Device, light: api::Light) -> Self {
-        let colormode = if light.color.is_some() {
-            LightColorMode::Xy
-        } else {
-            LightColorMode::Ct
+        let (light_type, productname) = Self::light_type(&light);
+        let capabilities = Self::capabilities(&light);
+
+        let ct = light.color_temperature.as_ref().map(|ct| {
+            ct.mirek
+                .unwrap_or(ct.mirek_schema.mirek_minimum)
+                .clamp(ct.mirek_schema.mirek_minimum, ct.mirek_schema.mirek_maximum)
+        });
+
+        /* lights in color temperature mode have a (valid) mirek value */
+        let colormode = match (&light.color, &light.color_temperature) {
+            (Some(_), _) if light.as_mirek_opt().is_none() => Some(LightColorMode::Xy),
+            (_, Some(_)) => Some(LightColorMode::Ct),
+            (Some(_), None) => Some(LightColorMode::Xy),
+            (None, None) => None,
         };
 
+        let color = light.color.is_some();
+        let archetype = serde_json::to_value(&light.metadata.archetype).unwrap_or_default();
+
         Self {
             state: ApiLightState {
                 on: light.on.on,
                 bri: light
                     .dimming
-                    .map(|dim| (dim.brightness * 2.54) as u32)
-                    .unwrap_or_default(),
-                hue: 0,
-                sat: 0,
-                effect: String::new(),
-                xy: light.color.map(|col| col.xy.into()).unwrap_or_default(),
-                ct: light
-                    .color_temperature
-                    .and_then(|ct| ct.mirek)
-                    .unwrap_or_default(),
-                alert: String::new(),
+                    .map(|dim| units::percent_to_v1(dim.brightness)),
+                hue: color.then_some(0),
+                sat: color.then_some(0),
+                effect: color.then(|| "none".to_string()),
+                xy: light.color.map(|col| col.xy.into()),
+                ct,
+                alert: "none".to_string(),
                 colormode,
                 mode: "homeautomation".to_string(),
                 reachable: true,
@@ -408,31 +630,11 @@ impl ApiLight {
             name: light.metadata.name,
             modelid: dev.product_data.product_name,
             manufacturername: dev.product_data.manufacturer_name,
-            productname: "Hue color spot".to_string(),
+            productname: productname.to_string(),
             productid: dev.product_data.model_id,
-            capabilities: json!({
-                "certified": true,
-                "control": {
-                    "colorgamut": [
-                        [0.6915, 0.3083 ],
-                        [0.17,   0.7    ],
-                        [0.1532, 0.0475 ],
-                    ],
-                    "colorgamuttype": "C",
-                    "ct": {
-                        "max": 500,
-                        "min": 153
-                    },
-                    "maxlumen": 300,
-                    "mindimlevel": 200
-                },
-                "streaming": {
-                    "proxy": true,
-                    "renderer": true
-                }
-            }),
+            capabilities,
             config: json!({
-                "archetype": "spotbulb",
+                "archetype": archetype,
                 "function": "mixed",
                 "direction": "downwards",
                 "startup": {
@@ -440,7 +642,7 @@ impl ApiLight {
                     "configured": true
                 }
             }),
-            light_type: "Extended color light".to_string(),
+            light_type: light_type.to_string(),
             uniqueid: uuid.as_simple().to_string(),
             swversion: dev.product_data.software_version,
             swconfigid: String::new(),
@@ -525,8 +727,202 @@ impl ApiScene {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiSchedule {}
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ApiSensorType {
+    /* Zigbee Green Power switch (Hue Tap, Friends of Hue) */
+    #[serde(rename = "ZGPSwitch")]
+    ZgpSwitch,
+    /* Zigbee Light Link switch (Hue dimmer switch) */
+    #[serde(rename = "ZLLSwitch")]
+    ZllSwitch,
+    /* Built-in sensor, reporting whether the sun is up */
+    Daylight,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ApiSensor {}
+#[serde(untagged)]
+pub enum ApiSensorState {
+    Switch {
+        buttonevent: Option<u32>,
+        lastupdated: String,
+    },
+    Daylight {
+        daylight: Option<bool>,
+        lastupdated: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiSensorConfig {
+    pub on: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reachable: Option<bool>,
+    /* daylight sensor: location set, and offsets (in minutes) */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configured: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunriseoffset: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunsetoffset: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiSensor {
+    pub state: ApiSensorState,
+    pub config: ApiSensorConfig,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub sensor_type: ApiSensorType,
+    pub modelid: String,
+    pub manufacturername: String,
+    pub productname: String,
+    pub uniqueid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swversion: Option<String>,
+}
+
+impl ApiSensor {
+    /// Id of the daylight sensor, which has no resource of its own
+    pub const DAYLIGHT_ID: Uuid = uuid!("44499d7d-70f2-4be0-99e5-83f034e92f9f");
+
+    /// Hue Tap button events, for control ids 1 through 4
+    const TAP_EVENTS: [u32; 4] = [34, 16, 17, 18];
+
+    /// Model id of a hue dimmer switch
+    const DIMMER_MODEL_ID: &'static str = "RWL021";
+
+    /// Report metadata the way homebridge-hue expects it. It only exposes
+    /// switches with a model id it knows the buttons of, so dimmer switches
+    /// are reported as hue dimmer switches.
+    #[must_use]
+    pub fn with_quirks(mut self, uuid: &Uuid) -> Self {
+        match self.sensor_type {
+            ApiSensorType::ZgpSwitch => {
+                self.uniqueid = quirks_uniqueid(uuid, "f2");
+            }
+            ApiSensorType::ZllSwitch => {
+                self.modelid = Self::DIMMER_MODEL_ID.to_string();
+                self.manufacturername = "Signify Netherlands B.V.".to_string();
+                self.uniqueid = quirks_uniqueid(uuid, "02-fc00");
+            }
+            ApiSensorType::Daylight => {}
+        }
+        self.swversion = self.swversion.as_deref().map(quirks_firmware_version);
+        self
+    }
+
+    /// Build a v1 switch sensor from a device and its buttons. Dimmer
+    /// switches are recognized by their support for long presses.
+    #[must_use]
+    pub fn from_dev_and_buttons(uuid: &Uuid, dev: &api::Device, buttons: &[api::Button]) -> Self {
+        let sensor_type = if buttons.iter().any(|btn| {
+            btn.button
+                .event_values
+                .as_ref()
+                .and_then(Value::as_array)
+                .is_some_and(|values| values.iter().any(|v| v == "long_release"))
+        }) {
+            ApiSensorType::ZllSwitch
+        } else {
+            ApiSensorType::ZgpSwitch
+        };
+
+        let last = buttons
+            .iter()
+            .filter_map(|btn| Some((btn.metadata.control_id, btn.button.button_report.as_ref()?)))
+            .max_by_key(|(_, report)| report.updated);
+
+        let buttonevent = last.and_then(|(control_id, report)| match sensor_type {
+            ApiSensorType::ZgpSwitch => Self::TAP_EVENTS
+                .get(usize::try_from(control_id).ok()?.checked_sub(1)?)
+                .copied(),
+            ApiSensorType::ZllSwitch => {
+                let code = match report.event.as_str() {
+                    "initial_press" => 0,
+                    "repeat" => 1,
+                    "short_release" => 2,
+                    "long_release" => 3,
+                    _ => return None,
+                };
+                Some(control_id * 1000 + code)
+            }
+            ApiSensorType::Daylight => None,
+        });
+
+        let lastupdated = last.map_or_else(
+            || "none".to_string(),
+            |(_, report)| report.updated.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        );
+
+        let (modelid, manufacturername, productname, swversion) =
+            if sensor_type == ApiSensorType::ZgpSwitch {
+                (
+                    "ZGPSWITCH".to_string(),
+                    "Philips".to_string(),
+                    "Hue tap switch".to_string(),
+                    None,
+                )
+            } else {
+                (
+                    dev.product_data.product_name.clone(),
+                    dev.product_data.manufacturer_name.clone(),
+                    "Hue dimmer switch".to_string(),
+                    Some(dev.product_data.software_version.clone()),
+                )
+            };
+
+        Self {
+            state: ApiSensorState::Switch {
+                buttonevent,
+                lastupdated,
+            },
+            config: ApiSensorConfig {
+                on: true,
+                battery: None,
+                reachable: (sensor_type == ApiSensorType::ZllSwitch).then_some(true),
+                configured: None,
+                sunriseoffset: None,
+                sunsetoffset: None,
+            },
+            name: dev.metadata.name.clone(),
+            sensor_type,
+            modelid,
+            manufacturername,
+            productname,
+            uniqueid: uuid.as_simple().to_string(),
+            swversion,
+        }
+    }
+
+    /// The daylight sensor of the bridge, with the sunrise and sunset
+    /// offsets (in minutes) it was configured with
+    #[must_use]
+    pub fn daylight(daylight: bool, sunriseoffset: i32, sunsetoffset: i32) -> Self {
+        Self {
+            state: ApiSensorState::Daylight {
+                daylight: Some(daylight),
+                lastupdated: "none".to_string(),
+            },
+            config: ApiSensorConfig {
+                on: true,
+                battery: None,
+                reachable: None,
+                configured: Some(true),
+                sunriseoffset: Some(sunriseoffset),
+                sunsetoffset: Some(sunsetoffset),
+            },
+            name: "Daylight".to_string(),
+            sensor_type: ApiSensorType::Daylight,
+            modelid: "PHDL00".to_string(),
+            manufacturername: "Signify Netherlands B.V.".to_string(),
+            productname: "Daylight".to_string(),
+            uniqueid: Self::DAYLIGHT_ID.as_simple().to_string(),
+            swversion: Some("1.0".to_string()),
+        }
+    }
+}
 
 #[allow(clippy::zero_sized_map_values)]
 #[derive(Debug, Serialize, Deserialize)]
@@ -538,7 +934,7 @@ pub struct ApiUserConfig {
     pub rules: HashMap<u32, ApiRule>,
     pub scenes: HashMap<String, ApiScene>,
     pub schedules: HashMap<u32, ApiSchedule>,
-    pub sensors: HashMap<u32, ApiSensor>,
+    pub sensors: HashMap<String, ApiSensor>,
 }
 
 impl Default for ApiConfig {
@@ -562,7 +958,7 @@ impl Default for ApiConfig {
             gateway: Ipv4Addr::UNSPECIFIED,
             timezone: best_guess_timezone(),
             utc: Utc::now(),
-            localtime: Local::now(),
+            localtime: Local::now().naive_local(),
             whitelist: HashMap::new(),
         }
     }
@@ -626,7 +1022,7 @@ pub struct Capabilities {
 
 impl Capabilities {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(limits: &LimitsConfig, rules: u32) -> Self {
         Self {
             lights: Capacity::new(63, 60),
             sensors: SensorsCapacity {
@@ -637,10 +1033,11 @@ impl Capabilities {
                 zgp: Capacity::new(64, 63),
             },
             groups: Capacity::new(64, 60),
-            schedules: Capacity::new(100, 95),
+            /* schedules are not supported, so all of them are available */
+            schedules: Capacity::new(100, 100),
             rules: RulesCapacity {
-                available: 233,
-                total: 255,
+                available: limits.rules.saturating_sub(rules),
+                total: limits.rules,
                 conditions: Capacity::new(1500, 1451),
                 actions: Capacity::new(1000, 954),
             },
@@ -648,7 +1045,7 @@ impl Capabilities {
             streaming: StreamingCapacity {
                 available: 1,
                 total: 1,
-                channels: 20,
+                channels: limits.entertainment_channels,
             },
             timezones: json!({
                 "values": [
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::hue::scene_icons;
+
+/// Language of the names bifrost gives to the resources it creates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    Da,
+    De,
+    #[default]
+    En,
+    Es,
+    Fr,
+    Nl,
+}
+
+impl Locale {
+    /// Name of the bridge home device
+    #[must_use]
+    pub const fn bridge_home(self) -> &'static str {
+        match self {
+            Self::Da => "Bifrost Bridge Hjem",
+            Self::De => "Bifrost Bridge Zuhause",
+            Self::En => "Bifrost Bridge Home",
+            Self::Es => "Hogar Bifrost Bridge",
+            Self::Fr => "Maison Bifrost Bridge",
+            Self::Nl => "Bifrost Bridge Thuis",
+        }
+    }
+
+    /// Names of the built-in hue scenes, as shown by the hue app
+    #[must_use]
+    pub const fn scene_names(self) -> &'static [(&'static str, Uuid)] {
+        match self {
+            Self::Da => &[
+                ("Lys", scene_icons::BRIGHT),
+                ("Afslap", scene_icons::RELAX),
+                ("Natlys", scene_icons::NIGHT_LIGHT),
+                ("Hvil", scene_icons::REST),
+                ("Koncentrer", scene_icons::CONCENTRATE),
+                ("Dæmpet", scene_icons::DIMMED),
+                ("Energi", scene_icons::ENERGIZE),
+                ("Læs", scene_icons::READ),
+                ("Køligt lys", scene_icons::COOL_BRIGHT),
+            ],
+            Self::De => &[
+                ("Hell", scene_icons::BRIGHT),
+                ("Entspannen", scene_icons::RELAX),
+                ("Nachtlicht", scene_icons::NIGHT_LIGHT),
+                ("Ausruhen", scene_icons::REST),
+                ("Konzentrieren", scene_icons::CONCENTRATE),
+                ("Gedimmt", scene_icons::DIMMED),
+                ("Energie tanken", scene_icons::ENERGIZE),
+                ("Lesen", scene_icons::READ),
+                ("Kühles Licht", scene_icons::COOL_BRIGHT),
+            ],
+            Self::En => &[
+                ("Bright", scene_icons::BRIGHT),
+                ("Relax", scene_icons::RELAX),
+                ("Night Light", scene_icons::NIGHT_LIGHT),
+                ("Rest", scene_icons::REST),
+                ("Concentrate", scene_icons::CONCENTRATE),
+                ("Dimmed", scene_icons::DIMMED),
+                ("Energize", scene_icons::ENERGIZE),
+                ("Read", scene_icons::READ),
+                ("Cool Bright", scene_icons::COOL_BRIGHT),
+            ],
+            Self::Es => &[
+                ("Brillante", scene_icons::BRIGHT),
+                ("Relajarse", scene_icons::RELAX),
+                ("Luz nocturna", scene_icons::NIGHT_LIGHT),
+                ("Descansar", scene_icons::REST),
+                ("Concentrarse", scene_icons::CONCENTRATE),
+                ("Atenuada", scene_icons::DIMMED),
+                ("Energía", scene_icons::ENERGIZE),
+                ("Leer", scene_icons::READ),
+                ("Brillo frío", scene_icons::COOL_BRIGHT),
+            ],
+            Self::Fr => &[
+                ("Lumineux", scene_icons::BRIGHT),
+                ("Détente", scene_icons::RELAX),
+                ("Veilleuse", scene_icons::NIGHT_LIGHT),
+                ("Repos", scene_icons::REST),
+                ("Concentration", scene_icons::CONCENTRATE),
+                ("Tamisé", scene_icons::DIMMED),
+                ("Énergie", scene_icons::ENERGIZE),
+                ("Lecture", scene_icons::READ),
+                ("Lumière froide", scene_icons::COOL_BRIGHT),
+            ],
+            Self::Nl => &[
+                ("Helder", scene_icons::BRIGHT),
+                ("Ontspannen", scene_icons::RELAX),
+                ("Nachtlampje", scene_icons::NIGHT_LIGHT),
+                ("Rust", scene_icons::REST),
+                ("Concentreren", scene_icons::CONCENTRATE),
+                ("Gedimd", scene_icons::DIMMED),
+                ("Energie", scene_icons::ENERGIZE),
+                ("Lezen", scene_icons::READ),
+                ("Koel helder", scene_icons::COOL_BRIGHT),
+            ],
+        }
+    }
+
+    /// Icon of a built-in scene, by its (localized) name
+    #[must_use]
+    pub fn scene_icon(self, name: &str) -> Option<Uuid> {
+        self.scene_names()
+            .iter()
+            .find(|(scene, _)| scene.eq_ignore_ascii_case(name))
+            .map(|(_, icon)| *icon)
+    }
+}
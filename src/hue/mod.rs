@@ -2,7 +2,10 @@ pub mod api;
 pub mod date_format;
 pub mod event;
 pub mod legacy_api;
+pub mod locale;
 pub mod scene_icons;
+pub mod stream;
+pub mod upnp;
 
 pub const HUE_BRIDGE_V2_MODEL_ID: &str = "BSB002";
 
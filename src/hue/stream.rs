@@ -0,0 +1,280 @@
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::model::types::XY;
+use crate::z2m::update::DeviceUpdate;
+
+/// Magic bytes at the start of every entertainment stream frame
+const MAGIC: &[u8] = b"HueStream";
+
+/// Size of the part of the header shared by all protocol versions (magic,
+/// version, sequence number and color space)
+const COMMON_HEADER_SIZE: usize = 16;
+
+/// Size of the frame header (up to, and including, the configuration id)
+const HEADER_SIZE: usize = 52;
+
+/// Size of each channel in a frame: channel id, followed by three 16-bit
+/// color values
+const CHANNEL_SIZE: usize = 7;
+
+/// Channels in a single frame, at most
+pub const MAX_CHANNELS: usize = 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamColorSpace {
+    Rgb,
+    Xy,
+}
+
+/// The color of one channel, as 16-bit values: red, green and blue, or x, y
+/// and brightness (depending on the color space of the frame)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StreamChannel {
+    pub id: u8,
+    pub values: [u16; 3],
+}
+
+/// A frame of an entertainment stream (protocol version 2), as sent by
+/// streaming clients (e.g., Hue Sync) to the bridge
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamFrame {
+    pub sequence: u8,
+    pub color_space: StreamColorSpace,
+    pub config_id: Uuid,
+    pub channels: Vec<StreamChannel>,
+}
+
+fn invalid(msg: &str) -> ApiError {
+    ApiError::InvalidStreamFrame(msg.to_string())
+}
+
+impl StreamFrame {
+    pub fn parse(data: &[u8]) -> ApiResult<Self> {
+        if data.len() < COMMON_HEADER_SIZE || !data.starts_with(MAGIC) {
+            return Err(invalid("missing header"));
+        }
+        match data[9] {
+            2 => {}
+            1 => return Err(invalid("protocol version 1 is not supported")),
+            _ => return Err(invalid("unsupported protocol version")),
+        }
+        if data.len() < HEADER_SIZE {
+            return Err(invalid("truncated header"));
+        }
+
+        let color_space = match data[14] {
+            0 => StreamColorSpace::Rgb,
+            1 => StreamColorSpace::Xy,
+            _ => return Err(invalid("unknown color space")),
+        };
+
+        let config_id = std::str::from_utf8(&data[16..HEADER_SIZE])
+            .ok()
+            .and_then(|id| Uuid::try_parse(id).ok())
+            .ok_or_else(|| invalid("invalid entertainment configuration id"))?;
+
+        let body = &data[HEADER_SIZE..];
+        if body.len() % CHANNEL_SIZE != 0 || body.len() / CHANNEL_SIZE > MAX_CHANNELS {
+            return Err(invalid("invalid channel data"));
+        }
+
+        let channels = body
+            .chunks_exact(CHANNEL_SIZE)
+            .map(|chunk| StreamChannel {
+                id: chunk[0],
+                values: [1, 3, 5].map(|i| u16::from_be_bytes([chunk[i], chunk[i + 1]])),
+            })
+            .collect();
+
+        Ok(Self {
+            sequence: data[11],
+            color_space,
+            config_id,
+            channels,
+        })
+    }
+}
+
+impl StreamChannel {
+    /// The z2m update setting a light to the color of this channel. Updates
+    /// are applied without transition, since frames follow each other
+    /// closely.
+    #[must_use]
+    pub fn device_update(&self, color_space: StreamColorSpace) -> DeviceUpdate {
+        let [a, b, c] = self
+            .values
+            .map(|value| f64::from(value) / f64::from(u16::MAX));
+
+        let (xy, level) = match color_space {
+            StreamColorSpace::Rgb => {
+                let (xy, _) = XY::from_rgb_unit([a, b, c]);
+                (xy, a.max(b).max(c))
+            }
+            StreamColorSpace::Xy => (XY::new(a, b), c),
+        };
+
+        DeviceUpdate::default()
+            .with_state(Some(level > 0.0))
+            .with_brightness(Some((level * 254.0).round()))
+            .with_color_xy(Some(xy))
+            .with_transition(Some(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::{uuid, Uuid};
+
+    use super::{StreamChannel, StreamColorSpace, StreamFrame};
+    use crate::z2m::update::DeviceState;
+
+    const CONFIG_ID: Uuid = uuid!("1a8d99cc-967b-44f2-9202-43f976c0fa6b");
+
+    fn header(version: u8, color_space: u8) -> Vec<u8> {
+        let mut data = b"HueStream".to_vec();
+        data.extend([version, 0, 7, 0, 0, color_space, 0]);
+        if version == 2 {
+            data.extend(CONFIG_ID.hyphenated().to_string().as_bytes());
+        }
+        data
+    }
+
+    fn frame(color_space: u8, channels: &[(u8, [u16; 3])]) -> Vec<u8> {
+        let mut data = header(2, color_space);
+        for (id, values) in channels {
+            data.push(*id);
+            for value in values {
+                data.extend(value.to_be_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn parse_rgb_frame() {
+        let data = frame(0, &[(0, [0xffff, 0, 0]), (3, [0x0102, 0x0304, 0x0506])]);
+        let frame = StreamFrame::parse(&data).unwrap();
+
+        assert_eq!(frame.sequence, 7);
+        assert_eq!(frame.color_space, StreamColorSpace::Rgb);
+        assert_eq!(frame.config_id, CONFIG_ID);
+        assert_eq!(
+            frame.channels,
+            [
+                StreamChannel {
+                    id: 0,
+                    values: [0xffff, 0, 0]
+                },
+                StreamChannel {
+                    id: 3,
+                    values: [0x0102, 0x0304, 0x0506]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_xy_frame() {
+        let data = frame(1, &[(1, [0x4000, 0x8000, 0xffff])]);
+        let frame = StreamFrame::parse(&data).unwrap();
+
+        assert_eq!(frame.color_space, StreamColorSpace::Xy);
+        assert_eq!(
+            frame.channels,
+            [StreamChannel {
+                id: 1,
+                values: [0x4000, 0x8000, 0xffff]
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_empty_frame() {
+        let frame = StreamFrame::parse(&frame(0, &[])).unwrap();
+        assert!(frame.channels.is_empty());
+    }
+
+    #[test]
+    fn reject_headers() {
+        /* protocol version 1 has a shorter header, without configuration id */
+        let mut v1 = header(1, 0);
+        v1.extend([0, 0, 1, 0xff, 0xff, 0, 0, 0, 0]);
+        assert!(StreamFrame::parse(&v1).is_err());
+
+        assert!(StreamFrame::parse(&header(3, 0)).is_err());
+        assert!(StreamFrame::parse(&frame(2, &[])).is_err());
+
+        let mut data = frame(0, &[]);
+        data[0] = b'h';
+        assert!(StreamFrame::parse(&data).is_err());
+
+        let mut data = frame(0, &[]);
+        data[20] = b'x';
+        assert!(StreamFrame::parse(&data).is_err());
+    }
+
+    #[test]
+    fn reject_truncated() {
+        use super::{CHANNEL_SIZE, HEADER_SIZE};
+
+        let data = frame(0, &[(0, [1, 2, 3]), (1, [4, 5, 6])]);
+        for len in 0..data.len() {
+            /* frames ending on a channel boundary are complete */
+            if len >= HEADER_SIZE && (len - HEADER_SIZE) % CHANNEL_SIZE == 0 {
+                continue;
+            }
+            assert!(StreamFrame::parse(&data[..len]).is_err(), "length {len}");
+        }
+    }
+
+    #[test]
+    fn reject_too_many_channels() {
+        let channels = [(0, [0; 3]); super::MAX_CHANNELS + 1];
+        assert!(StreamFrame::parse(&frame(0, &channels)).is_err());
+        assert!(StreamFrame::parse(&frame(0, &channels[1..])).is_ok());
+    }
+
+    #[test]
+    fn rgb_uses_full_resolution() {
+        /* the low byte matters: a dim, but not dark, channel stays on */
+        let dim = StreamChannel {
+            id: 0,
+            values: [0x00ff, 0x00ff, 0x00ff],
+        };
+        let update = dim.device_update(StreamColorSpace::Rgb);
+        assert!(matches!(update.state, Some(DeviceState::On)));
+        assert_eq!(update.brightness, Some(1.0));
+
+        let white = StreamChannel {
+            id: 0,
+            values: [0xffff; 3],
+        };
+        let update = white.device_update(StreamColorSpace::Rgb);
+        assert_eq!(update.brightness, Some(254.0));
+
+        let off = StreamChannel {
+            id: 0,
+            values: [0; 3],
+        };
+        assert!(matches!(
+            off.device_update(StreamColorSpace::Rgb).state,
+            Some(DeviceState::Off)
+        ));
+    }
+
+    #[test]
+    fn xy_channel() {
+        let channel = StreamChannel {
+            id: 0,
+            values: [0x8000, 0x4000, 0xffff],
+        };
+        let update = channel.device_update(StreamColorSpace::Xy);
+        let xy = update.color.unwrap().xy;
+
+        assert!((xy.x - 0.5).abs() < 0.001);
+        assert!((xy.y - 0.25).abs() < 0.001);
+        assert_eq!(update.brightness, Some(254.0));
+        assert_eq!(update.transition, Some(0.0));
+    }
+}
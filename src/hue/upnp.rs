@@ -0,0 +1,134 @@
+use std::net::Ipv4Addr;
+
+use mac_address::MacAddress;
+
+use crate::hue::legacy_api::ApiShortConfig;
+use crate::hue::HUE_BRIDGE_V2_MODEL_ID;
+
+/// Path of the bridge icon listed in the description document
+pub const ICON_PATH: &str = "hue_logo_0.png";
+
+/// Size (in pixels) of the bridge icon
+pub const ICON_SIZE: u32 = 48;
+
+/// Prefix of the upnp device uuid of hue bridges, followed by the mac address
+const UDN_PREFIX: &str = "2f402f80-da50-11e1-9b23-";
+
+/// Search targets a hue bridge answers to (besides "ssdp:all"). The device
+/// uuid is the third.
+const SEARCH_TARGETS: [&str; 2] = ["upnp:rootdevice", "urn:schemas-upnp-org:device:basic:1"];
+
+/// Seconds clients may cache an ssdp answer
+const MAX_AGE: u32 = 100;
+
+#[allow(clippy::format_collect)]
+fn serial(mac: MacAddress) -> String {
+    mac.bytes().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The upnp device uuid of the bridge
+#[must_use]
+pub fn udn(mac: MacAddress) -> String {
+    format!("uuid:{UDN_PREFIX}{}", serial(mac))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// The upnp device description of the bridge (served as /description.xml),
+/// in the same layout as a real hue bridge
+#[must_use]
+pub fn description_xml(name: &str, ip: Ipv4Addr, http_port: u16, mac: MacAddress) -> String {
+    let serial = serial(mac);
+    let udn = udn(mac);
+    let name = escape(name);
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" ?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+<specVersion>
+<major>1</major>
+<minor>0</minor>
+</specVersion>
+<URLBase>http://{ip}:{http_port}/</URLBase>
+<device>
+<deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType>
+<friendlyName>{name} ({ip})</friendlyName>
+<manufacturer>Signify</manufacturer>
+<manufacturerURL>http://www.philips-hue.com</manufacturerURL>
+<modelDescription>Philips hue Personal Wireless Lighting</modelDescription>
+<modelName>Philips hue bridge 2015</modelName>
+<modelNumber>{HUE_BRIDGE_V2_MODEL_ID}</modelNumber>
+<modelURL>http://www.philips-hue.com</modelURL>
+<serialNumber>{serial}</serialNumber>
+<UDN>{udn}</UDN>
+<presentationURL>index.html</presentationURL>
+<iconList>
+<icon>
+<mimetype>image/png</mimetype>
+<height>{ICON_SIZE}</height>
+<width>{ICON_SIZE}</width>
+<depth>24</depth>
+<url>{ICON_PATH}</url>
+</icon>
+</iconList>
+</device>
+</root>
+"#
+    )
+}
+
+/// Answers to an ssdp search request (M-SEARCH) for search target `st`.
+///
+/// Answers have the same form as those of a real hue bridge. Echo devices (for local hue control)
+/// check the server header and bridge id before fetching the description.
+#[must_use]
+pub fn ssdp_answers(
+    st: &str,
+    ip: Ipv4Addr,
+    http_port: u16,
+    config: &ApiShortConfig,
+) -> Vec<String> {
+    let udn = udn(config.mac);
+    let targets: Vec<&str> = if st == "ssdp:all" {
+        vec![SEARCH_TARGETS[0], &udn, SEARCH_TARGETS[1]]
+    } else if st.eq_ignore_ascii_case(&udn) {
+        vec![&udn]
+    } else {
+        SEARCH_TARGETS
+            .into_iter()
+            .filter(|target| st.eq_ignore_ascii_case(target))
+            .collect()
+    };
+
+    let bridge_id = config.bridgeid.to_uppercase();
+    let apiversion = &config.apiversion;
+
+    targets
+        .into_iter()
+        .map(|target| {
+            let usn = if target == udn {
+                udn.clone()
+            } else {
+                format!("{udn}::{target}")
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                 HOST: 239.255.255.250:1900\r\n\
+                 EXT:\r\n\
+                 CACHE-CONTROL: max-age={MAX_AGE}\r\n\
+                 LOCATION: http://{ip}:{http_port}/description.xml\r\n\
+                 SERVER: Linux/3.14.0 UPnP/1.0 IpBridge/{apiversion}\r\n\
+                 hue-bridgeid: {bridge_id}\r\n\
+                 ST: {target}\r\n\
+                 USN: {usn}\r\n\
+                 \r\n"
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::error::ApiResult;
+use crate::hue::api::Resource;
+use crate::resource::{AuxData, Resources};
+
+/// A single change to the saved state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalEntry {
+    Put { id: Uuid, obj: Arc<Resource> },
+    Delete { id: Uuid },
+    Aux { id: Uuid, aux: Option<AuxData> },
+}
+
+/// Append-only journal of changes made since the state file was last saved.
+///
+/// Saving the state file is delayed until changes settle, so the journal is
+/// written as soon as anything changes, and replayed on top of the state file
+/// at startup. Each entry is a single line of json.
+///
+/// Finding changes needs the resources, but writing them does not, so the
+/// two are separate steps ([`Self::collect`] and [`Self::append`]), and the
+/// resources lock does not have to be held during file I/O.
+#[derive(Debug)]
+pub struct Journal {
+    filename: Utf8PathBuf,
+    fd: File,
+    /* state as of the last journal entry, to find what changed since.
+     * Resources are shared with the store (which copies them on write), so
+     * unchanged resources are found without serializing them. */
+    res: HashMap<Uuid, (Arc<Resource>, Value)>,
+    aux: HashMap<Uuid, Value>,
+}
+
+impl Journal {
+    /// The journal file that belongs to a state file
+    #[must_use]
+    pub fn filename(state_file: &Utf8Path) -> Utf8PathBuf {
+        state_file.with_extension("journal")
+    }
+
+    /// Apply the entries of a journal file (if any) to `res`, and return the
+    /// number of entries applied. An incomplete last entry (from a crash
+    /// while writing it) is skipped.
+    pub fn replay(res: &mut Resources, filename: &Utf8Path) -> ApiResult<usize> {
+        let Ok(fd) = File::open(filename) else {
+            return Ok(0);
+        };
+
+        let mut count = 0;
+        for line in BufReader::new(fd).lines() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(entry) => {
+                    res.journal_apply(entry);
+                    count += 1;
+                }
+                Err(err) => {
+                    log::warn!("Skipping invalid entry in journal {filename}: {err}");
+                    break;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Start a new (empty) journal, discarding any previous journal. Call
+    /// [`Self::rebase`] with the state saved in the state file, before
+    /// collecting changes.
+    pub fn create(filename: Utf8PathBuf) -> ApiResult<Self> {
+        let fd = File::create(&filename)?;
+        Ok(Self {
+            filename,
+            fd,
+            res: HashMap::new(),
+            aux: HashMap::new(),
+        })
+    }
+
+    /// Forget recorded changes, and start over from `res` (e.g., after the
+    /// state file was reloaded). The journal should be truncated as well.
+    pub fn rebase(&mut self, res: &Resources) -> ApiResult<()> {
+        self.res = res
+            .res
+            .iter()
+            .map(|(id, obj)| Ok((*id, (obj.clone(), serde_json::to_value(obj)?))))
+            .collect::<ApiResult<_>>()?;
+        self.aux = res
+            .aux_iter()
+            .map(|(id, aux)| Ok((*id, serde_json::to_value(aux)?)))
+            .collect::<ApiResult<_>>()?;
+        Ok(())
+    }
+
+    fn changes(&mut self, res: &Resources) -> ApiResult<Vec<(JournalEntry, Option<Value>)>> {
+        let mut entries = vec![];
+
+        for (id, obj) in &res.res {
+            let known = self.res.get_mut(id);
+            if known
+                .as_ref()
+                .is_some_and(|(known, _)| Arc::ptr_eq(known, obj))
+            {
+                continue;
+            }
+
+            let value = serde_json::to_value(obj)?;
+            match known {
+                /* written again, but not changed */
+                Some((known, old)) if *old == value => *known = obj.clone(),
+                _ => entries.push((
+                    JournalEntry::Put {
+                        id: *id,
+                        obj: obj.clone(),
+                    },
+                    Some(value),
+                )),
+            }
+        }
+        for id in self.res.keys().filter(|id| !res.res.contains_key(id)) {
+            entries.push((JournalEntry::Delete { id: *id }, None));
+        }
+
+        let aux: HashMap<&Uuid, &AuxData> = res.aux_iter().collect();
+        for (id, data) in &aux {
+            let value = serde_json::to_value(data)?;
+            if self.aux.get(id) != Some(&value) {
+                let entry = JournalEntry::Aux {
+                    id: **id,
+                    aux: Some((*data).clone()),
+                };
+                entries.push((entry, Some(value)));
+            }
+        }
+        for id in self.aux.keys().filter(|id| !aux.contains_key(id)) {
+            entries.push((JournalEntry::Aux { id: *id, aux: None }, None));
+        }
+
+        Ok(entries)
+    }
+
+    /// Collect everything that changed in `res` since the last call, as
+    /// journal entries for [`Self::append`]
+    pub fn collect(&mut self, res: &Resources) -> ApiResult<String> {
+        let changes = self.changes(res)?;
+
+        let mut data = String::new();
+        for (entry, value) in changes {
+            data += &serde_json::to_string(&entry)?;
+            data.push('\n');
+
+            match (entry, value) {
+                (JournalEntry::Put { id, obj }, Some(value)) => {
+                    self.res.insert(id, (obj, value));
+                }
+                (JournalEntry::Put { id, .. } | JournalEntry::Delete { id }, _) => {
+                    self.res.remove(&id);
+                }
+                (JournalEntry::Aux { id, .. }, Some(value)) => {
+                    self.aux.insert(id, value);
+                }
+                (JournalEntry::Aux { id, .. }, None) => {
+                    self.aux.remove(&id);
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Append entries from [`Self::collect`], and wait for them to reach
+    /// the disk
+    pub fn append(&mut self, data: &str) -> ApiResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        log::trace!("Appending to journal {}: {data}", self.filename);
+        self.fd.write_all(data.as_bytes())?;
+        self.fd.sync_data()?;
+        Ok(())
+    }
+
+    /// Empty the journal, after the state file has been saved. Everything
+    /// recorded so far must be part of the saved state.
+    pub fn truncate(&mut self) -> ApiResult<()> {
+        self.fd = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.filename)?;
+        Ok(())
+    }
+}
@@ -16,11 +16,19 @@
 
 pub mod config;
 pub mod error;
+pub mod fade;
+pub mod history;
 pub mod hue;
+pub mod journal;
+pub mod logging;
 pub mod mdns;
+pub mod migrate;
 pub mod model;
+pub mod otlp;
 pub mod resource;
 pub mod routes;
 pub mod server;
+pub mod ssdp;
 pub mod state;
+pub mod timezone;
 pub mod z2m;
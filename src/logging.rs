@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::Write;
+use std::sync::{OnceLock, PoisonError, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger::{self, filter::Filter, fmt::Formatter};
+use serde::{Deserialize, Serialize};
+
+use crate::config::LoggingConfig;
+use crate::error::ApiResult;
+
+/* Try to provide reasonable default filters, when RUST_LOG is not specified */
+pub const DEFAULT_LOG_FILTERS: &[&str] = &[
+    "debug",
+    "mdns_sd=off",
+    "tower_http::trace::on_request=info",
+    "axum::rejection=trace",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogOverride {
+    pub level: LevelFilter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+struct LogState {
+    filters: String,
+    overrides: BTreeMap<String, LogOverride>,
+    filter: Filter,
+}
+
+impl LogState {
+    fn new(filters: &str) -> Self {
+        let mut res = Self {
+            filters: filters.to_string(),
+            overrides: BTreeMap::new(),
+            filter: env_logger::filter::Builder::new().build(),
+        };
+        res.rebuild();
+        res
+    }
+
+    fn rebuild(&mut self) {
+        let mut builder = env_logger::filter::Builder::new();
+        builder.parse(&self.filters);
+        for (target, ovr) in &self.overrides {
+            builder.filter_module(target, ovr.level);
+        }
+        self.filter = builder.build();
+        log::set_max_level(self.filter.filter());
+    }
+}
+
+/*
+ * Logger that wraps env_logger (for formatting), but keeps the filters
+ * separate, so they can be changed while bifrost is running.
+ */
+struct BifrostLogger {
+    inner: env_logger::Logger,
+    state: RwLock<LogState>,
+}
+
+impl Log for BifrostLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        state.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        if !state.filter.matches(record) {
+            return;
+        }
+        drop(state);
+
+        /* Tag messages logged while handling a request with its id */
+        match request_id() {
+            Some(id) => self.inner.log(
+                &Record::builder()
+                    .metadata(record.metadata().clone())
+                    .args(format_args!("[{id}] {}", record.args()))
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.inner.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+static LOGGER: OnceLock<BifrostLogger> = OnceLock::new();
+
+tokio::task_local! {
+    static REQUEST_ID: Option<String>;
+}
+
+/// The id of the request currently being handled (if any)
+#[must_use]
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok().flatten()
+}
+
+/// Run `fut` on behalf of the request with the given id, so that everything
+/// it logs can be correlated with that request
+pub async fn with_request_id<F: Future>(id: Option<String>, fut: F) -> F::Output {
+    REQUEST_ID.scope(id, fut).await
+}
+
+fn with_state<T>(func: impl FnOnce(&mut LogState) -> T) -> Option<T> {
+    let logger = LOGGER.get()?;
+    let mut state = logger.state.write().unwrap_or_else(PoisonError::into_inner);
+    Some(func(&mut state))
+}
+
+/*
+ * Formatter function to output in syslog format. This makes sense when running
+ * as a service (where output might go to a log file, or the system journal)
+ */
+fn syslog_format(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    writeln!(
+        buf,
+        "<{}>{}: {}",
+        match record.level() {
+            log::Level::Error => 3,
+            log::Level::Warn => 4,
+            log::Level::Info => 6,
+            log::Level::Debug | log::Level::Trace => 7,
+        },
+        record.target(),
+        record.args()
+    )
+}
+
+pub fn init(filters: &str) -> ApiResult<()> {
+    /* Detect if we need syslog or human-readable formatting */
+    let mut builder = if std::env::var("SYSTEMD_EXEC_PID")
+        .is_ok_and(|pid| pid == std::process::id().to_string())
+    {
+        let mut builder = env_logger::builder();
+        builder.format(syslog_format);
+        builder
+    } else {
+        pretty_env_logger::formatted_timed_builder()
+    };
+
+    /* Filtering is done by BifrostLogger, so let everything through here */
+    let inner = builder.filter_level(LevelFilter::Trace).build();
+
+    let logger = LOGGER.get_or_init(|| BifrostLogger {
+        inner,
+        state: RwLock::new(LogState::new(filters)),
+    });
+
+    log::set_logger(logger)?;
+
+    /* log::set_logger() resets the max level, so rebuild filters */
+    with_state(LogState::rebuild);
+
+    Ok(())
+}
+
+/* Builds filters from the logging section of the config file */
+#[must_use]
+pub fn config_filters(config: &LoggingConfig) -> String {
+    let mut filters: Vec<String> = DEFAULT_LOG_FILTERS
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    if let Some(level) = config.level {
+        filters.push(level.to_string().to_lowercase());
+    }
+
+    for (target, level) in &config.targets {
+        filters.push(format!("{target}={}", level.to_string().to_lowercase()));
+    }
+
+    filters.join(",")
+}
+
+pub fn set_filters(filters: &str) {
+    with_state(|state| {
+        filters.clone_into(&mut state.filters);
+        state.rebuild();
+    });
+}
+
+#[must_use]
+pub fn filters() -> String {
+    with_state(|state| state.filters.clone()).unwrap_or_default()
+}
+
+#[must_use]
+pub fn overrides() -> BTreeMap<String, LogOverride> {
+    with_state(|state| state.overrides.clone()).unwrap_or_default()
+}
+
+/*
+ * Temporarily change the log level for a single target (i.e., module). If a
+ * duration is given, the override is removed again after it expires.
+ */
+#[must_use]
+pub fn set_override(target: &str, level: LevelFilter, duration: Option<Duration>) -> LogOverride {
+    let ovr = LogOverride {
+        level,
+        expires: duration
+            .and_then(|d| chrono::Duration::from_std(d).ok())
+            .map(|d| Utc::now() + d),
+    };
+
+    with_state(|state| {
+        state.overrides.insert(target.to_string(), ovr.clone());
+        state.rebuild();
+    });
+
+    if let (Some(duration), Some(expires)) = (duration, ovr.expires) {
+        let target = target.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            with_state(|state| {
+                /* Only remove the override, if it has not been replaced since */
+                if state.overrides.get(&target).and_then(|o| o.expires) == Some(expires) {
+                    log::info!("Log level override for {target} expired");
+                    state.overrides.remove(&target);
+                    state.rebuild();
+                }
+            });
+        });
+    }
+
+    ovr
+}
+
+#[must_use]
+pub fn clear_override(target: &str) -> Option<LogOverride> {
+    with_state(|state| {
+        let res = state.overrides.remove(target);
+        state.rebuild();
+        res
+    })
+    .flatten()
+}
@@ -1,112 +1,331 @@
-use std::io::Write;
+use std::net::Ipv4Addr;
+use std::process::ExitCode;
+use std::time::Duration;
 
+use camino::Utf8PathBuf;
+use clap::Parser;
 use tokio::task::JoinSet;
 
-use bifrost::config;
+use bifrost::config::{self, ConfigOverrides};
 use bifrost::error::ApiResult;
+use bifrost::fade;
+use bifrost::logging;
 use bifrost::mdns;
-use bifrost::server::{self, banner};
+use bifrost::model::units;
+use bifrost::otlp;
+#[cfg(feature = "server-systemd")]
+use bifrost::server::failover::while_active;
+use bifrost::server::systemd;
+use bifrost::server::{self, banner, doctor};
+use bifrost::ssdp;
 use bifrost::state::AppState;
 use bifrost::z2m;
 
-/*
- * Formatter function to output in syslog format. This makes sense when running
- * as a service (where output might go to a log file, or the system journal)
- */
-fn syslog_format(
-    buf: &mut pretty_env_logger::env_logger::fmt::Formatter,
-    record: &log::Record,
-) -> std::io::Result<()> {
-    writeln!(
-        buf,
-        "<{}>{}: {}",
-        match record.level() {
-            log::Level::Error => 3,
-            log::Level::Warn => 4,
-            log::Level::Info => 6,
-            log::Level::Debug => 7,
-            log::Level::Trace => 7,
-        },
-        record.target(),
-        record.args()
-    )
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Check configuration, certificates, ports, mdns and zigbee2mqtt
+    /// connectivity, print a report, and exit
+    #[arg(long)]
+    doctor: bool,
+
+    /// Configuration file to read
+    #[arg(long, env = "BIFROST_CONFIG", default_value = "config.yaml")]
+    config: Utf8PathBuf,
+
+    /// State file, overriding bifrost.state_file
+    #[arg(long, env = "BIFROST_STATE")]
+    state: Option<Utf8PathBuf>,
+
+    /// Certificate file, overriding bifrost.cert_file
+    #[arg(long, env = "BIFROST_CERT")]
+    cert: Option<Utf8PathBuf>,
+
+    /// Http port, overriding bridge.http_port
+    #[arg(long, env = "BIFROST_HTTP_PORT")]
+    http_port: Option<u16>,
+
+    /// Https port, overriding bridge.https_port
+    #[arg(long, env = "BIFROST_HTTPS_PORT")]
+    https_port: Option<u16>,
+
+    /// Log filters (e.g. "debug" or "info,bifrost::z2m=trace"), overriding
+    /// both RUST_LOG and the logging section of the config file
+    #[arg(long, env = "BIFROST_LOG_LEVEL")]
+    log_level: Option<String>,
 }
 
-fn init_logging() -> ApiResult<()> {
-    /* Try to provide reasonable default filters, when RUST_LOG is not specified */
-    const DEFAULT_LOG_FILTERS: &[&str] = &[
-        "debug",
-        "mdns_sd=off",
-        "tower_http::trace::on_request=info",
-        "axum::rejection=trace",
-    ];
-
-    let log_filters = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_FILTERS.join(","));
-
-    /* Detect if we need syslog or human-readable formatting */
-    if std::env::var("SYSTEMD_EXEC_PID").is_ok_and(|pid| pid == std::process::id().to_string()) {
-        Ok(pretty_env_logger::env_logger::builder()
-            .format(syslog_format)
-            .parse_filters(&log_filters)
-            .try_init()?)
-    } else {
-        Ok(pretty_env_logger::formatted_timed_builder()
-            .parse_filters(&log_filters)
-            .try_init()?)
+impl Args {
+    fn overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            state_file: self.state.clone(),
+            cert_file: self.cert.clone(),
+            http_port: self.http_port,
+            https_port: self.https_port,
+        }
+    }
+
+    /// Log filters given on the command line, or in RUST_LOG
+    fn log_filters(&self) -> Option<String> {
+        self.log_level
+            .clone()
+            .or_else(|| std::env::var("RUST_LOG").ok())
     }
 }
 
-async fn build_tasks(appstate: AppState) -> ApiResult<JoinSet<ApiResult<()>>> {
+fn init_logging(args: &Args) -> ApiResult<()> {
+    let log_filters = args
+        .log_filters()
+        .unwrap_or_else(|| logging::DEFAULT_LOG_FILTERS.join(","));
+
+    logging::init(&log_filters)
+}
+
+/// Spawn the tasks serving a single home (virtual bridge). Socket activation
+/// from systemd is only supported for the primary home.
+#[cfg_attr(not(feature = "server-systemd"), allow(unused_variables))]
+async fn build_tasks(
+    appstate: AppState,
+    primary: bool,
+    tasks: &mut JoinSet<ApiResult<()>>,
+) -> ApiResult<()> {
     let bconf = &appstate.config().bridge;
-    let _mdns = mdns::register_mdns(bconf.mac, bconf.ipaddress);
+    /* a standby instance only announces the bridge when taking over */
+    let failover = appstate.config().bifrost.failover.clone();
+    /* when following an interface, the address watcher announces the bridge */
+    let _mdns = (failover.is_none() && bconf.interface.is_none())
+        .then(|| mdns::register_mdns(bconf.mac, bconf.id(), bconf.ipaddress, bconf.http_port));
+    if let Some(interface) = &bconf.interface {
+        tasks.spawn(server::netwatch::address_watcher(
+            appstate.clone(),
+            interface.clone(),
+            failover.is_none(),
+        ));
+    }
 
-    let mut tasks = JoinSet::new();
+    /* like mdns, a standby instance leaves ssdp to the primary */
+    if bconf.ssdp && failover.is_none() {
+        tasks.spawn(ssdp::responder(appstate.clone()));
+    }
 
     let svc = server::build_service(appstate.clone());
 
-    log::info!("Serving mac [{}]", bconf.mac);
+    log::info!("Serving mac [{}], bridge id [{}]", bconf.mac, bconf.id());
 
-    let tls_config = appstate.tls_config().await?;
+    let tls_config = appstate.tls_config()?;
+    let cert_file = appstate.config().bifrost.cert_file.clone();
+    let cert_reload_interval = appstate.config().bifrost.cert_reload_interval;
     let state_file = appstate.config().bifrost.state_file.clone();
+    let state_file_mode = appstate.config().bifrost.state_file_mode;
 
-    tasks.spawn(server::http_server(
-        bconf.ipaddress,
-        bconf.http_port,
-        svc.clone(),
-    ));
-    tasks.spawn(server::https_server(
-        bconf.ipaddress,
-        bconf.https_port,
-        svc,
-        tls_config,
+    /* Use listening sockets from systemd, if we were socket activated */
+    #[cfg(feature = "server-systemd")]
+    let (http_listener, https_listener) = if primary {
+        systemd::take_listeners(bconf.http_port, bconf.https_port)?
+    } else {
+        (None, None)
+    };
+    #[cfg(not(feature = "server-systemd"))]
+    let (http_listener, https_listener) = (None, None);
+
+    if bconf.http_enabled {
+        if let Some(listener) = http_listener {
+            tasks.spawn(server::http_server(
+                listener,
+                svc.clone(),
+                bconf.http_limits.clone(),
+            ));
+        } else {
+            let svc = svc.clone();
+            let limits = bconf.http_limits.clone();
+            tasks.spawn(server::netwatch::follow_address(
+                appstate.address_updates(),
+                "http",
+                bconf.http_port,
+                bconf.http_port_fallback,
+                move |listener| server::http_server(listener, svc.clone(), limits.clone()),
+            ));
+        }
+    } else {
+        log::info!("Http listener disabled by config");
+    }
+
+    if bconf.https_enabled {
+        if let Some(listener) = https_listener {
+            tasks.spawn(server::https_server(
+                listener,
+                svc,
+                tls_config.clone(),
+                bconf.http_limits.clone(),
+            ));
+        } else {
+            let tls_config = tls_config.clone();
+            let limits = bconf.http_limits.clone();
+            tasks.spawn(server::netwatch::follow_address(
+                appstate.address_updates(),
+                "https",
+                bconf.https_port,
+                bconf.https_port_fallback,
+                move |listener| {
+                    server::https_server(listener, svc.clone(), tls_config.clone(), limits.clone())
+                },
+            ));
+        }
+
+        /* With acme enabled, the acme provisioner manages the https certificate */
+        match appstate.config().bifrost.acme.clone() {
+            #[cfg(feature = "server-acme")]
+            Some(acme_config) => {
+                tasks.spawn(server::acme::acme_provisioner(
+                    tls_config,
+                    acme_config,
+                    appstate.acme_tokens.clone(),
+                    appstate.client_verifier(),
+                ));
+            }
+            #[cfg(not(feature = "server-acme"))]
+            Some(_) => {
+                log::warn!(
+                    "ACME is configured, but bifrost was built without the server-acme feature"
+                );
+                tasks.spawn(server::cert_reloader(
+                    tls_config,
+                    cert_file,
+                    bconf.id(),
+                    cert_reload_interval,
+                    appstate.client_verifier(),
+                ));
+            }
+            None => {
+                tasks.spawn(server::cert_reloader(
+                    tls_config,
+                    cert_file,
+                    bconf.id(),
+                    cert_reload_interval,
+                    appstate.client_verifier(),
+                ));
+            }
+        }
+    } else {
+        log::info!("Https listener disabled by config");
+    }
+
+    tasks.spawn(server::config_writer(
+        appstate.res.clone(),
+        state_file,
+        state_file_mode,
     ));
-    tasks.spawn(server::config_writer(appstate.res.clone(), state_file));
+    if appstate.config().bifrost.features.entertainment {
+        /* when following an interface, the address can change */
+        let ip = if bconf.interface.is_some() {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            bconf.ipaddress
+        };
+        tasks.spawn(server::entertainment::entertainment_server(
+            appstate.res.clone(),
+            ip,
+        ));
+        /* sessions are kept alive by the frames received by the listener */
+        tasks.spawn(server::entertainment_watchdog(
+            appstate.res.clone(),
+            Duration::from_secs(
+                appstate
+                    .config()
+                    .bifrost
+                    .entertainment_timeout
+                    .unwrap_or(10),
+            ),
+        ));
+    }
+
+    /* a standby instance leaves automations (and z2m) to the primary, until
+     * it takes over */
+    let active = appstate.res.lock().await.active_channel();
+    active.send_replace(failover.is_none());
 
-    for (name, server) in &appstate.config().z2m.servers {
-        let client = z2m::Client::new(
-            name.clone(),
-            server.clone(),
-            appstate.config(),
+    if appstate.config().bifrost.effect_emulation {
+        let res = appstate.res.clone();
+        tasks.spawn(while_active(active.subscribe(), move || {
+            server::effect_runner(res.clone())
+        }));
+    }
+    let res = appstate.res.clone();
+    tasks.spawn(while_active(active.subscribe(), move || {
+        server::rollout_runner(res.clone())
+    }));
+    if appstate.config().bifrost.features.sensors {
+        let res = appstate.res.clone();
+        tasks.spawn(while_active(active.subscribe(), move || {
+            server::motion_runner(res.clone())
+        }));
+    }
+    if let Some(failover) = failover {
+        tasks.spawn(server::failover::standby(
+            appstate.res.clone(),
+            bconf.clone(),
+            appstate.address_updates(),
+            failover,
+            active.clone(),
+        ));
+    }
+    if let Some(otlp) = &appstate.config().bifrost.otlp {
+        tasks.spawn(otlp::exporter(otlp.clone()));
+    }
+    let (res, fades) = (appstate.res.clone(), appstate.fades.clone());
+    tasks.spawn(while_active(active.subscribe(), move || {
+        fade::fade_runner(res.clone(), fades.clone())
+    }));
+    if appstate.config().bifrost.features.behaviors {
+        let (res, fades, tz) = (
             appstate.res.clone(),
-        )?;
-        tasks.spawn(client.run_forever());
+            appstate.fades.clone(),
+            appstate.timezone(),
+        );
+        tasks.spawn(while_active(active.subscribe(), move || {
+            fade::behavior_runner(res.clone(), fades.clone(), tz)
+        }));
     }
 
-    Ok(tasks)
+    let (config, res) = (appstate.config(), appstate.res.clone());
+    tasks.spawn(while_active(active.subscribe(), move || {
+        z2m::supervisor::supervisor(config.clone(), res.clone())
+    }));
+
+    Ok(())
 }
 
-async fn run() -> ApiResult<()> {
-    init_logging()?;
+async fn run(args: &Args) -> ApiResult<()> {
+    init_logging(args)?;
 
     #[cfg(feature = "server-banner")]
     banner::print()?;
 
-    let config = config::parse("config.yaml".into())?;
-    log::debug!("Configuration loaded successfully");
+    let config = config::parse_with_overrides(&args.config, &args.overrides())?;
+    log::debug!("Configuration [{}] loaded successfully", args.config);
+
+    /* --log-level and RUST_LOG take precedence over the config file */
+    if args.log_filters().is_none() {
+        logging::set_filters(&logging::config_filters(&config.logging));
+    }
+
+    units::set_precision(config.bifrost.brightness_precision());
+
+    let mut tasks = JoinSet::new();
 
-    let appstate = AppState::from_config(config)?;
+    for (idx, (name, home)) in config.homes()?.into_iter().enumerate() {
+        log::info!("Starting home [{name}]");
+        let appstate = AppState::from_config(home)?;
+        build_tasks(appstate, idx == 0, &mut tasks).await?;
+    }
+
+    #[cfg(feature = "server-systemd")]
+    if let Some(interval) = systemd::watchdog_interval() {
+        tasks.spawn(systemd::watchdog(interval));
+    }
 
-    let mut tasks = build_tasks(appstate).await?;
+    #[cfg(feature = "server-systemd")]
+    systemd::notify_ready();
 
     loop {
         match tasks.join_next().await {
@@ -119,9 +338,28 @@ async fn run() -> ApiResult<()> {
 }
 
 #[tokio::main]
-async fn main() {
-    if let Err(err) = run().await {
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if args.doctor {
+        let report = doctor::run(&args.config, &args.overrides()).await;
+        print!("{report}");
+        return match report.status() {
+            doctor::CheckStatus::Failed => ExitCode::FAILURE,
+            _ => ExitCode::SUCCESS,
+        };
+    }
+
+    let res = run(&args).await;
+
+    #[cfg(feature = "server-systemd")]
+    systemd::notify_stopping();
+
+    if let Err(err) = res {
         log::error!("Bifrost error: {err}");
         log::error!("Fatal error encountered, cannot continue.");
+        return ExitCode::FAILURE;
     }
+
+    ExitCode::SUCCESS
 }
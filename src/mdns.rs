@@ -5,26 +5,38 @@ use mdns_sd::{ServiceDaemon, ServiceInfo};
 
 use crate::error::ApiResult;
 use crate::hue;
-use crate::server::certificate;
+use crate::server::certificate::BridgeId;
 
-pub fn register_mdns(mac: MacAddress, ip: Ipv4Addr) -> ApiResult<ServiceDaemon> {
-    /* Create a new mDNS daemon. */
-    let mdns = ServiceDaemon::new()?;
-    let service_type = "_hue._tcp.local.";
+const SERVICE_TYPE: &str = "_hue._tcp.local.";
 
+fn instance_name(mac: MacAddress) -> String {
     let m = mac.bytes();
-    let instance_name = format!(
+    format!(
         "bifrost-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
         m[0], m[1], m[2], m[3], m[4], m[5]
-    );
+    )
+}
+
+/// Announce the bridge, served on `ip` and (http) `port`
+pub fn register_mdns(
+    mac: MacAddress,
+    bridge_id: BridgeId,
+    ip: Ipv4Addr,
+    port: u16,
+) -> ApiResult<ServiceDaemon> {
+    /* Create a new mDNS daemon. */
+    let mdns = ServiceDaemon::new()?;
+    let service_type = SERVICE_TYPE;
+
+    let instance_name = instance_name(mac);
 
     let service_hostname = format!("{instance_name}.{service_type}");
     let service_addr = ip.to_string();
-    let service_port = 80;
+    let service_port = port;
 
     let properties = [
         ("modelid", hue::HUE_BRIDGE_V2_MODEL_ID),
-        ("bridgeid", &certificate::hue_bridge_id(mac)),
+        ("bridgeid", &bridge_id.to_string()),
     ];
 
     let service_info = ServiceInfo::new(
@@ -42,3 +54,15 @@ pub fn register_mdns(mac: MacAddress, ip: Ipv4Addr) -> ApiResult<ServiceDaemon>
 
     Ok(mdns)
 }
+
+/// Stop announcing the service registered by [`register_mdns`], and shut
+/// down the daemon
+pub fn unregister_mdns(mdns: &ServiceDaemon, mac: MacAddress) -> ApiResult<()> {
+    let fullname = format!("{}.{SERVICE_TYPE}", instance_name(mac));
+    mdns.unregister(&fullname)?;
+    mdns.shutdown()?;
+
+    log::info!("Unregistered service {fullname}");
+
+    Ok(())
+}
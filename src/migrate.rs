@@ -0,0 +1,70 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::hue::api::RoomArchetype;
+
+/// Layout version of the state file written by this version of bifrost.
+///
+///  - 0: state files from before versioning. Aux data could be a bare topic
+///    string, and scene indices were sometimes quoted. Zones could have
+///    device archetypes.
+///  - 1: aux data is always a map, with numeric scene indices.
+pub const STATE_VERSION: u32 = 1;
+
+/// Bring an aux data entry, as written to a state file with layout `version`,
+/// up to date. Returns `true` if anything was changed.
+pub fn aux_entry(version: u32, value: &mut Value) -> bool {
+    if version >= STATE_VERSION {
+        return false;
+    }
+
+    match value {
+        /* bare topic name */
+        Value::String(topic) => {
+            let mut map = Mapping::new();
+            map.insert("topic".into(), Value::String(std::mem::take(topic)));
+            *value = Value::Mapping(map);
+            true
+        }
+        Value::Null => {
+            *value = Value::Mapping(Mapping::new());
+            true
+        }
+        Value::Mapping(map) => {
+            let Some(index) = map.get_mut("index") else {
+                return false;
+            };
+            let Some(number) = index.as_str().and_then(|s| s.trim().parse::<u32>().ok()) else {
+                return false;
+            };
+            *index = Value::Number(number.into());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Bring a resource, as written to a state file with layout `version`, up to
+/// date. Returns `true` if anything was changed.
+pub fn resource_entry(version: u32, value: &mut Value) -> bool {
+    if version >= STATE_VERSION {
+        return false;
+    }
+
+    if value.get("type").and_then(Value::as_str) != Some("zone") {
+        return false;
+    }
+
+    /* zones used to share device metadata, with device archetypes, which
+     * have no room equivalent */
+    let Some(archetype) = value
+        .get_mut("metadata")
+        .and_then(|md| md.get_mut("archetype"))
+    else {
+        return false;
+    };
+    if serde_yaml::from_value::<RoomArchetype>(archetype.clone()).is_ok() {
+        return false;
+    }
+    *archetype = Value::String("other".to_string());
+    true
+}
@@ -0,0 +1,167 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+
+use crate::config::LocationConfig;
+use crate::error::{ApiError, ApiResult};
+use crate::timezone;
+
+/// Zenith angle of the sun at sunrise and sunset, in degrees. Accounts for
+/// atmospheric refraction and the size of the sun's disc.
+const SUNRISE_ZENITH: f64 = 90.833;
+
+/// Sunrise and sunset on a given day
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SunTimes {
+    pub sunrise: DateTime<Utc>,
+    pub sunset: DateTime<Utc>,
+}
+
+/// Equation of time (in minutes) and declination of the sun (in radians)
+/// at noon on `date` (NOAA approximation)
+#[allow(clippy::suboptimal_flops)]
+fn solar_position(date: NaiveDate) -> (f64, f64) {
+    let days = if date.leap_year() { 366.0 } else { 365.0 };
+    let gamma = 2.0 * PI / days * f64::from(date.ordinal0());
+
+    let eqtime = 229.18
+        * (0.000_075 + 0.001_868 * gamma.cos()
+            - 0.032_077 * gamma.sin()
+            - 0.014_615 * (2.0 * gamma).cos()
+            - 0.040_849 * (2.0 * gamma).sin());
+
+    let decl = 0.006_918 - 0.399_912 * gamma.cos() + 0.070_257 * gamma.sin()
+        - 0.006_758 * (2.0 * gamma).cos()
+        + 0.000_907 * (2.0 * gamma).sin()
+        - 0.002_697 * (3.0 * gamma).cos()
+        + 0.001_48 * (3.0 * gamma).sin();
+
+    (eqtime, decl)
+}
+
+/// Sunrise and sunset at `loc` on `date` (accurate to about a minute), or
+/// `None` if the sun does not rise or set that day (polar day or night)
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn sun_times(loc: &LocationConfig, date: NaiveDate) -> Option<SunTimes> {
+    let (eqtime, decl) = solar_position(date);
+    let lat = loc.latitude.to_radians();
+
+    let cos_ha = lat.tan().mul_add(
+        -decl.tan(),
+        SUNRISE_ZENITH.to_radians().cos() / (lat.cos() * decl.cos()),
+    );
+    if !(-1.0..=1.0).contains(&cos_ha) {
+        return None;
+    }
+    let ha = cos_ha.acos().to_degrees();
+
+    let midnight = date.and_time(NaiveTime::MIN).and_utc();
+    let at = |minutes: f64| midnight + Duration::seconds((minutes * 60.0).round() as i64);
+
+    Some(SunTimes {
+        sunrise: at(4.0f64.mul_add(-(loc.longitude + ha), 720.0) - eqtime),
+        sunset: at(4.0f64.mul_add(-(loc.longitude - ha), 720.0) - eqtime),
+    })
+}
+
+/// Whether the sun is up at `loc` at the time `now`, with the configured
+/// sunrise and sunset offsets applied (like the daylight sensor of a hue
+/// bridge)
+#[must_use]
+pub fn is_daylight(loc: &LocationConfig, tz: Tz, now: DateTime<Utc>) -> bool {
+    let date = timezone::to_local(tz, &now).date_naive();
+    let Some(times) = sun_times(loc, date) else {
+        /* polar day, if the sun is on the same side of the equator */
+        return solar_position(date).1.signum() == loc.latitude.signum();
+    };
+
+    let sunrise = times.sunrise + Duration::minutes(loc.sunrise_offset.into());
+    let sunset = times.sunset + Duration::minutes(loc.sunset_offset.into());
+    sunrise <= now && now < sunset
+}
+
+/// A time of day, either fixed (e.g., "22:30"), or relative to sunrise or
+/// sunset (e.g., "sunset-30m", "sunrise+1h15m")
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Fixed(NaiveTime),
+    Sunrise(Duration),
+    Sunset(Duration),
+}
+
+fn parse_offset(text: &str) -> Option<Duration> {
+    let (sign, rest) = match text.as_bytes().first()? {
+        b'+' => (1, &text[1..]),
+        b'-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    if rest.is_empty() {
+        return None;
+    }
+
+    let (hours, minutes) = match rest.split_once('h') {
+        Some((hours, minutes)) => (hours.parse().ok()?, minutes),
+        None => (0, rest),
+    };
+    let minutes = match minutes.strip_suffix('m') {
+        Some(minutes) => minutes.parse().ok()?,
+        None if minutes.is_empty() => 0,
+        None => return None,
+    };
+
+    Some(Duration::minutes(sign * (hours * 60 + minutes)))
+}
+
+impl TimeOfDay {
+    pub fn parse(text: &str) -> ApiResult<Self> {
+        let invalid = || {
+            ApiError::InvalidConfig(format!(
+                "invalid time {text:?} (expected e.g. \"22:30\", \"sunrise\" or \"sunset-30m\")"
+            ))
+        };
+
+        let relative = |rest: &str| {
+            if rest.is_empty() {
+                Ok(Duration::zero())
+            } else {
+                parse_offset(rest).ok_or_else(invalid)
+            }
+        };
+
+        if let Some(rest) = text.strip_prefix("sunrise") {
+            Ok(Self::Sunrise(relative(rest)?))
+        } else if let Some(rest) = text.strip_prefix("sunset") {
+            Ok(Self::Sunset(relative(rest)?))
+        } else {
+            NaiveTime::parse_from_str(text, "%H:%M")
+                .map(Self::Fixed)
+                .map_err(|_| invalid())
+        }
+    }
+
+    /// Whether this time depends on the location of the bridge
+    #[must_use]
+    pub const fn is_solar(&self) -> bool {
+        !matches!(self, Self::Fixed(_))
+    }
+
+    /// The (bridge local) time of day this refers to on `date`. Times
+    /// relative to the sun cannot be resolved without a location, or on
+    /// days the sun does not rise or set.
+    #[must_use]
+    pub fn resolve(
+        &self,
+        date: NaiveDate,
+        tz: Tz,
+        loc: Option<&LocationConfig>,
+    ) -> Option<NaiveTime> {
+        let (base, offset) = match self {
+            Self::Fixed(time) => return Some(*time),
+            Self::Sunrise(offset) => (sun_times(loc?, date)?.sunrise, offset),
+            Self::Sunset(offset) => (sun_times(loc?, date)?.sunset, offset),
+        };
+        Some(timezone::to_local(tz, &(base + *offset)).time())
+    }
+}
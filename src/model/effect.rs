@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::hue::api::LightEffect;
+use crate::model::units;
+use crate::z2m::update::DeviceUpdate;
+
+/// Shortest time between two updates to the same light. Zigbee networks do
+/// not cope well with more frequent updates, even from a handful of lights.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(400);
+
+/// How an emulated effect flickers
+#[derive(Debug, Clone, Copy)]
+struct Flicker {
+    /// Color temperature of the effect, for lights that support it
+    mirek: u32,
+    /// Largest dip in brightness, relative to the base brightness
+    depth: f64,
+    /// Longest time between two updates (the shortest is [`MIN_INTERVAL`])
+    interval: Duration,
+}
+
+impl Flicker {
+    const fn for_effect(effect: LightEffect) -> Option<Self> {
+        match effect {
+            LightEffect::NoEffect => None,
+            LightEffect::Candle => Some(Self {
+                mirek: 454,
+                depth: 0.25,
+                interval: Duration::from_millis(1200),
+            }),
+            LightEffect::Fire => Some(Self {
+                mirek: 500,
+                depth: 0.45,
+                interval: Duration::from_millis(800),
+            }),
+        }
+    }
+}
+
+/// An effect emulated by streaming updates to a light
+#[derive(Debug, Clone)]
+pub struct EffectSession {
+    pub effect: LightEffect,
+    /// Brightness (in percent) the effect flickers below
+    pub brightness: f64,
+    flicker: Flicker,
+    /// Color temperature to set with the next update, if any
+    mirek: Option<u32>,
+    next: Instant,
+}
+
+/// Active emulated effects, by light id
+#[derive(Debug, Clone, Default)]
+pub struct EffectSessions {
+    sessions: HashMap<Uuid, EffectSession>,
+}
+
+impl EffectSessions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start emulating `effect` on a light, replacing any running effect.
+    /// Returns false (and stops any running effect) if the effect cannot be
+    /// emulated.
+    pub fn start(&mut self, id: Uuid, effect: LightEffect, brightness: f64, ct: bool) -> bool {
+        let Some(flicker) = Flicker::for_effect(effect) else {
+            self.stop(&id);
+            return false;
+        };
+
+        let session = EffectSession {
+            effect,
+            brightness,
+            flicker,
+            mirek: ct.then_some(flicker.mirek),
+            next: Instant::now(),
+        };
+        self.sessions.insert(id, session);
+        true
+    }
+
+    pub fn stop(&mut self, id: &Uuid) -> Option<EffectSession> {
+        self.sessions.remove(id)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &Uuid) -> Option<&EffectSession> {
+        self.sessions.get(id)
+    }
+
+    /// Change the brightness an effect flickers below
+    pub fn set_brightness(&mut self, id: &Uuid, brightness: f64) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.brightness = brightness;
+        }
+    }
+
+    /// Updates for all lights that are due for one at `now`. Each update
+    /// transitions smoothly until the next one is due.
+    pub fn due(&mut self, now: Instant) -> Vec<(Uuid, DeviceUpdate)> {
+        let mut rng = rand::thread_rng();
+        let mut res = vec![];
+
+        for (id, session) in &mut self.sessions {
+            if session.next > now {
+                continue;
+            }
+
+            let flicker = session.flicker;
+            let delay = rng.gen_range(MIN_INTERVAL..=flicker.interval.max(MIN_INTERVAL));
+            let dip = rng.gen_range(0.0..=flicker.depth);
+            let brightness = (session.brightness * (1.0 - dip)).max(1.0);
+
+            let upd = DeviceUpdate::default()
+                .with_brightness(Some(units::percent_to_device(brightness)))
+                .with_color_temp(session.mirek.take())
+                .with_transition(Some(delay.as_secs_f64()));
+
+            session.next = now + delay;
+            res.push((*id, upd));
+        }
+
+        res
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::hue::api::ResourceLink;
+use crate::model::state::LightState;
+
+/// Least time between stream frames applied to the lights. Streaming clients
+/// send 25 to 60 frames per second, far more than a zigbee network can take.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An active entertainment session
+#[derive(Debug, Clone)]
+pub struct StreamSession {
+    /// Last time the streaming peer was heard from
+    pub last_seen: Instant,
+    /// Last time a stream frame was applied to the lights
+    pub last_frame: Option<Instant>,
+    /// Light states from before the session started, restored when it ends
+    pub prior: Vec<(ResourceLink, LightState)>,
+}
+
+/// Active entertainment sessions, by entertainment configuration id
+#[derive(Debug, Clone, Default)]
+pub struct StreamSessions {
+    sessions: HashMap<Uuid, StreamSession>,
+}
+
+impl StreamSessions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(&mut self, id: Uuid, prior: Vec<(ResourceLink, LightState)>) {
+        let session = StreamSession {
+            last_seen: Instant::now(),
+            last_frame: None,
+            prior,
+        };
+        self.sessions.insert(id, session);
+    }
+
+    pub fn stop(&mut self, id: &Uuid) -> Option<StreamSession> {
+        self.sessions.remove(id)
+    }
+
+    /// Record a stream frame for the session, keeping it alive. Returns
+    /// true, if the frame should be applied (see [`FRAME_INTERVAL`]).
+    pub fn frame(&mut self, id: &Uuid) -> bool {
+        let Some(session) = self.sessions.get_mut(id) else {
+            return false;
+        };
+        session.last_seen = Instant::now();
+        let due = session
+            .last_frame
+            .map_or(true, |last| last.elapsed() >= FRAME_INTERVAL);
+        if due {
+            session.last_frame = Some(session.last_seen);
+        }
+        due
+    }
+
+    /// Sessions that have not seen any activity for `timeout`
+    #[must_use]
+    pub fn expired(&self, timeout: Duration) -> Vec<Uuid> {
+        self.sessions
+            .iter()
+            .filter(|(_, session)| session.last_seen.elapsed() >= timeout)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
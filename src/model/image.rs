@@ -0,0 +1,216 @@
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::error::{ApiError, ApiResult};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Images larger than this are rejected, to bound memory use
+pub const MAX_PIXELS: usize = 16 * 1024 * 1024;
+
+/// A decoded image, as rgb pixels. Transparent pixels are left out.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+fn invalid(msg: impl Into<String>) -> ApiError {
+    ApiError::InvalidImage(msg.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Gray,
+    Rgb,
+    Palette,
+    GrayAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn from_png(value: u8) -> ApiResult<Self> {
+        match value {
+            0 => Ok(Self::Gray),
+            2 => Ok(Self::Rgb),
+            3 => Ok(Self::Palette),
+            4 => Ok(Self::GrayAlpha),
+            6 => Ok(Self::Rgba),
+            other => Err(invalid(format!("unknown png color type {other}"))),
+        }
+    }
+
+    /// Bytes per pixel, at 8 bits per sample
+    const fn bpp(self) -> usize {
+        match self {
+            Self::Gray | Self::Palette => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: [u8; 4], body: &[u8]) {
+    let len = u32::try_from(body.len()).unwrap_or(u32::MAX);
+    out.extend_from_slice(&len.to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(body);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undo the per-row filters of png image data, in place
+fn unfilter(data: &mut [u8], stride: usize, bpp: usize) -> ApiResult<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::with_capacity(data.len());
+    let mut prev = vec![0u8; stride];
+
+    for row in data.chunks_exact_mut(stride + 1) {
+        let (filter, line) = row.split_at_mut(1);
+        for i in 0..stride {
+            let a = if i >= bpp { line[i - bpp] } else { 0 };
+            let b = prev[i];
+            let c = if i >= bpp { prev[i - bpp] } else { 0 };
+            line[i] = line[i].wrapping_add(match filter[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => a / 2 + b / 2 + (a & b & 1),
+                4 => paeth(a, b, c),
+                other => return Err(invalid(format!("unknown png filter {other}"))),
+            });
+        }
+        prev.copy_from_slice(line);
+        out.extend_from_slice(line);
+    }
+
+    Ok(out)
+}
+
+impl Image {
+    /// Encode the image as an (8 bit, rgb) png image. All rows of the image
+    /// must be complete, i.e., it must not have any transparent pixels left
+    /// out.
+    #[must_use]
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = vec![];
+        for row in self.pixels.chunks(self.width.max(1) as usize) {
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        let mut header = vec![];
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut out = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut out, *b"IHDR", &header);
+        png_chunk(&mut out, *b"IDAT", &compress_to_vec_zlib(&raw, 6));
+        png_chunk(&mut out, *b"IEND", &[]);
+        out
+    }
+
+    /// Decode a png image. Only non-interlaced images with 8 bits per sample
+    /// are supported (which covers what most tools produce).
+    pub fn from_png(data: &[u8]) -> ApiResult<Self> {
+        let mut rest = data
+            .strip_prefix(PNG_SIGNATURE)
+            .ok_or_else(|| invalid("not a png image"))?;
+
+        let mut header = None;
+        let mut palette: &[u8] = &[];
+        let mut compressed = vec![];
+
+        while rest.len() >= 12 {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = &rest[4..8];
+            let body = rest
+                .get(8..8 + len)
+                .ok_or_else(|| invalid("truncated png chunk"))?;
+            rest = rest.get(12 + len..).unwrap_or_default();
+
+            match kind {
+                b"IHDR" if body.len() == 13 => header = Some(body),
+                b"PLTE" => palette = body,
+                b"IDAT" => compressed.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        let header = header.ok_or_else(|| invalid("missing png header"))?;
+        let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let color = ColorType::from_png(header[9])?;
+
+        if header[8] != 8 {
+            return Err(invalid("only 8 bit png images are supported"));
+        }
+        if header[12] != 0 {
+            return Err(invalid("interlaced png images are not supported"));
+        }
+
+        let pixels = width as usize * height as usize;
+        if pixels == 0 || pixels > MAX_PIXELS {
+            return Err(invalid(format!("unsupported image size {width}x{height}")));
+        }
+
+        let bpp = color.bpp();
+        let stride = width as usize * bpp;
+        let expected = (stride + 1) * height as usize;
+        let mut raw = decompress_to_vec_zlib_with_limit(&compressed, expected)
+            .map_err(|err| invalid(format!("corrupt png data: {err}")))?;
+        if raw.len() != expected {
+            return Err(invalid("truncated png data"));
+        }
+
+        let data = unfilter(&mut raw, stride, bpp)?;
+
+        let pixels = data
+            .chunks_exact(bpp)
+            .filter_map(|px| match color {
+                ColorType::Gray => Some([px[0]; 3]),
+                ColorType::GrayAlpha => (px[1] >= 128).then_some([px[0]; 3]),
+                ColorType::Rgb => Some([px[0], px[1], px[2]]),
+                ColorType::Rgba => (px[3] >= 128).then_some([px[0], px[1], px[2]]),
+                ColorType::Palette => {
+                    let idx = usize::from(px[0]) * 3;
+                    palette
+                        .get(idx..idx + 3)
+                        .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
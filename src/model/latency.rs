@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Round-trip latency of z2m commands for a single resource: the time from
+/// sending a `set` request, until the corresponding state update is received.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// Number of measured round trips
+    pub count: u64,
+    /// Number of requests that never got a state update
+    pub timeouts: u64,
+    /// Number of commands sent again, after getting no state update
+    #[serde(default)]
+    pub retries: u64,
+    /// Number of commands that got no state update after all retries
+    #[serde(default)]
+    pub failures: u64,
+    pub total_ms: u64,
+    pub min_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+    pub last_ms: Option<u64>,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        let ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        self.count += 1;
+        self.total_ms = self.total_ms.saturating_add(ms);
+        self.min_ms = Some(self.min_ms.map_or(ms, |min| min.min(ms)));
+        self.max_ms = Some(self.max_ms.map_or(ms, |max| max.max(ms)));
+        self.last_ms = Some(ms);
+    }
+
+    pub const fn record_timeout(&mut self) {
+        self.timeouts += 1;
+    }
+
+    pub const fn record_retry(&mut self) {
+        self.retries += 1;
+    }
+
+    pub const fn record_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    #[must_use]
+    pub const fn mean_ms(&self) -> Option<u64> {
+        self.total_ms.checked_div(self.count)
+    }
+}
@@ -0,0 +1,273 @@
+use std::hash::Hash;
+
+use serde_json::json;
+
+use crate::config::{LayoutConfig, LayoutScene};
+use crate::error::ApiResult;
+use crate::hue::api::{
+    GroupedLight, RType, Resource, ResourceLink, Room, RoomArchetype, RoomMetadata, Scene,
+    SceneActionElement, SceneMetadata, SceneStatus, Zone,
+};
+use crate::hue::locale::Locale;
+use crate::resource::{AuxData, Resources};
+use crate::z2m::guess_scene_icon;
+
+/* ids are derived from names, so renaming a room (or zone, or scene) in the
+ * config creates a new one, and leaves the old one in place */
+fn layout_link(rtype: RType, name: impl Hash) -> ResourceLink {
+    rtype.deterministic(("layout", name))
+}
+
+fn find_device(res: &Resources, name: &str) -> Option<ResourceLink> {
+    res.get_resources_by_type(RType::Device)
+        .into_iter()
+        .find(|rr| matches!(&rr.obj, Resource::Device(dev) if dev.metadata.name == name))
+        .map(|rr| RType::Device.link_to(rr.id))
+}
+
+/// A light by name, or the light of a device by name
+fn find_light(res: &Resources, name: &str) -> Option<ResourceLink> {
+    res.get_resources_by_type(RType::Light)
+        .into_iter()
+        .find(|rr| matches!(&rr.obj, Resource::Light(light) if light.metadata.name == name))
+        .map(|rr| RType::Light.link_to(rr.id))
+        .or_else(|| {
+            let dev = find_device(res, name)?;
+            match res.get_resource_by_id(&dev.rid).ok()?.obj {
+                Resource::Device(dev) => dev.light_service().copied(),
+                _ => None,
+            }
+        })
+}
+
+/// A room or zone by name. Rooms and zones from the config come first.
+fn find_group(res: &Resources, name: &str) -> Option<ResourceLink> {
+    [RType::Room, RType::Zone]
+        .into_iter()
+        .map(|rtype| layout_link(rtype, name))
+        .find(|link| res.get_resource_by_id(&link.rid).is_ok())
+        .or_else(|| {
+            res.get_resources_by_type(RType::Room)
+                .into_iter()
+                .chain(res.get_resources_by_type(RType::Zone))
+                .find_map(|rr| match &rr.obj {
+                    Resource::Room(room) if room.metadata.name == name => {
+                        Some(RType::Room.link_to(rr.id))
+                    }
+                    Resource::Zone(zone) if zone.metadata.name == name => {
+                        Some(RType::Zone.link_to(rr.id))
+                    }
+                    _ => None,
+                })
+        })
+}
+
+fn resolve(
+    res: &Resources,
+    names: &[String],
+    find: fn(&Resources, &str) -> Option<ResourceLink>,
+    group: &str,
+) -> Vec<ResourceLink> {
+    let mut links = vec![];
+    for name in names {
+        match find(res, name) {
+            Some(link) if !links.contains(&link) => links.push(link),
+            Some(_) => {}
+            None => log::warn!("Layout of {group:?}: {name:?} not found"),
+        }
+    }
+    links
+}
+
+/// Create a room or zone, or make sure an existing one has the configured
+/// name, icon and members. Members added by other means are kept.
+fn reconcile_group(
+    res: &mut Resources,
+    rtype: RType,
+    name: &str,
+    icon: Option<RoomArchetype>,
+    members: Vec<ResourceLink>,
+) -> ApiResult<()> {
+    let link = layout_link(rtype, name);
+
+    let current = match res.get_resource_by_id(&link.rid).map(|rr| rr.obj) {
+        Ok(Resource::Room(room)) => Some((room.children, room.metadata)),
+        Ok(Resource::Zone(zone)) => Some((zone.children, zone.metadata)),
+        _ => None,
+    };
+
+    let Some((mut children, mut metadata)) = current else {
+        log::info!("Adding {link:?} ({name}) from config");
+        let link_glight = RType::GroupedLight.deterministic(link.rid);
+        let metadata = RoomMetadata::new(icon.unwrap_or(RoomArchetype::Other), name);
+        let obj = match rtype {
+            RType::Zone => Resource::Zone(Zone {
+                children: members,
+                metadata,
+                services: vec![link_glight],
+            }),
+            _ => Resource::Room(Room {
+                children: members,
+                metadata,
+                services: vec![link_glight],
+            }),
+        };
+        res.add(&link, obj)?;
+        return res.add(
+            &link_glight,
+            Resource::GroupedLight(GroupedLight::new(link)),
+        );
+    };
+
+    let before = (children.len(), metadata.clone());
+    for member in members {
+        if !children.contains(&member) {
+            children.push(member);
+        }
+    }
+    metadata.name = name.to_string();
+    if let Some(icon) = icon {
+        metadata.archetype = icon;
+    }
+
+    if before.0 == children.len()
+        && before.1.name == metadata.name
+        && before.1.archetype == metadata.archetype
+    {
+        return Ok(());
+    }
+
+    log::info!("Updating {link:?} ({name}) from config");
+    match rtype {
+        RType::Zone => res.update::<Zone>(&link.rid, |zone| {
+            zone.children = children;
+            zone.metadata = metadata;
+        }),
+        _ => res.update::<Room>(&link.rid, |room| {
+            room.children = children;
+            room.metadata = metadata;
+        }),
+    }
+}
+
+/// Create a scene, or make sure an existing one has the configured actions.
+/// Actions for other lights (e.g., added by an app) are kept.
+fn reconcile_scene(res: &mut Resources, scene: &LayoutScene, locale: Locale) -> ApiResult<()> {
+    let Some(group) = find_group(res, &scene.group) else {
+        log::warn!(
+            "Layout of scene {:?}: room or zone {:?} not found",
+            scene.name,
+            scene.group
+        );
+        return Ok(());
+    };
+
+    let link = layout_link(RType::Scene, (&scene.group, &scene.name));
+
+    let mut actions: Vec<SceneActionElement> = vec![];
+    for act in &scene.actions {
+        let Some(target) = find_light(res, &act.light) else {
+            log::warn!(
+                "Layout of scene {:?}: {:?} not found",
+                scene.name,
+                act.light
+            );
+            continue;
+        };
+        actions.push(SceneActionElement {
+            action: act.action.clone(),
+            target,
+        });
+    }
+
+    let Ok(current) = res.get::<Scene>(&link) else {
+        log::info!("Adding {link:?} ({}) from config", scene.name);
+
+        /* not stored in z2m, so it is recalled by sending its actions */
+        res.aux_set(&link, AuxData::new().with_topic(&scene.name));
+        return res.add(
+            &link,
+            Resource::Scene(Scene {
+                actions,
+                auto_dynamic: false,
+                group,
+                metadata: SceneMetadata {
+                    appdata: None,
+                    image: guess_scene_icon(&scene.name, locale),
+                    name: scene.name.clone(),
+                },
+                palette: json!({
+                    "color": [],
+                    "dimming": [],
+                    "color_temperature": [],
+                    "effects": [],
+                }),
+                speed: 0.5,
+                status: Some(SceneStatus::Inactive),
+            }),
+        );
+    };
+
+    for sae in &current.actions {
+        if !actions.iter().any(|act| act.target == sae.target) {
+            actions.push(sae.clone());
+        }
+    }
+
+    if serde_json::to_value(&actions)? == serde_json::to_value(&current.actions)?
+        && current.metadata.name == scene.name
+    {
+        return Ok(());
+    }
+
+    log::info!("Updating {link:?} ({}) from config", scene.name);
+    res.update::<Scene>(&link.rid, |scn| {
+        scn.actions = actions;
+        scn.metadata.name.clone_from(&scene.name);
+    })
+}
+
+/// Bring the rooms, zones and scenes declared in the config into the
+/// resource store. Nothing is ever deleted.
+///
+/// Devices and lights are found by name, so members that are not known yet
+/// (e.g., before z2m has reported its devices) are skipped, and added the
+/// next time the layout is applied.
+pub fn reconcile(res: &mut Resources, layout: &LayoutConfig, locale: Locale) -> ApiResult<()> {
+    res.transaction(|res| {
+        for room in &layout.rooms {
+            let link = layout_link(RType::Room, &room.name);
+
+            /* a device can only be in one room */
+            let devices = resolve(res, &room.devices, find_device, &room.name)
+                .into_iter()
+                .filter(|dev| {
+                    let other = res.get_resources_by_type(RType::Room).into_iter().find(
+                        |rr| matches!(&rr.obj, Resource::Room(other) if rr.id != link.rid && other.children.contains(dev)),
+                    );
+                    if let Some(other) = &other {
+                        log::warn!(
+                            "Layout of {:?}: {dev:?} is already in room {}",
+                            room.name,
+                            other.id
+                        );
+                    }
+                    other.is_none()
+                })
+                .collect();
+
+            reconcile_group(res, RType::Room, &room.name, room.icon, devices)?;
+        }
+
+        for zone in &layout.zones {
+            let lights = resolve(res, &zone.lights, find_light, &zone.name);
+            reconcile_group(res, RType::Zone, &zone.name, zone.icon, lights)?;
+        }
+
+        for scene in &layout.scenes {
+            reconcile_scene(res, scene, locale)?;
+        }
+
+        Ok(())
+    })
+}
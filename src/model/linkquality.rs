@@ -0,0 +1,49 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Samples kept per device. Older samples are dropped.
+pub const HISTORY_LEN: usize = 120;
+
+/// A link quality reading, as reported by z2m with a device message
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LinkQualitySample {
+    pub time: DateTime<Utc>,
+    pub linkquality: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+}
+
+/// Recent link quality readings of each device, for diagnosing devices
+/// with a flaky connection. Only kept in memory.
+#[derive(Debug, Clone, Default)]
+pub struct LinkQualityHistory {
+    devices: HashMap<Uuid, VecDeque<LinkQualitySample>>,
+}
+
+impl LinkQualityHistory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, device: Uuid, sample: LinkQualitySample) {
+        let samples = self.devices.entry(device).or_default();
+        if samples.len() >= HISTORY_LEN {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Samples of a device, oldest first
+    #[must_use]
+    pub fn samples(&self, device: &Uuid) -> Option<&VecDeque<LinkQualitySample>> {
+        self.devices.get(device)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = (&Uuid, &VecDeque<LinkQualitySample>)> {
+        self.devices.iter()
+    }
+}
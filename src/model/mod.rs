@@ -1 +1,18 @@
+pub mod astronomy;
+pub mod effect;
+pub mod entertainment;
+pub mod image;
+pub mod latency;
+pub mod layout;
+pub mod linkquality;
+pub mod motion;
+pub mod networkmap;
+pub mod nightmode;
+pub mod pairing;
+pub mod palette;
+pub mod rollout;
+pub mod state;
 pub mod types;
+pub mod units;
+pub mod whitelist;
+pub mod z2mservers;
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+/// Time between checks for motion holds that have expired
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Timing configured for a motion sensor
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MotionTiming {
+    /// Motion is reported for (at least) this long after the last detection
+    pub hold: Option<Duration>,
+    /// New motion is ignored for this long after motion ended
+    pub retrigger_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FilterState {
+    timing: MotionTiming,
+    detected: Option<Instant>,
+    cleared: Option<Instant>,
+    /* motion ended early, and is reported as ended at this time */
+    pending_clear: Option<Instant>,
+}
+
+/// Occupancy hold times and re-trigger suppression for motion sensors,
+/// applied on top of what the sensors (and z2m) report. Sensors without
+/// configured timing are passed through unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct MotionFilters {
+    sensors: HashMap<Uuid, FilterState>,
+}
+
+impl MotionFilters {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn configure(&mut self, motion: Uuid, timing: MotionTiming) {
+        if timing.hold.is_none() && timing.retrigger_delay.is_none() {
+            self.sensors.remove(&motion);
+        } else {
+            self.sensors.entry(motion).or_default().timing = timing;
+        }
+    }
+
+    /// Filter an occupancy report from a sensor. Returns the occupancy to
+    /// report now, or `None` if the report is held back (or ignored).
+    pub fn report(&mut self, motion: Uuid, occupancy: bool, now: Instant) -> Option<bool> {
+        let Some(state) = self.sensors.get_mut(&motion) else {
+            return Some(occupancy);
+        };
+
+        if occupancy {
+            if let (Some(cleared), Some(delay)) = (state.cleared, state.timing.retrigger_delay) {
+                if state.pending_clear.is_none() && now < cleared + delay {
+                    log::debug!("Ignoring motion from {motion}, within re-trigger delay");
+                    return None;
+                }
+            }
+            state.detected = Some(now);
+            state.pending_clear = None;
+            return Some(true);
+        }
+
+        let hold_until = state
+            .detected
+            .zip(state.timing.hold)
+            .map(|(det, hold)| det + hold);
+        match hold_until {
+            Some(until) if now < until => {
+                state.pending_clear = Some(until);
+                None
+            }
+            _ => {
+                state.pending_clear = None;
+                state.cleared = Some(now);
+                Some(false)
+            }
+        }
+    }
+
+    /// Motion sensors whose hold time has passed, so the end of motion
+    /// should be reported now
+    pub fn expire(&mut self, now: Instant) -> Vec<Uuid> {
+        let mut res = vec![];
+        for (id, state) in &mut self.sensors {
+            if state.pending_clear.is_some_and(|until| until <= now) {
+                state.pending_clear = None;
+                state.cleared = Some(now);
+                res.push(*id);
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::hue::date_format;
+
+/// Output formats of the z2m network map
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMapType {
+    Raw,
+    Graphviz,
+    Plantuml,
+}
+
+/// Network map (mesh topology) reported by a z2m server. The value is a json
+/// object for raw maps, and a string (the rendered graph) otherwise.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkMap {
+    #[serde(with = "date_format::utc")]
+    pub time: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub map_type: NetworkMapType,
+    #[serde(default)]
+    pub routes: bool,
+    pub value: Value,
+}
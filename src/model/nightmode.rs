@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Timelike};
+use chrono_tz::Tz;
+use uuid::Uuid;
+
+use crate::config::{LocationConfig, NightModeConfig};
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{RType, Resource};
+use crate::model::astronomy::TimeOfDay;
+use crate::model::units;
+use crate::resource::Resources;
+use crate::timezone;
+use crate::z2m::update::{DeviceState, DeviceUpdate};
+
+/// Quiet hours: a daily period during which updates sent to the lights of
+/// some rooms are rewritten to keep them dim and warm.
+#[derive(Debug, Clone)]
+pub struct NightMode {
+    start: TimeOfDay,
+    end: TimeOfDay,
+    location: Option<LocationConfig>,
+    rooms: Vec<String>,
+    /* on the zigbee scale */
+    max_brightness: f64,
+    mirek: u32,
+    tz: Tz,
+}
+
+impl NightMode {
+    pub fn new(
+        conf: &NightModeConfig,
+        tz: Tz,
+        location: Option<&LocationConfig>,
+    ) -> ApiResult<Self> {
+        let start = TimeOfDay::parse(&conf.start)?;
+        let end = TimeOfDay::parse(&conf.end)?;
+        if location.is_none() && (start.is_solar() || end.is_solar()) {
+            return Err(ApiError::InvalidConfig(
+                "night mode times relative to the sun need a bridge location".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            location: location.cloned(),
+            rooms: conf.rooms.clone(),
+            max_brightness: units::percent_to_device(conf.max_brightness),
+            mirek: conf.mirek,
+            tz,
+        })
+    }
+
+    /// Returns true, if `now` is within quiet hours. Quiet hours can span
+    /// midnight (e.g., from 22:00 to 06:30). Times relative to the sun are
+    /// taken from the same day; on days without sunrise or sunset, quiet
+    /// hours are not active.
+    #[must_use]
+    pub fn is_active_at(&self, now: &DateTime<Tz>) -> bool {
+        let date = now.date_naive();
+        let loc = self.location.as_ref();
+        let (Some(start), Some(end)) = (
+            self.start.resolve(date, self.tz, loc),
+            self.end.resolve(date, self.tz, loc),
+        ) else {
+            return false;
+        };
+
+        let time = now.time().with_second(0).unwrap_or_else(|| now.time());
+        if start <= end {
+            start <= time && time < end
+        } else {
+            start <= time || time < end
+        }
+    }
+
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(&timezone::now(self.tz))
+    }
+
+    /// The rooms and zones (with their grouped lights and lights) quiet
+    /// hours apply to right now. Outside of quiet hours, there are none.
+    #[must_use]
+    pub fn targets(&self, res: &Resources) -> HashSet<Uuid> {
+        let mut targets = HashSet::new();
+        if !self.is_active() {
+            return targets;
+        }
+
+        for rr in res
+            .get_resources_by_type(RType::Room)
+            .into_iter()
+            .chain(res.get_resources_by_type(RType::Zone))
+        {
+            let (metadata, services) = match &rr.obj {
+                Resource::Room(room) => (&room.metadata, &room.services),
+                Resource::Zone(zone) => (&zone.metadata, &zone.services),
+                _ => continue,
+            };
+            if !self.rooms.contains(&metadata.name) {
+                continue;
+            }
+
+            targets.insert(rr.id);
+            targets.extend(services.iter().map(|svc| svc.rid));
+            targets.extend(
+                res.get_lights_for_group(&rr.obj.rtype().link_to(rr.id))
+                    .iter()
+                    .map(|light| light.rid),
+            );
+        }
+
+        targets
+    }
+
+    /// The update to send instead of `upd` during quiet hours, or `None` if
+    /// it can be sent as-is.
+    ///
+    /// Brightness is capped, colors are replaced by the night color
+    /// temperature (or a warmer one, if asked for), and lights turned on
+    /// without a brightness are turned on dimmed, since they would otherwise
+    /// return to their last (daytime) brightness.
+    #[must_use]
+    pub fn apply(&self, upd: &DeviceUpdate) -> Option<DeviceUpdate> {
+        let turn_on = matches!(upd.state, Some(DeviceState::On));
+        let brightness = match upd.brightness {
+            Some(bri) => Some(bri.min(self.max_brightness)),
+            None if turn_on => Some(self.max_brightness),
+            None => None,
+        };
+        let mirek = match upd.color_temp {
+            Some(mirek) => Some(mirek.max(self.mirek)),
+            None if turn_on || upd.color.is_some() => Some(self.mirek),
+            None => None,
+        };
+
+        if brightness == upd.brightness && mirek == upd.color_temp && upd.color.is_none() {
+            return None;
+        }
+
+        let mut night = upd.clone().with_brightness(brightness);
+        night.color = None;
+        night.color_mode = None;
+        Some(night.with_color_temp(mirek))
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Records kept at most. The oldest records are dropped first.
+pub const MAX_RECORDS: usize = 64;
+
+/// Time after which an unfinished pairing is no longer considered in
+/// progress (e.g., when a device was powered off while being interviewed)
+pub const PAIRING_TIMEOUT: Duration = Duration::minutes(5);
+
+/// Steps of adding a device to a zigbee network, as reported by z2m
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairingStage {
+    Joined,
+    Announced,
+    InterviewStarted,
+    InterviewSuccessful,
+    InterviewFailed,
+    Left,
+}
+
+impl PairingStage {
+    /// Whether more steps are expected to follow
+    #[must_use]
+    pub const fn is_pending(self) -> bool {
+        matches!(
+            self,
+            Self::Joined | Self::Announced | Self::InterviewStarted
+        )
+    }
+}
+
+/// Pairing progress of a single device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PairingProgress {
+    pub server: String,
+    pub ieee_address: String,
+    pub friendly_name: String,
+    pub stage: PairingStage,
+    /* known once the interview succeeded */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supported: Option<bool>,
+    pub updated: DateTime<Utc>,
+}
+
+impl PairingProgress {
+    /// Whether this device is still being paired
+    #[must_use]
+    pub fn in_progress(&self) -> bool {
+        self.stage.is_pending() && Utc::now() - self.updated < PAIRING_TIMEOUT
+    }
+}
+
+/// Recent pairing progress of each device, for showing progress while
+/// adding devices. Only kept in memory.
+#[derive(Debug, Clone, Default)]
+pub struct Pairings {
+    devices: HashMap<String, PairingProgress>,
+}
+
+impl Pairings {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the next step of pairing a device. Details learned in
+    /// earlier steps (e.g., the model) are kept, if not reported again.
+    pub fn record(&mut self, mut progress: PairingProgress) -> PairingProgress {
+        if let Some(prev) = self.devices.remove(&progress.ieee_address) {
+            progress.model = progress.model.or(prev.model);
+            progress.vendor = progress.vendor.or(prev.vendor);
+            progress.description = progress.description.or(prev.description);
+            progress.supported = progress.supported.or(prev.supported);
+        }
+
+        if self.devices.len() >= MAX_RECORDS {
+            let oldest = self
+                .devices
+                .values()
+                .min_by_key(|rec| rec.updated)
+                .map(|rec| rec.ieee_address.clone());
+            if let Some(oldest) = oldest {
+                self.devices.remove(&oldest);
+            }
+        }
+
+        self.devices
+            .insert(progress.ieee_address.clone(), progress.clone());
+        progress
+    }
+
+    /// Pairing progress of all devices paired through `server`, most
+    /// recent first
+    #[must_use]
+    pub fn for_server(&self, server: &str) -> Vec<&PairingProgress> {
+        let mut records: Vec<_> = self
+            .devices
+            .values()
+            .filter(|rec| rec.server == server)
+            .collect();
+        records.sort_by_key(|rec| std::cmp::Reverse(rec.updated));
+        records
+    }
+}
@@ -0,0 +1,127 @@
+use crate::model::image::Image;
+use crate::model::types::XY;
+
+/// Number of pixels sampled from an image. Larger images are subsampled,
+/// which does not change the dominant colors noticeably.
+const MAX_SAMPLES: usize = 16384;
+
+/// Pixels darker than this (in every channel) are ignored, since a light
+/// cannot reproduce black anyway
+const DARK_THRESHOLD: u8 = 24;
+
+/// Lowest brightness used for palette colors, so no light is (almost) off
+const MIN_BRIGHTNESS: f64 = 10.0;
+
+/// A dominant color of an image
+#[derive(Debug, Clone, Copy)]
+pub struct PaletteColor {
+    pub rgb: [u8; 3],
+    pub xy: XY,
+    /// Brightness (in percent), from the value of the color
+    pub brightness: f64,
+    /// Fraction of the (sampled) image covered by this color
+    pub weight: f64,
+}
+
+/// A set of pixels, split by the median cut algorithm
+struct ColorBox {
+    pixels: Vec<[u8; 3]>,
+}
+
+impl ColorBox {
+    /// The channel with the largest range, and that range
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|ch| {
+                let (min, max) = self
+                    .pixels
+                    .iter()
+                    .fold((u8::MAX, u8::MIN), |(min, max), px| {
+                        (min.min(px[ch]), max.max(px[ch]))
+                    });
+                (ch, max.saturating_sub(min))
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    }
+
+    fn split(mut self) -> (Self, Self) {
+        let (ch, _) = self.widest_channel();
+        self.pixels.sort_unstable_by_key(|px| px[ch]);
+        let upper = self.pixels.split_off(self.pixels.len() / 2);
+        (self, Self { pixels: upper })
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn average(&self) -> [u8; 3] {
+        let len = self.pixels.len().max(1) as u64;
+        let mut sum = [0u64; 3];
+        for px in &self.pixels {
+            for ch in 0..3 {
+                sum[ch] += u64::from(px[ch]);
+            }
+        }
+        sum.map(|total| (total / len) as u8)
+    }
+}
+
+/// Find (up to) `count` dominant colors of an image, using the median cut
+/// algorithm. Colors are returned most common first. Images with fewer
+/// distinct colors give a shorter palette.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn extract(image: &Image, count: usize) -> Vec<PaletteColor> {
+    let step = (image.pixels.len() / MAX_SAMPLES).max(1);
+    let samples: Vec<[u8; 3]> = image.pixels.iter().step_by(step).copied().collect();
+
+    let bright: Vec<[u8; 3]> = samples
+        .iter()
+        .filter(|px| px.iter().any(|c| *c >= DARK_THRESHOLD))
+        .copied()
+        .collect();
+
+    /* (mostly) dark images still get a palette */
+    let pixels = if bright.len() >= count {
+        bright
+    } else {
+        samples
+    };
+    let total = pixels.len();
+    if total == 0 || count == 0 {
+        return vec![];
+    }
+
+    let mut boxes = vec![ColorBox { pixels }];
+    while boxes.len() < count {
+        let Some((idx, _)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(idx, cbox)| (idx, cbox.widest_channel().1))
+            .filter(|(_, range)| *range > 0)
+            .max_by_key(|(_, range)| *range)
+        else {
+            break;
+        };
+
+        let (lower, upper) = boxes.swap_remove(idx).split();
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|cbox| std::cmp::Reverse(cbox.pixels.len()));
+
+    boxes
+        .iter()
+        .map(|cbox| {
+            let rgb = cbox.average();
+            let (xy, _) = XY::from_rgb(rgb);
+            let value = f64::from(rgb.into_iter().max().unwrap_or_default()) / 255.0;
+            PaletteColor {
+                rgb,
+                xy,
+                brightness: (value * 100.0).max(MIN_BRIGHTNESS),
+                weight: cbox.pixels.len() as f64 / total as f64,
+            }
+        })
+        .collect()
+}
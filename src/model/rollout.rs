@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::hue::api::ResourceLink;
+use crate::hue::date_format;
+use crate::z2m::update::DeviceUpdate;
+
+/// Smallest number of lights in a group update that is rolled out in slices
+pub const MIN_LIGHTS: usize = 50;
+
+/// Number of light updates sent per slice
+pub const SLICE_SIZE: usize = 10;
+
+/// Time between two slices
+pub const SLICE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A group update, sent to one light at a time
+#[derive(Debug, Clone)]
+struct Rollout {
+    id: Uuid,
+    group: ResourceLink,
+    upd: DeviceUpdate,
+    pending: VecDeque<ResourceLink>,
+    total: usize,
+    started: DateTime<Utc>,
+}
+
+/// Progress of a group update that is being rolled out
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutStatus {
+    pub id: Uuid,
+    pub group: ResourceLink,
+    pub total: usize,
+    pub sent: usize,
+    #[serde(with = "date_format::utc")]
+    pub started: DateTime<Utc>,
+}
+
+/// Group updates in progress, oldest first.
+///
+/// Slices are shared by all rollouts, so a later update never overtakes an
+/// earlier one, and the overall rate stays the same.
+#[derive(Debug, Clone, Default)]
+pub struct Rollouts {
+    active: VecDeque<Rollout>,
+}
+
+impl Rollouts {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule `upd` for each of `lights`, and return the id of the rollout
+    pub fn start(
+        &mut self,
+        group: ResourceLink,
+        lights: Vec<ResourceLink>,
+        upd: DeviceUpdate,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        self.active.push_back(Rollout {
+            id,
+            group,
+            upd,
+            total: lights.len(),
+            pending: lights.into(),
+            started: Utc::now(),
+        });
+        id
+    }
+
+    #[must_use]
+    pub fn status(&self) -> Vec<RolloutStatus> {
+        self.active
+            .iter()
+            .map(|rollout| RolloutStatus {
+                id: rollout.id,
+                group: rollout.group,
+                total: rollout.total,
+                sent: rollout.total - rollout.pending.len(),
+                started: rollout.started,
+            })
+            .collect()
+    }
+
+    /// Take the next slice of light updates, and forget finished rollouts
+    pub fn next_slice(&mut self) -> Vec<(ResourceLink, DeviceUpdate)> {
+        let mut res = vec![];
+
+        while res.len() < SLICE_SIZE {
+            let Some(rollout) = self.active.front_mut() else {
+                break;
+            };
+
+            let count = (SLICE_SIZE - res.len()).min(rollout.pending.len());
+            res.extend(
+                rollout
+                    .pending
+                    .drain(..count)
+                    .map(|light| (light, rollout.upd.clone())),
+            );
+
+            if rollout.pending.is_empty() {
+                log::debug!(
+                    "Rollout {} to {} lights of {:?} done",
+                    rollout.id,
+                    rollout.total,
+                    rollout.group
+                );
+                self.active.pop_front();
+            }
+        }
+
+        res
+    }
+}
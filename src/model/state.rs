@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::hue::api::{
+    ColorTemperatureUpdate, ColorUpdate, DimmingUpdate, LightUpdate, On, SceneAction,
+};
+use crate::model::types::XY;
+use crate::model::units;
+use crate::z2m::update::{DeviceColorMode, DeviceState, DeviceUpdate};
+
+#[derive(Copy, Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    ColorTemperature,
+    Xy,
+}
+
+/// Last known state of a light. Fields are `None` until the state is known
+#[derive(Copy, Debug, Serialize, Clone, Default, PartialEq)]
+pub struct LightState {
+    pub on: Option<bool>,
+    /// Brightness, in percent (like the hue api)
+    pub brightness: Option<f64>,
+    pub color_mode: Option<ColorMode>,
+    pub xy: Option<XY>,
+    pub mirek: Option<u32>,
+}
+
+impl LightState {
+    /// Apply a state update reported by z2m
+    pub fn apply_device_update(&mut self, upd: &DeviceUpdate) {
+        if let Some(state) = upd.state {
+            self.on = Some(matches!(state, DeviceState::On));
+        }
+
+        if let Some(b) = upd.brightness {
+            self.brightness = Some(units::device_to_percent(b));
+        }
+
+        if let Some(mirek) = upd.color_temp {
+            self.mirek = Some(mirek);
+        }
+
+        if let Some(col) = upd.color {
+            self.xy = Some(col.xy);
+        }
+
+        /* z2m reports both color and color temperature, so prefer the
+         * reported color mode, if any */
+        match (upd.color_mode, upd.color_temp, upd.color) {
+            (Some(DeviceColorMode::ColorTemp), _, _) | (None, Some(_), None) => {
+                self.color_mode = Some(ColorMode::ColorTemperature);
+            }
+            (Some(DeviceColorMode::Xy), _, _) | (None, None, Some(_)) => {
+                self.color_mode = Some(ColorMode::Xy);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply a state change requested through the api
+    pub fn apply_light_update(&mut self, upd: &LightUpdate) {
+        if let Some(on) = upd.on {
+            self.on = Some(on.on);
+        }
+
+        if let Some(dim) = &upd.dimming {
+            self.brightness = Some(dim.brightness);
+        }
+
+        if let Some(ct) = &upd.color_temperature {
+            self.mirek = Some(ct.mirek);
+            self.color_mode = Some(ColorMode::ColorTemperature);
+        }
+
+        if let Some(col) = &upd.color {
+            self.xy = Some(col.xy);
+            self.color_mode = Some(ColorMode::Xy);
+        }
+    }
+
+    /// This state, as a z2m update (only including the active color mode)
+    #[must_use]
+    pub fn as_device_update(&self) -> DeviceUpdate {
+        let action = self.as_scene_action();
+
+        DeviceUpdate::default()
+            .with_state(self.on)
+            .with_brightness(self.brightness.map(units::percent_to_device))
+            .with_color_temp(action.color_temperature.map(|ct| ct.mirek))
+            .with_color_xy(action.color.map(|col| col.xy))
+    }
+
+    /// This state, as a scene action (only including the active color mode)
+    #[must_use]
+    pub fn as_scene_action(&self) -> SceneAction {
+        let color_temperature = self
+            .mirek
+            .filter(|_| self.color_mode == Some(ColorMode::ColorTemperature))
+            .map(|mirek| ColorTemperatureUpdate { mirek });
+
+        let color = self
+            .xy
+            .filter(|_| self.color_mode == Some(ColorMode::Xy))
+            .map(|xy| ColorUpdate { xy });
+
+        SceneAction {
+            color,
+            color_temperature,
+            dimming: self
+                .brightness
+                .map(|brightness| DimmingUpdate { brightness }),
+            on: self.on.map(On::new),
+        }
+    }
+}
+
+/// Cache of last known light states, updated from z2m reports and api writes
+#[derive(Debug, Clone, Default)]
+pub struct StateCache {
+    lights: HashMap<Uuid, LightState>,
+}
+
+impl StateCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &Uuid) -> Option<&LightState> {
+        self.lights.get(id)
+    }
+
+    pub fn update(&mut self, id: &Uuid, func: impl FnOnce(&mut LightState)) {
+        func(self.lights.entry(*id).or_default());
+    }
+
+    pub fn remove(&mut self, id: &Uuid) {
+        self.lights.remove(id);
+    }
+
+    /// Combined state of a number of lights (e.g., a room): on if any light
+    /// is on, with the average brightness of the lights that are on.
+    #[must_use]
+    pub fn aggregate<'a>(&self, ids: impl IntoIterator<Item = &'a Uuid>) -> LightState {
+        let states: Vec<&LightState> = ids.into_iter().filter_map(|id| self.get(id)).collect();
+
+        if states.is_empty() {
+            return LightState::default();
+        }
+
+        let on = states.iter().any(|st| st.on == Some(true));
+
+        let lit: Vec<f64> = states
+            .iter()
+            .filter(|st| st.on == Some(true))
+            .filter_map(|st| st.brightness)
+            .collect();
+
+        #[allow(clippy::cast_precision_loss)]
+        let brightness = if lit.is_empty() {
+            None
+        } else {
+            Some(lit.iter().sum::<f64>() / lit.len() as f64)
+        };
+
+        LightState {
+            on: Some(on),
+            brightness,
+            ..LightState::default()
+        }
+    }
+}
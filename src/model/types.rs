@@ -16,6 +16,36 @@ impl XY {
     pub const fn new(x: f64, y: f64) -> Self {
         Self { x, y }
     }
+
+    /// Convert an srgb color to xy coordinates (using the wide gamut
+    /// conversion recommended for hue lights), and its relative luminance
+    #[must_use]
+    pub fn from_rgb(rgb: [u8; 3]) -> (Self, f64) {
+        Self::from_rgb_unit(rgb.map(|value| f64::from(value) / 255.0))
+    }
+
+    /// Like [`Self::from_rgb`], for color components from 0.0 to 1.0
+    #[must_use]
+    pub fn from_rgb_unit(rgb: [f64; 3]) -> (Self, f64) {
+        let [red, green, blue] = rgb.map(|value| {
+            if value > 0.04045 {
+                ((value + 0.055) / 1.055).powf(2.4)
+            } else {
+                value / 12.92
+            }
+        });
+
+        let cie_x = red.mul_add(0.664_511, green.mul_add(0.154_324, blue * 0.162_028));
+        let cie_y = red.mul_add(0.283_881, green.mul_add(0.668_433, blue * 0.047_685));
+        let cie_z = red.mul_add(0.000_088, green.mul_add(0.072_310, blue * 0.986_039));
+
+        let sum = cie_x + cie_y + cie_z;
+        if sum <= 0.0 {
+            return (Self::D65_WHITE_POINT, 0.0);
+        }
+
+        (Self::new(cie_x / sum, cie_y / sum), cie_y)
+    }
 }
 
 impl From<[f64; 2]> for XY {
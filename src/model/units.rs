@@ -0,0 +1,221 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::hue::api::MirekSchema;
+
+/// Highest brightness on the zigbee scale (used by z2m, and the v1 api)
+pub const BRIGHTNESS_MAX: f64 = 254.0;
+
+/// Lowest brightness on the zigbee scale, for lights that are on
+pub const BRIGHTNESS_MIN: f64 = 1.0;
+
+/// Default number of decimals of brightness percentages
+pub const DEFAULT_PRECISION: u32 = 2;
+
+/// Fewest decimals of brightness percentages. With less, some brightness
+/// steps share a percentage, and do not survive a round trip.
+pub const MIN_PRECISION: u32 = 2;
+
+/* decimals of brightness percentages, set from the config at startup */
+static PRECISION: AtomicU32 = AtomicU32::new(DEFAULT_PRECISION);
+
+/// Set the number of decimals brightness percentages are rounded to
+/// (between [`MIN_PRECISION`] and 6)
+pub fn set_precision(decimals: u32) {
+    if decimals < MIN_PRECISION {
+        log::warn!("Brightness precision {decimals} is too low, using {MIN_PRECISION}");
+    }
+    PRECISION.store(decimals.clamp(MIN_PRECISION, 6), Ordering::Relaxed);
+}
+
+fn round_to(value: f64, decimals: u32) -> f64 {
+    let scale = f64::from(10u32.pow(decimals));
+    (value * scale).round() / scale
+}
+
+/// Convert a brightness percentage (as used by the hue api) to the zigbee
+/// scale, rounded to a whole step, since that is what lights store.
+#[must_use]
+pub fn percent_to_device(percent: f64) -> f64 {
+    (percent / 100.0 * BRIGHTNESS_MAX)
+        .round()
+        .clamp(BRIGHTNESS_MIN, BRIGHTNESS_MAX)
+}
+
+/// Convert a brightness on the zigbee scale to a percentage, rounded to the
+/// configured precision.
+///
+/// Converting back with [`percent_to_device`] always gives the original
+/// value, so repeated reads and writes do not slowly change the brightness
+/// of a light.
+#[must_use]
+pub fn device_to_percent(bri: f64) -> f64 {
+    let percent = bri.clamp(0.0, BRIGHTNESS_MAX) / BRIGHTNESS_MAX * 100.0;
+    round_to(percent, PRECISION.load(Ordering::Relaxed))
+}
+
+/// A brightness percentage as a (v1 api) brightness value
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn percent_to_v1(percent: f64) -> u32 {
+    percent_to_device(percent) as u32
+}
+
+/// Highest hue value in the v1 api
+pub const V1_HUE_MAX: f64 = 65535.0;
+
+/// Highest saturation value in the v1 api
+pub const V1_SAT_MAX: f64 = 254.0;
+
+/// A v1 hue and saturation, as hue in degrees and saturation in percent
+#[must_use]
+pub fn v1_hs_to_device(hue: u32, sat: u32) -> (f64, f64) {
+    (
+        f64::from(hue).min(V1_HUE_MAX) / V1_HUE_MAX * 360.0,
+        f64::from(sat).min(V1_SAT_MAX) / V1_SAT_MAX * 100.0,
+    )
+}
+
+/// The color temperature range of a light, from the range reported by z2m.
+/// Bounds are rounded inwards, so the range only holds values the light
+/// supports.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn mirek_schema(min: f64, max: f64) -> MirekSchema {
+    let mirek_minimum = min.ceil().max(1.0) as u32;
+    let mirek_maximum = (max.floor() as u32).max(mirek_minimum);
+    MirekSchema {
+        mirek_minimum,
+        mirek_maximum,
+    }
+}
+
+/// Limit a color temperature to the range of a light
+#[must_use]
+pub fn clamp_mirek(mirek: u32, schema: &MirekSchema) -> u32 {
+    mirek.clamp(schema.mirek_minimum, schema.mirek_maximum)
+}
+
+/// Map brightness (zigbee scale) onto the range of a light, above its
+/// minimum brightness `min` (in percent)
+#[must_use]
+pub fn brightness_to_device(bri: f64, min: f64) -> f64 {
+    let floor = min / 100.0 * BRIGHTNESS_MAX;
+    bri.mul_add((BRIGHTNESS_MAX - floor) / BRIGHTNESS_MAX, floor)
+        .round()
+}
+
+/// Inverse of [`brightness_to_device`].
+///
+/// Lights dimmed below their minimum (e.g. by a remote) are reported at the
+/// lowest brightness. Lights with a minimum of 100% only have one
+/// brightness, which is reported as the highest.
+#[must_use]
+pub fn brightness_from_device(bri: f64, min: f64) -> f64 {
+    let floor = min / 100.0 * BRIGHTNESS_MAX;
+    if floor >= BRIGHTNESS_MAX {
+        return BRIGHTNESS_MAX;
+    }
+    ((bri - floor) * BRIGHTNESS_MAX / (BRIGHTNESS_MAX - floor))
+        .round()
+        .clamp(BRIGHTNESS_MIN, BRIGHTNESS_MAX)
+}
+
+/* brightness values are whole numbers, so they compare exactly */
+#[cfg(test)]
+#[allow(clippy::float_cmp, clippy::suboptimal_flops)]
+mod tests {
+    use crate::hue::api::MirekSchema;
+
+    use super::{
+        brightness_from_device, brightness_to_device, clamp_mirek, device_to_percent, mirek_schema,
+        percent_to_device, BRIGHTNESS_MAX, BRIGHTNESS_MIN,
+    };
+
+    #[test]
+    fn percent_round_trip() {
+        for step in 0..=254 {
+            let bri = f64::from(step);
+            assert_eq!(
+                percent_to_device(device_to_percent(bri)),
+                bri.max(BRIGHTNESS_MIN),
+                "brightness {bri}"
+            );
+        }
+    }
+
+    #[test]
+    fn percent_clamping() {
+        /* lights that are on never go fully dark */
+        assert_eq!(percent_to_device(0.0), BRIGHTNESS_MIN);
+        assert_eq!(percent_to_device(-10.0), BRIGHTNESS_MIN);
+        assert_eq!(percent_to_device(100.0), BRIGHTNESS_MAX);
+        assert_eq!(percent_to_device(250.0), BRIGHTNESS_MAX);
+
+        assert_eq!(device_to_percent(-1.0), 0.0);
+        assert_eq!(device_to_percent(BRIGHTNESS_MAX), 100.0);
+        assert_eq!(device_to_percent(255.0), 100.0);
+    }
+
+    #[test]
+    fn mirek_schema_rounds_inwards() {
+        let schema = mirek_schema(153.4, 454.6);
+        assert_eq!(schema.mirek_minimum, 154);
+        assert_eq!(schema.mirek_maximum, 454);
+
+        let schema = mirek_schema(153.0, 500.0);
+        assert_eq!(schema.mirek_minimum, 153);
+        assert_eq!(schema.mirek_maximum, 500);
+
+        /* never an empty (or zero) range */
+        let schema = mirek_schema(0.0, 0.5);
+        assert_eq!(schema.mirek_minimum, 1);
+        assert_eq!(schema.mirek_maximum, 1);
+
+        let schema = MirekSchema {
+            mirek_minimum: 200,
+            mirek_maximum: 400,
+        };
+        assert_eq!(clamp_mirek(100, &schema), 200);
+        assert_eq!(clamp_mirek(300, &schema), 300);
+        assert_eq!(clamp_mirek(500, &schema), 400);
+    }
+
+    #[test]
+    fn brightness_min_round_trip() {
+        for min in [0.0, 1.0, 10.0, 25.0, 50.0] {
+            /* the usable range of the light has fewer steps, so brightness
+             * may be off by the size of one of them */
+            let step = BRIGHTNESS_MAX / (BRIGHTNESS_MAX - min / 100.0 * BRIGHTNESS_MAX);
+            for bri in 1..=254 {
+                let bri = f64::from(bri);
+                let dev = brightness_to_device(bri, min);
+                assert!(
+                    dev >= min / 100.0 * BRIGHTNESS_MAX - 0.5,
+                    "min {min}%, {bri}"
+                );
+                assert!(dev <= BRIGHTNESS_MAX);
+
+                let back = brightness_from_device(dev, min);
+                assert!(
+                    (back - bri).abs() <= step.ceil(),
+                    "min {min}%, {bri} -> {back}"
+                );
+            }
+            assert_eq!(brightness_to_device(BRIGHTNESS_MAX, min), BRIGHTNESS_MAX);
+            assert_eq!(brightness_from_device(BRIGHTNESS_MAX, min), BRIGHTNESS_MAX);
+        }
+
+        /* dimmed below the minimum by a remote */
+        assert_eq!(brightness_from_device(5.0, 10.0), BRIGHTNESS_MIN);
+    }
+
+    #[test]
+    fn brightness_min_full() {
+        assert_eq!(brightness_to_device(1.0, 100.0), BRIGHTNESS_MAX);
+        assert_eq!(
+            brightness_from_device(BRIGHTNESS_MAX, 100.0),
+            BRIGHTNESS_MAX
+        );
+        assert_eq!(brightness_from_device(100.0, 100.0), BRIGHTNESS_MAX);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An application paired with the bridge (POST /api)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WhitelistUser {
+    /* the "devicetype" given when pairing, e.g., "hue_app#iphone" */
+    pub name: String,
+    pub clientkey: Option<Uuid>,
+    pub create_date: DateTime<Utc>,
+    pub last_use_date: DateTime<Utc>,
+}
+
+/// Paired applications, by username (their application key), and the state
+/// of the link button needed to pair new ones
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Whitelist {
+    users: BTreeMap<Uuid, WhitelistUser>,
+    #[serde(skip)]
+    pressed_until: Option<DateTime<Utc>>,
+}
+
+impl Whitelist {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the paired applications from `other` (e.g., a reloaded state
+    /// file), keeping the state of the link button
+    pub fn load(&mut self, other: Self) {
+        self.users = other.users;
+    }
+
+    /// Press the link button, for `duration`
+    pub fn press(&mut self, duration: Duration) {
+        let until = Utc::now() + duration;
+        self.pressed_until = self.pressed_until.max(Some(until));
+    }
+
+    #[must_use]
+    pub fn is_pressed(&self) -> bool {
+        self.pressed_until.is_some_and(|until| Utc::now() < until)
+    }
+
+    /// Pair a new application, returning its username
+    pub fn add(&mut self, name: &str, clientkey: Option<Uuid>) -> Uuid {
+        let username = Uuid::new_v4();
+        let now = Utc::now();
+        self.users.insert(
+            username,
+            WhitelistUser {
+                name: name.to_string(),
+                clientkey,
+                create_date: now,
+                last_use_date: now,
+            },
+        );
+        username
+    }
+
+    pub fn remove(&mut self, username: &Uuid) -> Option<WhitelistUser> {
+        self.users.remove(username)
+    }
+
+    #[must_use]
+    pub fn get(&self, username: &Uuid) -> Option<&WhitelistUser> {
+        self.users.get(username)
+    }
+
+    /// Whether `key` is the username of a paired application. The last use
+    /// date is updated, but only saved along with other changes.
+    pub fn touch(&mut self, key: &str) -> bool {
+        let Some(user) = Uuid::parse_str(key)
+            .ok()
+            .and_then(|username| self.users.get_mut(&username))
+        else {
+            return false;
+        };
+        user.last_use_date = Utc::now();
+        true
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &WhitelistUser)> {
+        self.users.iter()
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Z2mConfig, Z2mServer};
+
+/// Changes made to the z2m servers at runtime (through the admin api), on
+/// top of the servers in the config file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Z2mServers {
+    /* servers added or changed at runtime, replacing any config file entry */
+    #[serde(default)]
+    servers: BTreeMap<String, Z2mServer>,
+    /* config file servers removed at runtime */
+    #[serde(default)]
+    removed: BTreeSet<String>,
+}
+
+impl Z2mServers {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The servers to run: those in `config` (unless removed), with runtime
+    /// changes applied
+    #[must_use]
+    pub fn effective(&self, config: &Z2mConfig) -> BTreeMap<String, Z2mServer> {
+        let mut res: BTreeMap<String, Z2mServer> = config
+            .servers
+            .iter()
+            .filter(|(name, _)| !self.removed.contains(*name))
+            .map(|(name, server)| (name.clone(), server.clone()))
+            .collect();
+        res.extend(self.servers.clone());
+        res
+    }
+
+    /// Whether the server `name` was added or changed at runtime
+    #[must_use]
+    pub fn is_runtime(&self, name: &str) -> bool {
+        self.servers.contains_key(name)
+    }
+
+    /// Add the server `name`, or replace it, if it exists
+    pub fn set(&mut self, name: &str, server: Z2mServer) {
+        self.removed.remove(name);
+        self.servers.insert(name.to_string(), server);
+    }
+
+    /// Remove the server `name`. Servers from the config file are remembered
+    /// as removed, so they stay removed after a restart.
+    pub fn remove(&mut self, name: &str, config: &Z2mConfig) {
+        self.servers.remove(name);
+        if config.servers.contains_key(name) {
+            self.removed.insert(name.to_string());
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt::Write as _;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{interval, timeout};
+
+use crate::config::OtlpConfig;
+use crate::error::{ApiError, ApiResult};
+
+/// Spans kept between two exports. Further spans are dropped.
+const MAX_SPANS: usize = 4096;
+
+/// Time allowed for a single request to the collector
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Histogram bucket bounds for z2m round-trip times, in milliseconds
+const ROUNDTRIP_BOUNDS: [f64; 9] = [5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0];
+
+/* otlp span kinds and status codes */
+const KIND_SERVER: u32 = 2;
+const KIND_CLIENT: u32 = 3;
+const STATUS_OK: u32 = 1;
+const STATUS_ERROR: u32 = 2;
+
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    name: String,
+    kind: u32,
+    start: SystemTime,
+    end: SystemTime,
+    error: bool,
+    attributes: Vec<(&'static str, String)>,
+}
+
+/// Telemetry collected since the last export. Counters are cumulative.
+#[derive(Debug)]
+struct Collector {
+    start: SystemTime,
+    spans: Vec<SpanRecord>,
+    dropped: u64,
+    requests: u64,
+    roundtrip_counts: [u64; ROUNDTRIP_BOUNDS.len() + 1],
+    roundtrip_sum: f64,
+    roundtrip_timeouts: u64,
+    retries: u64,
+    delivery_failures: u64,
+}
+
+impl Collector {
+    fn new() -> Self {
+        Self {
+            start: SystemTime::now(),
+            spans: vec![],
+            dropped: 0,
+            requests: 0,
+            roundtrip_counts: Default::default(),
+            roundtrip_sum: 0.0,
+            roundtrip_timeouts: 0,
+            retries: 0,
+            delivery_failures: 0,
+        }
+    }
+
+    fn push(&mut self, span: SpanRecord) {
+        if self.spans.len() < MAX_SPANS {
+            self.spans.push(span);
+        } else {
+            self.dropped += 1;
+        }
+    }
+}
+
+/* only set when otlp export is configured, so recording is free otherwise */
+static COLLECTOR: OnceLock<Mutex<Collector>> = OnceLock::new();
+
+fn with_collector(func: impl FnOnce(&mut Collector)) {
+    if let Some(collector) = COLLECTOR.get() {
+        func(&mut collector.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+}
+
+/// Record a handled http request
+pub fn record_request(method: &str, path: &str, status: u16, start: SystemTime, request_id: &str) {
+    with_collector(|col| {
+        col.requests += 1;
+        col.push(SpanRecord {
+            /* paths contain resource ids, so they are left out of the name */
+            name: method.to_string(),
+            kind: KIND_SERVER,
+            start,
+            end: SystemTime::now(),
+            error: status >= 500,
+            attributes: vec![
+                ("http.request.method", method.to_string()),
+                ("url.path", path.to_string()),
+                ("http.response.status_code", status.to_string()),
+                ("bifrost.request_id", request_id.to_string()),
+            ],
+        });
+    });
+}
+
+/// Record the round-trip time of a z2m command, or `None` if no response
+/// arrived in time
+pub fn record_roundtrip(server: &str, target: &str, latency: Option<Duration>) {
+    with_collector(|col| {
+        let Some(latency) = latency else {
+            col.roundtrip_timeouts += 1;
+            return;
+        };
+
+        let ms = latency.as_secs_f64() * 1000.0;
+        let bucket = ROUNDTRIP_BOUNDS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(ROUNDTRIP_BOUNDS.len());
+        col.roundtrip_counts[bucket] += 1;
+        col.roundtrip_sum += ms;
+
+        let end = SystemTime::now();
+        col.push(SpanRecord {
+            name: "z2m roundtrip".to_string(),
+            kind: KIND_CLIENT,
+            start: end - latency,
+            end,
+            error: false,
+            attributes: vec![
+                ("bifrost.z2m.server", server.to_string()),
+                ("bifrost.target", target.to_string()),
+            ],
+        });
+    });
+}
+
+/// Record a z2m command being sent again, after getting no response
+pub fn record_retry() {
+    with_collector(|col| col.retries += 1);
+}
+
+/// Record a z2m command that got no response, even after retrying
+pub fn record_delivery_failure() {
+    with_collector(|col| col.delivery_failures += 1);
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn random_id(bytes: usize) -> String {
+    (0..bytes).fold(String::new(), |mut id, _| {
+        let _ = write!(id, "{:02x}", rand::random::<u8>());
+        id
+    })
+}
+
+fn attributes(attrs: &[(&str, String)]) -> Vec<Value> {
+    attrs
+        .iter()
+        .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+        .collect()
+}
+
+fn resource(conf: &OtlpConfig) -> Value {
+    json!({
+        "attributes": attributes(&[
+            ("service.name", conf.service_name.clone()),
+            ("service.version", env!("CARGO_PKG_VERSION").to_string()),
+        ]),
+    })
+}
+
+fn scope() -> Value {
+    json!({"name": "bifrost", "version": env!("CARGO_PKG_VERSION")})
+}
+
+fn traces_json(conf: &OtlpConfig, spans: &[SpanRecord]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            json!({
+                "traceId": random_id(16),
+                "spanId": random_id(8),
+                "name": span.name,
+                "kind": span.kind,
+                "startTimeUnixNano": nanos(span.start),
+                "endTimeUnixNano": nanos(span.end),
+                "attributes": attributes(&span.attributes),
+                "status": {"code": if span.error { STATUS_ERROR } else { STATUS_OK }},
+            })
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": resource(conf),
+            "scopeSpans": [{"scope": scope(), "spans": spans}],
+        }]
+    })
+}
+
+fn metrics_json(conf: &OtlpConfig, col: &Collector) -> Value {
+    let start = nanos(col.start);
+    let now = nanos(SystemTime::now());
+
+    let counter = |name: &str, value: u64| {
+        json!({
+            "name": name,
+            "unit": "1",
+            "sum": {
+                "aggregationTemporality": 2,
+                "isMonotonic": true,
+                "dataPoints": [{
+                    "startTimeUnixNano": start,
+                    "timeUnixNano": now,
+                    "asInt": value.to_string(),
+                }],
+            },
+        })
+    };
+
+    let roundtrips = json!({
+        "name": "bifrost.z2m.roundtrip",
+        "unit": "ms",
+        "histogram": {
+            "aggregationTemporality": 2,
+            "dataPoints": [{
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "count": col.roundtrip_counts.iter().sum::<u64>().to_string(),
+                "sum": col.roundtrip_sum,
+                "bucketCounts": col.roundtrip_counts.map(|count| count.to_string()),
+                "explicitBounds": ROUNDTRIP_BOUNDS,
+            }],
+        },
+    });
+
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(conf),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": [
+                    counter("bifrost.http.requests", col.requests),
+                    counter("bifrost.z2m.timeouts", col.roundtrip_timeouts),
+                    counter("bifrost.z2m.retries", col.retries),
+                    counter("bifrost.z2m.delivery_failures", col.delivery_failures),
+                    counter("bifrost.otlp.dropped_spans", col.dropped),
+                    roundtrips,
+                ],
+            }],
+        }]
+    })
+}
+
+/// Address of an otlp/http collector
+#[derive(Debug, Clone)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    const DEFAULT_PORT: u16 = 4318;
+
+    fn parse(url: &str) -> ApiResult<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            ApiError::InvalidConfig(format!("otlp endpoint must be an http:// url: {url}"))
+        })?;
+
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|_| {
+                    ApiError::InvalidConfig(format!("invalid port in otlp endpoint: {url}"))
+                })?;
+                (host, port)
+            }
+            None => (authority, Self::DEFAULT_PORT),
+        };
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Post a json document to `signal` (e.g., "traces"), and return the
+    /// http status of the reply
+    async fn post(&self, signal: &str, body: &Value) -> ApiResult<u16> {
+        let body = serde_json::to_vec(body)?;
+        let path = if self.path.is_empty() {
+            format!("/v1/{signal}")
+        } else {
+            format!("/{}/v1/{signal}", self.path)
+        };
+
+        let head = format!(
+            "POST {path} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.host,
+            self.port,
+            body.len()
+        );
+
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&body).await?;
+
+        let mut reply = vec![];
+        stream.take(64 * 1024).read_to_end(&mut reply).await?;
+
+        /* "HTTP/1.1 200 OK" */
+        let status = String::from_utf8_lossy(&reply)
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or_default();
+
+        Ok(status)
+    }
+
+    async fn export(&self, signal: &str, body: &Value) {
+        match timeout(EXPORT_TIMEOUT, self.post(signal, body)).await {
+            Ok(Ok(status)) if (200..300).contains(&status) => {}
+            Ok(Ok(status)) => log::warn!("Otlp collector rejected {signal} (status {status})"),
+            Ok(Err(err)) => log::warn!("Failed to export {signal} to otlp collector: {err}"),
+            Err(_) => log::warn!("Timeout exporting {signal} to otlp collector"),
+        }
+    }
+}
+
+/// Traces collected since the last export (if any), and current metrics
+fn take_batch(conf: &OtlpConfig, collector: &Mutex<Collector>) -> (Option<Value>, Value) {
+    let mut col = collector.lock().unwrap_or_else(PoisonError::into_inner);
+    let spans = std::mem::take(&mut col.spans);
+    let metrics = metrics_json(conf, &col);
+    drop(col);
+
+    let traces = (!spans.is_empty()).then(|| traces_json(conf, &spans));
+    (traces, metrics)
+}
+
+/// Start collecting telemetry, and periodically export it to the configured
+/// otlp collector
+pub async fn exporter(conf: OtlpConfig) -> ApiResult<()> {
+    let endpoint = Endpoint::parse(&conf.endpoint)?;
+    let collector = COLLECTOR.get_or_init(|| Mutex::new(Collector::new()));
+
+    log::info!(
+        "Exporting telemetry to otlp collector at {}:{}",
+        endpoint.host,
+        endpoint.port
+    );
+
+    let mut tick = interval(Duration::from_secs(conf.interval.max(1)));
+    loop {
+        tick.tick().await;
+
+        let (traces, metrics) = take_batch(&conf, collector);
+
+        if let Some(traces) = traces {
+            endpoint.export("traces", &traces).await;
+        }
+        endpoint.export("metrics", &metrics).await;
+    }
+}
@@ -1,27 +1,71 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Read, Write};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use chrono::Utc;
+use serde::de::DeserializeOwned;
 use serde::{self, Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Map, Value};
+use serde_yaml::Mapping;
 use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::Notify;
+use tokio::sync::{watch, Notify};
 use uuid::Uuid;
 
+use crate::config::{Z2mConfig, Z2mServer};
 use crate::error::{ApiError, ApiResult};
 use crate::hue::api::{
-    Bridge, BridgeHome, Device, DeviceArchetype, DeviceProductData, Metadata, RType, Resource,
-    ResourceLink, ResourceRecord, TimeZone, ZigbeeConnectivity, ZigbeeConnectivityStatus,
-    ZigbeeDeviceDiscovery,
+    Bridge, BridgeHome, Device, DeviceArchetype, DeviceProductData, Entertainment,
+    EntertainmentConfiguration, EntertainmentConfigurationStatus, GroupedLight, Light, LightEffect,
+    LightMode, Metadata, Motion, MotionReport, RType, Resource, ResourceLink, ResourceRecord, Room,
+    Scene, SceneAction, SceneStatus, SharedRecord, TimeZone, ZigbeeConnectivity,
+    ZigbeeConnectivityStatus, ZigbeeDeviceDiscovery, Zone,
 };
-use crate::hue::api::{GroupedLightUpdate, LightUpdate, SceneUpdate, Update};
-use crate::hue::event::EventBlock;
-use crate::z2m::request::ClientRequest;
+use crate::hue::api::{
+    ButtonUpdate, EntertainmentConfigurationUpdate, GroupedLightUpdate, LightLevelUpdate,
+    LightUpdate, MotionUpdate, RoomUpdate, SceneUpdate, TemperatureUpdate, Update,
+    ZigbeeConnectivityUpdate, ZigbeeDeviceDiscoveryUpdate,
+};
+use crate::hue::event::{EventBlock, EventSequence};
+use crate::hue::locale::Locale;
+use crate::hue::stream::StreamFrame;
+use crate::journal::JournalEntry;
+use crate::logging;
+use crate::migrate;
+use crate::model::effect::EffectSessions;
+use crate::model::entertainment::StreamSessions;
+use crate::model::latency::LatencyStats;
+use crate::model::linkquality::{LinkQualityHistory, LinkQualitySample};
+use crate::model::motion::{MotionFilters, MotionTiming};
+use crate::model::networkmap::{NetworkMap, NetworkMapType};
+use crate::model::pairing::{PairingProgress, Pairings};
+use crate::model::rollout::{self, RolloutStatus, Rollouts};
+use crate::model::state::{LightState, StateCache};
+use crate::model::whitelist::{Whitelist, WhitelistUser};
+use crate::model::z2mservers::Z2mServers;
+use crate::z2m::coverage::UnmappedExpose;
+use crate::z2m::request::{self, ClientRequest, TaggedRequest};
+use crate::z2m::update::DeviceUpdate;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct AuxData {
     pub topic: Option<String>,
     pub index: Option<u32>,
+    /* default transition time (in seconds), set through the api */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<f64>,
+    /* application that created this resource, for private resources */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    /* name of the z2m server that reported this device */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /* round-trip latency of z2m commands */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyStats>,
+    /* opaque client preferences (e.g., app customization), kept verbatim */
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub prefs: Map<String, Value>,
 }
 
 impl AuxData {
@@ -45,15 +89,185 @@ impl AuxData {
             ..self
         }
     }
+
+    #[must_use]
+    pub fn with_server(self, server: &str) -> Self {
+        Self {
+            server: Some(server.to_string()),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_transition(self, transition: Option<f64>) -> Self {
+        Self { transition, ..self }
+    }
+}
+
+fn merge_missing(dst: &mut Map<String, Value>, src: &Map<String, Value>) {
+    for (key, val) in src {
+        match (dst.get_mut(key), val) {
+            (None, _) => {
+                dst.insert(key.clone(), val.clone());
+            }
+            (Some(Value::Object(dst)), Value::Object(src)) => merge_missing(dst, src),
+            (Some(_), _) => {}
+        }
+    }
+}
+
+/// A resource record shared with the store, along with the stored client
+/// preferences to merge into it. Taking a snapshot is cheap, and all the
+/// serializing happens later, without holding the lock.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub rec: SharedRecord,
+    prefs: Map<String, Value>,
+}
+
+impl Snapshot {
+    /// The resource record as json, merged with any stored client
+    /// preferences. Fields of the resource are never overwritten, so
+    /// preferences cannot mask actual state.
+    pub fn to_json(&self) -> ApiResult<Value> {
+        let mut value = serde_json::to_value(&self.rec)?;
+        if let Value::Object(dst) = &mut value {
+            merge_missing(dst, &self.prefs);
+        }
+        Ok(value)
+    }
+}
+
+/// Restore the entries saved in the undo log of a transaction
+fn rollback<V>(map: &mut HashMap<Uuid, V>, undo: HashMap<Uuid, Option<V>>) {
+    for (id, old) in undo {
+        match old {
+            Some(value) => map.insert(id, value),
+            None => map.remove(&id),
+        };
+    }
+}
+
+/// On-disk layout of the state file. The event sequence was added later, so
+/// it is optional when reading older state files.
+///
+/// Resources and aux data are read as plain yaml, and parsed one entry at a
+/// time (see [`parse_entries`]). The layout version is missing from state
+/// files written before it was added (see [`migrate::STATE_VERSION`]), and
+/// so are the paired applications, and runtime changes to z2m servers.
+#[derive(Deserialize)]
+struct StateFile(
+    Mapping,
+    Mapping,
+    #[serde(default)] EventSequence,
+    #[serde(default)] u32,
+    #[serde(default)] Whitelist,
+    #[serde(default)] Z2mServers,
+);
+
+/// The state file as written, with resources and aux data sorted by id
+type OrderedState<'a> = (
+    BTreeMap<&'a Uuid, &'a Resource>,
+    BTreeMap<&'a Uuid, &'a AuxData>,
+    EventSequence,
+    u32,
+    &'a Whitelist,
+    &'a Z2mServers,
+);
+
+/// What happened while loading a state file
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Entries that could not be parsed, and were skipped
+    pub skipped: Vec<String>,
+    /// Layout version of the state file, if it was older than the current one
+    pub migrated_from: Option<u32>,
+    /// Number of entries that were upgraded or repaired
+    pub repaired: usize,
+}
+
+impl LoadReport {
+    /// True if the loaded state differs from the state file
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        !self.skipped.is_empty() || self.migrated_from.is_some() || self.repaired > 0
+    }
+}
+
+/// Parse the entries of a state file section, skipping (and reporting) any
+/// entry that cannot be parsed
+fn parse_entries<T: DeserializeOwned>(
+    section: &str,
+    entries: Mapping,
+    problems: &mut Vec<String>,
+) -> HashMap<Uuid, T> {
+    entries
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let name = serde_yaml::to_string(&key).unwrap_or_default();
+            let name = name.trim();
+            let id = match serde_yaml::from_value(key) {
+                Ok(id) => id,
+                Err(err) => {
+                    problems.push(format!("{section} {name}: invalid id: {err}"));
+                    return None;
+                }
+            };
+            match serde_yaml::from_value(value) {
+                Ok(obj) => Some((id, obj)),
+                Err(err) => {
+                    problems.push(format!("{section} {name}: {err}"));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Undo log of a transaction, and side effects (hue events and z2m requests)
+/// held back until it is committed.
+///
+/// Entries are saved as they were before their first change in the
+/// transaction (`None` if they did not exist), so only the entries touched by
+/// the transaction are copied.
+#[derive(Clone, Debug, Default)]
+struct Transaction {
+    res: HashMap<Uuid, Option<Arc<Resource>>>,
+    aux: HashMap<Uuid, Option<AuxData>>,
+    light_states: HashMap<Uuid, Option<LightState>>,
+    transition_defaults: HashMap<Uuid, Option<f64>>,
+    events: Vec<EventBlock>,
+    /* z2m requests are sent from shared references, hence the lock */
+    requests: Arc<std::sync::Mutex<Vec<TaggedRequest>>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Resources {
     aux: HashMap<Uuid, AuxData>,
+    txn: Option<Box<Transaction>>,
+    events: EventSequence,
+    light_states: StateCache,
+    streams: StreamSessions,
+    effects: EffectSessions,
+    rollouts: Rollouts,
+    motion_filters: MotionFilters,
+    linkquality: LinkQualityHistory,
+    pairings: Pairings,
+    whitelist: Whitelist,
+    z2m_servers: Z2mServers,
+    network_maps: HashMap<(String, NetworkMapType), NetworkMap>,
+    unmapped_exposes: HashMap<String, Vec<UnmappedExpose>>,
+    transition_defaults: HashMap<Uuid, f64>,
     state_updates: Arc<Notify>,
-    pub res: HashMap<Uuid, Resource>,
+    z2m_server_updates: Arc<Notify>,
+    /* false while standing by for a primary instance (see
+     * crate::server::failover), which then sends all z2m commands */
+    active: watch::Sender<bool>,
+    /* resources are shared with readers (and the journal), and copied on
+     * write, so snapshots never need a deep copy */
+    pub res: HashMap<Uuid, Arc<Resource>>,
     pub hue_updates: Sender<EventBlock>,
-    pub z2m_updates: Sender<Arc<ClientRequest>>,
+    pub z2m_updates: Sender<Arc<TaggedRequest>>,
 }
 
 impl Resources {
@@ -65,19 +279,127 @@ impl Resources {
         Self {
             res: HashMap::new(),
             aux: HashMap::new(),
+            txn: None,
+            events: EventSequence::default(),
+            light_states: StateCache::new(),
+            streams: StreamSessions::new(),
+            effects: EffectSessions::new(),
+            rollouts: Rollouts::new(),
+            motion_filters: MotionFilters::new(),
+            linkquality: LinkQualityHistory::new(),
+            pairings: Pairings::new(),
+            whitelist: Whitelist::new(),
+            z2m_servers: Z2mServers::new(),
+            network_maps: HashMap::new(),
+            unmapped_exposes: HashMap::new(),
+            transition_defaults: HashMap::new(),
             state_updates: Arc::new(Notify::new()),
+            z2m_server_updates: Arc::new(Notify::new()),
+            active: watch::Sender::new(true),
             hue_updates: Sender::new(32),
             z2m_updates: Sender::new(32),
         }
     }
 
-    pub fn read(&mut self, rdr: impl Read) -> ApiResult<()> {
-        (self.res, self.aux) = serde_yaml::from_reader(rdr)?;
-        Ok(())
+    /// Load resources from a state file.
+    ///
+    /// Entries that cannot be parsed (e.g., after a manual edit, or a
+    /// downgrade) are skipped, and reported, so a single bad entry does not
+    /// cost the rest of the state. State files written by older versions are
+    /// migrated to the current layout.
+    pub fn read(&mut self, rdr: impl Read) -> ApiResult<LoadReport> {
+        let StateFile(mut res, mut aux, events, version, whitelist, z2m_servers) =
+            serde_yaml::from_reader(rdr)?;
+
+        let mut report = LoadReport::default();
+        if version < migrate::STATE_VERSION {
+            report.migrated_from = Some(version);
+            for value in res.values_mut() {
+                if migrate::resource_entry(version, value) {
+                    report.repaired += 1;
+                }
+            }
+            for value in aux.values_mut() {
+                if migrate::aux_entry(version, value) {
+                    report.repaired += 1;
+                }
+            }
+        }
+
+        self.res = parse_entries("resource", res, &mut report.skipped);
+        self.aux = parse_entries("aux data for", aux, &mut report.skipped);
+        report.repaired += self.repair_scene_aux();
+        self.whitelist.load(whitelist);
+        self.z2m_servers = z2m_servers;
+
+        /* never move the event sequence backwards */
+        self.events = self.events.max(events);
+        Ok(report)
     }
 
-    fn ordered_state(&self) -> (BTreeMap<&Uuid, &Resource>, BTreeMap<&Uuid, &AuxData>) {
-        (self.res.iter().collect(), self.aux.iter().collect())
+    /// Restore missing scene indices (needed to recall scenes through z2m).
+    /// Scene ids are derived from their room and index, so the index can be
+    /// found again.
+    fn repair_scene_aux(&mut self) -> usize {
+        let mut repaired = 0;
+
+        for (id, obj) in &self.res {
+            let Resource::Scene(scene) = &**obj else {
+                continue;
+            };
+            if self.aux.get(id).is_some_and(|aux| aux.index.is_some()) {
+                continue;
+            }
+
+            let Some(index) = (0..Self::MAX_SCENE_ID)
+                .find(|index| RType::Scene.deterministic((scene.group.rid, *index)).rid == *id)
+            else {
+                continue;
+            };
+
+            log::info!(
+                "Restored index {index} of scene {id} ({})",
+                scene.metadata.name
+            );
+            let aux = self.aux.entry(*id).or_default();
+            aux.index = Some(index);
+            if aux.topic.is_none() {
+                aux.topic = Some(scene.metadata.name.clone());
+            }
+            repaired += 1;
+        }
+
+        repaired
+    }
+
+    /// Apply a change from the state journal (at startup, so no events are
+    /// sent)
+    pub fn journal_apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Put { id, obj } => {
+                self.res.insert(id, obj);
+            }
+            JournalEntry::Delete { id } => {
+                self.res.remove(&id);
+            }
+            JournalEntry::Aux { id, aux: Some(aux) } => {
+                self.aux.insert(id, aux);
+            }
+            JournalEntry::Aux { id, aux: None } => {
+                self.aux.remove(&id);
+            }
+        }
+    }
+
+    fn ordered_state(&self) -> OrderedState<'_> {
+        (
+            self.res.iter().map(|(id, obj)| (id, &**obj)).collect(),
+            self.aux.iter().collect(),
+            self.events,
+            migrate::STATE_VERSION,
+            &self.whitelist,
+            &self.z2m_servers,
+        )
     }
 
     pub fn write(&self, wr: impl Write) -> ApiResult<()> {
@@ -88,8 +410,525 @@ impl Resources {
         Ok(serde_yaml::to_string(&self.ordered_state())?)
     }
 
-    pub fn init(&mut self, bridge_id: &str) -> ApiResult<()> {
-        self.add_bridge(bridge_id.to_owned())
+    /// Run `func` as a single transaction: if it fails, all changes made to
+    /// resources are rolled back, and no events or z2m requests are sent.
+    ///
+    /// Resources, aux data, light states and transition defaults are rolled
+    /// back. Runtime state kept on the side (entertainment and effect
+    /// sessions, rollouts, pairing progress, network maps, etc) is not.
+    ///
+    /// Transactions started from within `func` are joined with this one.
+    pub fn transaction<T>(&mut self, func: impl FnOnce(&mut Self) -> ApiResult<T>) -> ApiResult<T> {
+        if self.txn.is_some() {
+            return func(self);
+        }
+
+        self.txn = Some(Box::default());
+
+        let result = func(self);
+
+        let Some(txn) = self.txn.take() else {
+            return result;
+        };
+
+        if let Err(err) = &result {
+            log::warn!(
+                "Transaction failed, rolling back {} change(s): {err}",
+                txn.events.len()
+            );
+            rollback(&mut self.res, txn.res);
+            rollback(&mut self.aux, txn.aux);
+            rollback(&mut self.transition_defaults, txn.transition_defaults);
+            for (id, state) in txn.light_states {
+                match state {
+                    Some(state) => self.light_states.update(&id, |st| *st = state),
+                    None => self.light_states.remove(&id),
+                }
+            }
+        } else {
+            for evt in txn.events {
+                self.hue_event(evt);
+            }
+            let requests = std::mem::take(&mut *txn.requests.lock().unwrap());
+            for req in request::coalesce(requests) {
+                self.z2m_send(req)?;
+            }
+        }
+
+        self.state_updates.notify_one();
+
+        result
+    }
+
+    /* save entries to the undo log of the current transaction (if any),
+     * before they are changed */
+
+    fn txn_save_res(&mut self, id: &Uuid) {
+        if let Some(txn) = &mut self.txn {
+            txn.res
+                .entry(*id)
+                .or_insert_with(|| self.res.get(id).cloned());
+        }
+    }
+
+    fn txn_save_aux(&mut self, id: &Uuid) {
+        if let Some(txn) = &mut self.txn {
+            txn.aux
+                .entry(*id)
+                .or_insert_with(|| self.aux.get(id).cloned());
+        }
+    }
+
+    fn txn_save_light_state(&mut self, id: &Uuid) {
+        if let Some(txn) = &mut self.txn {
+            txn.light_states
+                .entry(*id)
+                .or_insert_with(|| self.light_states.get(id).copied());
+        }
+    }
+
+    fn txn_save_transition(&mut self, id: &Uuid) {
+        if let Some(txn) = &mut self.txn {
+            txn.transition_defaults
+                .entry(*id)
+                .or_insert_with(|| self.transition_defaults.get(id).copied());
+        }
+    }
+
+    /// Last known state of the lights
+    #[must_use]
+    pub const fn light_states(&self) -> &StateCache {
+        &self.light_states
+    }
+
+    /// Update the last known state of a light
+    pub fn update_light_state(&mut self, id: &Uuid, func: impl FnOnce(&mut LightState)) {
+        self.txn_save_light_state(id);
+        self.light_states.update(id, func);
+    }
+
+    pub fn init(&mut self, bridge_id: &str, locale: Locale) -> ApiResult<()> {
+        self.add_bridge(bridge_id.to_owned(), locale)
+    }
+
+    /// Set the timezone reported by the bridge resource(s)
+    pub fn set_timezone(&mut self, name: &str) -> ApiResult<()> {
+        for rr in self.get_resources_by_type(RType::Bridge) {
+            let bridge: Bridge = rr.obj.try_into()?;
+            if bridge.time_zone.time_zone != name {
+                self.update::<Bridge>(&rr.id, |bridge| {
+                    bridge.time_zone.time_zone = name.to_string();
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start an entertainment session, switching its lights to streaming
+    /// mode. A light can only be part of one active session at a time.
+    pub fn entertainment_start(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        let ec = self.get::<EntertainmentConfiguration>(link)?;
+        if ec.is_active() {
+            return Ok(());
+        }
+
+        let lights = ec.light_services.clone();
+        if let Some(light) = lights.iter().find(|light| self.is_streaming(light)) {
+            return Err(ApiError::LightStreaming(light.rid));
+        }
+
+        /* remember the current light states, to restore after the session */
+        let prior = lights
+            .iter()
+            .filter_map(|light| Some((*light, *self.light_states.get(&light.rid)?)))
+            .collect();
+        self.streams.start(link.rid, prior);
+
+        for light in &lights {
+            self.effect_stop(light)?;
+            self.update::<Light>(&light.rid, |light| light.mode = LightMode::Streaming)?;
+        }
+
+        self.update::<EntertainmentConfiguration>(&link.rid, |ec| {
+            ec.status = EntertainmentConfigurationStatus::Active;
+        })
+    }
+
+    /// Stop an entertainment session, returning its lights to normal mode,
+    /// and to the state they had before the session started
+    pub fn entertainment_stop(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        let ec = self.get::<EntertainmentConfiguration>(link)?;
+        if !ec.is_active() {
+            return Ok(());
+        }
+
+        for light in ec.light_services.clone() {
+            if self.is_streaming(&light) {
+                self.update::<Light>(&light.rid, |light| light.mode = LightMode::Normal)?;
+            }
+        }
+
+        if let Some(session) = self.streams.stop(&link.rid) {
+            for (light, state) in session.prior {
+                self.update_light_state(&light.rid, |st| *st = state);
+                self.z2m_request(ClientRequest::light_update(light, state.as_device_update()))?;
+            }
+        }
+
+        self.update::<EntertainmentConfiguration>(&link.rid, |ec| {
+            ec.status = EntertainmentConfigurationStatus::Inactive;
+            ec.active_streamer = None;
+        })
+    }
+
+    /// The lights of each channel of an entertainment configuration. Channel
+    /// members refer to entertainment services, which render to a light.
+    /// Configurations without channels have one channel for each light.
+    fn entertainment_channels(
+        &self,
+        ec: &EntertainmentConfiguration,
+    ) -> HashMap<u8, Vec<ResourceLink>> {
+        if ec.channels.is_empty() {
+            return (0..=u8::MAX)
+                .zip(ec.light_services.iter())
+                .map(|(id, light)| (id, vec![*light]))
+                .collect();
+        }
+
+        let mut channels: HashMap<u8, Vec<ResourceLink>> = HashMap::new();
+        for channel in &ec.channels {
+            let Some(id) = channel["channel_id"]
+                .as_u64()
+                .and_then(|id| u8::try_from(id).ok())
+            else {
+                continue;
+            };
+            let members = channel["members"].as_array().into_iter().flatten();
+            for member in members {
+                let Ok(service) = serde_json::from_value::<ResourceLink>(member["service"].clone())
+                else {
+                    continue;
+                };
+                if let Ok(ent) = self.get::<Entertainment>(&service) {
+                    channels.entry(id).or_default().push(ent.renderer_reference);
+                }
+            }
+        }
+        channels
+    }
+
+    /// Apply a frame of an entertainment stream to the lights of its
+    /// session. Frames for configurations without an active session are
+    /// ignored, like a real bridge does, and so are frames following the
+    /// last applied one too closely (see [`crate::model::entertainment::FRAME_INTERVAL`]).
+    pub fn entertainment_frame(&mut self, frame: &StreamFrame) -> ApiResult<()> {
+        let link = RType::EntertainmentConfiguration.link_to(frame.config_id);
+        let ec = self.get::<EntertainmentConfiguration>(&link)?;
+        if !ec.is_active() {
+            return Ok(());
+        }
+
+        let channels = self.entertainment_channels(ec);
+        if !self.streams.frame(&link.rid) {
+            return Ok(());
+        }
+
+        for channel in &frame.channels {
+            let upd = channel.device_update(frame.color_space);
+            for light in channels.get(&channel.id).into_iter().flatten() {
+                self.z2m_request(ClientRequest::stream_update(*light, upd.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop entertainment sessions whose streaming peer has not been heard
+    /// from within `timeout`
+    pub fn entertainment_expire(&mut self, timeout: Duration) -> ApiResult<()> {
+        for id in self.streams.expired(timeout) {
+            log::warn!("Entertainment session {id} timed out, stopping");
+            let link = RType::EntertainmentConfiguration.link_to(id);
+            if let Err(err) = self.entertainment_stop(&link) {
+                log::error!("Failed to stop entertainment session {id}: {err}");
+                self.streams.stop(&id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop all entertainment sessions (e.g., after a restart, since the
+    /// streams cannot have survived)
+    pub fn entertainment_reset(&mut self) -> ApiResult<()> {
+        for rr in self.get_resources_by_type(RType::EntertainmentConfiguration) {
+            self.entertainment_stop(&RType::EntertainmentConfiguration.link_to(rr.id))?;
+        }
+        Ok(())
+    }
+
+    /// Start (or with [`LightEffect::NoEffect`], stop) an emulated effect on
+    /// a light. The effect flickers below `brightness`, or the current
+    /// brightness of the light if not given.
+    pub fn effect_start(
+        &mut self,
+        link: &ResourceLink,
+        effect: LightEffect,
+        brightness: Option<f64>,
+    ) -> ApiResult<()> {
+        let light = self.get::<Light>(link)?;
+        if light.effects.is_none() {
+            return Err(ApiError::EffectUnsupported(link.rid, effect));
+        }
+
+        let brightness = brightness
+            .or_else(|| light.dimming.map(|dim| dim.brightness))
+            .unwrap_or(100.0);
+        let ct = light.color_temperature.is_some();
+
+        if self.effects.start(link.rid, effect, brightness, ct) {
+            log::info!("Starting {effect:?} effect on {link:?}");
+        }
+
+        self.update::<Light>(&link.rid, |light| {
+            light.effects = light.effects.take().map(|fx| fx.with_status(effect));
+        })
+    }
+
+    /// Stop the emulated effect on a light, if any
+    pub fn effect_stop(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        if let Some(session) = self.effects.stop(&link.rid) {
+            log::info!("Stopped {:?} effect on {link:?}", session.effect);
+            self.update::<Light>(&link.rid, |light| {
+                light.effects = light
+                    .effects
+                    .take()
+                    .map(|fx| fx.with_status(LightEffect::NoEffect));
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Change the brightness of a light running an emulated effect
+    pub fn effect_brightness(&mut self, link: &ResourceLink, brightness: f64) {
+        self.effects.set_brightness(&link.rid, brightness);
+    }
+
+    /// Send the next update for all emulated effects that are due
+    pub fn effect_step(&mut self) -> ApiResult<()> {
+        for (id, upd) in self.effects.due(Instant::now()) {
+            self.z2m_request(ClientRequest::light_update(RType::Light.link_to(id), upd))?;
+        }
+        Ok(())
+    }
+
+    /// Returns true, if an update to a group should be rolled out to its
+    /// lights in slices. Large zones spread over several z2m servers cannot
+    /// use a z2m group, so each light would be updated at once otherwise.
+    #[must_use]
+    pub fn needs_rollout(&self, group: &ResourceLink, lights: &[ResourceLink]) -> bool {
+        if group.rtype != RType::Zone || lights.len() < rollout::MIN_LIGHTS {
+            return false;
+        }
+
+        let servers: HashSet<&str> = lights
+            .iter()
+            .filter_map(|light| self.get::<Light>(light).ok())
+            .filter_map(|light| self.aux_get(&light.owner).ok()?.server.as_deref())
+            .collect();
+
+        servers.len() > 1
+    }
+
+    /// Schedule an update to each of `lights`, sent a slice at a time
+    pub fn rollout_start(
+        &mut self,
+        group: ResourceLink,
+        lights: Vec<ResourceLink>,
+        upd: DeviceUpdate,
+    ) -> Uuid {
+        let count = lights.len();
+        let id = self.rollouts.start(group, lights, upd);
+        log::info!("Rolling out update to {count} lights of {group:?} ({id})");
+        id
+    }
+
+    #[must_use]
+    pub fn rollout_status(&self) -> Vec<RolloutStatus> {
+        self.rollouts.status()
+    }
+
+    /// Send the next slice of rolled out group updates
+    pub fn rollout_step(&mut self) -> ApiResult<()> {
+        for (light, upd) in self.rollouts.next_slice() {
+            self.z2m_request(ClientRequest::light_update(light, upd))?;
+        }
+        Ok(())
+    }
+
+    /// Record a link quality reading of a device
+    pub fn linkquality_record(&mut self, device: &ResourceLink, sample: LinkQualitySample) {
+        self.linkquality.record(device.rid, sample);
+    }
+
+    /// Recent link quality readings of all devices
+    #[must_use]
+    pub const fn linkquality_history(&self) -> &LinkQualityHistory {
+        &self.linkquality
+    }
+
+    /// Record the next step of pairing a device, and report it on the event
+    /// stream, as an update of the zigbee device discovery service
+    pub fn pairing_record(&mut self, progress: PairingProgress) -> ApiResult<()> {
+        let progress = self.pairings.record(progress);
+
+        let Some((id, status)) = self.res.iter().find_map(|(id, obj)| match &**obj {
+            Resource::ZigbeeDeviceDiscovery(zbdd) => Some((*id, zbdd.status.clone())),
+            _ => None,
+        }) else {
+            return Ok(());
+        };
+
+        let upd = ZigbeeDeviceDiscoveryUpdate {
+            status,
+            pairing: Some(progress),
+        };
+        self.hue_event(EventBlock::update(&id, Update::ZigbeeDeviceDiscovery(upd))?);
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub const fn whitelist(&self) -> &Whitelist {
+        &self.whitelist
+    }
+
+    /// Press the link button, allowing new applications to pair for a while
+    pub fn link_button_press(&mut self, duration: chrono::Duration) {
+        log::info!(
+            "Link button pressed, pairing allowed for {}s",
+            duration.num_seconds()
+        );
+        self.whitelist.press(duration);
+    }
+
+    /// Pair a new application, returning its username
+    pub fn whitelist_add(&mut self, name: &str, clientkey: Option<Uuid>) -> Uuid {
+        let username = self.whitelist.add(name, clientkey);
+        log::info!("Paired application [{name}] as {}", username.simple());
+        self.state_updates.notify_one();
+        username
+    }
+
+    pub fn whitelist_remove(&mut self, username: &Uuid) -> ApiResult<WhitelistUser> {
+        let user = self
+            .whitelist
+            .remove(username)
+            .ok_or(ApiError::NotFound(*username))?;
+        log::info!(
+            "Removed application [{}] ({})",
+            user.name,
+            username.simple()
+        );
+        self.state_updates.notify_one();
+        Ok(user)
+    }
+
+    /// Whether `key` belongs to a paired application (see [`Whitelist::touch`])
+    pub fn whitelist_touch(&mut self, key: &str) -> bool {
+        self.whitelist.touch(key)
+    }
+
+    #[must_use]
+    pub const fn z2m_servers(&self) -> &Z2mServers {
+        &self.z2m_servers
+    }
+
+    /// Add or replace the z2m server `name`. Clients are restarted by the
+    /// z2m supervisor (see [`crate::z2m::supervisor`]).
+    pub fn z2m_server_set(&mut self, name: &str, server: Z2mServer) {
+        log::info!("Setting z2m server [{name}] to {}", server.url);
+        self.z2m_servers.set(name, server);
+        self.state_updates.notify_one();
+        self.z2m_server_updates.notify_one();
+    }
+
+    pub fn z2m_server_remove(&mut self, name: &str, config: &Z2mConfig) {
+        log::info!("Removing z2m server [{name}]");
+        self.z2m_servers.remove(name, config);
+        self.state_updates.notify_one();
+        self.z2m_server_updates.notify_one();
+    }
+
+    #[must_use]
+    pub fn z2m_server_notify(&self) -> Arc<Notify> {
+        self.z2m_server_updates.clone()
+    }
+
+    /// Whether this instance is in charge of the bridge: always, unless it
+    /// is a standby for a healthy primary instance. The sender is used by
+    /// [`crate::server::failover::standby`].
+    #[must_use]
+    pub fn active_channel(&self) -> watch::Sender<bool> {
+        self.active.clone()
+    }
+
+    #[must_use]
+    pub const fn pairings(&self) -> &Pairings {
+        &self.pairings
+    }
+
+    /// Set the hold time and re-trigger delay of a motion sensor
+    pub fn motion_configure(&mut self, motion: &ResourceLink, timing: MotionTiming) {
+        self.motion_filters.configure(motion.rid, timing);
+    }
+
+    /// Filter an occupancy report of a motion sensor (see [`MotionFilters`])
+    pub fn motion_filter(&mut self, motion: &ResourceLink, occupancy: bool) -> Option<bool> {
+        self.motion_filters
+            .report(motion.rid, occupancy, Instant::now())
+    }
+
+    /// Report the end of motion for sensors whose hold time has passed
+    pub fn motion_expire(&mut self) -> ApiResult<()> {
+        for id in self.motion_filters.expire(Instant::now()) {
+            let link = RType::Motion.link_to(id);
+            let Ok(motion) = self.get::<Motion>(&link) else {
+                continue;
+            };
+            if !motion.enabled || !motion.motion.motion {
+                continue;
+            }
+
+            self.update::<Motion>(&id, |motion| {
+                motion.motion.motion = false;
+                motion.motion.motion_report = Some(MotionReport {
+                    changed: Utc::now(),
+                    motion: false,
+                });
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns false, if the zigbee connectivity service of a device reports
+    /// it as disconnected
+    #[must_use]
+    pub fn is_reachable(&self, device: &ResourceLink) -> bool {
+        let Ok(dev) = self.get::<Device>(device) else {
+            return true;
+        };
+
+        !dev.services
+            .iter()
+            .filter(|svc| svc.rtype == RType::ZigbeeConnectivity)
+            .filter_map(|svc| self.get::<ZigbeeConnectivity>(svc).ok())
+            .any(|zbc| zbc.status == ZigbeeConnectivityStatus::Disconnected)
+    }
+
+    /// Returns true, if the light is used by an active entertainment session
+    #[must_use]
+    pub fn is_streaming(&self, light: &ResourceLink) -> bool {
+        self.get::<Light>(light)
+            .is_ok_and(|light| light.mode == LightMode::Streaming)
     }
 
     pub fn aux_get(&self, link: &ResourceLink) -> ApiResult<&AuxData> {
@@ -98,10 +937,202 @@ impl Resources {
             .ok_or_else(|| ApiError::AuxNotFound(*link))
     }
 
+    pub fn aux_iter(&self) -> impl Iterator<Item = (&Uuid, &AuxData)> {
+        self.aux.iter()
+    }
+
     pub fn aux_set(&mut self, link: &ResourceLink, aux: AuxData) {
+        self.txn_save_aux(&link.rid);
         self.aux.insert(link.rid, aux);
     }
 
+    /// Set the default transition time for a resource from the config file
+    pub fn set_transition_default(&mut self, link: &ResourceLink, transition: Option<f64>) {
+        self.txn_save_transition(&link.rid);
+        if let Some(transition) = transition {
+            self.transition_defaults.insert(link.rid, transition);
+        } else {
+            self.transition_defaults.remove(&link.rid);
+        }
+    }
+
+    /// Set (or clear) the default transition time for a resource through the
+    /// api. This takes precedence over the config file.
+    pub fn set_transition_override(&mut self, link: &ResourceLink, transition: Option<f64>) {
+        self.txn_save_aux(&link.rid);
+        let aux = self.aux.entry(link.rid).or_default();
+        aux.transition = transition;
+        self.state_updates.notify_one();
+    }
+
+    /// Store client preferences for `link`, merged with the ones already
+    /// stored (nested objects are merged, everything else is replaced)
+    pub fn set_preferences(&mut self, link: &ResourceLink, prefs: Map<String, Value>) {
+        if prefs.is_empty() {
+            return;
+        }
+
+        log::debug!("Storing preferences for {link:?}: {prefs:?}");
+
+        self.txn_save_aux(&link.rid);
+        let aux = self.aux.entry(link.rid).or_default();
+        for (key, val) in prefs {
+            match (aux.prefs.get_mut(&key), val) {
+                (Some(Value::Object(dst)), Value::Object(src)) => dst.extend(src),
+                (_, val) => {
+                    aux.prefs.insert(key, val);
+                }
+            }
+        }
+        self.state_updates.notify_one();
+    }
+
+    /// Mark `link` as private to the application `app_id`
+    pub fn set_app_owner(&mut self, link: &ResourceLink, app_id: &str) {
+        self.txn_save_aux(&link.rid);
+        let aux = self.aux.entry(link.rid).or_default();
+        aux.app_id = Some(app_id.to_string());
+        self.state_updates.notify_one();
+    }
+
+    /// The application a private resource belongs to, if any
+    #[must_use]
+    pub fn app_owner(&self, id: &Uuid) -> Option<&str> {
+        self.aux.get(id).and_then(|aux| aux.app_id.as_deref())
+    }
+
+    /// Returns true, if the resource should be shown to `app_id`. Private
+    /// resources are only shown to their own application, so requests that
+    /// do not identify an application only see public resources.
+    #[must_use]
+    pub fn visible_to(&self, id: &Uuid, app_id: Option<&str>) -> bool {
+        self.app_owner(id)
+            .map_or(true, |owner| app_id == Some(owner))
+    }
+
+    /// Record the z2m round-trip latency for a resource, or a timeout if
+    /// `latency` is `None`. The statistics are persisted with the state.
+    pub fn record_latency(&mut self, id: &Uuid, latency: Option<Duration>) {
+        let Some(stats) = self.latency_entry(id) else {
+            return;
+        };
+
+        match latency {
+            Some(latency) => stats.record(latency),
+            None => stats.record_timeout(),
+        }
+    }
+
+    /// Record a z2m command to a resource being sent again, since the
+    /// resource did not report a new state in time
+    pub fn record_retry(&mut self, id: &Uuid) {
+        if let Some(stats) = self.latency_entry(id) {
+            stats.record_retry();
+        }
+    }
+
+    /// Record a z2m command to a resource that was not confirmed, even
+    /// after retrying
+    pub fn record_delivery_failure(&mut self, id: &Uuid) {
+        if let Some(stats) = self.latency_entry(id) {
+            stats.record_failure();
+        }
+    }
+
+    fn latency_entry(&mut self, id: &Uuid) -> Option<&mut LatencyStats> {
+        if !self.res.contains_key(id) {
+            return None;
+        }
+
+        self.txn_save_aux(id);
+        Some(
+            self.aux
+                .entry(*id)
+                .or_default()
+                .latency
+                .get_or_insert_with(LatencyStats::default),
+        )
+    }
+
+    /// Latency statistics for all resources that have any
+    #[must_use]
+    pub fn latency_stats(&self) -> Vec<(ResourceLink, &LatencyStats)> {
+        self.aux
+            .iter()
+            .filter_map(|(id, aux)| {
+                let rtype = self.res.get(id)?.rtype();
+                Some((rtype.link_to(*id), aux.latency.as_ref()?))
+            })
+            .collect()
+    }
+
+    /// All devices reported by the z2m server `server`
+    /// Store the latest network map (of its type) from a z2m server
+    pub fn set_network_map(&mut self, server: &str, map: NetworkMap) {
+        self.network_maps
+            .insert((server.to_string(), map.map_type), map);
+    }
+
+    #[must_use]
+    pub fn network_map(&self, server: &str, map_type: NetworkMapType) -> Option<&NetworkMap> {
+        self.network_maps.get(&(server.to_string(), map_type))
+    }
+
+    /// Store the exposes of the devices on a z2m server that are not mapped
+    /// to any hue resource
+    pub fn set_unmapped_exposes(&mut self, server: &str, exposes: Vec<UnmappedExpose>) {
+        self.unmapped_exposes.insert(server.to_string(), exposes);
+    }
+
+    #[must_use]
+    pub fn unmapped_exposes(&self, server: &str) -> Option<&[UnmappedExpose]> {
+        self.unmapped_exposes.get(server).map(Vec::as_slice)
+    }
+
+    #[must_use]
+    pub fn get_devices_by_server(&self, server: &str) -> Vec<ResourceLink> {
+        self.res
+            .iter()
+            .filter(|(_, obj)| obj.rtype() == RType::Device)
+            .filter(|(id, _)| {
+                self.aux
+                    .get(id)
+                    .is_some_and(|aux| aux.server.as_deref() == Some(server))
+            })
+            .map(|(id, _)| RType::Device.link_to(*id))
+            .collect()
+    }
+
+    fn own_transition(&self, id: &Uuid) -> Option<f64> {
+        self.aux
+            .get(id)
+            .and_then(|aux| aux.transition)
+            .or_else(|| self.transition_defaults.get(id).copied())
+    }
+
+    /// Default transition time (in seconds) for updates to `link`, used when
+    /// the client does not specify one. Lights without a default of their own
+    /// use the default of their room.
+    #[must_use]
+    pub fn default_transition(&self, link: &ResourceLink) -> Option<f64> {
+        match link.rtype {
+            RType::Light => self.own_transition(&link.rid).or_else(|| {
+                let owner = self.get::<Light>(link).ok()?.owner;
+                self.res.iter().find_map(|(id, obj)| match &**obj {
+                    Resource::Room(room) if room.children.contains(&owner) => {
+                        self.own_transition(id)
+                    }
+                    _ => None,
+                })
+            }),
+            RType::GroupedLight => {
+                let owner = self.get::<GroupedLight>(link).ok()?.owner;
+                self.own_transition(&owner.rid)
+            }
+            _ => self.own_transition(&link.rid),
+        }
+    }
+
     fn generate_update(obj: &Resource) -> ApiResult<Option<Update>> {
         match obj {
             Resource::Light(light) => {
@@ -109,7 +1140,8 @@ impl Resources {
                     .with_brightness(light.dimming)
                     .with_on(light.on)
                     .with_color_temperature(light.as_mirek_opt())
-                    .with_color_xy(light.as_color_opt());
+                    .with_color_xy(light.as_color_opt())
+                    .with_mode(light.mode);
 
                 Ok(Some(Update::Light(upd)))
             }
@@ -123,11 +1155,64 @@ impl Resources {
             Resource::Scene(scene) => {
                 let upd = SceneUpdate::new()
                     .with_actions(Some(scene.actions.clone()))
+                    .with_metadata(Some(scene.metadata.clone()))
                     .with_recall_action(scene.status);
 
                 Ok(Some(Update::Scene(upd)))
             }
-            Resource::Room(_) => Ok(None),
+            Resource::Button(button) => {
+                let upd = ButtonUpdate {
+                    button: button.button.clone(),
+                };
+
+                Ok(Some(Update::Button(upd)))
+            }
+            Resource::LightLevel(light_level) => {
+                let upd = LightLevelUpdate::new()
+                    .with_enabled(light_level.enabled)
+                    .with_light(light_level.light.clone());
+
+                Ok(Some(Update::LightLevel(upd)))
+            }
+            Resource::Temperature(temperature) => {
+                let upd = TemperatureUpdate::new()
+                    .with_enabled(temperature.enabled)
+                    .with_temperature(temperature.temperature.clone());
+
+                Ok(Some(Update::Temperature(upd)))
+            }
+            Resource::Motion(motion) => {
+                let upd = MotionUpdate::new()
+                    .with_enabled(motion.enabled)
+                    .with_motion(motion.motion.clone())
+                    .with_sensitivity(motion.sensitivity.as_ref());
+
+                Ok(Some(Update::Motion(upd)))
+            }
+            Resource::Room(room) => {
+                let upd = RoomUpdate::new().with_metadata(room.metadata.clone());
+
+                Ok(Some(Update::Room(upd)))
+            }
+            Resource::Zone(zone) => {
+                let upd = RoomUpdate::new().with_metadata(zone.metadata.clone());
+
+                Ok(Some(Update::Zone(upd)))
+            }
+            Resource::EntertainmentConfiguration(ec) => {
+                let upd = EntertainmentConfigurationUpdate::new()
+                    .with_metadata(ec.metadata.clone())
+                    .with_status(ec.status);
+
+                Ok(Some(Update::EntertainmentConfiguration(upd)))
+            }
+            Resource::ZigbeeConnectivity(zbc) => {
+                let upd = ZigbeeConnectivityUpdate { status: zbc.status };
+
+                Ok(Some(Update::ZigbeeConnectivity(upd)))
+            }
+            /* changes are stored, but not (yet) reported as events */
+            Resource::Bridge(_) | Resource::Device(_) => Ok(None),
             obj => Err(ApiError::UpdateUnsupported(obj.rtype())),
         }
     }
@@ -140,11 +1225,13 @@ impl Resources {
     where
         for<'a> &'a mut T: TryFrom<&'a mut Resource, Error = ApiError>,
     {
-        let obj = self.res.get_mut(id).ok_or(ApiError::NotFound(*id))?;
+        self.txn_save_res(id);
+        let obj = Arc::make_mut(self.res.get_mut(id).ok_or(ApiError::NotFound(*id))?);
         func(obj.try_into()?)?;
 
         if let Some(delta) = Self::generate_update(obj)? {
-            self.hue_event(EventBlock::update(id, delta)?);
+            let app_id = self.aux.get(id).and_then(|aux| aux.app_id.clone());
+            self.hue_event(EventBlock::update(id, delta)?.with_app_id(app_id));
         }
 
         self.state_updates.notify_one();
@@ -167,7 +1254,7 @@ impl Resources {
         self.res
             .iter()
             .filter_map(|(k, v)| {
-                if let Resource::Scene(scn) = v {
+                if let Resource::Scene(scn) = &**v {
                     if &scn.group.rid == id {
                         Some(k)
                     } else {
@@ -181,6 +1268,58 @@ impl Resources {
             .collect()
     }
 
+    /// Returns the active scenes with an action for `light`
+    #[must_use]
+    pub fn get_active_scenes_for_light(&self, light: &ResourceLink) -> Vec<(Uuid, SceneAction)> {
+        self.res
+            .iter()
+            .filter_map(|(id, obj)| match &**obj {
+                Resource::Scene(scn) if scn.is_active() => scn
+                    .actions
+                    .iter()
+                    .find(|sae| sae.target == *light)
+                    .map(|sae| (*id, sae.action.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Mark active scenes for `light` as inactive, if the light state no
+    /// longer matches the scene (or unconditionally, if `light` is `None`).
+    pub fn deactivate_scenes(
+        &mut self,
+        rlink: &ResourceLink,
+        light: Option<&Light>,
+    ) -> ApiResult<()> {
+        for (id, action) in self.get_active_scenes_for_light(rlink) {
+            if light.is_some_and(|light| action.matches(light)) {
+                continue;
+            }
+            log::debug!("Scene {id} deactivated by change to light {}", rlink.rid);
+            self.update::<Scene>(&id, |scn| scn.status = Some(SceneStatus::Inactive))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the lights in a room or zone
+    #[must_use]
+    pub fn get_lights_for_group(&self, group: &ResourceLink) -> Vec<ResourceLink> {
+        let children = match self.res.get(&group.rid).map(|obj| &**obj) {
+            Some(Resource::Room(room)) => &room.children,
+            Some(Resource::Zone(zone)) => &zone.children,
+            _ => return vec![],
+        };
+
+        children
+            .iter()
+            .filter_map(|child| match child.rtype {
+                RType::Light => Some(*child),
+                RType::Device => self.get::<Device>(child).ok()?.light_service().copied(),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn add(&mut self, link: &ResourceLink, obj: Resource) -> ApiResult<()> {
         assert!(
             link.rtype == obj.rtype(),
@@ -194,11 +1333,13 @@ impl Resources {
             return Ok(());
         }
 
-        self.res.insert(link.rid, obj);
+        self.txn_save_res(&link.rid);
+        self.res.insert(link.rid, Arc::new(obj));
 
         self.state_updates.notify_one();
 
-        let evt = EventBlock::add(serde_json::to_value(self.get_resource_by_id(&link.rid)?)?);
+        let evt = EventBlock::add(serde_json::to_value(self.get_resource_by_id(&link.rid)?)?)
+            .with_app_id(self.app_owner(&link.rid).map(ToString::to_string));
 
         log::trace!("Send event: {evt:?}");
 
@@ -209,22 +1350,98 @@ impl Resources {
 
     pub fn delete(&mut self, link: &ResourceLink) -> ApiResult<()> {
         log::info!("Deleting {link:?}..");
+        self.txn_save_res(&link.rid);
+        self.txn_save_aux(&link.rid);
+        self.txn_save_light_state(&link.rid);
         self.res
             .remove(&link.rid)
             .ok_or(ApiError::NotFound(link.rid))?;
 
-        self.aux.remove(&link.rid);
+        let aux = self.aux.remove(&link.rid);
+        self.light_states.remove(&link.rid);
 
         self.state_updates.notify_one();
 
-        let evt = EventBlock::delete(link)?;
+        let evt = EventBlock::delete(link)?.with_app_id(aux.and_then(|aux| aux.app_id));
 
         self.hue_event(evt);
 
         Ok(())
     }
 
-    pub fn add_bridge(&mut self, bridge_id: String) -> ApiResult<()> {
+    /// Delete a device, all of its services, and any references to them
+    /// from rooms and zones.
+    pub fn delete_device(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        self.transaction(|res| res.delete_device_services(link))
+    }
+
+    /// Delete a room or zone, along with its services and scenes
+    pub fn delete_group(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        let services = match self.get_resource_by_id(&link.rid)?.obj {
+            Resource::Room(room) => room.services,
+            Resource::Zone(zone) => zone.services,
+            _ => return Err(ApiError::WrongType(RType::Room, link.rtype)),
+        };
+
+        for scene in self.get_scenes_for_room(&link.rid) {
+            self.delete(&RType::Scene.link_to(scene))?;
+        }
+
+        for svc in &services {
+            if self.res.contains_key(&svc.rid) {
+                self.delete(svc)?;
+            }
+        }
+
+        self.delete(link)
+    }
+
+    /// Rooms and zones with the device at `link` (or one of its services)
+    /// among their children
+    pub fn device_groups(&self, link: &ResourceLink) -> ApiResult<Vec<ResourceLink>> {
+        let dev: &Device = self.get(link)?;
+        let links: Vec<ResourceLink> = dev.services.iter().chain([link]).copied().collect();
+
+        Ok(self
+            .res
+            .iter()
+            .filter(|(_, obj)| match &***obj {
+                Resource::Room(room) => room.children.iter().any(|c| links.contains(c)),
+                Resource::Zone(zone) => zone.children.iter().any(|c| links.contains(c)),
+                _ => false,
+            })
+            .map(|(id, obj)| ResourceLink::new(*id, obj.rtype()))
+            .collect())
+    }
+
+    fn delete_device_services(&mut self, link: &ResourceLink) -> ApiResult<()> {
+        let groups = self.device_groups(link)?;
+        let dev: &Device = self.get(link)?;
+        let mut links = dev.services.clone();
+
+        for svc in &links {
+            if self.res.contains_key(&svc.rid) {
+                self.delete(svc)?;
+            }
+        }
+        links.push(*link);
+
+        for group in groups {
+            match group.rtype {
+                RType::Room => self.update::<Room>(&group.rid, |room| {
+                    room.children.retain(|c| !links.contains(c));
+                })?,
+                RType::Zone => self.update::<Zone>(&group.rid, |zone| {
+                    zone.children.retain(|c| !links.contains(c));
+                })?,
+                _ => {}
+            }
+        }
+
+        self.delete(link)
+    }
+
+    pub fn add_bridge(&mut self, bridge_id: String, locale: Locale) -> ApiResult<()> {
         let link_bridge = RType::Bridge.deterministic(&bridge_id);
         let link_bridge_home = RType::BridgeHome.deterministic(format!("{bridge_id}HOME"));
         let link_bridge_dev = RType::Device.deterministic(link_bridge.rid);
@@ -246,7 +1463,7 @@ impl Resources {
 
         let bridge_home_dev = Device {
             product_data: DeviceProductData::hue_bridge_v2(),
-            metadata: Metadata::new(DeviceArchetype::BridgeV2, "Bifrost Bridge Home"),
+            metadata: Metadata::new(DeviceArchetype::BridgeV2, locale.bridge_home()),
             services: vec![link_bridge],
         };
 
@@ -269,6 +1486,9 @@ impl Resources {
                 "value": "channel_25",
             })),
             extended_pan_id: String::from("0123456789abcdef"),
+            last_seen: None,
+            linkquality: None,
+            rssi: None,
         };
 
         self.add(&link_bridge_dev, Resource::Device(bridge_dev))?;
@@ -313,10 +1533,11 @@ impl Resources {
     where
         &'a T: TryFrom<&'a Resource, Error = ApiError>,
     {
-        self.res
+        (&**self
+            .res
             .get(&link.rid)
             .filter(|id| id.rtype() == link.rtype)
-            .ok_or_else(|| ApiError::NotFound(link.rid))?
+            .ok_or_else(|| ApiError::NotFound(link.rid))?)
             .try_into()
     }
 
@@ -335,15 +1556,53 @@ impl Resources {
             .ok_or_else(|| ApiError::NotFound(*id))
     }
 
+    #[must_use]
     pub fn get_resources(&self) -> Vec<ResourceRecord> {
-        self.res.iter().map(ResourceRecord::from_ref).collect()
+        self.res
+            .iter()
+            .map(|(id, r)| ResourceRecord::from_ref((id, r)))
+            .collect()
     }
 
+    #[must_use]
     pub fn get_resources_by_type(&self, ty: RType) -> Vec<ResourceRecord> {
         self.res
             .iter()
             .filter(|(_, r)| r.rtype() == ty)
-            .map(ResourceRecord::from_ref)
+            .map(|(id, r)| ResourceRecord::from_ref((id, r)))
+            .collect()
+    }
+
+    fn snapshot_of(&self, id: &Uuid, obj: &Arc<Resource>) -> Snapshot {
+        Snapshot {
+            rec: SharedRecord::new(*id, obj.clone()),
+            prefs: self
+                .aux
+                .get(id)
+                .map(|aux| aux.prefs.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// A snapshot of a resource, sharing it with the store, so it can be
+    /// serialized after the lock is released.
+    pub fn snapshot(&self, ty: RType, id: &Uuid) -> ApiResult<Snapshot> {
+        self.res
+            .get(id)
+            .filter(|obj| obj.rtype() == ty)
+            .map(|obj| self.snapshot_of(id, obj))
+            .ok_or(ApiError::NotFound(*id))
+    }
+
+    /// Snapshots of all resources (of type `ty`, if given) visible to the
+    /// application `app_id` (see [`Self::visible_to`])
+    #[must_use]
+    pub fn snapshots(&self, ty: Option<RType>, app_id: Option<&str>) -> Vec<Snapshot> {
+        self.res
+            .iter()
+            .filter(|(_, obj)| ty.map_or(true, |ty| obj.rtype() == ty))
+            .filter(|(id, _)| self.visible_to(id, app_id))
+            .map(|(id, obj)| self.snapshot_of(id, obj))
             .collect()
     }
 
@@ -357,20 +1616,45 @@ impl Resources {
         self.hue_updates.subscribe()
     }
 
-    fn hue_event(&self, evt: EventBlock) {
+    fn hue_event(&mut self, mut evt: EventBlock) {
+        if let Some(txn) = &mut self.txn {
+            txn.events.push(evt);
+            return;
+        }
+
+        evt.seq = self.events.advance();
         if let Err(err) = self.hue_updates.send(evt) {
             log::trace!("Overflow on hue event pipe: {err}");
         }
     }
 
     #[must_use]
-    pub fn z2m_channel(&self) -> Receiver<Arc<ClientRequest>> {
+    pub fn z2m_channel(&self) -> Receiver<Arc<TaggedRequest>> {
         self.z2m_updates.subscribe()
     }
 
     pub fn z2m_request(&self, req: ClientRequest) -> ApiResult<()> {
         log::debug!("z2m request: {req:#?}");
 
+        let req = TaggedRequest {
+            request_id: logging::request_id(),
+            req,
+        };
+
+        if let Some(txn) = &self.txn {
+            txn.requests.lock().unwrap().push(req);
+            return Ok(());
+        }
+
+        self.z2m_send(req)
+    }
+
+    fn z2m_send(&self, req: TaggedRequest) -> ApiResult<()> {
+        if !*self.active.borrow() {
+            log::debug!("Standing by, not sending z2m request");
+            return Ok(());
+        }
+
         self.z2m_updates.send(Arc::new(req))?;
 
         Ok(())
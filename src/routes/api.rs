@@ -7,6 +7,7 @@ use axum::{
     Json, Router,
 };
 
+use chrono::{Duration, Utc};
 use log::{info, warn};
 use serde_json::{json, Value};
 use tokio::sync::MutexGuard;
@@ -14,13 +15,16 @@ use uuid::Uuid;
 
 use crate::error::{ApiError, ApiResult};
 use crate::hue::api::{
-    Device, GroupedLight, Light, RType, ResourceLink, Room, Scene, V1ReplyBuilder,
+    Button, Device, GroupedLight, Light, RType, ResourceLink, Room, Scene, SceneStatus,
+    SceneUpdate, V1ReplyBuilder,
 };
 use crate::hue::legacy_api::{
-    ApiGroup, ApiLight, ApiLightStateUpdate, ApiResourceType, ApiScene, ApiUserConfig,
-    Capabilities, HueResult, NewUser, NewUserReply,
+    ApiGroup, ApiGroupActionUpdate, ApiLight, ApiLightSearch, ApiLightStateUpdate, ApiResourceType,
+    ApiScene, ApiSensor, ApiUserConfig, Capabilities, HueError, HueResult, NewUser, NewUserReply,
 };
+use crate::model::{astronomy, units};
 use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
 use crate::state::AppState;
 use crate::z2m::request::ClientRequest;
 use crate::z2m::update::DeviceUpdate;
@@ -29,57 +33,112 @@ async fn get_api_config(State(state): State<AppState>) -> impl IntoResponse {
     Json(state.api_short_config())
 }
 
-async fn post_api(Json(j): Json<NewUser>) -> impl IntoResponse {
+/// Pair a new application. With [`crate::config::LinkButtonConfig::required`],
+/// this fails until the link button has been pressed.
+async fn post_api(State(state): State<AppState>, Json(j): Json<NewUser>) -> impl IntoResponse {
     info!("post: {j:?}");
-    let res = NewUserReply {
-        clientkey: Uuid::new_v4(),
-        username: Uuid::new_v4(),
-    };
-    Json(vec![HueResult::Success(res)])
+    let mut lock = state.res.lock().await;
+
+    if state.config().bifrost.link_button.required && !lock.whitelist().is_pressed() {
+        warn!("Not pairing [{}]: link button not pressed", j.devicetype());
+        return Json(vec![HueResult::Error(HueError::link_button_not_pressed())]);
+    }
+
+    let clientkey = (!state.quirks() || j.wants_clientkey()).then(Uuid::new_v4);
+    let username = lock.whitelist_add(j.devicetype(), clientkey);
+    drop(lock);
+
+    Json(vec![HueResult::Success(NewUserReply {
+        username,
+        clientkey,
+    })])
 }
 
-fn get_lights(res: &MutexGuard<Resources>) -> ApiResult<HashMap<String, ApiLight>> {
+fn get_lights(res: &MutexGuard<Resources>, quirks: bool) -> ApiResult<HashMap<String, ApiLight>> {
     let mut lights = HashMap::new();
 
     for rr in res.get_resources_by_type(RType::Light) {
         let light: Light = rr.obj.try_into()?;
         let dev = res.get::<Device>(&light.owner)?.clone();
-        lights.insert(
-            rr.id.simple().to_string(),
-            ApiLight::from_dev_and_light(&rr.id, dev, light),
-        );
+        let reachable = res.is_reachable(&light.owner);
+        let mut api_light =
+            ApiLight::from_dev_and_light(&rr.id, dev, light).with_reachable(reachable);
+        if quirks {
+            api_light = api_light.with_quirks(&rr.id);
+        }
+        lights.insert(rr.id.simple().to_string(), api_light);
     }
 
     Ok(lights)
 }
 
+fn get_group(res: &MutexGuard<Resources>, id: Uuid, room: Room) -> ApiResult<ApiGroup> {
+    let uuid = room
+        .services
+        .iter()
+        .find(|rl| rl.rtype == RType::GroupedLight)
+        .ok_or(ApiError::NotFound(id))?;
+
+    let glight = res.get::<GroupedLight>(uuid)?.clone();
+    let lights: Vec<(Uuid, Light)> = room
+        .children
+        .iter()
+        .filter_map(|rl| res.get(rl).ok())
+        .filter_map(Device::light_service)
+        .filter_map(|rl| Some((rl.rid, res.get::<Light>(rl).ok()?.clone())))
+        .collect();
+
+    Ok(ApiGroup::from_lights_and_room(glight, &lights, room))
+}
+
 fn get_groups(res: &MutexGuard<Resources>) -> ApiResult<HashMap<String, ApiGroup>> {
     let mut rooms = HashMap::new();
 
     for rr in res.get_resources_by_type(RType::Room) {
         let room: Room = rr.obj.try_into()?;
-        let uuid = room
-            .services
-            .iter()
-            .find(|rl| rl.rtype == RType::GroupedLight)
-            .ok_or(ApiError::NotFound(rr.id))?;
+        rooms.insert(rr.id.simple().to_string(), get_group(res, rr.id, room)?);
+    }
+
+    Ok(rooms)
+}
+
+/// Switches, and the daylight sensor (if the bridge location is configured)
+fn get_sensors(
+    res: &MutexGuard<Resources>,
+    state: &AppState,
+) -> ApiResult<HashMap<String, ApiSensor>> {
+    let quirks = state.quirks();
+    let mut sensors = HashMap::new();
+
+    if let Some(loc) = &state.config().bridge.location {
+        let daylight = astronomy::is_daylight(loc, state.timezone(), Utc::now());
+        sensors.insert(
+            ApiSensor::DAYLIGHT_ID.simple().to_string(),
+            ApiSensor::daylight(daylight, loc.sunrise_offset, loc.sunset_offset),
+        );
+    }
 
-        let glight = res.get::<GroupedLight>(uuid)?.clone();
-        let lights: Vec<(Uuid, Light)> = room
-            .children
+    for rr in res.get_resources_by_type(RType::Device) {
+        let dev: Device = rr.obj.try_into()?;
+        let buttons: Vec<Button> = dev
+            .services
             .iter()
-            .filter_map(|rl| res.get(rl).ok())
-            .filter_map(Device::light_service)
-            .filter_map(|rl| Some((rl.rid, res.get::<Light>(rl).ok()?.clone())))
+            .filter(|rl| rl.rtype == RType::Button)
+            .filter_map(|rl| res.get::<Button>(rl).ok().cloned())
             .collect();
 
-        rooms.insert(
-            rr.id.simple().to_string(),
-            ApiGroup::from_lights_and_room(glight, &lights, room),
-        );
+        if buttons.is_empty() {
+            continue;
+        }
+
+        let mut sensor = ApiSensor::from_dev_and_buttons(&rr.id, &dev, &buttons);
+        if quirks {
+            sensor = sensor.with_quirks(&rr.id);
+        }
+        sensors.insert(rr.id.simple().to_string(), sensor);
     }
 
-    Ok(rooms)
+    Ok(sensors)
 }
 
 fn get_scenes(owner: &Uuid, res: &MutexGuard<Resources>) -> ApiResult<HashMap<String, ApiScene>> {
@@ -105,35 +164,101 @@ async fn get_api_user(
     let lock = state.res.lock().await;
 
     Ok(Json(ApiUserConfig {
-        config: state.api_config(username),
+        config: state.api_config(username, lock.whitelist()),
         groups: get_groups(&lock)?,
-        lights: get_lights(&lock)?,
+        lights: get_lights(&lock, state.quirks())?,
         resourcelinks: HashMap::new(),
         rules: HashMap::new(),
         scenes: get_scenes(&username, &lock)?,
         schedules: HashMap::new(),
-        sensors: HashMap::new(),
+        sensors: get_sensors(&lock, &state)?,
     }))
 }
 
+#[allow(clippy::significant_drop_tightening)]
 async fn get_api_user_resource(
     State(state): State<AppState>,
     Path((username, resource)): Path<(Uuid, ApiResourceType)>,
 ) -> ApiResult<Json<Value>> {
     let lock = &state.res.lock().await;
     match resource {
-        ApiResourceType::Config => Ok(Json(json!(state.api_config(username)))),
-        ApiResourceType::Lights => Ok(Json(json!(get_lights(lock)?))),
+        ApiResourceType::Config => Ok(Json(json!(state.api_config(username, lock.whitelist())))),
+        ApiResourceType::Lights => Ok(Json(json!(get_lights(lock, state.quirks())?))),
         ApiResourceType::Groups => Ok(Json(json!(get_groups(lock)?))),
         ApiResourceType::Scenes => Ok(Json(json!(get_scenes(&username, lock)?))),
-        ApiResourceType::Resourcelinks
+        ApiResourceType::Sensors => Ok(Json(json!(get_sensors(lock, &state)?))),
+        ApiResourceType::Resourcelinks | ApiResourceType::Rules | ApiResourceType::Schedules => {
+            Ok(Json(json!({})))
+        }
+        ApiResourceType::Capabilities => {
+            let rules = lock.get_resources_by_type(RType::BehaviorInstance).len();
+            let caps = Capabilities::new(
+                &state.config().bridge.limits,
+                u32::try_from(rules).unwrap_or(u32::MAX),
+            );
+            Ok(Json(json!(caps)))
+        }
+    }
+}
+
+/* Duration of a light search (same as a real hue bridge) */
+const LIGHT_SEARCH_SECS: u32 = 40;
+
+async fn post_api_user_resource(
+    State(state): State<AppState>,
+    Path((_username, resource)): Path<(String, ApiResourceType)>,
+    Json(req): Json<Value>,
+) -> ApiResult<Json<Value>> {
+    match resource {
+        ApiResourceType::Lights => {
+            let search: ApiLightSearch = serde_json::from_value(req)?;
+            let lock = state.res.lock().await;
+
+            /* zigbee2mqtt cannot search for specific serial numbers, so the
+             * best we can do is to open the network for new devices. Install
+             * codes, on the other hand, can be passed directly to z2m. */
+            for deviceid in search.deviceid {
+                if deviceid.len() > 6 {
+                    info!("Adding install code {deviceid}");
+                    lock.z2m_request(ClientRequest::install_code(deviceid))?;
+                } else {
+                    info!("Searching for serial {deviceid} (permitting join)");
+                }
+            }
+
+            lock.z2m_request(ClientRequest::permit_join(LIGHT_SEARCH_SECS))?;
+            drop(lock);
+
+            *state.lightscan.lock().await = Some(Utc::now());
+
+            Ok(Json(json!([HueResult::Success(
+                json!({"/lights": "Searching for new devices"})
+            )])))
+        }
+        ApiResourceType::Config
+        | ApiResourceType::Groups
+        | ApiResourceType::Resourcelinks
         | ApiResourceType::Rules
+        | ApiResourceType::Scenes
         | ApiResourceType::Schedules
-        | ApiResourceType::Sensors => Ok(Json(json!({}))),
-        ApiResourceType::Capabilities => Ok(Json(json!(Capabilities::new()))),
+        | ApiResourceType::Sensors
+        | ApiResourceType::Capabilities => Err(ApiError::V1CreateUnsupported(resource)),
     }
 }
 
+async fn get_api_user_lights_new(State(state): State<AppState>) -> impl IntoResponse {
+    let lastscan = *state.lightscan.lock().await;
+    let lastscan = match lastscan {
+        Some(ts) if Utc::now() - ts < Duration::seconds(LIGHT_SEARCH_SECS.into()) => {
+            "active".to_string()
+        }
+        Some(ts) => ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        None => "none".to_string(),
+    };
+
+    Json(json!({ "lastscan": lastscan }))
+}
+
 async fn put_api_user_resource(
     Path((_username, _resource)): Path<(String, String)>,
     Json(req): Json<Value>,
@@ -155,11 +280,18 @@ async fn get_api_user_resource_id(
             let link = ResourceLink::new(id, RType::Light);
             let light = lock.get::<Light>(&link)?;
             let dev = lock.get::<Device>(&light.owner)?.clone();
-            Ok(Json(json!(ApiLight::from_dev_and_light(
-                &id,
-                dev,
-                light.clone(),
-            ))))
+            let reachable = lock.is_reachable(&light.owner);
+            let mut api_light =
+                ApiLight::from_dev_and_light(&id, dev, light.clone()).with_reachable(reachable);
+            if state.quirks() {
+                api_light = api_light.with_quirks(&id);
+            }
+            Ok(Json(json!(api_light)))
+        }
+        ApiResourceType::Groups => {
+            let lock = state.res.lock().await;
+            let room = lock.get::<Room>(&RType::Room.link_to(id))?.clone();
+            Ok(Json(json!(get_group(&lock, id, room)?)))
         }
         ApiResourceType::Scenes => {
             let lock = state.res.lock().await;
@@ -167,10 +299,52 @@ async fn get_api_user_resource_id(
             let scene = lock.get::<Scene>(&link)?.clone();
             Ok(Json(json!(ApiScene::from_scene(username, scene))))
         }
+        ApiResourceType::Sensors => {
+            let lock = state.res.lock().await;
+            let sensors = get_sensors(&lock, &state)?;
+            drop(lock);
+            let sensor = sensors
+                .get(&id.simple().to_string())
+                .ok_or(ApiError::NotFound(id))?;
+            Ok(Json(json!(sensor)))
+        }
         _ => Err(ApiError::NotFound(id)),
     }
 }
 
+/// The z2m update for a v1 light (or group) state update. Hue and
+/// saturation are only used together, since neither is a color by itself.
+fn state_update(upd: &ApiLightStateUpdate) -> DeviceUpdate {
+    let payload = DeviceUpdate::default()
+        .with_state(upd.on)
+        .with_brightness(upd.bri.map(f64::from))
+        .with_color_temp(upd.ct);
+
+    match (upd.xy, upd.hue.zip(upd.sat)) {
+        (Some(xy), _) => payload.with_color_xy(Some(xy.into())),
+        (None, Some((hue, sat))) => payload.with_color_hs(Some(units::v1_hs_to_device(hue, sat))),
+        (None, None) => payload,
+    }
+}
+
+fn state_reply(
+    prefix: String,
+    upd: &ApiLightStateUpdate,
+    scene: Option<Uuid>,
+) -> ApiResult<Json<Value>> {
+    let hs = upd.xy.is_none() && upd.hue.is_some() && upd.sat.is_some();
+    let reply = V1ReplyBuilder::new(prefix)
+        .add_option("scene", scene.map(|id| id.simple().to_string()))?
+        .add_option("on", upd.on)?
+        .add_option("bri", upd.bri)?
+        .add_option("hue", upd.hue.filter(|_| hs))?
+        .add_option("sat", upd.sat.filter(|_| hs))?
+        .add_option("xy", upd.xy)?
+        .add_option("ct", upd.ct)?;
+
+    Ok(Json(reply.json()))
+}
+
 async fn put_api_user_resource_id(
     State(state): State<AppState>,
     Path((_username, resource, id, path)): Path<(String, ApiResourceType, Uuid, String)>,
@@ -185,51 +359,53 @@ async fn put_api_user_resource_id(
                     let link = ResourceLink::new(id, RType::Light);
                     let upd: ApiLightStateUpdate = serde_json::from_value(req)?;
 
-                    let payload = DeviceUpdate::default()
-                        .with_state(upd.on)
-                        .with_brightness(upd.bri.map(f64::from))
-                        .with_color_xy(upd.xy.map(Into::into))
-                        .with_color_temp(upd.ct);
+                    /* the entertainment session has exclusive control of the light */
+                    if lock.is_streaming(&link) {
+                        return Err(ApiError::LightStreaming(id));
+                    }
 
-                    lock.z2m_request(ClientRequest::light_update(link, payload))?;
+                    lock.z2m_request(ClientRequest::light_update(link, state_update(&upd)))?;
                     drop(lock);
 
-                    let reply = V1ReplyBuilder::new(format!("/lights/{}/{path}", id.as_simple()))
-                        .add_option("on", upd.on)?
-                        .add_option("bri", upd.bri)?
-                        .add_option("xy", upd.xy)?
-                        .add_option("ct", upd.ct)?;
-
-                    Ok(Json(reply.json()))
+                    state_reply(format!("/lights/{}/{path}", id.as_simple()), &upd, None)
                 }
                 _ => Err(ApiError::NotFound(id)),
             }
         }
         ApiResourceType::Groups => match path.as_str() {
             "action" => {
-                let lock = state.res.lock().await;
+                let mut lock = state.res.lock().await;
                 let link = ResourceLink::new(id, RType::Room);
                 let room: &Room = lock.get(&link)?;
-                let glight = room.grouped_light_service().unwrap();
+                let glight = *room.grouped_light_service().ok_or(ApiError::NotFound(id))?;
 
-                let upd: ApiLightStateUpdate = serde_json::from_value(req)?;
+                let upd: ApiGroupActionUpdate = serde_json::from_value(req)?;
 
-                let payload = DeviceUpdate::default()
-                    .with_state(upd.on)
-                    .with_brightness(upd.bri.map(f64::from))
-                    .with_color_xy(upd.xy.map(Into::into))
-                    .with_color_temp(upd.ct);
+                /* group commands would also reach lights used for entertainment */
+                if !upd.state.is_empty() {
+                    let lights = lock.get_lights_for_group(&link);
+                    if let Some(light) = lights.iter().find(|light| lock.is_streaming(light)) {
+                        return Err(ApiError::LightStreaming(light.rid));
+                    }
+                }
 
-                lock.z2m_request(ClientRequest::group_update(*glight, payload))?;
+                /* scenes are recalled like through the v2 api, so scene
+                 * status is updated too */
+                if let Some(scene) = upd.scene {
+                    let recall = SceneUpdate::new().with_recall_action(Some(SceneStatus::Static));
+                    Scene::put(&mut lock, &RType::Scene.link_to(scene), recall)?;
+                }
+                if !upd.state.is_empty() {
+                    let payload = state_update(&upd.state);
+                    lock.z2m_request(ClientRequest::group_update(glight, payload))?;
+                }
                 drop(lock);
 
-                let reply = V1ReplyBuilder::new(format!("/groups/{}/{path}", id.as_simple()))
-                    .add_option("on", upd.on)?
-                    .add_option("bri", upd.bri)?
-                    .add_option("xy", upd.xy)?
-                    .add_option("ct", upd.ct)?;
-
-                Ok(Json(reply.json()))
+                state_reply(
+                    format!("/groups/{}/{path}", id.as_simple()),
+                    &upd.state,
+                    upd.scene,
+                )
             }
             _ => Err(ApiError::NotFound(id)),
         },
@@ -243,13 +419,23 @@ async fn put_api_user_resource_id(
     }
 }
 
-pub fn router() -> Router<AppState> {
-    Router::new()
+/// Router for hue api v1. Without `v1`, only what is needed for pairing (and
+/// bridge discovery) is available.
+pub fn router(v1: bool) -> Router<AppState> {
+    let router = Router::new()
         .route("/", post(post_api))
-        .route("/config", get(get_api_config))
+        .route("/config", get(get_api_config));
+
+    if !v1 {
+        return router;
+    }
+
+    router
         .route("/:user", get(get_api_user))
         .route("/:user/:rtype", get(get_api_user_resource))
         .route("/:user/:rtype", put(put_api_user_resource))
+        .route("/:user/:rtype", post(post_api_user_resource))
+        .route("/:user/lights/new", get(get_api_user_lights_new))
         .route("/:user/:rtype/:id", get(get_api_user_resource_id))
         .route("/:user/:rtype/:id/:key", put(put_api_user_resource_id))
 }
@@ -0,0 +1,80 @@
+use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::hue::api::{ResourceLink, V2Reply};
+use crate::routes::clip::{application_id, apply_put, ApiV2Result};
+use crate::state::AppState;
+
+/// Largest number of operations in a single batch
+const MAX_OPERATIONS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct Operation {
+    #[serde(flatten)]
+    link: ResourceLink,
+    /* same as the body of a PUT request for the resource */
+    update: Value,
+}
+
+/// Apply a list of PUT operations at once.
+///
+/// All operations are applied while holding the resource lock once, and the
+/// resulting zigbee2mqtt requests are sent together afterwards (with updates
+/// for the same light combined). Operations are independent: a failed
+/// operation is reported in the errors of the reply, and does not undo the
+/// others. Private resources of other applications are skipped, just like
+/// for a single PUT request.
+async fn post_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(ops): Json<Vec<Operation>>,
+) -> ApiV2Result {
+    log::info!("POST batch: {} operations", ops.len());
+
+    if ops.len() > MAX_OPERATIONS {
+        return Err(ApiError::InvalidBatch(format!(
+            "at most {MAX_OPERATIONS} operations are allowed"
+        )));
+    }
+
+    let config = state.config();
+    let app_id = application_id(&headers);
+    let mut lock = state.res.lock().await;
+
+    let (data, errors) = lock.transaction(|res| {
+        let mut data = vec![];
+        let mut errors = vec![];
+        for op in &ops {
+            let link = op.link;
+            if !res.visible_to(&link.rid, app_id) {
+                errors.push(format!("{link:?}: {}", ApiError::NotFound(link.rid)));
+                continue;
+            }
+            match apply_put(res, &config, &link, &op.update) {
+                Ok(()) => data.push(serde_json::to_value(link)?),
+                Err(ApiError::PartialFailure(updated, errs)) => {
+                    data.push(serde_json::to_value(link)?);
+                    for target in updated {
+                        data.push(serde_json::to_value(target)?);
+                    }
+                    errors.extend(errs.into_iter().map(|err| format!("{link:?}: {err}")));
+                }
+                Err(err) => errors.push(format!("{link:?}: {err}")),
+            }
+        }
+        Ok::<_, ApiError>((data, errors))
+    })?;
+    drop(lock);
+
+    for err in &errors {
+        log::warn!("POST batch: {err}");
+    }
+
+    Ok(Json(V2Reply { data, errors }))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", post(post_batch))
+}
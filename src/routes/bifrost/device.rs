@@ -0,0 +1,40 @@
+use axum::{
+    extract::{Path, State},
+    routing::put,
+    Json, Router,
+};
+use uuid::Uuid;
+
+use crate::config::DeviceOptions;
+use crate::error::ApiError;
+use crate::hue::api::{RType, V2Reply};
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+use crate::z2m::request::ClientRequest;
+
+async fn put_device_options(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(options): Json<DeviceOptions>,
+) -> ApiV2Result {
+    log::info!(
+        "PUT device/{id}/options: {}",
+        serde_json::to_string(&options)?
+    );
+
+    let lock = state.res.lock().await;
+    let link = lock.get_resource_by_id(&id)?.obj.rtype().link_to(id);
+
+    if !matches!(link.rtype, RType::Device | RType::Light) {
+        return Err(ApiError::WrongType(RType::Device, link.rtype));
+    }
+
+    lock.z2m_request(ClientRequest::device_options(link, options))?;
+    drop(lock);
+
+    V2Reply::ok(link)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:id/options", put(put_device_options))
+}
@@ -0,0 +1,40 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use uuid::Uuid;
+
+use crate::fade::{FadeRecord, FadeRequest};
+use crate::hue::api::V2Reply;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+async fn get_fades(State(state): State<AppState>) -> ApiV2Result {
+    V2Reply::list(state.fades.lock().await.jobs())
+}
+
+async fn post_fade(State(state): State<AppState>, Json(req): Json<FadeRequest>) -> ApiV2Result {
+    log::info!("POST fade: {}", serde_json::to_string(&req)?);
+
+    let res = state.res.lock().await;
+    let id = state.fades.lock().await.start(&res, req)?;
+    drop(res);
+
+    V2Reply::ok(id)
+}
+
+async fn delete_fade(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiV2Result {
+    log::info!("DELETE fade/{id}");
+
+    let job = state.fades.lock().await.cancel(&id)?;
+
+    V2Reply::ok(FadeRecord { id, job: &job })
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_fades))
+        .route("/", post(post_fade))
+        .route("/:id", delete(delete_fade))
+}
@@ -0,0 +1,52 @@
+use axum::{extract::State, routing::get, Router};
+use serde::Serialize;
+
+use crate::hue::api::V2Reply;
+use crate::hue::legacy_api::ApiShortConfig;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+/// What this bifrost instance supports, so clients can adapt to it instead of
+/// probing with requests that fail
+#[derive(Debug, Serialize)]
+struct FeaturesReply {
+    version: &'static str,
+    /// Hue api versions served, and the api version reported by v1
+    api_versions: Vec<&'static str>,
+    hue_apiversion: String,
+    /// Names of all enabled features
+    features: Vec<&'static str>,
+}
+
+async fn get_features(State(state): State<AppState>) -> ApiV2Result {
+    let conf = &state.config().bifrost;
+
+    let mut api_versions = vec!["v2"];
+    if conf.features.v1_api {
+        api_versions.insert(0, "v1");
+    }
+
+    let features = [
+        ("v1_api", conf.features.v1_api),
+        ("entertainment", conf.features.entertainment),
+        ("behaviors", conf.features.behaviors),
+        ("sensors", conf.features.sensors),
+        ("effect_emulation", conf.effect_emulation),
+        ("acme", cfg!(feature = "server-acme") && conf.acme.is_some()),
+        ("client_certificates", !conf.client_certificates.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+
+    V2Reply::ok(FeaturesReply {
+        version: env!("CARGO_PKG_VERSION"),
+        api_versions,
+        hue_apiversion: ApiShortConfig::default().apiversion,
+        features,
+    })
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_features))
+}
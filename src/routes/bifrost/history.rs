@@ -0,0 +1,13 @@
+use axum::{extract::State, routing::get, Router};
+
+use crate::hue::api::V2Reply;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+async fn get_history(State(state): State<AppState>) -> ApiV2Result {
+    V2Reply::list(state.fades.lock().await.history())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_history))
+}
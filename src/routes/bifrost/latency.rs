@@ -0,0 +1,46 @@
+use axum::{extract::State, routing::get, Router};
+use serde::Serialize;
+
+use crate::hue::api::{Light, ResourceLink, V2Reply};
+use crate::model::latency::LatencyStats;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+struct LatencyRecord<'a> {
+    #[serde(flatten)]
+    link: ResourceLink,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    mean_ms: Option<u64>,
+    #[serde(flatten)]
+    stats: &'a LatencyStats,
+}
+
+/// Latency statistics for all resources, slowest first
+#[allow(clippy::significant_drop_tightening)]
+async fn get_latency(State(state): State<AppState>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+
+    let mut records: Vec<LatencyRecord> = lock
+        .latency_stats()
+        .into_iter()
+        .map(|(link, stats)| LatencyRecord {
+            link,
+            name: lock
+                .get::<Light>(&link)
+                .ok()
+                .map(|light| light.metadata.name.as_str()),
+            mean_ms: stats.mean_ms(),
+            stats,
+        })
+        .collect();
+
+    records.sort_by_key(|rec| std::cmp::Reverse(rec.mean_ms));
+
+    V2Reply::list(records)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_latency))
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use axum::extract::{Path, State};
+use axum::routing::get;
+use axum::Router;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::hue::api::{Device, RType, ResourceLink, V2Reply};
+use crate::model::linkquality::LinkQualitySample;
+use crate::resource::Resources;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+struct LinkQualityRecord<'a> {
+    #[serde(flatten)]
+    link: ResourceLink,
+    name: &'a str,
+    min: Option<u8>,
+    max: Option<u8>,
+    mean: Option<u8>,
+    last: Option<u8>,
+    samples: &'a VecDeque<LinkQualitySample>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn record<'a>(
+    res: &'a Resources,
+    device: &Uuid,
+    samples: &'a VecDeque<LinkQualitySample>,
+) -> Option<LinkQualityRecord<'a>> {
+    let link = RType::Device.link_to(*device);
+    let dev = res.get::<Device>(&link).ok()?;
+
+    let values = || samples.iter().map(|sample| sample.linkquality);
+    let total: usize = values().map(usize::from).sum();
+    let mean = total.checked_div(samples.len()).map(|mean| mean as u8);
+
+    Some(LinkQualityRecord {
+        link,
+        name: &dev.metadata.name,
+        min: values().min(),
+        max: values().max(),
+        mean,
+        last: samples.back().map(|sample| sample.linkquality),
+        samples,
+    })
+}
+
+/// Recent link quality of all devices, weakest (on average) first
+#[allow(clippy::significant_drop_tightening)]
+async fn get_linkquality(State(state): State<AppState>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+
+    let mut records: Vec<LinkQualityRecord> = lock
+        .linkquality_history()
+        .devices()
+        .filter_map(|(device, samples)| record(&lock, device, samples))
+        .collect();
+
+    records.sort_by_key(|rec| rec.mean);
+
+    V2Reply::list(records)
+}
+
+/// Recent link quality of a single device
+#[allow(clippy::significant_drop_tightening)]
+async fn get_device_linkquality(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiV2Result {
+    let lock = state.res.lock().await;
+
+    let rec = lock
+        .linkquality_history()
+        .samples(&id)
+        .and_then(|samples| record(&lock, &id, samples))
+        .ok_or(ApiError::NotFound(id))?;
+
+    V2Reply::ok(rec)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_linkquality))
+        .route("/:id", get(get_device_linkquality))
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use axum::{
+    extract::Path,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::hue::api::V2Reply;
+use crate::logging::{self, LogOverride};
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+struct LogStatus {
+    filters: String,
+    overrides: BTreeMap<String, LogOverride>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LogLevelRequest {
+    level: LevelFilter,
+    /* number of seconds until the override expires (or forever, if missing) */
+    duration: Option<u64>,
+}
+
+async fn get_log() -> ApiV2Result {
+    V2Reply::ok(LogStatus {
+        filters: logging::filters(),
+        overrides: logging::overrides(),
+    })
+}
+
+async fn put_log_target(
+    Path(target): Path<String>,
+    Json(req): Json<LogLevelRequest>,
+) -> ApiV2Result {
+    log::info!("PUT log/{target}: {}", serde_json::to_string(&req)?);
+
+    let duration = req.duration.map(Duration::from_secs);
+
+    V2Reply::ok(logging::set_override(&target, req.level, duration))
+}
+
+async fn delete_log_target(Path(target): Path<String>) -> ApiV2Result {
+    log::info!("DELETE log/{target}");
+
+    V2Reply::ok(logging::clear_override(&target))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_log))
+        .route("/:target", put(put_log_target))
+        .route("/:target", delete(delete_log_target))
+}
@@ -0,0 +1,91 @@
+pub mod batch;
+pub mod device;
+pub mod fade;
+pub mod features;
+pub mod history;
+pub mod latency;
+pub mod linkquality;
+pub mod log;
+pub mod palette;
+pub mod rollout;
+pub mod scene;
+pub mod state;
+pub mod transition;
+pub mod whitelist;
+pub mod z2m;
+
+use axum::extract::{Request, State};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use subtle::ConstantTimeEq;
+
+use crate::error::ApiError;
+use crate::server::clientcert::ClientCertificate;
+use crate::state::AppState;
+
+/// With pinned client certificates configured, only allow requests from
+/// connections that presented one of them.
+async fn require_client_cert(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let pinned = state.client_verifier().is_some();
+    let verified = matches!(
+        request.extensions().get::<Option<ClientCertificate>>(),
+        Some(Some(ClientCertificate))
+    );
+
+    if pinned && !verified {
+        ApiError::ClientCertRequired.into_response()
+    } else {
+        next.run(request).await
+    }
+}
+
+/// Only allow requests carrying the configured admin token. Without an admin
+/// token in the config, all /bifrost routes are disabled.
+async fn require_admin(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match (&state.config().bifrost.admin_token, token) {
+        (Some(expected), Some(token))
+            if bool::from(expected.as_bytes().ct_eq(token.as_bytes())) =>
+        {
+            next.run(request).await
+        }
+        _ => ApiError::AdminDenied.into_response(),
+    }
+}
+
+pub fn router(appstate: AppState) -> Router<AppState> {
+    Router::new()
+        .nest("/batch", batch::router())
+        .nest("/device", device::router())
+        .nest("/fade", fade::router())
+        .nest("/features", features::router())
+        .nest("/history", history::router())
+        .nest("/latency", latency::router())
+        .nest("/linkquality", linkquality::router())
+        .nest("/log", log::router())
+        .nest("/palette", palette::router())
+        .nest("/rollout", rollout::router())
+        .nest("/scene", scene::router())
+        .nest("/state", state::router())
+        .nest("/transition", transition::router())
+        .nest("/whitelist", whitelist::router())
+        .nest("/z2m", z2m::router())
+        .route_layer(middleware::from_fn_with_state(
+            appstate.clone(),
+            require_admin,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            appstate,
+            require_client_cert,
+        ))
+}
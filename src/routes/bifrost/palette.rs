@@ -0,0 +1,129 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    routing::post,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{
+    ColorUpdate, DimmingUpdate, Light, On, RType, ResourceLink, SceneAction, SceneActionElement,
+    V2Reply,
+};
+use crate::model::image::Image;
+use crate::model::palette::{self, PaletteColor};
+use crate::resource::Resources;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+/// Largest number of colors in a palette (same as the hue app)
+const MAX_COLORS: usize = 5;
+
+#[derive(Debug, Deserialize)]
+struct PaletteQuery {
+    /* number of colors to extract (default: one per light, at most 5) */
+    colors: Option<usize>,
+}
+
+/// A scene palette, and scene actions for the lights of the target, ready to
+/// be used for creating a scene
+#[derive(Debug, Serialize)]
+struct PaletteReply {
+    target: ResourceLink,
+    palette: Value,
+    actions: Vec<SceneActionElement>,
+}
+
+fn palette_json(colors: &[PaletteColor]) -> Value {
+    let color: Vec<Value> = colors
+        .iter()
+        .map(|col| {
+            json!({
+                "color": ColorUpdate::new(col.xy),
+                "dimming": DimmingUpdate::new(col.brightness),
+            })
+        })
+        .collect();
+
+    json!({
+        "color": color,
+        "dimming": [],
+        "color_temperature": [],
+        "effects": [],
+    })
+}
+
+/// Assign palette colors to lights, in turn. Lights without color support
+/// only get the brightness of their color.
+fn palette_actions(
+    res: &Resources,
+    lights: &[ResourceLink],
+    colors: &[PaletteColor],
+) -> Vec<SceneActionElement> {
+    lights
+        .iter()
+        .zip(colors.iter().cycle())
+        .map(|(link, col)| {
+            let has_color = res
+                .get::<Light>(link)
+                .is_ok_and(|light| light.color.is_some());
+            SceneActionElement {
+                target: *link,
+                action: SceneAction {
+                    color: has_color.then(|| ColorUpdate::new(col.xy)),
+                    color_temperature: None,
+                    dimming: Some(DimmingUpdate::new(col.brightness)),
+                    on: Some(On::new(true)),
+                },
+            }
+        })
+        .collect()
+}
+
+fn find_target(res: &Resources, id: Uuid) -> ApiResult<ResourceLink> {
+    let rtype = res.get_resource_by_id(&id)?.obj.rtype();
+    match rtype {
+        RType::Room | RType::Zone => Ok(rtype.link_to(id)),
+        rtype => Err(ApiError::WrongType(RType::Room, rtype)),
+    }
+}
+
+/// Generate a scene palette from a (png) image, like photo scenes in the hue
+/// app. The scene itself is not created.
+async fn post_palette(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<PaletteQuery>,
+    body: Bytes,
+) -> ApiV2Result {
+    log::info!("POST palette/{id}: {} bytes", body.len());
+
+    let image = Image::from_png(&body)?;
+    log::debug!("Decoded {}x{} image", image.width, image.height);
+
+    let lock = state.res.lock().await;
+    let target = find_target(&lock, id)?;
+    let lights = lock.get_lights_for_group(&target);
+
+    let count = query.colors.unwrap_or(lights.len()).clamp(1, MAX_COLORS);
+    let colors = palette::extract(&image, count);
+    if colors.is_empty() {
+        return Err(ApiError::InvalidImage("image has no visible pixels".into()));
+    }
+
+    let reply = PaletteReply {
+        target,
+        palette: palette_json(&colors),
+        actions: palette_actions(&lock, &lights, &colors),
+    };
+    drop(lock);
+
+    V2Reply::ok(reply)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:id", post(post_palette))
+}
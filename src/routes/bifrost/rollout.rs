@@ -0,0 +1,14 @@
+use axum::{extract::State, routing::get, Router};
+
+use crate::hue::api::V2Reply;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+/// Group updates that are still being rolled out, oldest first
+async fn get_rollouts(State(state): State<AppState>) -> ApiV2Result {
+    V2Reply::list(state.res.lock().await.rollout_status())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_rollouts))
+}
@@ -0,0 +1,148 @@
+use axum::{
+    extract::{Path, State},
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{
+    DeviceArchetype, Light, RType, Resource, ResourceLink, Scene, SceneActionElement, SceneStatus,
+    V2Reply,
+};
+use crate::resource::{AuxData, Resources};
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DuplicateRequest {
+    /* name of the new scene (default: the name of the original) */
+    name: Option<String>,
+    /* room or zone to create the new scene in (default: same as original) */
+    group: Option<Uuid>,
+}
+
+fn find_group(res: &Resources, id: Uuid) -> ApiResult<ResourceLink> {
+    let rtype = res.get_resource_by_id(&id)?.obj.rtype();
+    match rtype {
+        RType::Room | RType::Zone => Ok(rtype.link_to(id)),
+        rtype => Err(ApiError::WrongType(RType::Room, rtype)),
+    }
+}
+
+fn archetype(res: &Resources, light: &ResourceLink) -> Option<DeviceArchetype> {
+    res.get::<Light>(light)
+        .ok()
+        .map(|light| light.metadata.archetype.clone())
+}
+
+/// Map scene actions onto the lights of another group. Lights get the action
+/// of a light with the same archetype (e.g., a ceiling light gets the action
+/// of a ceiling light) if there is one left, and the remaining lights get
+/// the remaining actions by position, starting over if there are more lights
+/// than actions.
+fn remap_actions(
+    res: &Resources,
+    actions: &[SceneActionElement],
+    lights: &[ResourceLink],
+) -> Vec<SceneActionElement> {
+    let sources: Vec<Option<DeviceArchetype>> = actions
+        .iter()
+        .map(|sae| archetype(res, &sae.target))
+        .collect();
+
+    let mut used = vec![false; actions.len()];
+    let mut mapped: Vec<Option<usize>> = lights
+        .iter()
+        .map(|light| {
+            let arch = archetype(res, light)?;
+            let idx = (0..actions.len())
+                .find(|idx| !used[*idx] && sources[*idx].as_ref() == Some(&arch))?;
+            used[idx] = true;
+            Some(idx)
+        })
+        .collect();
+
+    let mut rest = (0..actions.len())
+        .filter(|idx| !used[*idx])
+        .collect::<Vec<_>>();
+    if rest.is_empty() {
+        rest = (0..actions.len()).collect();
+    }
+    let mut rest = rest.into_iter().cycle();
+    for idx in &mut mapped {
+        if idx.is_none() {
+            *idx = rest.next();
+        }
+    }
+
+    lights
+        .iter()
+        .zip(mapped)
+        .filter_map(|(light, idx)| {
+            Some(SceneActionElement {
+                target: *light,
+                action: actions.get(idx?)?.action.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Create a copy of a scene, in the same group, or with its actions mapped
+/// onto the lights of another room or zone.
+///
+/// The copy is not stored in zigbee2mqtt, so it is recalled by sending its
+/// light actions.
+async fn post_duplicate(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    req: Option<Json<DuplicateRequest>>,
+) -> ApiV2Result {
+    let Json(req) = req.unwrap_or_default();
+    log::info!("POST scene/{id}/duplicate: {req:?}");
+
+    let mut lock = state.res.lock().await;
+    let orig = lock.get::<Scene>(&RType::Scene.link_to(id))?.clone();
+
+    let group = match req.group {
+        Some(group) => find_group(&lock, group)?,
+        None => orig.group,
+    };
+
+    let limit = state.config().bridge.limits.scenes_per_group;
+    if lock.get_scenes_for_room(&group.rid).len() >= limit as usize {
+        return Err(ApiError::TooManyItems(RType::Scene, limit));
+    }
+
+    let mut scene = orig.clone();
+    scene.group = group;
+    scene.status = Some(SceneStatus::Inactive);
+    if let Some(name) = req.name {
+        scene.metadata.name = name;
+    }
+    if group != orig.group {
+        let lights = lock.get_lights_for_group(&group);
+        scene.actions = remap_actions(&lock, &orig.actions, &lights);
+    }
+
+    let link_scene = RType::Scene.link_to(Uuid::new_v4());
+
+    log::info!(
+        "New scene: {link_scene:?} ({}), copied from {id}",
+        scene.metadata.name
+    );
+
+    lock.transaction(|res| {
+        res.aux_set(&link_scene, AuxData::new().with_topic(&scene.metadata.name));
+        res.add(&link_scene, Resource::Scene(scene))
+    })?;
+    drop(lock);
+
+    V2Reply::ok(link_scene)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:id/duplicate", post(post_duplicate))
+}
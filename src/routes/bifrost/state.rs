@@ -0,0 +1,20 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+
+use crate::error::ApiResult;
+use crate::state::AppState;
+
+/// The complete bridge state, in the same format as the state file (used by
+/// standby instances to replicate the state of the primary)
+async fn get_state(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    let data = state.res.lock().await.serialize()?;
+
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], data))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_state))
+}
@@ -0,0 +1,81 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{RType, ResourceLink, V2Reply};
+use crate::resource::Resources;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TransitionRequest {
+    /* default transition time, in seconds */
+    transition: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct TransitionStatus {
+    target: ResourceLink,
+    transition: Option<f64>,
+}
+
+/// Default transitions can be set for lights, rooms and zones
+fn transition_target(res: &Resources, id: Uuid) -> ApiResult<ResourceLink> {
+    let link = res.get_resource_by_id(&id)?.obj.rtype().link_to(id);
+
+    match link.rtype {
+        RType::Light | RType::Room | RType::Zone => Ok(link),
+        rtype => Err(ApiError::WrongType(RType::Light, rtype)),
+    }
+}
+
+async fn get_transition(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    let target = transition_target(&lock, id)?;
+    let transition = lock.default_transition(&target);
+    drop(lock);
+
+    V2Reply::ok(TransitionStatus { target, transition })
+}
+
+async fn put_transition(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<TransitionRequest>,
+) -> ApiV2Result {
+    log::info!("PUT transition/{id}: {}", serde_json::to_string(&req)?);
+
+    let mut lock = state.res.lock().await;
+    let target = transition_target(&lock, id)?;
+    lock.set_transition_override(&target, Some(req.transition));
+    drop(lock);
+
+    V2Reply::ok(TransitionStatus {
+        target,
+        transition: Some(req.transition),
+    })
+}
+
+async fn delete_transition(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiV2Result {
+    log::info!("DELETE transition/{id}");
+
+    let mut lock = state.res.lock().await;
+    let target = transition_target(&lock, id)?;
+    lock.set_transition_override(&target, None);
+    let transition = lock.default_transition(&target);
+    drop(lock);
+
+    V2Reply::ok(TransitionStatus { target, transition })
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/:id", get(get_transition))
+        .route("/:id", put(put_transition))
+        .route("/:id", delete(delete_transition))
+}
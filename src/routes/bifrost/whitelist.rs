@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::hue::api::V2Reply;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+struct WhitelistRecord<'a> {
+    username: String,
+    name: &'a str,
+    create_date: DateTime<Utc>,
+    last_use_date: DateTime<Utc>,
+}
+
+/// Applications paired with the bridge (client keys are not included)
+#[allow(clippy::significant_drop_tightening)]
+async fn get_whitelist(State(state): State<AppState>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+
+    let records: Vec<WhitelistRecord> = lock
+        .whitelist()
+        .iter()
+        .map(|(username, user)| WhitelistRecord {
+            username: username.simple().to_string(),
+            name: &user.name,
+            create_date: user.create_date,
+            last_use_date: user.last_use_date,
+        })
+        .collect();
+
+    V2Reply::list(records)
+}
+
+/// Unpair an application. Its key is treated like any unknown key afterwards.
+async fn delete_user(State(state): State<AppState>, Path(username): Path<Uuid>) -> ApiV2Result {
+    log::info!("DELETE whitelist/{}", username.simple());
+
+    let user = state.res.lock().await.whitelist_remove(&username)?;
+
+    V2Reply::ok(json!({"username": username.simple().to_string(), "name": user.name}))
+}
+
+/// Press the link button, allowing new applications to pair for
+/// `link_button.press_duration` seconds
+async fn post_link_button(State(state): State<AppState>) -> ApiV2Result {
+    log::info!("POST whitelist/linkbutton");
+
+    let secs = state.config().bifrost.link_button.press_duration;
+    let duration = Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX));
+    state.res.lock().await.link_button_press(duration);
+
+    V2Reply::ok(json!({"pressed_until": Utc::now() + duration}))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_whitelist))
+        .route("/linkbutton", post(post_link_button))
+        .route("/:username", delete(delete_user))
+}
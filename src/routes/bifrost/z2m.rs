@@ -0,0 +1,315 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Z2mServer;
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{
+    Device, RType, ResourceLink, V2Reply, ZigbeeConnectivity, ZigbeeConnectivityStatus,
+};
+use crate::model::networkmap::NetworkMapType;
+use crate::model::pairing::PairingProgress;
+use crate::resource::Resources;
+use crate::routes::clip::ApiV2Result;
+use crate::state::AppState;
+use crate::z2m::api::IeeeAddress;
+use crate::z2m::installcode;
+use crate::z2m::request::ClientRequest;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PermitJoinRequest {
+    /* number of seconds to permit joining (or 0, to stop permitting joins) */
+    time: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NetworkMapRequest {
+    #[serde(rename = "type")]
+    map_type: NetworkMapType,
+    /* include routing tables (slower, and not supported by all devices) */
+    #[serde(default)]
+    routes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallCodeRequest {
+    /* install code, or the payload of the qr code on the device */
+    code: String,
+    /* device address, needed for plain install codes */
+    #[serde(default)]
+    ieee_address: Option<IeeeAddress>,
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkInfo {
+    server: String,
+    coordinator: ResourceLink,
+    software_version: String,
+    mac_address: String,
+    channel: Option<Value>,
+    extended_pan_id: String,
+    status: ZigbeeConnectivityStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct ServerRecord {
+    name: String,
+    /* "config" for servers from the config file, "runtime" for servers added
+     * or changed through this api */
+    source: &'static str,
+    #[serde(flatten)]
+    server: Z2mServer,
+}
+
+#[derive(Debug, Serialize)]
+struct PairingRecord<'a> {
+    #[serde(flatten)]
+    progress: &'a PairingProgress,
+    in_progress: bool,
+}
+
+/// Find the coordinator device of the named z2m server. Coordinators are
+/// recorded with their server and ieee address when z2m reports them, and
+/// the device id is derived from the address.
+fn find_coordinator(res: &Resources, server: &str) -> ApiResult<ResourceLink> {
+    res.get_resources_by_type(RType::Device)
+        .into_iter()
+        .map(|rr| (RType::Device.link_to(rr.id), rr.obj))
+        .find(|(link, obj)| {
+            let Ok(aux) = res.aux_get(link) else {
+                return false;
+            };
+            let ieee = aux.topic.as_deref().and_then(IeeeAddress::from_topic);
+            <&Device>::try_from(obj).is_ok_and(|dev| dev.product_data.is_coordinator())
+                && aux.server.as_deref() == Some(server)
+                && ieee.is_some_and(|ieee| RType::Device.deterministic(&ieee) == *link)
+        })
+        .map(|(link, _)| link)
+        .ok_or_else(|| ApiError::Z2mServerNotFound(server.to_string()))
+}
+
+async fn get_network(State(state): State<AppState>, Path(server): Path<String>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    let link = find_coordinator(&lock, &server)?;
+    let dev = lock.get::<Device>(&link)?;
+
+    let zbc = dev
+        .services
+        .iter()
+        .find(|svc| svc.rtype == RType::ZigbeeConnectivity)
+        .ok_or(ApiError::NotFound(link.rid))?;
+    let zbc = lock.get::<ZigbeeConnectivity>(zbc)?;
+
+    let info = NetworkInfo {
+        server,
+        coordinator: link,
+        software_version: dev.product_data.software_version.clone(),
+        mac_address: zbc.mac_address.clone(),
+        channel: zbc.channel.clone(),
+        extended_pan_id: zbc.extended_pan_id.clone(),
+        status: zbc.status,
+    };
+    drop(lock);
+
+    V2Reply::ok(info)
+}
+
+async fn put_permit_join(
+    State(state): State<AppState>,
+    Path(server): Path<String>,
+    Json(req): Json<PermitJoinRequest>,
+) -> ApiV2Result {
+    log::info!(
+        "PUT z2m/{server}/permit_join: {}",
+        serde_json::to_string(&req)?
+    );
+
+    let lock = state.res.lock().await;
+    let link = find_coordinator(&lock, &server)?;
+    lock.z2m_request(ClientRequest::permit_join_on(link, req.time))?;
+    drop(lock);
+
+    V2Reply::ok(req)
+}
+
+/// Ask z2m to scan the network. Scanning can take minutes on larger networks,
+/// so the result is fetched separately, once available.
+async fn post_network_map(
+    State(state): State<AppState>,
+    Path(server): Path<String>,
+    Json(req): Json<NetworkMapRequest>,
+) -> ApiV2Result {
+    log::info!(
+        "POST z2m/{server}/networkmap: {}",
+        serde_json::to_string(&req)?
+    );
+
+    let lock = state.res.lock().await;
+    let link = find_coordinator(&lock, &server)?;
+    lock.z2m_request(ClientRequest::network_map(link, req.map_type, req.routes))?;
+    drop(lock);
+
+    V2Reply::ok(req)
+}
+
+/// The latest network map of the given type
+async fn get_network_map(
+    State(state): State<AppState>,
+    Path((server, map_type)): Path<(String, NetworkMapType)>,
+) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    let map = lock
+        .network_map(&server, map_type)
+        .cloned()
+        .ok_or(ApiError::NetworkMapNotFound(server, map_type))?;
+    drop(lock);
+
+    V2Reply::ok(map)
+}
+
+/// Progress of devices recently paired through this server, most recent
+/// first. Updates are also sent on the event stream, as updates of the
+/// `zigbee_device_discovery` service.
+#[allow(clippy::significant_drop_tightening)]
+async fn get_pairing(State(state): State<AppState>, Path(server): Path<String>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    find_coordinator(&lock, &server)?;
+
+    let records: Vec<PairingRecord> = lock
+        .pairings()
+        .for_server(&server)
+        .into_iter()
+        .map(|progress| PairingRecord {
+            progress,
+            in_progress: progress.in_progress(),
+        })
+        .collect();
+
+    V2Reply::list(records)
+}
+
+/// Features exposed by the devices on this server that are not mapped to
+/// any hue resource, the most common first
+#[allow(clippy::significant_drop_tightening)]
+async fn get_unmapped(State(state): State<AppState>, Path(server): Path<String>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    find_coordinator(&lock, &server)?;
+
+    V2Reply::list(lock.unmapped_exposes(&server).unwrap_or_default().to_vec())
+}
+
+/// Submit an install code to z2m, allowing the device to join securely the
+/// next time joining is permitted.
+async fn post_install_code(
+    State(state): State<AppState>,
+    Path(server): Path<String>,
+    Json(req): Json<InstallCodeRequest>,
+) -> ApiV2Result {
+    log::info!("POST z2m/{server}/install_code");
+
+    let code = installcode::normalize(&req.code, req.ieee_address.as_ref())?;
+
+    let lock = state.res.lock().await;
+    let link = find_coordinator(&lock, &server)?;
+    lock.z2m_request(ClientRequest::install_code_on(link, code.clone()))?;
+    drop(lock);
+
+    V2Reply::ok(json!({"code": code}))
+}
+
+/// The z2m servers currently in use, including those added at runtime
+async fn get_servers(State(state): State<AppState>) -> ApiV2Result {
+    let lock = state.res.lock().await;
+    let servers = lock.z2m_servers();
+    let records: Vec<ServerRecord> = servers
+        .effective(&state.config().z2m)
+        .into_iter()
+        .map(|(name, server)| ServerRecord {
+            source: if servers.is_runtime(&name) {
+                "runtime"
+            } else {
+                "config"
+            },
+            name,
+            server,
+        })
+        .collect();
+    drop(lock);
+
+    V2Reply::list(records)
+}
+
+/// Add a z2m server, or replace an existing one. The change is saved in the
+/// state file, and takes precedence over the config file.
+async fn put_server(
+    State(state): State<AppState>,
+    Path(server): Path<String>,
+    Json(req): Json<Z2mServer>,
+) -> ApiV2Result {
+    log::info!("PUT z2m/{server}: {}", req.url);
+
+    if !(req.url.starts_with("ws://") || req.url.starts_with("wss://")) {
+        return Err(ApiError::InvalidZ2mServer(format!(
+            "url must start with ws:// or wss://, not {:?}",
+            req.url
+        )));
+    }
+
+    state.res.lock().await.z2m_server_set(&server, req.clone());
+
+    V2Reply::ok(ServerRecord {
+        name: server,
+        source: "runtime",
+        server: req,
+    })
+}
+
+/// Remove a z2m server, and stop its client. Devices from the server are
+/// kept, so rooms and scenes survive adding the server again.
+async fn delete_server(State(state): State<AppState>, Path(server): Path<String>) -> ApiV2Result {
+    log::info!("DELETE z2m/{server}");
+
+    let config = state.config();
+    let mut lock = state.res.lock().await;
+    if !lock
+        .z2m_servers()
+        .effective(&config.z2m)
+        .contains_key(&server)
+    {
+        return Err(ApiError::Z2mServerNotFound(server));
+    }
+    lock.z2m_server_remove(&server, &config.z2m);
+    drop(lock);
+
+    V2Reply::ok(json!({"name": server}))
+}
+
+async fn post_restart(State(state): State<AppState>, Path(server): Path<String>) -> ApiV2Result {
+    log::info!("POST z2m/{server}/restart");
+
+    let lock = state.res.lock().await;
+    let link = find_coordinator(&lock, &server)?;
+    lock.z2m_request(ClientRequest::bridge_restart(link))?;
+    drop(lock);
+
+    V2Reply::ok(link)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_servers))
+        .route("/:server", put(put_server))
+        .route("/:server", delete(delete_server))
+        .route("/:server/install_code", post(post_install_code))
+        .route("/:server/network", get(get_network))
+        .route("/:server/networkmap", post(post_network_map))
+        .route("/:server/networkmap/:type", get(get_network_map))
+        .route("/:server/pairing", get(get_pairing))
+        .route("/:server/permit_join", put(put_permit_join))
+        .route("/:server/unmapped", get(get_unmapped))
+        .route("/:server/restart", post(post_restart))
+}
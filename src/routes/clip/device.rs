@@ -0,0 +1,42 @@
+use crate::error::ApiResult;
+use crate::hue::api::{
+    Device, DeviceIdentifyAction, DeviceRestartAction, DeviceUpdate, ResourceLink,
+};
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
+use crate::z2m::request::ClientRequest;
+use crate::z2m::update::{self, DeviceEffect};
+
+impl UpdateHandler for Device {
+    type Update = DeviceUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: DeviceUpdate) -> ApiResult<()> {
+        let dev = res.get::<Self>(rlink)?;
+
+        if let Some(identify) = upd.identify {
+            if identify.action == DeviceIdentifyAction::Identify {
+                /* Only lights can visibly identify themselves */
+                if let Some(light) = dev.light_service() {
+                    let payload =
+                        update::DeviceUpdate::default().with_effect(Some(DeviceEffect::Breathe));
+
+                    res.z2m_request(ClientRequest::light_update(*light, payload))?;
+                } else {
+                    log::warn!("Device {} has no light, cannot identify", rlink.rid);
+                }
+            }
+        }
+
+        if let Some(restart) = upd.restart {
+            if restart.action == DeviceRestartAction::Restart {
+                if dev.product_data.is_coordinator() {
+                    res.z2m_request(ClientRequest::bridge_restart(*rlink))?;
+                } else {
+                    log::warn!("Device {} is not a coordinator, cannot restart", rlink.rid);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
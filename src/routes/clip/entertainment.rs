@@ -0,0 +1,27 @@
+use crate::error::ApiResult;
+use crate::hue::api::{
+    EntertainmentConfiguration, EntertainmentConfigurationAction, EntertainmentConfigurationUpdate,
+    ResourceLink,
+};
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
+
+impl UpdateHandler for EntertainmentConfiguration {
+    type Update = EntertainmentConfigurationUpdate;
+
+    fn put(
+        res: &mut Resources,
+        rlink: &ResourceLink,
+        upd: EntertainmentConfigurationUpdate,
+    ) -> ApiResult<()> {
+        if let Some(md) = upd.metadata {
+            res.update(&rlink.rid, |ec: &mut Self| ec.metadata = md)?;
+        }
+
+        match upd.action {
+            Some(EntertainmentConfigurationAction::Start) => res.entertainment_start(rlink),
+            Some(EntertainmentConfigurationAction::Stop) => res.entertainment_stop(rlink),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,69 +1,190 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::HeaderMap,
     response::IntoResponse,
     routing::{delete, get, post, put},
     Json, Router,
 };
+use serde::Deserialize;
 use serde_json::Value;
 use uuid::Uuid;
 
 use crate::error::ApiError;
-use crate::hue::api::{RType, Resource, ResourceLink, V2Reply};
-use crate::routes::clip::ApiV2Result;
+use crate::hue::api::{GroupedLight, RType, Resource, ResourceLink, V2Reply};
+use crate::resource::Snapshot;
+use crate::routes::clip::preferences::extract;
+use crate::routes::clip::validate::validate_post;
+use crate::routes::clip::{application_id, put_resource, ApiV2Result, PRIVATE_RTYPES};
 use crate::state::AppState;
 
-async fn get_root(State(state): State<AppState>) -> impl IntoResponse {
-    V2Reply::list(state.res.lock().await.get_resources())
+async fn get_root(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let app_id = application_id(&headers);
+    let snapshots = state.res.lock().await.snapshots(None, app_id);
+    let data: Vec<Value> = snapshots
+        .iter()
+        .map(Snapshot::to_json)
+        .collect::<Result<_, _>>()?;
+
+    V2Reply::list(data)
 }
 
-async fn get_resource(State(state): State<AppState>, Path(rtype): Path<RType>) -> ApiV2Result {
-    V2Reply::list(state.res.lock().await.get_resources_by_type(rtype))
+async fn get_resource(
+    State(state): State<AppState>,
+    Path(rtype): Path<RType>,
+    headers: HeaderMap,
+) -> ApiV2Result {
+    let app_id = application_id(&headers);
+    let snapshots = state.res.lock().await.snapshots(Some(rtype), app_id);
+    let data: Vec<Value> = snapshots
+        .iter()
+        .map(Snapshot::to_json)
+        .collect::<Result<_, _>>()?;
+
+    V2Reply::list(data)
 }
 
 async fn post_resource(
     State(state): State<AppState>,
     Path(rtype): Path<RType>,
+    headers: HeaderMap,
     Json(req): Json<Value>,
 ) -> impl IntoResponse {
     log::info!("POST: {rtype:?} {}", serde_json::to_string(&req)?);
 
-    let obj = Resource::from_value(rtype, req)?;
-
     let mut lock = state.res.lock().await;
 
+    /* behavior instances are the v2 equivalent of rules */
+    if rtype == RType::BehaviorInstance {
+        let limit = state.config().bridge.limits.rules;
+        if lock.get_resources_by_type(rtype).len() >= limit as usize {
+            return Err(ApiError::TooManyItems(rtype, limit));
+        }
+    }
+
+    let mut obj = validate_post(&lock, rtype, req.clone())?;
+
+    /* configurations without channels get one channel per light */
+    if let Resource::EntertainmentConfiguration(ec) = &obj {
+        let limit = state.config().bridge.limits.entertainment_channels;
+        let channels = if ec.channels.is_empty() {
+            ec.light_services.len()
+        } else {
+            ec.channels.len()
+        };
+        if channels > limit as usize {
+            return Err(ApiError::TooManyItems(rtype, limit));
+        }
+    }
+
+    /* some fields not understood by the bridge are kept as client preferences */
+    let prefs = extract(rtype, &req, &[serde_json::to_value(&obj)?]);
+
     let rlink = ResourceLink::new(Uuid::new_v4(), obj.rtype());
-    lock.add(&rlink, obj)?;
+
+    /* zones are controlled through a grouped light, just like rooms */
+    let mut glight = None;
+    if let Resource::Zone(zone) = &mut obj {
+        if zone.services.is_empty() {
+            let link_glight = RType::GroupedLight.deterministic(rlink.rid);
+            zone.services.push(link_glight);
+            glight = Some(link_glight);
+        }
+    }
+
+    /* never leave a zone without its grouped light behind */
+    lock.transaction(|res| {
+        if let Some(app_id) = application_id(&headers) {
+            if PRIVATE_RTYPES.contains(&rtype) {
+                res.set_app_owner(&rlink, app_id);
+            }
+        }
+
+        res.add(&rlink, obj)?;
+        res.set_preferences(&rlink, prefs);
+        if let Some(link_glight) = glight {
+            res.add(
+                &link_glight,
+                Resource::GroupedLight(GroupedLight::new(rlink)),
+            )?;
+        }
+        Ok(())
+    })?;
     drop(lock);
 
     V2Reply::ok(rlink)
 }
 
+#[derive(Debug, Deserialize)]
+struct GetQuery {
+    /* "services": also return the services of the resource (e.g., of a device) */
+    expand: Option<String>,
+}
+
 async fn get_resource_id(
     State(state): State<AppState>,
     Path((rtype, id)): Path<(RType, Uuid)>,
+    Query(query): Query<GetQuery>,
+    headers: HeaderMap,
 ) -> ApiV2Result {
-    V2Reply::ok(state.res.lock().await.get_resource(rtype, &id)?)
+    let app_id = application_id(&headers);
+    let lock = state.res.lock().await;
+    let snapshot = lock.snapshot(rtype, &id)?;
+    if !lock.visible_to(&id, app_id) {
+        return Err(ApiError::NotFound(id));
+    }
+    drop(lock);
+    let obj = snapshot.to_json()?;
+
+    if query.expand.as_deref() != Some("services") {
+        return V2Reply::ok(obj);
+    }
+
+    /* the resource itself, followed by its services, saving clients a
+     * request for each service */
+    let services: Vec<ResourceLink> = obj
+        .get("services")
+        .map(|svcs| serde_json::from_value(svcs.clone()))
+        .transpose()?
+        .unwrap_or_default();
+
+    let lock = state.res.lock().await;
+    let snapshots: Vec<Snapshot> = services
+        .iter()
+        .filter(|svc| lock.visible_to(&svc.rid, app_id))
+        .filter_map(|svc| lock.snapshot(svc.rtype, &svc.rid).ok())
+        .collect();
+    drop(lock);
+
+    let mut data = vec![obj];
+    for snapshot in &snapshots {
+        data.push(snapshot.to_json()?);
+    }
+
+    V2Reply::list(data)
 }
 
 async fn put_resource_id(
+    State(state): State<AppState>,
     Path((rtype, id)): Path<(RType, Uuid)>,
+    headers: HeaderMap,
     Json(put): Json<Value>,
 ) -> ApiV2Result {
-    log::info!("PUT {rtype:?}/{id}");
-    log::debug!("json data\n{}", serde_json::to_string_pretty(&put)?);
-
-    log::warn!("PUT {rtype:?}/{id}: state update not supported");
-
-    Err(ApiError::UpdateUnsupported(rtype))
+    put_resource(&state, rtype.link_to(id), application_id(&headers), put).await
 }
 
 async fn delete_resource_id(
     State(state): State<AppState>,
     Path((rtype, id)): Path<(RType, Uuid)>,
+    headers: HeaderMap,
 ) -> ApiV2Result {
     log::info!("DELETE {rtype:?}/{id}");
 
-    state.res.lock().await.get_resource(rtype, &id)?;
+    let lock = state.res.lock().await;
+    lock.get_resource(rtype, &id)?;
+    if !lock.visible_to(&id, application_id(&headers)) {
+        return Err(ApiError::NotFound(id));
+    }
+    drop(lock);
 
     Err(ApiError::DeleteDenied(id))?
 }
@@ -1,46 +1,94 @@
-use axum::{
-    extract::{Path, State},
-    routing::put,
-    Json, Router,
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{
+    GroupedLight, GroupedLightUpdate, Light, LightDynamicsUpdate, LightUpdate, Resource,
+    ResourceLink,
 };
-use serde_json::Value;
-use uuid::Uuid;
-
-use crate::hue::api::{GroupedLight, GroupedLightUpdate, RType, V2Reply};
-use crate::routes::clip::ApiV2Result;
-use crate::state::AppState;
+use crate::model::units;
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
 use crate::z2m::request::ClientRequest;
 use crate::z2m::update::DeviceUpdate;
 
-async fn put_grouped_light(
-    State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-    Json(put): Json<Value>,
-) -> ApiV2Result {
-    log::info!("PUT grouped_light/{id}");
-    log::debug!("json data\n{}", serde_json::to_string_pretty(&put)?);
+impl UpdateHandler for GroupedLight {
+    type Update = GroupedLightUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: GroupedLightUpdate) -> ApiResult<()> {
+        let owner = res.get::<Self>(rlink)?.owner;
+
+        /* group commands would also reach lights used for entertainment */
+        let lights = res.get_lights_for_group(&owner);
+        if let Some(light) = lights.iter().find(|light| res.is_streaming(light)) {
+            return Err(ApiError::LightStreaming(light.rid));
+        }
 
-    let rlink = RType::GroupedLight.link_to(id);
-    let lock = state.res.lock().await;
-    lock.get::<GroupedLight>(&rlink)?;
+        /* missing group members do not prevent updating the rest of the group */
+        let errors = match res.get_resource_by_id(&owner.rid)?.obj {
+            Resource::Room(room) => missing_children(res, &room.children),
+            Resource::Zone(zone) => missing_children(res, &zone.children),
+            _ => vec![],
+        };
 
-    log::info!("PUT grouped_light/{id}: updating");
+        let lupd = LightUpdate {
+            on: upd.on,
+            dimming: upd.dimming.clone(),
+            color: upd.color.clone(),
+            color_temperature: upd.color_temperature.clone(),
+            dynamics: upd.dynamics.clone(),
+            effects: None,
+            mode: None,
+        };
+        for light in &lights {
+            res.update_light_state(&light.rid, |state| state.apply_light_update(&lupd));
+            if lupd.changes_state() {
+                res.deactivate_scenes(light, None)?;
+            }
+            if upd.on.is_some_and(|on| !on.on) {
+                res.effect_stop(light)?;
+            }
+        }
 
-    let upd: GroupedLightUpdate = serde_json::from_value(put)?;
+        let transition = upd
+            .dynamics
+            .as_ref()
+            .and_then(LightDynamicsUpdate::transition)
+            .or_else(|| res.default_transition(rlink));
 
-    let payload = DeviceUpdate::default()
-        .with_state(upd.on.map(|on| on.on))
-        .with_brightness(upd.dimming.map(|dim| dim.brightness / 100.0 * 254.0))
-        .with_color_temp(upd.color_temperature.map(|ct| ct.mirek))
-        .with_color_xy(upd.color.map(|col| col.xy));
+        let payload = DeviceUpdate::default()
+            .with_state(upd.on.map(|on| on.on))
+            .with_brightness(
+                upd.dimming
+                    .map(|dim| units::percent_to_device(dim.brightness)),
+            )
+            .with_color_temp(upd.color_temperature.map(|ct| ct.mirek))
+            .with_color_xy(upd.color.map(|col| col.xy))
+            .with_effect(upd.alert.map(|alert| alert.action.into()))
+            .with_transition(transition);
 
-    lock.z2m_request(ClientRequest::group_update(rlink, payload))?;
+        let updated = lights
+            .iter()
+            .filter(|light| res.get::<Light>(light).is_ok())
+            .copied()
+            .collect();
 
-    drop(lock);
+        /* large zones are updated a slice at a time, instead of all at once */
+        if res.needs_rollout(&owner, &lights) {
+            res.rollout_start(*rlink, lights, payload);
+        } else {
+            res.z2m_request(ClientRequest::group_update(*rlink, payload))?;
+        }
 
-    V2Reply::ok(rlink)
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::PartialFailure(updated, errors))
+        }
+    }
 }
 
-pub fn router() -> Router<AppState> {
-    Router::new().route("/:id", put(put_grouped_light))
+fn missing_children(res: &Resources, children: &[ResourceLink]) -> Vec<String> {
+    children
+        .iter()
+        .filter(|child| res.get_resource_by_id(&child.rid).is_err())
+        .map(|child| format!("{:?} {} not found", child.rtype, child.rid))
+        .collect()
 }
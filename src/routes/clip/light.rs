@@ -1,45 +1,60 @@
-use axum::{
-    extract::{Path, State},
-    routing::put,
-    Json, Router,
-};
-use serde_json::Value;
-use uuid::Uuid;
-
-use crate::hue::api::{Light, LightUpdate, RType, V2Reply};
-use crate::routes::clip::ApiV2Result;
-use crate::state::AppState;
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{Light, LightDynamicsUpdate, LightEffect, LightUpdate, ResourceLink};
+use crate::model::units;
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
 use crate::z2m::request::ClientRequest;
 use crate::z2m::update::DeviceUpdate;
 
-async fn put_light(
-    State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-    Json(put): Json<Value>,
-) -> ApiV2Result {
-    log::info!("PUT light/{id}");
-    log::debug!("json data\n{}", serde_json::to_string_pretty(&put)?);
-
-    let rlink = RType::Light.link_to(id);
-    let lock = state.res.lock().await;
-
-    let _ = lock.get::<Light>(&rlink)?;
-
-    let upd: LightUpdate = serde_json::from_value(put)?;
-
-    let payload = DeviceUpdate::default()
-        .with_state(upd.on.map(|on| on.on))
-        .with_brightness(upd.dimming.map(|dim| dim.brightness / 100.0 * 254.0))
-        .with_color_temp(upd.color_temperature.map(|ct| ct.mirek))
-        .with_color_xy(upd.color.map(|col| col.xy));
-
-    lock.z2m_request(ClientRequest::light_update(rlink, payload))?;
-
-    drop(lock);
-
-    V2Reply::ok(rlink)
-}
-
-pub fn router() -> Router<AppState> {
-    Router::new().route("/:id", put(put_light))
+impl UpdateHandler for Light {
+    type Update = LightUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: LightUpdate) -> ApiResult<()> {
+        let light = res.get::<Self>(rlink)?;
+        let schema = light.color_temperature.as_ref().map(|ct| ct.mirek_schema);
+
+        /* the entertainment session has exclusive control of the light */
+        if res.is_streaming(rlink) {
+            return Err(ApiError::LightStreaming(rlink.rid));
+        }
+
+        res.update_light_state(&rlink.rid, |state| state.apply_light_update(&upd));
+
+        /* changing the light outside of a scene ends the scene */
+        if upd.changes_state() {
+            res.deactivate_scenes(rlink, None)?;
+        }
+
+        /* emulated effects end when the light is turned off */
+        let brightness = upd.dimming.map(|dim| dim.brightness);
+        let mut on = upd.on.map(|on| on.on);
+        if on == Some(false) {
+            res.effect_stop(rlink)?;
+        } else if let Some(fx) = upd.effects {
+            res.effect_start(rlink, fx.effect, brightness)?;
+            if fx.effect != LightEffect::NoEffect {
+                on = Some(true);
+            }
+        } else if let Some(brightness) = brightness {
+            res.effect_brightness(rlink, brightness);
+        }
+
+        let transition = upd
+            .dynamics
+            .as_ref()
+            .and_then(LightDynamicsUpdate::transition)
+            .or_else(|| res.default_transition(rlink));
+
+        let payload =
+            DeviceUpdate::default()
+                .with_state(on)
+                .with_brightness(brightness.map(units::percent_to_device))
+                .with_color_temp(upd.color_temperature.map(|ct| {
+                    schema.map_or(ct.mirek, |schema| units::clamp_mirek(ct.mirek, &schema))
+                }))
+                .with_color_xy(upd.color.map(|col| col.xy))
+                .with_transition(transition);
+
+        res.z2m_request(ClientRequest::light_update(*rlink, payload))
+    }
 }
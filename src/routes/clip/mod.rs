@@ -1,31 +1,165 @@
+pub mod device;
+pub mod entertainment;
 pub mod generic;
 pub mod grouped_light;
 pub mod light;
+pub mod preferences;
+pub mod room;
 pub mod scene;
+pub mod sensor;
+pub mod validate;
 
+use axum::http::HeaderMap;
 use axum::response::{IntoResponse, Response};
 use axum::{Json, Router};
 use hyper::StatusCode;
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::config::AppConfig;
 use crate::error::{ApiError, ApiResult};
-use crate::hue::api::V2Reply;
+use crate::hue::api::{
+    Device, EntertainmentConfiguration, GroupedLight, Light, LightLevel, Motion, RType,
+    ResourceLink, Room, Scene, Temperature, V2Reply, Zone,
+};
+use crate::resource::Resources;
 use crate::state::AppState;
 
-type ApiV2Result = ApiResult<Json<V2Reply<Value>>>;
+pub(crate) type ApiV2Result = ApiResult<Json<V2Reply<Value>>>;
+
+/// Header identifying the application making a request
+pub const HUE_APPLICATION_ID: &str = "hue-application-id";
+
+/// Resource types that are private to the application that created them
+pub const PRIVATE_RTYPES: &[RType] = &[RType::BehaviorInstance, RType::GeofenceClient];
+
+/// The application id of a request, if the client sent one
+#[must_use]
+pub fn application_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(HUE_APPLICATION_ID)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Implemented by resource types that support PUT requests
+pub trait UpdateHandler {
+    /// The (partial) update accepted by PUT requests
+    type Update: DeserializeOwned + Serialize;
+
+    /// Apply `upd` to the resource at `rlink`.
+    ///
+    /// Updates affecting multiple targets should apply as much as possible,
+    /// and report the targets that were updated, and the errors of those that
+    /// could not be (e.g., lights that have since been removed), with
+    /// [`ApiError::PartialFailure`]. A target counts as updated once its
+    /// command is queued for z2m; failing to queue the command fails the
+    /// whole update, and devices that do not respond are only logged.
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: Self::Update) -> ApiResult<()>;
+}
+
+fn put_update<H: UpdateHandler>(
+    res: &mut Resources,
+    rlink: &ResourceLink,
+    put: &Value,
+) -> ApiResult<()> {
+    let upd = H::Update::deserialize(put)?;
+
+    /* keep the client customizations we do not understand ourselves */
+    let known = [
+        serde_json::to_value(res.get_resource_by_id(&rlink.rid)?)?,
+        serde_json::to_value(&upd)?,
+    ];
+    let prefs = preferences::extract(rlink.rtype, put, &known);
+
+    let result = H::put(res, rlink, upd);
+    if matches!(result, Ok(()) | Err(ApiError::PartialFailure(..))) {
+        res.set_preferences(rlink, prefs);
+    }
+    result
+}
+
+/// Apply a PUT request to a resource, with the resource lock held
+pub(crate) fn apply_put(
+    res: &mut Resources,
+    config: &AppConfig,
+    rlink: &ResourceLink,
+    put: &Value,
+) -> ApiResult<()> {
+    if rlink.rtype == RType::EntertainmentConfiguration && !config.bifrost.features.entertainment {
+        return Err(ApiError::FeatureDisabled("entertainment"));
+    }
+
+    match rlink.rtype {
+        RType::Device => put_update::<Device>(res, rlink, put),
+        RType::EntertainmentConfiguration => {
+            put_update::<EntertainmentConfiguration>(res, rlink, put)
+        }
+        RType::GroupedLight => put_update::<GroupedLight>(res, rlink, put),
+        RType::Light => put_update::<Light>(res, rlink, put),
+        RType::LightLevel => put_update::<LightLevel>(res, rlink, put),
+        RType::Motion => put_update::<Motion>(res, rlink, put),
+        RType::Room => put_update::<Room>(res, rlink, put),
+        RType::Scene => put_update::<Scene>(res, rlink, put),
+        RType::Temperature => put_update::<Temperature>(res, rlink, put),
+        RType::Zone => put_update::<Zone>(res, rlink, put),
+        rtype => {
+            log::warn!("PUT {rtype:?}/{}: state update not supported", rlink.rid);
+            Err(ApiError::UpdateUnsupported(rtype))
+        }
+    }
+}
+
+pub(crate) async fn put_resource(
+    state: &AppState,
+    rlink: ResourceLink,
+    app_id: Option<&str>,
+    put: Value,
+) -> ApiV2Result {
+    log::info!("PUT {:?}/{}", rlink.rtype, rlink.rid);
+    log::debug!("json data\n{}", serde_json::to_string_pretty(&put)?);
+
+    let mut lock = state.res.lock().await;
+    /* private resources of other applications are not ours to change */
+    if !lock.visible_to(&rlink.rid, app_id) {
+        return Err(ApiError::NotFound(rlink.rid));
+    }
+    let result = apply_put(&mut lock, &state.config(), &rlink, &put);
+    drop(lock);
+
+    match result {
+        Ok(()) => V2Reply::ok(rlink),
+        Err(ApiError::PartialFailure(updated, errors)) => {
+            for err in &errors {
+                log::warn!("PUT {:?}/{}: {err}", rlink.rtype, rlink.rid);
+            }
+            V2Reply::partial(rlink, &updated, errors)
+        }
+        Err(err) => Err(err),
+    }
+}
 
 impl<T: Serialize> V2Reply<T> {
     #[allow(clippy::unnecessary_wraps)]
-    fn ok(obj: T) -> ApiV2Result {
+    pub(crate) fn ok(obj: T) -> ApiV2Result {
         Ok(Json(V2Reply {
             data: vec![serde_json::to_value(obj)?],
             errors: vec![],
         }))
     }
 
+    /// Reply for a request that succeeded for the `updated` targets of
+    /// `obj`, but failed with the listed errors for the rest
+    pub(crate) fn partial(obj: T, updated: &[ResourceLink], errors: Vec<String>) -> ApiV2Result {
+        let mut data = vec![serde_json::to_value(obj)?];
+        for link in updated {
+            data.push(serde_json::to_value(link)?);
+        }
+        Ok(Json(V2Reply { data, errors }))
+    }
+
     #[allow(clippy::unnecessary_wraps)]
-    fn list(data: Vec<T>) -> ApiV2Result {
+    pub(crate) fn list(data: Vec<T>) -> ApiV2Result {
         Ok(Json(V2Reply {
             data: data
                 .into_iter()
@@ -40,16 +174,34 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let error_msg = format!("{self}");
         log::error!("Request failed: {error_msg}");
+        let errors = match &self {
+            Self::InvalidResource(errors) => errors.clone(),
+            _ => vec![error_msg],
+        };
         let res = Json(V2Reply::<Value> {
             data: vec![],
-            errors: vec![error_msg],
+            errors,
         });
 
         let status = match self {
-            Self::NotFound(_) => StatusCode::NOT_FOUND,
-            Self::Full(_) => StatusCode::INSUFFICIENT_STORAGE,
+            Self::NotFound(_) | Self::Z2mServerNotFound(_) | Self::NetworkMapNotFound(..) => {
+                StatusCode::NOT_FOUND
+            }
+            Self::AdminDenied => StatusCode::UNAUTHORIZED,
+            Self::Full(_) | Self::TooManyItems(_, _) => StatusCode::INSUFFICIENT_STORAGE,
             Self::WrongType(_, _) => StatusCode::NOT_ACCEPTABLE,
-            Self::DeleteDenied(_) => StatusCode::FORBIDDEN,
+            Self::DeleteDenied(_)
+            | Self::ClientCertRequired
+            | Self::FeatureDisabled(_)
+            | Self::ScopeDenied(_)
+            | Self::UnauthorizedUser => StatusCode::FORBIDDEN,
+            Self::LightStreaming(_) => StatusCode::CONFLICT,
+            Self::InvalidResource(_)
+            | Self::EffectUnsupported(..)
+            | Self::InvalidInstallCode(_)
+            | Self::InvalidImage(_)
+            | Self::InvalidBatch(_)
+            | Self::InvalidZ2mServer(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -60,7 +212,5 @@ impl IntoResponse for ApiError {
 pub fn router() -> Router<AppState> {
     Router::new()
         .nest("/scene", scene::router())
-        .nest("/light", light::router())
-        .nest("/grouped_light", grouped_light::router())
         .nest("/", generic::router())
 }
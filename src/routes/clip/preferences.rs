@@ -0,0 +1,104 @@
+use serde_json::{Map, Value};
+
+use crate::hue::api::RType;
+
+/// Fields that identify a resource, which are never stored as preferences
+const IDENTITY: &[&str] = &["id", "id_v1", "type"];
+
+/// Preference fields clients may store for each resource type, when the
+/// bridge does not understand them itself. Fields inside an object are
+/// given as "object.field".
+const fn allowed(rtype: RType) -> &'static [&'static str] {
+    match rtype {
+        /* the light function (functional, decorative, ..), and the color
+         * temperature of lights that only have a fixed white */
+        RType::Light => &["metadata.function", "metadata.fixed_mired"],
+        /* participation in user tests */
+        RType::Device => &["usertest"],
+        /* opaque application data, as scenes have it */
+        RType::Room | RType::Zone | RType::EntertainmentConfiguration => &["metadata.appdata"],
+        _ => &[],
+    }
+}
+
+fn is_allowed(rtype: RType, key: &str, field: Option<&str>) -> bool {
+    allowed(rtype)
+        .iter()
+        .any(|path| match path.split_once('.') {
+            Some((k, f)) => k == key && Some(f) == field,
+            None => *path == key,
+        })
+}
+
+fn known_field<'a>(known: &'a [Value], key: &str) -> Vec<&'a Value> {
+    known.iter().filter_map(|obj| obj.get(key)).collect()
+}
+
+/// Extract the client preferences allowed for `rtype` from a request.
+///
+/// Preferences are fields of `req` that are not present in any of the
+/// `known` objects (typically the resource itself, and the parsed update).
+/// Unknown fields that are not allowed are ignored.
+///
+/// For objects that are known (e.g., `metadata`), only the unknown fields
+/// inside them are extracted.
+#[must_use]
+pub fn extract(rtype: RType, req: &Value, known: &[Value]) -> Map<String, Value> {
+    let mut prefs = unknown(req, known);
+
+    prefs.retain(|key, val| {
+        if is_allowed(rtype, key, None) {
+            return true;
+        }
+        let Value::Object(obj) = val else {
+            log::debug!("Ignoring unknown field {key} for {rtype:?}");
+            return false;
+        };
+        obj.retain(|field, _| {
+            let keep = is_allowed(rtype, key, Some(field));
+            if !keep {
+                log::debug!("Ignoring unknown field {key}.{field} for {rtype:?}");
+            }
+            keep
+        });
+        !obj.is_empty()
+    });
+
+    prefs
+}
+
+fn unknown(req: &Value, known: &[Value]) -> Map<String, Value> {
+    let Value::Object(req) = req else {
+        return Map::new();
+    };
+
+    let mut prefs = Map::new();
+
+    for (key, val) in req {
+        if IDENTITY.contains(&key.as_str()) {
+            continue;
+        }
+
+        let fields = known_field(known, key);
+        if fields.is_empty() {
+            prefs.insert(key.clone(), val.clone());
+            continue;
+        }
+
+        let Value::Object(obj) = val else {
+            continue;
+        };
+
+        let unknown: Map<String, Value> = obj
+            .iter()
+            .filter(|(k, _)| !fields.iter().any(|f| f.get(k.as_str()).is_some()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        if !unknown.is_empty() {
+            prefs.insert(key.clone(), Value::Object(unknown));
+        }
+    }
+
+    prefs
+}
@@ -0,0 +1,28 @@
+use crate::error::ApiResult;
+use crate::hue::api::{ResourceLink, Room, RoomUpdate, Zone};
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
+
+impl UpdateHandler for Room {
+    type Update = RoomUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: RoomUpdate) -> ApiResult<()> {
+        res.update(&rlink.rid, |room: &mut Self| {
+            if let Some(md) = upd.metadata {
+                room.metadata.apply(md);
+            }
+        })
+    }
+}
+
+impl UpdateHandler for Zone {
+    type Update = RoomUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: RoomUpdate) -> ApiResult<()> {
+        res.update(&rlink.rid, |zone: &mut Self| {
+            if let Some(md) = upd.metadata {
+                zone.metadata.apply(md);
+            }
+        })
+    }
+}
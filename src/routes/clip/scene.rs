@@ -1,5 +1,6 @@
 use axum::{
     extract::{Path, State},
+    http::HeaderMap,
     response::IntoResponse,
     routing::{delete, post, put},
     Json, Router,
@@ -9,10 +10,12 @@ use uuid::Uuid;
 
 use crate::error::{ApiError, ApiResult};
 use crate::hue::api::{
-    RType, Resource, Scene, SceneStatus, SceneStatusUpdate, SceneUpdate, V2Reply,
+    Light, RType, Resource, ResourceLink, Scene, SceneActionElement, SceneStatus,
+    SceneStatusUpdate, SceneUpdate, V2Reply,
 };
-use crate::resource::AuxData;
-use crate::routes::clip::ApiV2Result;
+use crate::resource::{AuxData, Resources};
+use crate::routes::clip::validate::validate_post;
+use crate::routes::clip::{application_id, put_resource, ApiV2Result, UpdateHandler};
 use crate::state::AppState;
 use crate::z2m::request::ClientRequest;
 
@@ -22,95 +25,163 @@ async fn post_scene(
 ) -> ApiResult<impl IntoResponse> {
     log::info!("POST: scene {}", serde_json::to_string(&req)?);
 
-    let scene: Scene = serde_json::from_value(req)?;
-
     let mut lock = state.res.lock().await;
 
+    let mut scene: Scene = validate_post(&lock, RType::Scene, req)?.try_into()?;
+
+    /* capture the current state of lights not given an explicit action */
+    for light in lock.get_lights_for_group(&scene.group) {
+        if scene.actions.iter().any(|sae| sae.target == light) {
+            continue;
+        }
+        let action = match lock.light_states().get(&light.rid) {
+            Some(state) => state.as_scene_action(),
+            None => match lock.get::<Light>(&light) {
+                Ok(obj) => obj.as_scene_action(),
+                Err(_) => continue,
+            },
+        };
+        scene.actions.push(SceneActionElement {
+            action,
+            target: light,
+        });
+    }
+
+    let limit = state.config().bridge.limits.scenes_per_group;
+    if lock.get_scenes_for_room(&scene.group.rid).len() >= limit as usize {
+        return Err(ApiError::TooManyItems(RType::Scene, limit));
+    }
+
     let sid = lock.get_next_scene_id(&scene.group)?;
 
     let link_scene = RType::Scene.deterministic((scene.group.rid, sid));
 
     log::info!("New scene: {link_scene:?} ({})", scene.metadata.name);
 
-    lock.aux_set(
-        &link_scene,
-        AuxData::new()
-            .with_topic(&scene.metadata.name)
-            .with_index(sid),
-    );
-
-    lock.z2m_request(ClientRequest::scene_store(
-        scene.group,
-        sid,
-        scene.metadata.name.clone(),
-    ))?;
-
-    lock.add(&link_scene, Resource::Scene(scene))?;
+    lock.transaction(|res| {
+        res.aux_set(
+            &link_scene,
+            AuxData::new()
+                .with_topic(&scene.metadata.name)
+                .with_index(sid),
+        );
+
+        res.z2m_request(ClientRequest::scene_store(
+            scene.group,
+            sid,
+            scene.metadata.name.clone(),
+        ))?;
+
+        res.add(&link_scene, Resource::Scene(scene))
+    })?;
     drop(lock);
 
     V2Reply::ok(link_scene)
 }
 
-async fn put_scene(
-    State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-    Json(put): Json<Value>,
-) -> ApiV2Result {
-    log::info!("PUT scene/{id}");
-    log::debug!("json data\n{}", serde_json::to_string_pretty(&put)?);
-
-    let rlink = RType::Scene.link_to(id);
-    let mut lock = state.res.lock().await;
-
-    log::info!("PUT scene/{id}: updating");
-
-    let upd: SceneUpdate = serde_json::from_value(put)?;
-
-    if let Some(md) = upd.metadata {
-        lock.update(&id, |scn: &mut Scene| {
-            if md.appdata.is_some() {
-                scn.metadata.appdata = md.appdata;
-            }
-            if md.image.is_some() {
-                scn.metadata.image = md.image;
-            }
-            scn.metadata.name = md.name;
-        })?;
-    }
+impl UpdateHandler for Scene {
+    type Update = SceneUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: SceneUpdate) -> ApiResult<()> {
+        let id = rlink.rid;
+
+        if let Some(md) = upd.metadata {
+            res.update(&id, |scn: &mut Self| {
+                if md.appdata.is_some() {
+                    scn.metadata.appdata = md.appdata;
+                }
+                if md.image.is_some() {
+                    scn.metadata.image = md.image;
+                }
+                scn.metadata.name = md.name;
+            })?;
+        }
 
-    let scene = lock.get::<Scene>(&rlink)?;
+        let scene = res.get::<Self>(rlink)?;
+
+        let mut updated = vec![];
+        let mut errors = vec![];
+
+        if let Some(recall) = upd.recall {
+            let status = match recall.action {
+                Some(SceneStatusUpdate::Active | SceneStatusUpdate::Static) => {
+                    Some(SceneStatus::Static)
+                }
+                /* palette animation is not supported, so the scene is recalled
+                 * as-is, but reported with the requested status */
+                Some(SceneStatusUpdate::DynamicPalette) => Some(SceneStatus::DynamicPalette),
+                None => None,
+            };
+
+            if let Some(active) = status {
+                /* lights removed since the scene was stored are skipped on recall */
+                for sae in &scene.actions {
+                    if res.get::<Light>(&sae.target).is_ok() {
+                        updated.push(sae.target);
+                    } else {
+                        errors.push(format!("Light {} not found", sae.target.rid));
+                    }
+                }
 
-    if let Some(recall) = upd.recall {
-        if recall.action == Some(SceneStatusUpdate::Active) {
-            let scenes = lock.get_scenes_for_room(&scene.group.rid);
-            for rid in scenes {
-                lock.update(&rid, |scn: &mut Scene| {
-                    if rid == id {
-                        scn.status = Some(SceneStatus::Static);
+                let scenes = res.get_scenes_for_room(&scene.group.rid);
+                for rid in scenes {
+                    let status = if rid == id {
+                        active
                     } else {
-                        scn.status = Some(SceneStatus::Inactive);
+                        SceneStatus::Inactive
+                    };
+                    if let Err(err) = res.update(&rid, |scn: &mut Self| scn.status = Some(status)) {
+                        errors.push(err.to_string());
                     }
-                })?;
+                }
+
+                /* recall duration is in milliseconds, z2m transitions in seconds */
+                let transition = recall.duration.map(|ms| f64::from(ms) / 1000.0);
+                res.z2m_request(ClientRequest::scene_recall(*rlink, transition))?;
+            } else {
+                log::error!("Scene recall type not supported: {recall:?}");
             }
+        }
 
-            lock.z2m_request(ClientRequest::scene_recall(rlink))?;
-            drop(lock);
+        if errors.is_empty() {
+            Ok(())
         } else {
-            log::error!("Scene recall type not supported: {recall:?}");
+            Err(ApiError::PartialFailure(updated, errors))
         }
     }
+}
 
-    V2Reply::ok(rlink)
+async fn put_scene(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(put): Json<Value>,
+) -> ApiV2Result {
+    put_resource(
+        &state,
+        RType::Scene.link_to(id),
+        application_id(&headers),
+        put,
+    )
+    .await
 }
 
 async fn delete_scene(State(state): State<AppState>, Path(id): Path<Uuid>) -> ApiV2Result {
     log::info!("DELETE scene/{id}");
     let link = RType::Scene.link_to(id);
 
-    let lock = state.res.lock().await;
+    let mut lock = state.res.lock().await;
     let res = lock.get_resource(RType::Scene, &id)?;
+    let stored = lock.aux_get(&link).is_ok_and(|aux| aux.index.is_some());
 
     match res.obj {
+        /* scenes not stored in z2m (e.g., copies) are removed right away */
+        Resource::Scene(_) if !stored => {
+            lock.delete(&link)?;
+            drop(lock);
+
+            V2Reply::ok(link)
+        }
         Resource::Scene(_) => {
             lock.z2m_request(ClientRequest::scene_remove(link))?;
 
@@ -0,0 +1,101 @@
+use crate::config::DeviceOptions;
+use crate::error::ApiResult;
+use crate::hue::api::{
+    LightLevel, LightLevelUpdate, Motion, MotionUpdate, ResourceLink, Temperature,
+    TemperatureUpdate,
+};
+use crate::resource::Resources;
+use crate::routes::clip::UpdateHandler;
+use crate::z2m::request::ClientRequest;
+
+impl UpdateHandler for Motion {
+    type Update = MotionUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: MotionUpdate) -> ApiResult<()> {
+        /* enabled is handled by bifrost (z2m has no such setting) */
+        if let Some(enabled) = upd.enabled {
+            res.update(&rlink.rid, |motion: &mut Self| motion.enabled = enabled)?;
+        }
+        let owner = res.get::<Self>(rlink)?.owner;
+
+        /* sensitivity is updated when z2m reports the new value back */
+        if let Some(sens) = upd.sensitivity {
+            res.z2m_request(ClientRequest::motion_sensitivity(*rlink, sens.sensitivity))?;
+        }
+
+        if let Some(timeout) = upd.occupancy_timeout {
+            let options = DeviceOptions {
+                occupancy_timeout: Some(timeout),
+                ..DeviceOptions::default()
+            };
+            res.z2m_request(ClientRequest::device_options(owner, options))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UpdateHandler for LightLevel {
+    type Update = LightLevelUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: LightLevelUpdate) -> ApiResult<()> {
+        if let Some(enabled) = upd.enabled {
+            res.update(&rlink.rid, |ll: &mut Self| ll.enabled = enabled)?;
+        } else {
+            res.get::<Self>(rlink)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UpdateHandler for Temperature {
+    type Update = TemperatureUpdate;
+
+    fn put(res: &mut Resources, rlink: &ResourceLink, upd: TemperatureUpdate) -> ApiResult<()> {
+        if let Some(enabled) = upd.enabled {
+            res.update(&rlink.rid, |tmp: &mut Self| tmp.enabled = enabled)?;
+        } else {
+            res.get::<Self>(rlink)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::config::DeviceOptions;
+    use crate::hue::api::{Motion, MotionUpdate, RType, Resource};
+    use crate::resource::Resources;
+    use crate::routes::clip::UpdateHandler;
+    use crate::z2m::request::ClientRequest;
+
+    #[test]
+    fn motion_occupancy_timeout() {
+        let mut res = Resources::new();
+        let owner = RType::Device.link_to(Uuid::new_v4());
+        let link = RType::Motion.link_to(Uuid::new_v4());
+        res.add(&link, Resource::Motion(Motion::new(owner)))
+            .unwrap();
+
+        let mut z2m = res.z2m_updates.subscribe();
+        let upd: MotionUpdate = serde_json::from_str(r#"{"occupancy_timeout": 60}"#).unwrap();
+        Motion::put(&mut res, &link, upd).unwrap();
+
+        let req = z2m.try_recv().unwrap();
+        let ClientRequest::DeviceOptions { device, options } = &req.req else {
+            panic!("Expected device options, got {req:?}");
+        };
+        assert_eq!(*device, owner);
+        assert_eq!(
+            *options,
+            DeviceOptions {
+                occupancy_timeout: Some(60),
+                ..DeviceOptions::default()
+            }
+        );
+    }
+}
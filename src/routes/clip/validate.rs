@@ -0,0 +1,117 @@
+use serde_json::Value;
+
+use crate::error::{ApiError, ApiResult};
+use crate::hue::api::{RType, Resource, ResourceLink};
+use crate::resource::Resources;
+
+/// Validate a resource created by a POST request, before it is accepted into
+/// the store.
+///
+/// Returns the parsed resource, or [`ApiError::InvalidResource`] with one
+/// error per offending field.
+pub fn validate_post(res: &Resources, rtype: RType, obj: Value) -> ApiResult<Resource> {
+    if !obj.is_object() {
+        return Err(ApiError::InvalidResource(vec![format!(
+            "expected {rtype:?} object, found {obj}"
+        )]));
+    }
+
+    /* required fields, and their types, are checked by deserialization */
+    let resource = Resource::from_value(rtype, obj)
+        .map_err(|err| ApiError::InvalidResource(vec![err.to_string()]))?;
+
+    let mut errors = vec![];
+
+    check_links(res, "", &serde_json::to_value(&resource)?, &mut errors);
+
+    match &resource {
+        Resource::Room(room) => {
+            for (idx, child) in room.children.iter().enumerate() {
+                let path = format!("children[{idx}]");
+                check_rtype(&path, child, &[RType::Device], &mut errors);
+            }
+        }
+        Resource::Zone(zone) => {
+            for (idx, child) in zone.children.iter().enumerate() {
+                let path = format!("children[{idx}]");
+                check_rtype(&path, child, &[RType::Light], &mut errors);
+            }
+        }
+        Resource::EntertainmentConfiguration(ec) => {
+            for (idx, light) in ec.light_services.iter().enumerate() {
+                let path = format!("light_services[{idx}]");
+                check_rtype(&path, light, &[RType::Light], &mut errors);
+            }
+        }
+        Resource::Scene(scene) => {
+            let groups = [RType::Room, RType::Zone];
+            check_rtype("group", &scene.group, &groups, &mut errors);
+            for (idx, act) in scene.actions.iter().enumerate() {
+                let path = format!("actions[{idx}].target");
+                check_rtype(&path, &act.target, &[RType::Light], &mut errors);
+            }
+        }
+        _ => {}
+    }
+
+    if errors.is_empty() {
+        Ok(resource)
+    } else {
+        Err(ApiError::InvalidResource(errors))
+    }
+}
+
+/// Check that all resource links in `value` refer to existing resources
+fn check_links(res: &Resources, path: &str, value: &Value, errors: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            if map.len() == 2 && map.contains_key("rid") && map.contains_key("rtype") {
+                if let Ok(link) = serde_json::from_value::<ResourceLink>(value.clone()) {
+                    check_link(res, path, &link, errors);
+                    return;
+                }
+            }
+            for (key, val) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                check_links(res, &path, val, errors);
+            }
+        }
+        Value::Array(list) => {
+            for (idx, val) in list.iter().enumerate() {
+                check_links(res, &format!("{path}[{idx}]"), val, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_link(res: &Resources, path: &str, link: &ResourceLink, errors: &mut Vec<String>) {
+    /* public images are built into the hue apps, and not known by the bridge */
+    if link.rtype == RType::PublicImage {
+        return;
+    }
+
+    match res.get_resource_by_id(&link.rid) {
+        Ok(obj) if obj.obj.rtype() == link.rtype => {}
+        Ok(obj) => errors.push(format!(
+            "{path}: resource {} is {:?}, not {:?}",
+            link.rid,
+            obj.obj.rtype(),
+            link.rtype
+        )),
+        Err(_) => errors.push(format!("{path}: resource {} not found", link.rid)),
+    }
+}
+
+fn check_rtype(path: &str, link: &ResourceLink, valid: &[RType], errors: &mut Vec<String>) {
+    if !valid.contains(&link.rtype) {
+        errors.push(format!(
+            "{path}: expected one of {valid:?}, found {:?}",
+            link.rtype
+        ));
+    }
+}
@@ -1,42 +1,53 @@
+use std::time::Duration;
+
 use axum::extract::State;
-use axum::response::sse::{Event, Sse};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::routing::get;
 use axum::Router;
-use chrono::Utc;
 use futures::stream::Stream;
 use futures::StreamExt;
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::error::ApiResult;
+use crate::routes::clip::application_id;
 use crate::state::AppState;
 
+/// Time between keep-alive comments on an idle event stream, so clients
+/// (and proxies) do not give up on a quiet connection
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The clip v2 event stream: a "hi" comment when connected, followed by
+/// add, update and delete events for all resource changes visible to the
+/// application, with keep-alive comments while idle.
 pub async fn get_clip_v2(
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Sse<impl Stream<Item = ApiResult<Event>>> {
     let hello = tokio_stream::iter([Ok(Event::default().comment("hi"))]);
 
-    let mut prev_ts = Utc::now().timestamp();
-    let mut idx = 0;
-
     let channel = state.res.lock().await.hue_channel();
+    let app_id = application_id(&headers).map(ToString::to_string);
+
+    let stream = BroadcastStream::new(channel)
+        .filter(move |e| {
+            let visible = e
+                .as_ref()
+                .map_or(true, |evt| evt.visible_to(app_id.as_deref()));
+            std::future::ready(visible)
+        })
+        .map(move |e| {
+            let evt = e?;
+            let id = evt.seq.to_string();
+            let json = [evt];
+            log::trace!(
+                "## EVENT ##: {}",
+                serde_json::to_string(&json).unwrap_or_else(|_| "ERROR".to_string())
+            );
+            Ok(Event::default().id(id).json_data(json)?)
+        });
 
-    let stream = BroadcastStream::new(channel).map(move |e| {
-        let json = [e?];
-        log::trace!(
-            "## EVENT ##: {}",
-            serde_json::to_string(&json).unwrap_or_else(|_| "ERROR".to_string())
-        );
-        let ts = Utc::now().timestamp();
-        if ts == prev_ts {
-            idx += 1;
-        } else {
-            idx = 0;
-            prev_ts = ts;
-        }
-        Ok(Event::default().id(format!("{ts}:{idx}")).json_data(json)?)
-    });
-
-    Sse::new(hello.chain(stream))
+    Sse::new(hello.chain(stream)).keep_alive(KeepAlive::new().interval(KEEPALIVE_INTERVAL))
 }
 
 pub fn router() -> Router<AppState> {
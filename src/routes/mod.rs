@@ -1,15 +1,35 @@
+use axum::middleware;
 use axum::Router;
 
 use crate::state::AppState;
 
 pub mod api;
+pub mod bifrost;
 pub mod clip;
 pub mod eventstream;
+pub mod permissions;
+pub mod upnp;
 
 pub fn router(appstate: AppState) -> Router<()> {
-    Router::new()
-        .nest("/api", api::router())
+    let hue = Router::new()
+        .nest(
+            "/api",
+            api::router(appstate.config().bifrost.features.v1_api),
+        )
         .nest("/clip/v2/resource", clip::router())
         .nest("/eventstream", eventstream::router())
-        .with_state(appstate)
+        .route_layer(middleware::from_fn_with_state(
+            appstate.clone(),
+            permissions::require_scope,
+        ));
+
+    let router = Router::new()
+        .merge(hue)
+        .nest("/bifrost", bifrost::router(appstate.clone()))
+        .merge(upnp::router());
+
+    #[cfg(feature = "server-acme")]
+    let router = router.nest("/.well-known/acme-challenge", crate::server::acme::router());
+
+    router.with_state(appstate)
 }
@@ -0,0 +1,101 @@
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::config::KeyScope;
+use crate::error::ApiError;
+use crate::hue::legacy_api::{HueError, HueResult};
+use crate::state::AppState;
+
+/// Header carrying the application key of v2 api requests
+pub const HUE_APPLICATION_KEY: &str = "hue-application-key";
+
+/// The scope needed to make a request. Updates (e.g., switching lights) only
+/// need control, while creating and deleting resources needs admin.
+const fn required_scope(method: &Method) -> KeyScope {
+    match *method {
+        Method::GET | Method::HEAD => KeyScope::Read,
+        Method::PUT | Method::PATCH => KeyScope::Control,
+        _ => KeyScope::Admin,
+    }
+}
+
+/// The application key of a request: the hue-application-key header, or the
+/// username in the path of v1 api requests (e.g., /api/<key>/lights)
+fn application_key<'a>(path: &'a str, request: &'a Request) -> Option<&'a str> {
+    if let Some(key) = request.headers().get(HUE_APPLICATION_KEY) {
+        return key.to_str().ok();
+    }
+
+    path.strip_prefix("/api/")?
+        .split('/')
+        .next()
+        .filter(|user| !user.is_empty() && *user != "config")
+}
+
+/// Refuse a request from an unknown application: like a real bridge, with
+/// error 1 on the v1 api, and 403 Forbidden on the v2 api
+fn unauthorized(path: &str) -> Response {
+    if let Some(address) = path.strip_prefix("/api/") {
+        let address = address
+            .split_once('/')
+            .map_or_else(|| "/".to_string(), |(_, rest)| format!("/{rest}"));
+        let error: HueResult<()> = HueResult::Error(HueError::unauthorized_user(&address));
+        return Json(vec![error]).into_response();
+    }
+    ApiError::UnauthorizedUser.into_response()
+}
+
+/// Only allow requests that the scope of their application key permits
+pub async fn require_scope(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path())
+        .to_string();
+
+    /* pairing a new application, and reading the public part of the
+     * bridge config, is always allowed */
+    let public = match path.trim_end_matches('/') {
+        "/api" => request.method() == Method::POST,
+        "/api/config" => true,
+        _ => false,
+    };
+    if public {
+        return next.run(request).await;
+    }
+
+    let conf = &state.config().bifrost;
+    let required = required_scope(request.method());
+    let key = application_key(&path, &request);
+    let mut lock = state.res.lock().await;
+    let paired = key.is_some_and(|key| lock.whitelist_touch(key));
+    /* once applications are paired, unknown keys are refused (also keys of
+     * applications removed from the whitelist) */
+    let enforced = conf.link_button.required || !lock.whitelist().is_empty();
+    drop(lock);
+
+    let Some(scope) = conf.permissions.scope(key, paired, enforced) else {
+        log::warn!(
+            "{} {path}: denied, unknown application key",
+            request.method()
+        );
+        return unauthorized(&path);
+    };
+
+    if scope < required {
+        log::warn!(
+            "{} {path}: denied, needs {required:?} scope",
+            request.method()
+        );
+        return ApiError::ScopeDenied(required).into_response();
+    }
+
+    next.run(request).await
+}
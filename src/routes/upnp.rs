@@ -0,0 +1,45 @@
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+
+use crate::hue::upnp::{self, ICON_PATH, ICON_SIZE};
+use crate::model::image::Image;
+use crate::state::AppState;
+
+async fn get_description(State(state): State<AppState>) -> impl IntoResponse {
+    let bconf = &state.config().bridge;
+    let xml = upnp::description_xml(&bconf.name, state.ipaddress(), bconf.http_port, bconf.mac);
+
+    ([(header::CONTENT_TYPE, "text/xml")], xml)
+}
+
+/// A simple bridge icon: a white circle on a dark background
+#[allow(clippy::cast_precision_loss)]
+async fn get_icon() -> impl IntoResponse {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 * 0.4;
+
+    let pixels = (0..ICON_SIZE * ICON_SIZE)
+        .map(|idx| {
+            let x = (idx % ICON_SIZE) as f32 - center;
+            let y = (idx / ICON_SIZE) as f32 - center;
+            if x.hypot(y) <= radius {
+                [0xFF, 0xFF, 0xFF]
+            } else {
+                [0x20, 0x20, 0x28]
+            }
+        })
+        .collect();
+
+    let icon = Image {
+        width: ICON_SIZE,
+        height: ICON_SIZE,
+        pixels,
+    };
+
+    ([(header::CONTENT_TYPE, "image/png")], icon.to_png())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/description.xml", get(get_description))
+        .route(&format!("/{ICON_PATH}"), get(get_icon))
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use camino::Utf8Path;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, OrderStatus, RetryPolicy,
+};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::config::{AcmeChallenge, AcmeConfig};
+use crate::error::{ApiError, ApiResult};
+use crate::server::certificate;
+use crate::server::clientcert::{self, PinnedClientVerifier};
+use crate::state::AppState;
+
+/// Pending http-01 challenges, mapping tokens to key authorizations
+pub type AcmeTokens = Arc<Mutex<HashMap<String, String>>>;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+async fn get_challenge(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<String, StatusCode> {
+    state
+        .acme_tokens
+        .lock()
+        .await
+        .get(&token)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:token", get(get_challenge))
+}
+
+/// Returns true if the certificate in `conf.cert_file` is missing, or due for
+/// renewal.
+fn needs_renewal(conf: &AcmeConfig) -> bool {
+    let Ok(fd) = File::open(&conf.cert_file) else {
+        return true;
+    };
+
+    let Ok(Some(expiry)) = certificate::extract_expiry(fd) else {
+        log::warn!("Cannot read expiry of [{}], renewing", conf.cert_file);
+        return true;
+    };
+
+    /* a renewal window reaching before the epoch means "renew now" */
+    conf.renew_days
+        .checked_mul(24 * 60 * 60)
+        .and_then(|secs| expiry.checked_sub(Duration::from_secs(secs)))
+        .map_or(true, |renew_at| renew_at <= SystemTime::now())
+}
+
+async fn load_account(conf: &AcmeConfig) -> ApiResult<Account> {
+    if let Ok(data) = std::fs::read_to_string(&conf.account_file) {
+        let credentials: AccountCredentials = serde_json::from_str(&data)?;
+        return Ok(Account::builder()?.from_credentials(credentials).await?);
+    }
+
+    log::info!("Creating new ACME account at {}", conf.directory);
+
+    let contact = conf.email.as_ref().map(|email| format!("mailto:{email}"));
+    let contact: Vec<&str> = contact.iter().map(String::as_str).collect();
+
+    let (account, credentials) = Account::builder()?
+        .create(
+            &NewAccount {
+                contact: &contact,
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            conf.directory.clone(),
+            None,
+        )
+        .await?;
+
+    write_private(
+        &conf.account_file,
+        &[serde_json::to_string(&credentials)?.as_bytes()],
+    )?;
+
+    Ok(account)
+}
+
+/// Write `parts` to `path`, readable only by the owner. A temporary file is
+/// written first, so a crash never leaves a partial file.
+fn write_private(path: &Utf8Path, parts: &[&[u8]]) -> ApiResult<()> {
+    let tmp = path.with_extension("tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut fd = options.open(&tmp)?;
+    for part in parts {
+        fd.write_all(part)?;
+    }
+    fd.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Run the dns hook, to publish (or remove) the TXT record for a dns-01
+/// challenge.
+async fn dns_hook(conf: &AcmeConfig, action: &str, value: &str) -> ApiResult<()> {
+    let Some(hook) = &conf.dns_hook else {
+        return Err(ApiError::AcmeFailed(
+            conf.hostname.clone(),
+            "dns-01 challenge requires dns_hook".to_string(),
+        ));
+    };
+
+    let status = Command::new(hook)
+        .args([action, &format!("_acme-challenge.{}", conf.hostname), value])
+        .status()
+        .await?;
+
+    if !status.success() {
+        return Err(ApiError::AcmeFailed(
+            conf.hostname.clone(),
+            format!("dns hook {hook} {action} failed: {status}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Perform the challenges for all pending authorizations. Values published
+/// through the dns hook are added to `dns_values`, for later cleanup.
+async fn authorize(
+    conf: &AcmeConfig,
+    order: &mut instant_acme::Order,
+    tokens: &AcmeTokens,
+    dns_values: &mut Vec<String>,
+) -> ApiResult<()> {
+    let challenge_type = match conf.challenge {
+        AcmeChallenge::Http01 => ChallengeType::Http01,
+        AcmeChallenge::Dns01 => ChallengeType::Dns01,
+    };
+
+    let mut authorizations = order.authorizations();
+    while let Some(authz) = authorizations.next().await {
+        let mut authz = authz?;
+        match authz.status {
+            AuthorizationStatus::Pending => {}
+            AuthorizationStatus::Valid => continue,
+            status => {
+                return Err(ApiError::AcmeFailed(
+                    conf.hostname.clone(),
+                    format!("unexpected authorization status {status:?}"),
+                ))
+            }
+        }
+
+        let Some(mut challenge) = authz.challenge(challenge_type.clone()) else {
+            return Err(ApiError::AcmeFailed(
+                conf.hostname.clone(),
+                format!("no {challenge_type:?} challenge offered"),
+            ));
+        };
+
+        let key_auth = challenge.key_authorization();
+        match conf.challenge {
+            AcmeChallenge::Http01 => {
+                tokens
+                    .lock()
+                    .await
+                    .insert(challenge.token.clone(), key_auth.as_str().to_string());
+            }
+            AcmeChallenge::Dns01 => {
+                let value = key_auth.dns_value();
+                dns_values.push(value.clone());
+                dns_hook(conf, "set", &value).await?;
+                sleep(Duration::from_secs(conf.dns_delay)).await;
+            }
+        }
+
+        challenge.set_ready().await?;
+    }
+
+    Ok(())
+}
+
+async fn obtain_certificate(conf: &AcmeConfig, tokens: &AcmeTokens) -> ApiResult<()> {
+    let account = load_account(conf).await?;
+
+    let identifiers = [Identifier::Dns(conf.hostname.clone())];
+    let mut order = account.new_order(&NewOrder::new(&identifiers)).await?;
+
+    let mut dns_values = vec![];
+    let authorized = authorize(conf, &mut order, tokens, &mut dns_values).await;
+    let status = match authorized {
+        Ok(()) => order
+            .poll_ready(&RetryPolicy::default())
+            .await
+            .map_err(Into::into),
+        Err(err) => Err(err),
+    };
+
+    /* clean up challenges, whether they succeeded or not */
+    tokens.lock().await.clear();
+    for value in &dns_values {
+        if let Err(err) = dns_hook(conf, "clear", value).await {
+            log::warn!("Failed to remove dns challenge: {err}");
+        }
+    }
+
+    let status = status?;
+    if status != OrderStatus::Ready {
+        return Err(ApiError::AcmeFailed(
+            conf.hostname.clone(),
+            format!("unexpected order status {status:?}"),
+        ));
+    }
+
+    let private_key = order.finalize().await?;
+    let chain = order.poll_certificate(&RetryPolicy::default()).await?;
+
+    /* same layout as the bridge certificate: private key, then certificates */
+    write_private(&conf.cert_file, &[private_key.as_bytes(), chain.as_bytes()])?;
+
+    Ok(())
+}
+
+/// Obtain a certificate for `conf.hostname` from the ACME server, and keep it
+/// renewed. The https server is switched to the new certificate, whenever a
+/// certificate is obtained.
+pub async fn acme_provisioner(
+    config: RustlsConfig,
+    conf: AcmeConfig,
+    tokens: AcmeTokens,
+    verifier: Option<Arc<PinnedClientVerifier>>,
+) -> ApiResult<()> {
+    loop {
+        if !needs_renewal(&conf) {
+            sleep(CHECK_INTERVAL).await;
+            continue;
+        }
+
+        log::info!("Requesting ACME certificate for [{}]", conf.hostname);
+
+        if let Err(err) = obtain_certificate(&conf, &tokens).await {
+            log::error!("ACME certificate request failed: {err}");
+            sleep(RETRY_INTERVAL).await;
+            continue;
+        }
+
+        log::info!("ACME certificate saved to [{}]", conf.cert_file);
+
+        match clientcert::server_config(&conf.cert_file, verifier.clone()) {
+            Ok(tls) => config.reload_from_config(Arc::new(tls)),
+            Err(err) => log::error!("Failed to load certificate [{}]: {err}", conf.cert_file),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::error::{ApiError, ApiResult};
+
+/// Number of attempts to bind a port that is in use (e.g., by a previous
+/// bifrost instance that is still shutting down)
+const BIND_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry, doubled for each retry after that
+const BIND_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Socket inodes of tcp sockets listening on `port`
+fn listening_inodes(port: u16) -> Vec<String> {
+    let port = format!("{port:04X}");
+
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|file| std::fs::read_to_string(file).ok())
+        .flat_map(|data| {
+            data.lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let local_port = fields.get(1)?.rsplit(':').next()?;
+                    /* state 0A is LISTEN */
+                    (local_port == port && *fields.get(3)? == "0A")
+                        .then(|| fields.get(9).map(ToString::to_string))
+                        .flatten()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Find the process listening on a tcp port, as "name (pid 1234)". This only
+/// works on linux, and only for processes we are allowed to inspect.
+#[must_use]
+pub fn port_owner(port: u16) -> Option<String> {
+    let sockets: Vec<String> = listening_inodes(port)
+        .into_iter()
+        .map(|inode| format!("socket:[{inode}]"))
+        .collect();
+    if sockets.is_empty() {
+        return None;
+    }
+
+    std::fs::read_dir("/proc").ok()?.flatten().find_map(|proc| {
+        let pid: u32 = proc.file_name().to_str()?.parse().ok()?;
+        let owns = std::fs::read_dir(proc.path().join("fd"))
+            .ok()?
+            .flatten()
+            .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+            .any(|target| {
+                sockets
+                    .iter()
+                    .any(|sock| target.as_os_str() == sock.as_str())
+            });
+        if !owns {
+            return None;
+        }
+
+        let name = std::fs::read_to_string(proc.path().join("comm")).unwrap_or_default();
+        Some(format!("{} (pid {pid})", name.trim()))
+    })
+}
+
+/// A description of why binding `port` failed, and what to do about it
+#[must_use]
+pub fn explain(port: u16, err: &std::io::Error) -> String {
+    match err.kind() {
+        ErrorKind::AddrInUse => port_owner(port).map_or_else(
+            || format!("{err} (by another program, or bifrost instance)"),
+            |owner| format!("{err} (by {owner})"),
+        ),
+        ErrorKind::PermissionDenied => {
+            format!("{err} (ports below 1024 require root, or the CAP_NET_BIND_SERVICE capability)")
+        }
+        ErrorKind::AddrNotAvailable => {
+            format!("{err} (bridge.ipaddress must be an address of this machine)")
+        }
+        _ => err.to_string(),
+    }
+}
+
+async fn bind_with_retry(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let mut delay = BIND_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        match TcpListener::bind(addr) {
+            Err(err) if err.kind() == ErrorKind::AddrInUse && attempt < BIND_ATTEMPTS => {
+                log::warn!("{addr} is in use, retrying in {}s..", delay.as_secs());
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Bind the listening socket for `proto` (http or https). Ports in use are
+/// retried for a while, before trying the `fallback` port (if any).
+pub async fn listen(
+    proto: &str,
+    ip: Ipv4Addr,
+    port: u16,
+    fallback: Option<u16>,
+) -> ApiResult<TcpListener> {
+    let addr = SocketAddr::from((ip, port));
+    let err = match bind_with_retry(addr).await {
+        Ok(listener) => return Ok(listener),
+        Err(err) => err,
+    };
+
+    let Some(fallback) = fallback.filter(|alt| *alt != port) else {
+        return Err(ApiError::BindFailed(
+            proto.to_string(),
+            addr,
+            explain(port, &err),
+        ));
+    };
+
+    log::warn!(
+        "Cannot listen on {addr} for {proto}: {}, using port {fallback} instead",
+        explain(port, &err)
+    );
+
+    let addr = SocketAddr::from((ip, fallback));
+    TcpListener::bind(addr)
+        .map_err(|err| ApiError::BindFailed(proto.to_string(), addr, explain(fallback, &err)))
+}
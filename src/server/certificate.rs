@@ -1,6 +1,10 @@
-use std::fs::File;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
 use std::io::{BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use camino::Utf8Path;
 use der::asn1::{GeneralizedTime, OctetString};
@@ -13,6 +17,7 @@ use p256::ecdsa::DerSignature;
 use p256::pkcs8::EncodePrivateKey;
 use rand_core::OsRng;
 use rsa::pkcs8::SubjectPublicKeyInfoRef;
+use serde::{Deserialize, Serialize};
 use sha1::Sha1;
 use sha2::Digest;
 use x509_cert::attr::AttributeTypeAndValue;
@@ -35,13 +40,68 @@ pub fn hue_bridge_id_raw(mac: MacAddress) -> [u8; 8] {
 }
 
 #[must_use]
-#[allow(clippy::format_collect)]
 pub fn hue_bridge_id(mac: MacAddress) -> String {
-    let bytes = hue_bridge_id_raw(mac);
-    bytes
-        .into_iter()
-        .map(|b| format!("{b:02x}"))
-        .collect::<String>()
+    BridgeId::from_mac(mac).to_string()
+}
+
+/// A hue bridge id, written as 16 hex digits (e.g., "001788fffe123456").
+///
+/// Normally derived from the mac address, but it can be set explicitly, to
+/// keep the identity of a bridge when moving to other hardware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BridgeId([u8; 8]);
+
+impl BridgeId {
+    #[must_use]
+    pub fn from_mac(mac: MacAddress) -> Self {
+        Self(hue_bridge_id_raw(mac))
+    }
+
+    #[must_use]
+    pub const fn bytes(&self) -> [u8; 8] {
+        self.0
+    }
+}
+
+impl Display for BridgeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+impl FromStr for BridgeId {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> ApiResult<Self> {
+        let digits: String = s.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+        let invalid =
+            || ApiError::InvalidConfig(format!("invalid bridge id {s:?} (expected 16 hex digits)"));
+
+        if digits.len() != 16 {
+            return Err(invalid());
+        }
+
+        let mut bytes = [0u8; 8];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl TryFrom<String> for BridgeId {
+    type Error = ApiError;
+
+    fn try_from(value: String) -> ApiResult<Self> {
+        value.parse()
+    }
+}
+
+impl From<BridgeId> for String {
+    fn from(value: BridgeId) -> Self {
+        value.to_string()
+    }
 }
 
 /// Generate a self-signed X509 certificate, closely matching the type and style
@@ -65,17 +125,15 @@ pub fn hue_bridge_id(mac: MacAddress) -> String {
 ///    acting as a kind of CA certificate for the instance certificate.
 ///    This also seems to have no negative impact.
 ///
-pub fn generate(secret_key: &p256::SecretKey, mac: MacAddress) -> ApiResult<CertificateInner> {
+pub fn generate(secret_key: &p256::SecretKey, bridge_id: BridgeId) -> ApiResult<CertificateInner> {
     let public_key = secret_key.public_key();
 
-    let bridge_id = hue_bridge_id(mac);
-
     let subject = Name::from_str(&format!("CN={bridge_id},O=Philips Hue,C=NL"))?;
 
     /* self-signed certificate, so subject == issuer */
     let issuer = subject.clone();
 
-    let serial_number = SerialNumber::new(&hue_bridge_id_raw(mac))?;
+    let serial_number = SerialNumber::new(&bridge_id.bytes())?;
 
     /* Philips Hue seems to start their certificates at the beginning of 2017.. */
     let not_before = GeneralizedTime::from_date_time(DateTime::new(2017, 1, 1, 0, 0, 0)?).into();
@@ -157,24 +215,64 @@ pub fn extract_common_name(rdr: impl Read) -> ApiResult<Option<String>> {
     Ok(None)
 }
 
-pub fn generate_and_save(certpath: &Utf8Path, mac: MacAddress) -> ApiResult<()> {
+/// Returns the expiry time of the first certificate in `rdr`
+pub fn extract_expiry(rdr: impl Read) -> ApiResult<Option<SystemTime>> {
+    let bufread = &mut BufReader::new(rdr);
+
+    if let Some(chunk) = rustls_pemfile::certs(bufread).next() {
+        let cert = Certificate::from_der(&chunk?)?;
+        return Ok(Some(
+            cert.tbs_certificate.validity.not_after.to_system_time(),
+        ));
+    }
+
+    Ok(None)
+}
+
+/// Generate a new certificate (see [`generate`]), and save it to `certpath`.
+///
+/// The private key is saved in the same file, so the file is only readable by
+/// the owner. Missing directories are created.
+pub fn generate_and_save(certpath: &Utf8Path, bridge_id: BridgeId) -> ApiResult<()> {
+    if certpath.is_dir() {
+        /* e.g., docker creates a directory when bind-mounting a missing file */
+        return Err(ApiError::InvalidConfig(format!(
+            "certificate file {certpath} is a directory"
+        )));
+    }
+
     let secret_key = p256::SecretKey::random(&mut OsRng);
-    let cert = generate(&secret_key, mac)?;
-    let mut fd = File::create(certpath)?;
+    let cert = generate(&secret_key, bridge_id)?;
+
+    if let Some(dir) = certpath.parent().filter(|dir| !dir.as_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    /* write to a temporary file first, so a crash never leaves a partial file */
+    let tmp = certpath.with_extension("tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut fd = options.open(&tmp)?;
     fd.write_all(secret_key.to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
     fd.write_all(cert.to_pem(LineEnding::LF)?.as_bytes())?;
+    fd.sync_all()?;
+    std::fs::rename(&tmp, certpath)?;
+
+    log::info!("Generated certificate for bridge id [{bridge_id}] in [{certpath}]");
     Ok(())
 }
 
-pub fn check_certificate(certpath: &Utf8Path, mac: MacAddress) -> ApiResult<()> {
+pub fn check_certificate(certpath: &Utf8Path, bridge_id: BridgeId) -> ApiResult<()> {
     let cn = extract_common_name(File::open(certpath)?)?;
-    let id = hue_bridge_id(mac);
+    let id = bridge_id.to_string();
     match cn {
         Some(cn) => {
             if cn == id {
                 log::debug!("Found existing certificate for bridge id [{id}]");
             } else {
-                log::error!("Certificate found, but mac address does not match!");
+                log::error!("Certificate found, but bridge id does not match!");
                 log::error!("  [{id}] (expected)");
                 log::error!("  [{cn}] {certpath}");
                 return Err(ApiError::CertificateInvalid(certpath.to_owned()));
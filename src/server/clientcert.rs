@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use axum::middleware::AddExtension;
+use axum::Extension;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::RustlsAcceptor;
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::future::BoxFuture;
+use rustls::server::{ClientCertVerified, ClientCertVerifier, NoClientAuth};
+use rustls::{Certificate, CertificateError, DistinguishedName, PrivateKey, ServerConfig};
+use rustls_pemfile::Item;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use x509_cert::der::{Decode, Encode};
+
+use crate::error::{ApiError, ApiResult};
+
+/// Accepts only the configured (pinned) client certificates, compared by
+/// their SHA-256 fingerprint.
+///
+/// Presenting a client certificate is optional, so regular clients (e.g. the
+/// hue app) are unaffected. Clients presenting any other certificate are
+/// rejected during the tls handshake.
+pub struct PinnedClientVerifier {
+    fingerprints: Vec<Vec<u8>>,
+    subjects: Vec<DistinguishedName>,
+}
+
+impl PinnedClientVerifier {
+    pub fn load(files: &[Utf8PathBuf]) -> ApiResult<Self> {
+        let mut fingerprints = vec![];
+        let mut subjects = vec![];
+
+        for file in files {
+            let fd = File::open(file).map_err(|err| ApiError::Certificate(file.clone(), err))?;
+            for cert in rustls_pemfile::certs(&mut BufReader::new(fd)) {
+                let cert = cert.map_err(|err| ApiError::Certificate(file.clone(), err))?;
+                let parsed = x509_cert::Certificate::from_der(&cert)?;
+
+                fingerprints.push(Sha256::digest(&cert).to_vec());
+                subjects.push(DistinguishedName::from(
+                    parsed.tbs_certificate.subject.to_der()?,
+                ));
+            }
+        }
+
+        if fingerprints.is_empty() {
+            return Err(ApiError::InvalidConfig(
+                "no client certificates found in client_certificates".to_string(),
+            ));
+        }
+
+        log::info!("Loaded {} pinned client certificate(s)", fingerprints.len());
+
+        Ok(Self {
+            fingerprints,
+            subjects,
+        })
+    }
+}
+
+impl ClientCertVerifier for PinnedClientVerifier {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &self.subjects
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let fingerprint = Sha256::digest(&end_entity.0).to_vec();
+        if self.fingerprints.contains(&fingerprint) {
+            Ok(ClientCertVerified::assertion())
+        } else {
+            log::warn!("Rejected unknown client certificate");
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+/// Load the server certificate (and key) from `certfile`, optionally asking
+/// clients for a pinned client certificate.
+pub fn server_config(
+    certfile: &Utf8Path,
+    verifier: Option<Arc<PinnedClientVerifier>>,
+) -> io::Result<ServerConfig> {
+    let mut certs = vec![];
+    let mut key = None;
+
+    let fd = File::open(certfile)?;
+    for item in rustls_pemfile::read_all(&mut BufReader::new(fd)) {
+        match item? {
+            Item::X509Certificate(cert) => certs.push(Certificate(cert.to_vec())),
+            Item::Sec1Key(k) => key = key.or_else(|| Some(k.secret_sec1_der().to_vec())),
+            Item::Pkcs1Key(k) => key = key.or_else(|| Some(k.secret_pkcs1_der().to_vec())),
+            Item::Pkcs8Key(k) => key = key.or_else(|| Some(k.secret_pkcs8_der().to_vec())),
+            _ => {}
+        }
+    }
+
+    let key = key.ok_or_else(|| io::Error::other("private key not found"))?;
+
+    let verifier: Arc<dyn ClientCertVerifier> = match verifier {
+        Some(verifier) => verifier,
+        None => NoClientAuth::boxed(),
+    };
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, PrivateKey(key))
+        .map_err(io::Error::other)?;
+
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Request extension, present on https connections where the client has
+/// presented a pinned client certificate.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientCertificate;
+
+/// Tls acceptor, marking requests from connections with a (verified) client
+/// certificate with the [`ClientCertificate`] extension.
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    #[must_use]
+    pub const fn new(inner: RustlsAcceptor) -> Self {
+        Self { inner }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, Option<ClientCertificate>>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .is_some_and(|certs| !certs.is_empty())
+                .then_some(ClientCertificate);
+
+            Ok((stream, Extension(cert).layer(service)))
+        })
+    }
+}
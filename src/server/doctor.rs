@@ -0,0 +1,314 @@
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::{Duration, SystemTime};
+
+use camino::Utf8Path;
+use mdns_sd::ServiceDaemon;
+use tokio::time::timeout;
+use tokio_tungstenite::connect_async;
+
+use crate::config::{self, AppConfig, ConfigOverrides};
+use crate::server::{bind, certificate, netwatch};
+
+/* Certificates expiring sooner than this are reported as a warning */
+const CERT_EXPIRY_WARNING: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/* Time allowed for connecting to each z2m server */
+const Z2M_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "  OK  "),
+            Self::Warning => write!(f, " WARN "),
+            Self::Failed => write!(f, " FAIL "),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Suggested fix, for checks that did not pass
+    pub hint: Option<String>,
+}
+
+/// Result of running all self-tests (see [`run`])
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        });
+    }
+
+    fn hint(&mut self, hint: impl Into<String>) {
+        if let Some(check) = self.checks.last_mut() {
+            check.hint = Some(hint.into());
+        }
+    }
+
+    /// The worst status of any check
+    #[must_use]
+    pub fn status(&self) -> CheckStatus {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Ok)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.detail)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "         -> {hint}")?;
+            }
+        }
+
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .count();
+        let warnings = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Warning)
+            .count();
+
+        writeln!(
+            f,
+            "\n{} checks, {failed} failed, {warnings} warnings",
+            self.checks.len()
+        )
+    }
+}
+
+/// Check configuration, certificates, ports, mdns and z2m connectivity,
+/// without starting any services.
+pub async fn run(filename: &Utf8Path, overrides: &ConfigOverrides) -> Report {
+    let mut report = Report::default();
+
+    let config = match config::parse_with_overrides(filename, overrides) {
+        Ok(config) => {
+            report.add("config", CheckStatus::Ok, format!("{filename} loaded"));
+            config
+        }
+        Err(err) => {
+            report.add("config", CheckStatus::Failed, format!("{filename}: {err}"));
+            report.hint("compare with config.example.yaml, and doc/config-reference.md");
+            return report;
+        }
+    };
+
+    let homes = match config.homes() {
+        Ok(homes) => homes,
+        Err(err) => {
+            report.add("config", CheckStatus::Failed, err.to_string());
+            return report;
+        }
+    };
+
+    for (name, home) in &homes {
+        check_certificate(&mut report, name, home);
+        check_ports(&mut report, name, home);
+    }
+
+    check_mdns(&mut report);
+
+    for (name, server) in &config.z2m.servers {
+        check_z2m(&mut report, name, &server.url).await;
+    }
+
+    report
+}
+
+fn check_certificate(report: &mut Report, home: &str, config: &AppConfig) {
+    let name = format!("[{home}] certificate");
+
+    if !config.bridge.https_enabled {
+        report.add(name, CheckStatus::Ok, "https disabled, not needed");
+        return;
+    }
+
+    if config.bifrost.acme.is_some() {
+        report.add(name, CheckStatus::Ok, "managed by acme");
+        return;
+    }
+
+    let certpath = Utf8Path::new(&config.bifrost.cert_file);
+    if certpath.is_dir() {
+        report.add(
+            name,
+            CheckStatus::Failed,
+            format!("{certpath} is a directory"),
+        );
+        report.hint("remove the directory (or fix the volume mount) to generate a certificate");
+        return;
+    }
+
+    if !certpath.metadata().is_ok_and(|meta| meta.len() > 0) {
+        report.add(
+            name,
+            CheckStatus::Warning,
+            format!("{certpath} not found, will be generated on startup"),
+        );
+        return;
+    }
+
+    let expected = config.bridge.id().to_string();
+    match File::open(certpath).map(certificate::extract_common_name) {
+        Ok(Ok(Some(cn))) if cn == expected => {}
+        Ok(Ok(Some(cn))) => {
+            report.add(
+                name,
+                CheckStatus::Failed,
+                format!("{certpath} is for bridge id [{cn}], expected [{expected}]"),
+            );
+            report
+                .hint("the mac address (or bridge_id) was changed; delete the certificate to generate a new one");
+            return;
+        }
+        Ok(Ok(None)) => {
+            report.add(
+                name,
+                CheckStatus::Failed,
+                format!("{certpath} has no common name"),
+            );
+            report.hint("delete the certificate to generate a new one");
+            return;
+        }
+        Ok(Err(err)) => {
+            report.add(name, CheckStatus::Failed, format!("{certpath}: {err}"));
+            report.hint("delete the certificate to generate a new one");
+            return;
+        }
+        Err(err) => {
+            report.add(name, CheckStatus::Failed, format!("{certpath}: {err}"));
+            return;
+        }
+    }
+
+    let expiry = File::open(certpath)
+        .ok()
+        .and_then(|fd| certificate::extract_expiry(fd).ok().flatten());
+
+    match expiry.map(|exp| exp.duration_since(SystemTime::now())) {
+        Some(Err(_)) => {
+            report.add(name, CheckStatus::Failed, format!("{certpath} has expired"));
+            report.hint("delete the certificate to generate a new one");
+        }
+        Some(Ok(left)) if left < CERT_EXPIRY_WARNING => {
+            report.add(
+                name,
+                CheckStatus::Warning,
+                format!("{certpath} expires in {} days", left.as_secs() / 86400),
+            );
+        }
+        _ => report.add(
+            name,
+            CheckStatus::Ok,
+            format!("{certpath} valid for bridge id [{expected}]"),
+        ),
+    }
+}
+
+fn check_ports(report: &mut Report, home: &str, config: &AppConfig) {
+    let bconf = &config.bridge;
+    let ip = bconf
+        .interface
+        .as_deref()
+        .and_then(netwatch::interface_address)
+        .unwrap_or(bconf.ipaddress);
+
+    for (proto, enabled, port) in [
+        ("http", bconf.http_enabled, bconf.http_port),
+        ("https", bconf.https_enabled, bconf.https_port),
+    ] {
+        if enabled {
+            check_port(report, &format!("[{home}] {proto} port"), ip, port);
+        }
+    }
+}
+
+fn check_port(report: &mut Report, name: &str, ip: Ipv4Addr, port: u16) {
+    match TcpListener::bind((ip, port)) {
+        Ok(_) => report.add(name, CheckStatus::Ok, format!("{ip}:{port} available")),
+        Err(err) => {
+            report.add(name, CheckStatus::Failed, format!("{ip}:{port}: {err}"));
+            match err.kind() {
+                ErrorKind::AddrInUse => match bind::port_owner(port) {
+                    Some(owner) => report.hint(format!("{owner} is using this port")),
+                    None => report.hint("another program (or bifrost instance) is using this port"),
+                },
+                ErrorKind::PermissionDenied => {
+                    report.hint(
+                        "ports below 1024 require root, or the CAP_NET_BIND_SERVICE capability",
+                    );
+                }
+                ErrorKind::AddrNotAvailable => {
+                    report.hint("bridge.ipaddress must be an address of this machine");
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn check_mdns(report: &mut Report) {
+    match ServiceDaemon::new() {
+        Ok(mdns) => {
+            let _ = mdns.shutdown();
+            report.add("mdns", CheckStatus::Ok, "responder can be started");
+        }
+        Err(err) => {
+            report.add("mdns", CheckStatus::Failed, err.to_string());
+            report.hint("mdns needs multicast on udp port 5353 (use host networking with docker)");
+        }
+    }
+}
+
+async fn check_z2m(report: &mut Report, server: &str, url: &str) {
+    let name = format!("z2m [{server}]");
+
+    match timeout(Z2M_CONNECT_TIMEOUT, connect_async(url)).await {
+        Ok(Ok(_)) => report.add(name, CheckStatus::Ok, format!("connected to {url}")),
+        Ok(Err(err)) => {
+            report.add(name, CheckStatus::Failed, format!("{url}: {err}"));
+            report.hint(
+                "check that the url points to the zigbee2mqtt frontend (e.g. ws://host:8080/api)",
+            );
+        }
+        Err(_) => {
+            report.add(
+                name,
+                CheckStatus::Failed,
+                format!("{url}: connection timed out"),
+            );
+            report.hint(
+                "check that the zigbee2mqtt frontend is enabled, and reachable from this host",
+            );
+        }
+    }
+}
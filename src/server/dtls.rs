@@ -0,0 +1,396 @@
+//! DTLS for entertainment streaming, provided by OpenSSL.
+//!
+//! Streaming clients connect with a pre-shared key: the application key
+//! (username) is the psk identity, and the client key from pairing is the
+//! psk. Only DTLS 1.2 with the `TLS_PSK_WITH_AES_128_GCM_SHA256` cipher suite
+//! is offered, since that is what the hue protocol uses.
+//!
+//! All connections share a single udp socket, so OpenSSL does not do any
+//! network i/o itself: datagrams from a peer are handed to its connection,
+//! and the datagrams it produces are collected, to be sent by the caller.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use openssl::error::ErrorStack;
+use openssl::ex_data::Index;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use openssl::ssl::{
+    ErrorCode, Ssl, SslContext, SslMethod, SslOptions, SslRef, SslStream, SslVersion,
+};
+
+use crate::error::{ApiError, ApiResult};
+
+/// Most connections (handshakes included) kept at a time
+pub const MAX_CONNECTIONS: usize = 16;
+
+/// Largest datagram sent to a peer
+const MTU: u32 = 1400;
+
+/// Largest datagram accepted from a peer
+const MAX_DATAGRAM: usize = 2048;
+
+/// `TLS_PSK_WITH_AES_128_GCM_SHA256`, in OpenSSL terms
+const CIPHERS: &str = "PSK-AES128-GCM-SHA256";
+
+const COOKIE_LEN: usize = 16;
+
+fn error(err: impl Display) -> ApiError {
+    ApiError::Dtls(err.to_string())
+}
+
+/// The datagrams exchanged by a single connection, in place of a socket
+#[derive(Debug, Default)]
+struct Datagrams {
+    incoming: VecDeque<Vec<u8>>,
+    outgoing: Vec<Vec<u8>>,
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(datagram) = self.incoming.pop_front() else {
+            return Err(io::ErrorKind::WouldBlock.into());
+        };
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(len)
+    }
+}
+
+impl Write for Datagrams {
+    /* OpenSSL writes a whole datagram at a time */
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outgoing.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Connection {
+    stream: SslStream<Datagrams>,
+    established: bool,
+    last_seen: Instant,
+}
+
+/// Returns true, if `datagram` starts with a (first) client hello, i.e., the
+/// peer starts over
+fn is_client_hello(datagram: &[u8]) -> bool {
+    const CONTENT_HANDSHAKE: u8 = 22;
+    const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+
+    /* record header: type, version, epoch (0), sequence number, length */
+    datagram.len() > 13
+        && datagram[0] == CONTENT_HANDSHAKE
+        && datagram[3..5] == [0, 0]
+        && datagram[13] == HANDSHAKE_CLIENT_HELLO
+}
+
+/// The cookie for a peer (see RFC 6347, 4.2.1), so clients have to prove
+/// they can receive at their address before a handshake proceeds
+fn cookie(
+    key: &PKey<Private>,
+    ssl: &SslRef,
+    peer: Index<Ssl, SocketAddr>,
+) -> Result<Vec<u8>, ErrorStack> {
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    if let Some(peer) = ssl.ex_data(peer) {
+        signer.update(peer.to_string().as_bytes())?;
+    }
+    let mut mac = signer.sign_to_vec()?;
+    mac.truncate(COOKIE_LEN);
+    Ok(mac)
+}
+
+/// What came of handling a datagram
+#[derive(Debug, Default)]
+pub struct Output {
+    /// Datagrams to send back to the peer
+    pub replies: Vec<Vec<u8>>,
+    /// Application data received from the peer
+    pub data: Vec<Vec<u8>>,
+    /// The psk identity of the peer, if the handshake was just completed
+    pub established: Option<Vec<u8>>,
+    /// Why the connection was dropped, if it was
+    pub error: Option<ApiError>,
+}
+
+/// The server side of all DTLS connections on a socket, by peer address
+pub struct DtlsServer {
+    ctx: SslContext,
+    peer_index: Index<Ssl, SocketAddr>,
+    connections: HashMap<SocketAddr, Connection>,
+}
+
+impl DtlsServer {
+    /// Create a server, looking up the pre-shared key for an identity with
+    /// `psk`
+    pub fn new(psk: impl Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync + 'static) -> ApiResult<Self> {
+        let mut secret = [0; 32];
+        openssl::rand::rand_bytes(&mut secret).map_err(error)?;
+        let cookie_key = PKey::hmac(&secret).map_err(error)?;
+        let peer_index = Ssl::new_ex_index::<SocketAddr>().map_err(error)?;
+
+        let mut builder = SslContext::builder(SslMethod::dtls_server()).map_err(error)?;
+        builder
+            .set_min_proto_version(Some(SslVersion::DTLS1_2))
+            .map_err(error)?;
+        builder
+            .set_max_proto_version(Some(SslVersion::DTLS1_2))
+            .map_err(error)?;
+        builder.set_cipher_list(CIPHERS).map_err(error)?;
+        builder.set_options(SslOptions::COOKIE_EXCHANGE | SslOptions::NO_QUERY_MTU);
+
+        /* unknown identities get an empty key, which fails the handshake */
+        builder.set_psk_server_callback(move |_ssl, identity, buf| match identity.and_then(&psk) {
+            Some(key) if key.len() <= buf.len() => {
+                buf[..key.len()].copy_from_slice(&key);
+                Ok(key.len())
+            }
+            _ => Ok(0),
+        });
+
+        let key = cookie_key.clone();
+        builder.set_cookie_generate_cb(move |ssl, buf| {
+            let cookie = cookie(&key, ssl, peer_index)?;
+            buf[..cookie.len()].copy_from_slice(&cookie);
+            Ok(cookie.len())
+        });
+        builder.set_cookie_verify_cb(move |ssl, received| {
+            cookie(&cookie_key, ssl, peer_index).is_ok_and(|expected| {
+                expected.len() == received.len() && memcmp::eq(&expected, received)
+            })
+        });
+
+        Ok(Self {
+            ctx: builder.build(),
+            peer_index,
+            connections: HashMap::new(),
+        })
+    }
+
+    /// Returns true, if the handshake with `peer` has been completed
+    #[must_use]
+    pub fn is_established(&self, peer: &SocketAddr) -> bool {
+        self.connections
+            .get(peer)
+            .is_some_and(|conn| conn.established)
+    }
+
+    /// Handle a datagram from `peer`. On errors, the connection is dropped,
+    /// and the replies include the alert sent by OpenSSL, if any.
+    pub fn handle(&mut self, peer: SocketAddr, datagram: &[u8]) -> Output {
+        let mut out = Output::default();
+        let res = self.handle_datagram(peer, datagram, &mut out);
+
+        if let Err(err) = res {
+            if let Some(mut conn) = self.connections.remove(&peer) {
+                out.replies.append(&mut conn.stream.get_mut().outgoing);
+            }
+            out.error = Some(err);
+        }
+
+        out
+    }
+
+    fn handle_datagram(
+        &mut self,
+        peer: SocketAddr,
+        datagram: &[u8],
+        out: &mut Output,
+    ) -> ApiResult<()> {
+        if self.is_established(&peer) && is_client_hello(datagram) {
+            self.connections.remove(&peer);
+        }
+        if !self.connections.contains_key(&peer) {
+            self.make_room()?;
+            let conn = self.connection(peer)?;
+            self.connections.insert(peer, conn);
+        }
+        let Some(conn) = self.connections.get_mut(&peer) else {
+            return Ok(());
+        };
+
+        conn.last_seen = Instant::now();
+        conn.stream.get_mut().incoming.push_back(datagram.to_vec());
+
+        let mut closed = false;
+        if !conn.established {
+            match conn.stream.accept() {
+                Ok(()) => {
+                    conn.established = true;
+                    let identity = conn.stream.ssl().psk_identity().unwrap_or_default();
+                    out.established = Some(identity.to_vec());
+                }
+                Err(err) if err.code() == ErrorCode::WANT_READ => {}
+                Err(err) => return Err(error(err)),
+            }
+        }
+
+        while conn.established {
+            let mut buf = [0; MAX_DATAGRAM];
+            match conn.stream.ssl_read(&mut buf) {
+                Ok(len) => out.data.push(buf[..len].to_vec()),
+                Err(err) if err.code() == ErrorCode::WANT_READ => break,
+                Err(err) if err.code() == ErrorCode::ZERO_RETURN => {
+                    closed = true;
+                    break;
+                }
+                Err(err) => return Err(error(err)),
+            }
+        }
+
+        out.replies.append(&mut conn.stream.get_mut().outgoing);
+        if closed {
+            self.connections.remove(&peer);
+        }
+        Ok(())
+    }
+
+    fn connection(&self, peer: SocketAddr) -> ApiResult<Connection> {
+        let mut ssl = Ssl::new(&self.ctx).map_err(error)?;
+        ssl.set_ex_data(self.peer_index, peer);
+        ssl.set_mtu(MTU).map_err(error)?;
+        Ok(Connection {
+            stream: SslStream::new(ssl, Datagrams::default()).map_err(error)?,
+            established: false,
+            last_seen: Instant::now(),
+        })
+    }
+
+    /// Make room for another connection, by dropping the oldest handshake in
+    /// progress. Established connections are never dropped for new ones.
+    fn make_room(&mut self) -> ApiResult<()> {
+        if self.connections.len() < MAX_CONNECTIONS {
+            return Ok(());
+        }
+
+        let oldest = self
+            .connections
+            .iter()
+            .filter(|(_, conn)| !conn.established)
+            .min_by_key(|(_, conn)| conn.last_seen)
+            .map(|(peer, _)| *peer)
+            .ok_or_else(|| error("too many connections"))?;
+        self.connections.remove(&oldest);
+        Ok(())
+    }
+
+    /// Drop connections not heard from within `timeout`
+    pub fn expire(&mut self, timeout: Duration) {
+        self.connections
+            .retain(|_, conn| conn.last_seen.elapsed() < timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::ssl::SslStream;
+
+    use super::*;
+
+    const IDENTITY: &[u8] = b"0b5ca2fc-7a67-4e24-a5e9-5b1b3d7ec1ac";
+    const KEY: &[u8] = &[0x42; 16];
+
+    fn server() -> DtlsServer {
+        DtlsServer::new(|identity| (identity == IDENTITY).then(|| KEY.to_vec())).unwrap()
+    }
+
+    fn client(key: &'static [u8]) -> SslStream<Datagrams> {
+        let mut builder = SslContext::builder(SslMethod::dtls_client()).unwrap();
+        builder.set_cipher_list(CIPHERS).unwrap();
+        builder.set_psk_client_callback(move |_ssl, _hint, identity, psk| {
+            identity[..IDENTITY.len()].copy_from_slice(IDENTITY);
+            identity[IDENTITY.len()] = 0;
+            psk[..key.len()].copy_from_slice(key);
+            Ok(key.len())
+        });
+        let ssl = Ssl::new(&builder.build()).unwrap();
+        SslStream::new(ssl, Datagrams::default()).unwrap()
+    }
+
+    /// Run the handshake, returning the server output of each round trip
+    fn handshake(server: &mut DtlsServer, client: &mut SslStream<Datagrams>) -> Vec<Output> {
+        let peer = "127.0.0.1:5000".parse().unwrap();
+        let mut rounds = vec![];
+        for _ in 0..5 {
+            if client.connect().is_ok() && client.get_mut().outgoing.is_empty() {
+                break;
+            }
+            let mut out = Output::default();
+            for datagram in std::mem::take(&mut client.get_mut().outgoing) {
+                let mut res = server.handle(peer, &datagram);
+                out.replies.append(&mut res.replies);
+                out.established = out.established.or(res.established);
+                out.error = out.error.or(res.error);
+            }
+            client
+                .get_mut()
+                .incoming
+                .extend(out.replies.iter().cloned());
+            let done = out.error.is_some() || out.established.is_some();
+            rounds.push(out);
+            if done {
+                client.connect().ok();
+                break;
+            }
+        }
+        rounds
+    }
+
+    #[test]
+    fn hello_verify_request() {
+        let mut server = server();
+        let mut client = client(KEY);
+        assert!(client.connect().is_err());
+
+        /* the first client hello is answered with a cookie */
+        let peer = "127.0.0.1:5000".parse().unwrap();
+        let hello = client.get_mut().outgoing.remove(0);
+        assert!(is_client_hello(&hello));
+        let out = server.handle(peer, &hello);
+        assert!(out.error.is_none());
+        assert_eq!(out.replies[0][13], 3);
+        assert!(!server.is_established(&peer));
+    }
+
+    #[test]
+    fn stream_data() {
+        let mut server = server();
+        let mut client = client(KEY);
+        let rounds = handshake(&mut server, &mut client);
+        assert_eq!(
+            rounds.last().unwrap().established.as_deref(),
+            Some(IDENTITY)
+        );
+
+        client.ssl_write(b"HueStream").unwrap();
+        let peer = "127.0.0.1:5000".parse().unwrap();
+        let datagram = client.get_mut().outgoing.remove(0);
+        let out = server.handle(peer, &datagram);
+        assert!(out.error.is_none());
+        assert_eq!(out.data, vec![b"HueStream".to_vec()]);
+    }
+
+    #[test]
+    fn wrong_key() {
+        let mut server = server();
+        let rounds = handshake(&mut server, &mut client(&[0x17; 16]));
+        assert!(rounds.iter().all(|out| out.established.is_none()));
+
+        /* records failing authentication are dropped silently (RFC 6347,
+         * 4.1.2.7), so the handshake never completes, and expires */
+        let peer = "127.0.0.1:5000".parse().unwrap();
+        assert!(!server.is_established(&peer));
+        server.expire(Duration::ZERO);
+        assert!(server.connections.is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::hue::stream::StreamFrame;
+use crate::resource::Resources;
+use crate::server::bind;
+use crate::server::dtls::DtlsServer;
+
+/// Udp port for entertainment streams (fixed by the hue protocol)
+pub const ENTERTAINMENT_PORT: u16 = 2100;
+
+/// Streaming clients not heard from for this long are dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The pre-shared keys of streaming clients: the client key each got when
+/// pairing, by the application key (username) it uses as identity
+type PskTable = HashMap<Uuid, Vec<u8>>;
+
+fn psk_table(res: &Resources) -> PskTable {
+    res.whitelist()
+        .iter()
+        .filter_map(|(username, user)| Some((*username, user.clientkey?.as_bytes().to_vec())))
+        .collect()
+}
+
+fn lookup_psk(keys: &PskTable, identity: &[u8]) -> Option<Vec<u8>> {
+    let username = std::str::from_utf8(identity)
+        .ok()
+        .and_then(|id| Uuid::try_parse(id).ok())?;
+    keys.get(&username).cloned()
+}
+
+/// Receive entertainment streams (DTLS, see [`crate::server::dtls`]), and
+/// apply their frames to the lights of active entertainment sessions
+pub async fn entertainment_server(res: Arc<Mutex<Resources>>, ip: Ipv4Addr) -> ApiResult<()> {
+    let addr = SocketAddr::from((ip, ENTERTAINMENT_PORT));
+    let socket = UdpSocket::bind(addr).await.map_err(|err| {
+        ApiError::BindFailed(
+            "entertainment".to_string(),
+            addr,
+            bind::explain(ENTERTAINMENT_PORT, &err),
+        )
+    })?;
+    log::info!("entertainment listening on udp {addr}");
+
+    /* keys are looked up by OpenSSL during the handshake, so a copy is
+     * kept, refreshed whenever a handshake is in progress */
+    let keys = Arc::new(std::sync::Mutex::new(PskTable::new()));
+    let table = keys.clone();
+    let mut server = DtlsServer::new(move |identity| {
+        let keys = table.lock().ok()?;
+        lookup_psk(&keys, identity)
+    })?;
+    let mut expire = interval(IDLE_TIMEOUT);
+    let mut buf = [0; 2048];
+
+    loop {
+        let (len, peer) = select! {
+            recv = socket.recv_from(&mut buf) => recv?,
+            _ = expire.tick() => {
+                server.expire(IDLE_TIMEOUT);
+                continue;
+            }
+        };
+
+        let mut lock = res.lock().await;
+        if !server.is_established(&peer) {
+            if let Ok(mut keys) = keys.lock() {
+                *keys = psk_table(&lock);
+            }
+        }
+        let out = server.handle(peer, &buf[..len]);
+        if let Some(err) = &out.error {
+            log::warn!("[{peer}] Dropping entertainment stream: {err}");
+        }
+
+        if let Some(identity) = &out.established {
+            log::info!(
+                "[{peer}] Entertainment stream connected for [{}]",
+                String::from_utf8_lossy(identity)
+            );
+        }
+
+        for data in &out.data {
+            /* frames arrive many times per second, so problems are not logged loudly */
+            match StreamFrame::parse(data) {
+                Ok(frame) => {
+                    if let Err(err) = lock.entertainment_frame(&frame) {
+                        log::debug!("[{peer}] Ignoring entertainment frame: {err}");
+                    }
+                }
+                Err(err) => log::debug!("[{peer}] Invalid entertainment frame: {err}"),
+            }
+        }
+        drop(lock);
+
+        for reply in &out.replies {
+            socket.send_to(reply, peer).await?;
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mdns_sd::ServiceDaemon;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::{watch, Mutex};
+use tokio::time::{interval, sleep_until, timeout, Instant, MissedTickBehavior};
+
+use crate::config::{BridgeConfig, FailoverConfig};
+use crate::error::{ApiError, ApiResult};
+use crate::mdns;
+use crate::resource::Resources;
+
+/// Delay between a change on the primary and fetching its state, so a burst
+/// of changes is replicated at once
+const SYNC_DELAY: Duration = Duration::from_millis(500);
+
+/// A plain http connection to the primary instance
+struct Primary<'a> {
+    conf: &'a FailoverConfig,
+    timeout: Duration,
+}
+
+impl<'a> Primary<'a> {
+    fn new(conf: &'a FailoverConfig) -> Self {
+        Self {
+            conf,
+            timeout: Duration::from_secs(conf.check_interval.max(1)),
+        }
+    }
+
+    fn error(&self, msg: impl Display) -> ApiError {
+        ApiError::PrimaryFailed(self.conf.primary.clone(), msg.to_string())
+    }
+
+    /// Send a GET request, and read the response head. Requests are made
+    /// with http/1.0, so response bodies simply end when the connection is
+    /// closed.
+    async fn get(&self, path: &str, accept: &str) -> ApiResult<BufReader<TcpStream>> {
+        let head = format!(
+            "GET {path} HTTP/1.0\r\nHost: {}\r\nAccept: {accept}\r\nAuthorization: Bearer {}\r\n\r\n",
+            self.conf.primary, self.conf.token
+        );
+
+        let request = async {
+            let mut stream = TcpStream::connect(self.conf.primary.as_str()).await?;
+            stream.write_all(head.as_bytes()).await?;
+
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await?;
+
+            /* "HTTP/1.1 200 OK" */
+            let status: u16 = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .unwrap_or_default();
+
+            /* skip headers */
+            while reader.read_line(&mut line).await? > 0 {
+                if line.ends_with("\r\n\r\n") || line.ends_with("\n\n") {
+                    break;
+                }
+            }
+
+            Ok::<_, std::io::Error>((status, reader))
+        };
+
+        let (status, reader) = timeout(self.timeout, request)
+            .await
+            .map_err(|_| self.error("timeout"))?
+            .map_err(|err| self.error(err))?;
+
+        if status == 200 {
+            Ok(reader)
+        } else {
+            Err(self.error(format!("{path} returned status {status}")))
+        }
+    }
+
+    async fn health_check(&self) -> ApiResult<()> {
+        self.get("/api/config", "application/json").await?;
+        Ok(())
+    }
+
+    async fn events(&self) -> ApiResult<BufReader<TcpStream>> {
+        self.get("/eventstream/clip/v2", "text/event-stream").await
+    }
+
+    async fn state(&self) -> ApiResult<String> {
+        let mut reader = self.get("/bifrost/state", "application/yaml").await?;
+        let mut data = String::new();
+        timeout(self.timeout, reader.read_to_string(&mut data))
+            .await
+            .map_err(|_| self.error("timeout"))?
+            .map_err(|err| self.error(err))?;
+        Ok(data)
+    }
+}
+
+async fn next_event(events: &mut Option<BufReader<TcpStream>>, buf: &mut [u8]) -> usize {
+    match events {
+        Some(reader) => reader.read(buf).await.unwrap_or_default(),
+        None => std::future::pending().await,
+    }
+}
+
+async fn sync_due(at: Option<Instant>) {
+    match at {
+        Some(at) => sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn replicate(primary: &Primary<'_>, res: &Mutex<Resources>) -> ApiResult<()> {
+    let data = primary.state().await?;
+
+    let mut lock = res.lock().await;
+    let report = lock.read(data.as_bytes())?;
+    for problem in report.skipped {
+        log::warn!("Skipped invalid entry in replicated state: {problem}");
+    }
+
+    /* save the replicated state */
+    lock.state_channel().notify_one();
+    drop(lock);
+
+    log::debug!("Replicated state from primary ({} bytes)", data.len());
+    Ok(())
+}
+
+/// Run `task` only while this instance is in charge of the bridge.
+///
+/// See [`Resources::active_channel`]. The task is stopped when a standby
+/// hands the bridge back to the primary, and started again on the next
+/// takeover.
+pub async fn while_active<F, T>(mut active: watch::Receiver<bool>, task: F) -> ApiResult<()>
+where
+    F: Fn() -> T,
+    T: Future<Output = ApiResult<()>> + Send + 'static,
+{
+    loop {
+        if active.wait_for(|active| *active).await.is_err() {
+            return Ok(());
+        }
+
+        let mut handle = tokio::spawn(task());
+        select! {
+            res = &mut handle => return res?,
+            _ = active.wait_for(|active| !*active) => handle.abort(),
+        }
+    }
+}
+
+/// Run as a standby for a primary bifrost instance.
+///
+/// While the primary is healthy, its state is replicated whenever its event
+/// stream reports a change, and the bridge is not announced. After a number
+/// of failed health checks in a row, this instance takes over, by announcing
+/// the bridge (with the same bridge id) over mdns. Once the primary is back,
+/// the announcement is withdrawn again, and replication resumes. Changes
+/// made while taken over are not copied back to the primary.
+///
+/// Only while taken over, `active` is set, which starts the automation
+/// runners and z2m clients (see [`while_active`]), and lets z2m commands
+/// through. Replicated state therefore never races local changes.
+///
+/// The bridge is announced on the current `address`, and announced again if
+/// it changes while taken over.
+pub async fn standby(
+    res: Arc<Mutex<Resources>>,
+    bridge: BridgeConfig,
+    mut address: watch::Receiver<Ipv4Addr>,
+    conf: FailoverConfig,
+    active: watch::Sender<bool>,
+) -> ApiResult<()> {
+    let primary = Primary::new(&conf);
+    active.send_replace(false);
+
+    let mut check = interval(Duration::from_secs(conf.check_interval.max(1)));
+    check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut failures = 0;
+    let mut announced: Option<ServiceDaemon> = None;
+    let mut events = None;
+    let mut sync_at = None;
+    let mut buf = [0u8; 4096];
+
+    log::info!("Running as standby for primary bifrost at {}", conf.primary);
+
+    loop {
+        select! {
+            _ = check.tick() => {
+                if let Err(err) = primary.health_check().await {
+                    events = None;
+                    failures += 1;
+                    if failures <= conf.failures {
+                        log::warn!("Health check {failures}/{} failed: {err}", conf.failures);
+                    } else {
+                        log::debug!("Health check failed: {err}");
+                    }
+
+                    if failures == conf.failures.max(1) {
+                        log::error!("Primary bifrost at {} is down, taking over", conf.primary);
+                        sync_at = None;
+                        active.send_replace(true);
+                        let ip = *address.borrow_and_update();
+                        match mdns::register_mdns(bridge.mac, bridge.id(), ip, bridge.http_port) {
+                            Ok(daemon) => announced = Some(daemon),
+                            Err(err) => log::error!("Failed to announce bridge: {err}"),
+                        }
+                    }
+                    continue;
+                }
+
+                failures = 0;
+                if active.send_replace(false) {
+                    log::warn!("Primary bifrost at {} is back, returning to standby", conf.primary);
+                }
+                if let Some(daemon) = announced.take() {
+                    if let Err(err) = mdns::unregister_mdns(&daemon, bridge.mac) {
+                        log::error!("Failed to withdraw bridge announcement: {err}");
+                    }
+                }
+
+                if events.is_none() {
+                    match primary.events().await {
+                        Ok(reader) => {
+                            events = Some(reader);
+                            sync_at = Some(Instant::now());
+                        }
+                        Err(err) => log::warn!("Cannot follow primary event stream: {err}"),
+                    }
+                }
+            }
+
+            Ok(()) = address.changed(), if announced.is_some() => {
+                let ip = *address.borrow_and_update();
+                if let Some(daemon) = announced.take() {
+                    if let Err(err) = mdns::unregister_mdns(&daemon, bridge.mac) {
+                        log::error!("Failed to withdraw bridge announcement: {err}");
+                    }
+                }
+                match mdns::register_mdns(bridge.mac, bridge.id(), ip, bridge.http_port) {
+                    Ok(daemon) => announced = Some(daemon),
+                    Err(err) => log::error!("Failed to announce bridge: {err}"),
+                }
+            }
+
+            count = next_event(&mut events, &mut buf) => {
+                if count == 0 {
+                    log::debug!("Primary event stream closed");
+                    events = None;
+                } else {
+                    sync_at.get_or_insert_with(|| Instant::now() + SYNC_DELAY);
+                }
+            }
+
+            () = sync_due(sync_at) => {
+                sync_at = None;
+                if *active.borrow() {
+                    continue;
+                }
+                if let Err(err) = replicate(&primary, &res).await {
+                    log::warn!("Failed to replicate state: {err}");
+                }
+            }
+        }
+    }
+}
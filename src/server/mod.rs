@@ -1,33 +1,57 @@
+#[cfg(feature = "server-acme")]
+pub mod acme;
 pub mod banner;
+pub mod bind;
 pub mod certificate;
+pub mod clientcert;
+pub mod doctor;
+pub mod dtls;
+pub mod entertainment;
+pub mod failover;
+pub mod netwatch;
+#[cfg(feature = "server-systemd")]
+pub mod systemd;
 
 use std::fs::File;
 use std::io::Write;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{SocketAddr, TcpListener};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use axum::body::Body;
-use axum::extract::Request;
+use axum::extract::{DefaultBodyLimit, Request};
+use axum::http::HeaderValue;
+use axum::middleware::{self, Next};
 use axum::response::Response;
 use axum::routing::IntoMakeService;
 use axum::{Router, ServiceExt};
 use axum_server::service::MakeService;
-use axum_server::tls_rustls::RustlsConfig;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
 use tokio::select;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::Mutex;
-use tokio::time::sleep_until;
+use tokio::time::{interval, sleep_until, Interval};
 use tower::Layer;
 use tower_http::normalize_path::{NormalizePath, NormalizePathLayer};
+use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{info_span, Span};
 
+use crate::config::{HttpLimitsConfig, StateFileMode};
 use crate::error::ApiResult;
+use crate::journal::Journal;
+use crate::logging;
+use crate::model::{motion, rollout};
+use crate::otlp;
 use crate::resource::Resources;
 use crate::routes;
+use crate::server::certificate::BridgeId;
+use crate::server::clientcert::{ClientCertAcceptor, PinnedClientVerifier};
 use crate::state::AppState;
 
 fn trace_layer_on_response(response: &Response<Body>, latency: Duration, span: &Span) {
@@ -38,20 +62,63 @@ fn trace_layer_on_response(response: &Response<Body>, latency: Duration, span: &
     span.record("status", tracing::field::display(response.status()));
 }
 
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Assign an id to each request (or use the one provided by the client), and
+/// return it in the response. Everything logged while handling the request
+/// is tagged with this id.
+async fn request_id_layer(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64)
+        .map_or_else(|| format!("{:08x}", rand::random::<u32>()), str::to_string);
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let start = SystemTime::now();
+
+    let mut response = logging::with_request_id(Some(request_id.clone()), next.run(request)).await;
+
+    otlp::record_request(
+        &method,
+        &path,
+        response.status().as_u16(),
+        start,
+        &request_id,
+    );
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
 fn router(appstate: AppState) -> Router<()> {
-    routes::router(appstate).layer(
-        TraceLayer::new_for_http()
-            .make_span_with(|request: &Request| {
-                info_span!(
-                    "http",
-                    method = ?request.method(),
-                    uri = ?request.uri(),
-                    status = tracing::field::Empty,
-                    /* latency = tracing::field::Empty, */
-                )
-            })
-            .on_response(trace_layer_on_response),
-    )
+    let limits = appstate.config().bridge.http_limits.clone();
+
+    routes::router(appstate)
+        .layer(DefaultBodyLimit::max(limits.max_body_size))
+        /* event streams are not affected, since their response starts right away */
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            limits.request_timeout,
+        )))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request| {
+                    info_span!(
+                        "http",
+                        method = ?request.method(),
+                        uri = ?request.uri(),
+                        status = tracing::field::Empty,
+                        /* latency = tracing::field::Empty, */
+                    )
+                })
+                .on_response(trace_layer_on_response),
+        )
+        .layer(middleware::from_fn(request_id_layer))
 }
 
 #[must_use]
@@ -61,48 +128,272 @@ pub fn build_service(appstate: AppState) -> IntoMakeService<NormalizePath<Router
     ServiceExt::<Request>::into_make_service(normalized)
 }
 
-pub async fn http_server<S>(listen_addr: Ipv4Addr, listen_port: u16, svc: S) -> ApiResult<()>
+/// Drop connections from clients that are too slow to send their request
+/// headers (see [`HttpLimitsConfig`])
+fn limit_connections(builder: &mut Builder<TokioExecutor>, limits: &HttpLimitsConfig) {
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(Duration::from_secs(limits.header_timeout));
+}
+
+/// Serve http on `listener` (see [`bind::listen`])
+pub async fn http_server<S>(
+    listener: TcpListener,
+    svc: S,
+    limits: HttpLimitsConfig,
+) -> ApiResult<()>
 where
     S: Send + MakeService<SocketAddr, Request<Incoming>>,
     S::MakeFuture: Send,
 {
-    let addr = SocketAddr::from((listen_addr, listen_port));
-    log::info!("http listening on {}", addr);
-
-    axum_server::bind(addr).serve(svc).await?;
+    log::info!("http listening on {}", listener.local_addr()?);
+    let mut server = axum_server::from_tcp(listener);
+    limit_connections(server.http_builder(), &limits);
+    server.serve(svc).await?;
 
     Ok(())
 }
 
+/// Serve https on `listener` (see [`bind::listen`])
 pub async fn https_server<S>(
-    listen_addr: Ipv4Addr,
-    listen_port: u16,
+    listener: TcpListener,
     svc: S,
     config: RustlsConfig,
+    limits: HttpLimitsConfig,
 ) -> ApiResult<()>
 where
     S: Send + MakeService<SocketAddr, Request<Incoming>>,
     S::MakeFuture: Send,
 {
-    let addr = SocketAddr::from((listen_addr, listen_port));
-    log::info!("https listening on {}", addr);
+    let acceptor = ClientCertAcceptor::new(
+        RustlsAcceptor::new(config)
+            .handshake_timeout(Duration::from_secs(limits.handshake_timeout)),
+    );
 
-    axum_server::bind_rustls(addr, config).serve(svc).await?;
+    log::info!("https listening on {}", listener.local_addr()?);
+    let mut server = axum_server::from_tcp(listener).acceptor(acceptor);
+    limit_connections(server.http_builder(), &limits);
+    server.serve(svc).await?;
 
     Ok(())
 }
 
-pub async fn config_writer(res: Arc<Mutex<Resources>>, filename: Utf8PathBuf) -> ApiResult<()> {
+fn file_mtime(filename: &Utf8Path) -> Option<SystemTime> {
+    std::fs::metadata(filename)
+        .and_then(|md| md.modified())
+        .ok()
+}
+
+/// Create an interval ticking every `secs` seconds (at least one).
+#[must_use]
+pub fn interval_secs(secs: u64) -> Interval {
+    interval(Duration::from_secs(secs.max(1)))
+}
+
+/// Wait for the next tick of an optional interval. Without an interval, this
+/// never completes.
+pub async fn optional_tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Reload the https certificate when the certificate file changes (checked
+/// every `reload_interval` seconds, if set), or when receiving SIGHUP.
+///
+/// Existing connections (including event streams) are not affected, and
+/// an invalid certificate is rejected, keeping the current one in use.
+pub async fn cert_reloader(
+    config: RustlsConfig,
+    certfile: Utf8PathBuf,
+    bridge_id: BridgeId,
+    reload_interval: Option<u64>,
+    verifier: Option<Arc<PinnedClientVerifier>>,
+) -> ApiResult<()> {
+    let mut sighup = signal(SignalKind::hangup())?;
+    let mut reload = reload_interval.map(interval_secs);
+    let mut mtime = file_mtime(&certfile);
+
+    loop {
+        select! {
+            _ = sighup.recv() => {
+                log::info!("Received SIGHUP, reloading certificate [{certfile}]");
+            }
+            () = optional_tick(&mut reload) => {
+                let new_mtime = file_mtime(&certfile);
+                if new_mtime.is_none() || new_mtime == mtime {
+                    continue;
+                }
+                log::info!("Certificate [{certfile}] changed, reloading..");
+            }
+        }
+
+        mtime = file_mtime(&certfile);
+
+        if let Err(err) = certificate::check_certificate(&certfile, bridge_id) {
+            log::error!("Not reloading certificate [{certfile}]: {err}");
+            continue;
+        }
+
+        match clientcert::server_config(&certfile, verifier.clone()) {
+            Ok(tls) => {
+                config.reload_from_config(Arc::new(tls));
+                log::info!("Certificate [{certfile}] reloaded");
+            }
+            Err(err) => log::error!("Failed to reload certificate [{certfile}]: {err}"),
+        }
+    }
+}
+
+/// Tracks the state file on disk, to detect changes made by someone else
+struct StateFileWatcher {
+    filename: Utf8PathBuf,
+    mtime: Option<SystemTime>,
+}
+
+impl StateFileWatcher {
+    fn new(filename: Utf8PathBuf) -> Self {
+        let mut res = Self {
+            filename,
+            mtime: None,
+        };
+        res.mark();
+        res
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        file_mtime(&self.filename)
+    }
+
+    /// Remember the current state file as our own
+    fn mark(&mut self) {
+        self.mtime = self.modified();
+    }
+
+    /// Returns the contents of the state file, if it was changed by someone
+    /// else (i.e., it no longer matches `known`)
+    fn external_change(&mut self, known: &str) -> ApiResult<Option<String>> {
+        let mtime = self.modified();
+        if mtime.is_none() || mtime == self.mtime {
+            return Ok(None);
+        }
+
+        let data = std::fs::read_to_string(&self.filename)?;
+        if data == known {
+            self.mtime = mtime;
+            return Ok(None);
+        }
+
+        Ok(Some(data))
+    }
+}
+
+fn reload_state(res: &mut Resources, filename: &Utf8Path, data: &str) -> ApiResult<String> {
+    log::warn!("State file {filename} changed externally, reloading..");
+
+    match res.read(data.as_bytes()) {
+        Ok(report) => {
+            for problem in report.skipped {
+                log::error!("Skipped invalid entry in state file {filename}: {problem}");
+            }
+        }
+        Err(err) => log::error!("Failed to reload state file {filename}: {err}"),
+    }
+
+    res.serialize()
+}
+
+/// Stop entertainment sessions when the streaming peer disappears, i.e., no
+/// stream data has been received within `timeout`.
+pub async fn entertainment_watchdog(
+    res: Arc<Mutex<Resources>>,
+    timeout: Duration,
+) -> ApiResult<()> {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    let mut check = interval(CHECK_INTERVAL);
+    loop {
+        check.tick().await;
+        res.lock().await.entertainment_expire(timeout)?;
+    }
+}
+
+pub async fn effect_runner(res: Arc<Mutex<Resources>>) -> ApiResult<()> {
+    const STEP_INTERVAL: Duration = Duration::from_millis(100);
+
+    let mut step = interval(STEP_INTERVAL);
+    loop {
+        step.tick().await;
+        res.lock().await.effect_step()?;
+    }
+}
+
+pub async fn motion_runner(res: Arc<Mutex<Resources>>) -> ApiResult<()> {
+    let mut check = interval(motion::CHECK_INTERVAL);
+    loop {
+        check.tick().await;
+        res.lock().await.motion_expire()?;
+    }
+}
+
+pub async fn rollout_runner(res: Arc<Mutex<Resources>>) -> ApiResult<()> {
+    let mut step = interval(rollout::SLICE_INTERVAL);
+    loop {
+        step.tick().await;
+        res.lock().await.rollout_step()?;
+    }
+}
+
+/// Append changes to the journal. Only finding the changes needs the
+/// resources lock, so writing (and syncing) the journal does not hold up
+/// requests.
+async fn journal_record(journal: &mut Journal, res: &Mutex<Resources>) -> ApiResult<()> {
+    let data = journal.collect(&*res.lock().await)?;
+    journal.append(&data)
+}
+
+pub async fn config_writer(
+    res: Arc<Mutex<Resources>>,
+    filename: Utf8PathBuf,
+    mode: StateFileMode,
+) -> ApiResult<()> {
     const STABILIZE_TIME: Duration = Duration::from_secs(1);
+    const WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
     let rx = res.lock().await.state_channel();
     let tmp = filename.with_extension("tmp");
 
     let mut old_state = res.lock().await.serialize()?;
+    let mut journal = Journal::create(Journal::filename(&filename))?;
+    journal.rebase(&*res.lock().await)?;
+    let mut watcher = StateFileWatcher::new(filename.clone());
+    let mut watch = interval(WATCH_INTERVAL);
 
     loop {
-        /* Wait for change notification */
-        rx.notified().await;
+        /* Wait for change notification (or, in reload mode, external changes) */
+        select! {
+            () = rx.notified() => {},
+            _ = watch.tick() => {
+                if mode == StateFileMode::Reload {
+                    if let Some(data) = watcher.external_change(&old_state)? {
+                        let mut lock = res.lock().await;
+                        old_state = reload_state(&mut lock, &filename, &data)?;
+                        journal.rebase(&lock)?;
+                        drop(lock);
+                        journal.truncate()?;
+                        watcher.mark();
+                    }
+                }
+                continue;
+            }
+        }
+
+        /* Journal changes right away, since saving the state file waits */
+        journal_record(&mut journal, &res).await?;
 
         /* Updates often happen in burst, and we don't want to write the state
          * file over and over, so ignore repeated update notifications within
@@ -110,7 +401,7 @@ pub async fn config_writer(res: Arc<Mutex<Resources>>, filename: Utf8PathBuf) ->
         let deadline = tokio::time::Instant::now() + STABILIZE_TIME;
         loop {
             select! {
-                () = rx.notified() => {},
+                () = rx.notified() => journal_record(&mut journal, &res).await?,
                 () = sleep_until(deadline) => break,
             }
         }
@@ -123,11 +414,35 @@ pub async fn config_writer(res: Arc<Mutex<Resources>>, filename: Utf8PathBuf) ->
             continue;
         }
 
+        /* Make sure we don't silently clobber changes made by someone else */
+        if let Some(data) = watcher.external_change(&old_state)? {
+            match mode {
+                StateFileMode::Overwrite => {
+                    log::warn!("State file {filename} changed externally, overwriting..");
+                }
+                StateFileMode::Reload => {
+                    let mut lock = res.lock().await;
+                    old_state = reload_state(&mut lock, &filename, &data)?;
+                    journal.rebase(&lock)?;
+                    drop(lock);
+                    journal.truncate()?;
+                    watcher.mark();
+                    continue;
+                }
+                StateFileMode::Locked => {
+                    log::warn!("State file {filename} changed externally, not saving (locked)");
+                    continue;
+                }
+            }
+        }
+
         log::debug!("Config changed, saving..");
 
         let mut fd = File::create(&tmp)?;
         fd.write_all(new_state.as_bytes())?;
         std::fs::rename(&tmp, &filename)?;
+        watcher.mark();
+        journal.truncate()?;
 
         old_state = new_state;
     }
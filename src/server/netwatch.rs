@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::time::Duration;
+
+use mdns_sd::ServiceDaemon;
+use tokio::select;
+use tokio::sync::watch;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::error::ApiResult;
+use crate::mdns;
+use crate::server::bind;
+use crate::state::AppState;
+
+/// Time between checks for a changed interface address
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The (first) ipv4 address of a network interface, if it has one
+#[must_use]
+pub fn interface_address(name: &str) -> Option<Ipv4Addr> {
+    if_addrs::get_if_addrs()
+        .ok()?
+        .into_iter()
+        .filter(|iface| iface.name == name)
+        .find_map(|iface| match iface.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+}
+
+/// Follow the address of the network interface the bridge is on.
+///
+/// When the address changes (e.g., after a dhcp renewal, or wifi
+/// reconnect), the address the bridge reports to clients is updated, and
+/// listeners started with [`follow_address`] are bound again on the new
+/// address.
+///
+/// With `announce` set, the bridge is announced over mdns, and announced
+/// again whenever the address changes.
+pub async fn address_watcher(
+    appstate: AppState,
+    interface: String,
+    announce: bool,
+) -> ApiResult<()> {
+    let bconf = appstate.config().bridge.clone();
+
+    let mut daemon: Option<ServiceDaemon> = if announce {
+        Some(mdns::register_mdns(
+            bconf.mac,
+            bconf.id(),
+            appstate.ipaddress(),
+            bconf.http_port,
+        )?)
+    } else {
+        None
+    };
+
+    let mut check = interval(CHECK_INTERVAL);
+    check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut missing = false;
+
+    log::info!("Following address of interface {interface}");
+
+    loop {
+        check.tick().await;
+
+        let Some(ip) = interface_address(&interface) else {
+            /* keep the last known address, until the interface is back */
+            if !missing {
+                log::warn!("Interface {interface} has no ipv4 address");
+                missing = true;
+            }
+            continue;
+        };
+        missing = false;
+
+        if !appstate.set_ipaddress(ip) {
+            continue;
+        }
+
+        log::warn!("Address of interface {interface} changed to {ip}");
+
+        if let Some(old) = daemon.take() {
+            if let Err(err) = mdns::unregister_mdns(&old, bconf.mac) {
+                log::error!("Failed to withdraw bridge announcement: {err}");
+            }
+        }
+
+        if announce {
+            match mdns::register_mdns(bconf.mac, bconf.id(), ip, bconf.http_port) {
+                Ok(new) => daemon = Some(new),
+                Err(err) => log::error!("Failed to announce bridge: {err}"),
+            }
+        }
+    }
+}
+
+/// Bind a listener for `proto` on the bridge address (see [`bind::listen`]),
+/// and run `serve` on it. When the bridge address changes, the listener is
+/// closed, and bound again on the new address.
+pub async fn follow_address<F, Fut>(
+    mut address: watch::Receiver<Ipv4Addr>,
+    proto: &'static str,
+    port: u16,
+    fallback: Option<u16>,
+    serve: F,
+) -> ApiResult<()>
+where
+    F: Fn(TcpListener) -> Fut + Send,
+    Fut: Future<Output = ApiResult<()>> + Send,
+{
+    loop {
+        let ip = *address.borrow_and_update();
+        let listener = bind::listen(proto, ip, port, fallback).await?;
+
+        select! {
+            res = serve(listener) => return res,
+            Ok(()) = address.changed() => {
+                log::info!("Bridge address changed, restarting {proto} listener");
+            }
+        }
+    }
+}
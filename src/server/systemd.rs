@@ -0,0 +1,77 @@
+use std::net::TcpListener;
+use std::time::Duration;
+
+use listenfd::ListenFd;
+use sd_notify::NotifyState;
+use tokio::time::sleep;
+
+use crate::error::ApiResult;
+
+/*
+ * Take over listening sockets passed in by systemd (socket activation)
+ *
+ * The sockets are matched to the http and https servers by their port number,
+ * so the socket unit does not need to name them in any particular way.
+ */
+#[allow(clippy::similar_names)]
+pub fn take_listeners(
+    http_port: u16,
+    https_port: u16,
+) -> ApiResult<(Option<TcpListener>, Option<TcpListener>)> {
+    let mut fds = ListenFd::from_env();
+    let mut http_listener = None;
+    let mut https_listener = None;
+
+    for idx in 0..fds.len() {
+        let Some(listener) = fds.take_tcp_listener(idx)? else {
+            continue;
+        };
+
+        let addr = listener.local_addr()?;
+        if addr.port() == http_port && http_listener.is_none() {
+            log::info!("Using http socket {addr} from systemd");
+            http_listener = Some(listener);
+        } else if addr.port() == https_port && https_listener.is_none() {
+            log::info!("Using https socket {addr} from systemd");
+            https_listener = Some(listener);
+        } else {
+            log::warn!("Ignoring unexpected socket {addr} from systemd");
+        }
+    }
+
+    Ok((http_listener, https_listener))
+}
+
+fn notify(state: NotifyState) {
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        log::warn!("Failed to notify systemd: {err}");
+    }
+}
+
+pub fn notify_ready() {
+    notify(NotifyState::Ready);
+}
+
+pub fn notify_stopping() {
+    notify(NotifyState::Stopping);
+}
+
+/* If the systemd watchdog is enabled, returns the interval to ping it at */
+#[must_use]
+pub fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        Some(Duration::from_micros(usec) / 2)
+    } else {
+        None
+    }
+}
+
+pub async fn watchdog(interval: Duration) -> ApiResult<()> {
+    log::debug!("Pinging systemd watchdog every {}ms", interval.as_millis());
+
+    loop {
+        sd_notify::notify(false, &[NotifyState::Watchdog])?;
+        sleep(interval).await;
+    }
+}
@@ -0,0 +1,72 @@
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+use crate::error::ApiResult;
+use crate::hue::upnp;
+use crate::state::AppState;
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+
+/* other upnp services (e.g., media servers) often listen on the ssdp port
+ * too, so the port is shared */
+fn ssdp_socket() -> ApiResult<UdpSocket> {
+    let sock = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    sock.set_reuse_address(true)?;
+    sock.set_nonblocking(true)?;
+    sock.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SSDP_PORT).into())?;
+    sock.join_multicast_v4(&SSDP_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    Ok(UdpSocket::from_std(sock.into())?)
+}
+
+/// The search target of an ssdp search request, or None for other messages
+/// (e.g., announcements of other devices)
+fn search_target(msg: &str) -> Option<&str> {
+    let mut lines = msg.lines();
+    if !lines.next()?.starts_with("M-SEARCH ") {
+        return None;
+    }
+    lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("st").then(|| value.trim())
+    })
+}
+
+/// Answer ssdp searches for hue bridges, like a real bridge does. Echo
+/// devices only find bridges this way (not over mdns).
+pub async fn responder(appstate: AppState) -> ApiResult<()> {
+    let sock = ssdp_socket()?;
+    let http_port = appstate.config().bridge.http_port;
+
+    log::info!("Answering ssdp searches on port {SSDP_PORT}");
+
+    let mut buf = [0u8; 2048];
+    loop {
+        let (len, peer) = sock.recv_from(&mut buf).await?;
+        let Some(st) = std::str::from_utf8(&buf[..len])
+            .ok()
+            .and_then(search_target)
+        else {
+            continue;
+        };
+
+        let answers = upnp::ssdp_answers(
+            st,
+            appstate.ipaddress(),
+            http_port,
+            &appstate.api_short_config(),
+        );
+
+        if !answers.is_empty() {
+            log::debug!("Answering ssdp search for {st} from {peer}");
+        }
+
+        for answer in answers {
+            if let Err(err) = sock.send_to(answer.as_bytes(), peer).await {
+                log::warn!("Failed to answer ssdp search from {peer}: {err}");
+            }
+        }
+    }
+}
@@ -1,60 +1,206 @@
 use std::collections::HashMap;
 use std::fs::File;
+use std::net::Ipv4Addr;
 use std::sync::Arc;
 
 use axum_server::tls_rustls::RustlsConfig;
 use camino::Utf8Path;
-use chrono::Utc;
-use tokio::sync::Mutex;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use tokio::sync::{watch, Mutex};
 use uuid::Uuid;
 
 use crate::config::AppConfig;
 use crate::error::{ApiError, ApiResult};
+use crate::fade::FadeEngine;
 use crate::hue::legacy_api::{ApiConfig, ApiShortConfig, Whitelist};
+use crate::journal::Journal;
+use crate::migrate;
+use crate::model::layout;
+use crate::model::whitelist;
 use crate::resource::Resources;
-use crate::server::{self, certificate};
+#[cfg(feature = "server-acme")]
+use crate::server::acme;
+use crate::server::certificate;
+use crate::server::clientcert::{self, PinnedClientVerifier};
+use crate::server::netwatch;
+use crate::timezone;
 
 #[derive(Clone)]
 pub struct AppState {
     conf: Arc<AppConfig>,
     pub res: Arc<Mutex<Resources>>,
+    pub fades: Arc<Mutex<FadeEngine>>,
+    pub lightscan: Arc<Mutex<Option<DateTime<Utc>>>>,
+    address: Arc<watch::Sender<Ipv4Addr>>,
+    tz: Tz,
+    client_verifier: Option<Arc<PinnedClientVerifier>>,
+    #[cfg(feature = "server-acme")]
+    pub acme_tokens: acme::AcmeTokens,
 }
 
 impl AppState {
     pub fn from_config(config: AppConfig) -> ApiResult<Self> {
         let certfile = &config.bifrost.cert_file;
+        let tz = timezone::parse(&config.bridge.timezone)?;
 
         let certpath = Utf8Path::new(certfile);
-        if certpath.is_file() {
-            certificate::check_certificate(certpath, config.bridge.mac)?;
-        } else {
+        if !certpath.is_file() {
             log::warn!("Missing certificate file [{certfile}], generating..");
-            certificate::generate_and_save(certpath, config.bridge.mac)?;
+            certificate::generate_and_save(certpath, config.bridge.id())?;
+        } else if certpath.metadata()?.len() == 0 {
+            log::warn!("Empty certificate file [{certfile}], generating..");
+            certificate::generate_and_save(certpath, config.bridge.id())?;
+        } else {
+            certificate::check_certificate(certpath, config.bridge.id())?;
         }
 
         let mut res = Resources::new();
 
         if let Ok(fd) = File::open(&config.bifrost.state_file) {
             log::debug!("Existing state file found, loading..");
-            res.read(fd)?;
+            let report = res.read(fd)?;
+            if !report.skipped.is_empty() {
+                /* keep the skipped entries around, since the next save drops them */
+                let backup = config.bifrost.state_file.with_extension("invalid.yaml");
+                log::warn!(
+                    "Skipped {} invalid entries in state file, original saved as {backup}:",
+                    report.skipped.len()
+                );
+                for problem in &report.skipped {
+                    log::warn!("  {problem}");
+                }
+                std::fs::copy(&config.bifrost.state_file, &backup)?;
+            }
+            if let Some(version) = report.migrated_from {
+                /* older versions of bifrost cannot read the new layout */
+                let backup = config
+                    .bifrost
+                    .state_file
+                    .with_extension(format!("v{version}.yaml"));
+                log::info!(
+                    "Migrating state file from version {version} to {} ({} entries updated), original saved as {backup}",
+                    migrate::STATE_VERSION,
+                    report.repaired
+                );
+                std::fs::copy(&config.bifrost.state_file, &backup)?;
+            } else if report.repaired > 0 {
+                log::info!("Repaired {} entries in state file", report.repaired);
+            }
+
+            /* changes made after the state file was last saved */
+            let journal = Journal::filename(&config.bifrost.state_file);
+            let count = Journal::replay(&mut res, &journal)?;
+            if count > 0 {
+                log::info!("Replayed {count} change(s) from journal {journal}");
+            }
+            if count > 0 || report.changed() {
+                log::info!("Saving updated state file..");
+                let tmp = config.bifrost.state_file.with_extension("tmp");
+                res.write(File::create(&tmp)?)?;
+                std::fs::rename(&tmp, &config.bifrost.state_file)?;
+            }
         } else {
             log::debug!("No state file found, initializing..");
-            res.init(&server::certificate::hue_bridge_id(config.bridge.mac))?;
+            res.init(&config.bridge.id().to_string(), config.bridge.locale)?;
+        }
+
+        res.set_timezone(tz.name())?;
+        res.entertainment_reset()?;
+        layout::reconcile(&mut res, &config.layout, config.bridge.locale)?;
+
+        let link_button = &config.bifrost.link_button;
+        if link_button.required && link_button.startup_window > 0 {
+            let window = i64::try_from(link_button.startup_window).unwrap_or(i64::MAX);
+            res.link_button_press(Duration::seconds(window));
+        }
+
+        let fades = FadeEngine::new(&config.bifrost.fade_file)?;
+
+        let client_verifier = if config.bifrost.client_certificates.is_empty() {
+            None
+        } else {
+            Some(Arc::new(PinnedClientVerifier::load(
+                &config.bifrost.client_certificates,
+            )?))
+        };
+
+        let mut ipaddress = config.bridge.ipaddress;
+        if let Some(interface) = &config.bridge.interface {
+            if let Some(ip) = netwatch::interface_address(interface) {
+                ipaddress = ip;
+            } else {
+                log::warn!("Interface {interface} has no ipv4 address, using {ipaddress}");
+            }
         }
 
         let conf = Arc::new(config);
         let res = Arc::new(Mutex::new(res));
+        let fades = Arc::new(Mutex::new(fades));
+        let lightscan = Arc::new(Mutex::new(None));
 
-        Ok(Self { conf, res })
+        Ok(Self {
+            conf,
+            res,
+            fades,
+            lightscan,
+            address: Arc::new(watch::Sender::new(ipaddress)),
+            tz,
+            client_verifier,
+            #[cfg(feature = "server-acme")]
+            acme_tokens: acme::AcmeTokens::default(),
+        })
     }
 
-    pub async fn tls_config(&self) -> ApiResult<RustlsConfig> {
-        let certfile = &self.conf.bifrost.cert_file;
+    pub fn tls_config(&self) -> ApiResult<RustlsConfig> {
+        let mut certfile = &self.conf.bifrost.cert_file;
+
+        /* use the acme certificate instead, once it has been obtained */
+        if let Some(acme) = &self.conf.bifrost.acme {
+            if cfg!(feature = "server-acme") && acme.cert_file.is_file() {
+                certfile = &acme.cert_file;
+            }
+        }
 
         log::debug!("Loading certificate from [{certfile}]");
-        RustlsConfig::from_pem_file(&certfile, &certfile)
-            .await
-            .map_err(|e| ApiError::Certificate(certfile.to_owned(), e))
+        let config = clientcert::server_config(certfile, self.client_verifier())
+            .map_err(|e| ApiError::Certificate(certfile.to_owned(), e))?;
+
+        Ok(RustlsConfig::from_config(Arc::new(config)))
+    }
+
+    /// Verifier for pinned client certificates, if configured
+    #[must_use]
+    pub fn client_verifier(&self) -> Option<Arc<PinnedClientVerifier>> {
+        self.client_verifier.clone()
+    }
+
+    /// The current address of the bridge. This is `bridge.ipaddress`, unless
+    /// `bridge.interface` is set (see [`netwatch::address_watcher`]).
+    #[must_use]
+    pub fn ipaddress(&self) -> Ipv4Addr {
+        *self.address.borrow()
+    }
+
+    /// Update the address of the bridge. Returns true if it changed.
+    #[must_use]
+    pub fn set_ipaddress(&self, ip: Ipv4Addr) -> bool {
+        self.address.send_if_modified(|current| {
+            let changed = *current != ip;
+            *current = ip;
+            changed
+        })
+    }
+
+    /// Follow changes of the bridge address
+    #[must_use]
+    pub fn address_updates(&self) -> watch::Receiver<Ipv4Addr> {
+        self.address.subscribe()
+    }
+
+    #[must_use]
+    pub const fn timezone(&self) -> Tz {
+        self.tz
     }
 
     #[must_use]
@@ -64,30 +210,55 @@ impl AppState {
 
     #[must_use]
     pub fn api_short_config(&self) -> ApiShortConfig {
-        let mac = self.conf.bridge.mac;
-        ApiShortConfig {
-            bridgeid: certificate::hue_bridge_id(mac),
-            mac,
+        let config = ApiShortConfig {
+            bridgeid: self.conf.bridge.id().to_string(),
+            mac: self.conf.bridge.mac,
             ..Default::default()
+        };
+        if self.quirks() {
+            config.with_quirks()
+        } else {
+            config
         }
     }
 
+    /// True if the v1 api should make the adjustments homebridge-hue needs
     #[must_use]
-    pub fn api_config(&self, username: Uuid) -> ApiConfig {
+    pub fn quirks(&self) -> bool {
+        self.conf.bifrost.quirks.homebridge
+    }
+
+    /// The v1 bridge config, as seen by `username`. Unpaired usernames are
+    /// listed too, since they are accepted until the whitelist is enforced
+    /// (see [`crate::config::PermissionsConfig`]).
+    #[must_use]
+    pub fn api_config(&self, username: Uuid, users: &whitelist::Whitelist) -> ApiConfig {
+        let mut whitelist: HashMap<Uuid, Whitelist> = users
+            .iter()
+            .map(|(id, user)| {
+                let entry = Whitelist {
+                    create_date: user.create_date,
+                    last_use_date: user.last_use_date,
+                    name: user.name.clone(),
+                };
+                (*id, entry)
+            })
+            .collect();
+        whitelist.entry(username).or_insert_with(|| Whitelist {
+            create_date: Utc::now(),
+            last_use_date: Utc::now(),
+            name: "User#foo".to_string(),
+        });
+
         ApiConfig {
             short_config: self.api_short_config(),
-            ipaddress: self.conf.bridge.ipaddress,
+            ipaddress: self.ipaddress(),
             netmask: self.conf.bridge.netmask,
             gateway: self.conf.bridge.gateway,
-            timezone: self.conf.bridge.timezone.clone(),
-            whitelist: HashMap::from([(
-                username,
-                Whitelist {
-                    create_date: Utc::now(),
-                    last_use_date: Utc::now(),
-                    name: "User#foo".to_string(),
-                },
-            )]),
+            timezone: self.tz.name().to_string(),
+            localtime: timezone::now(self.tz).naive_local(),
+            linkbutton: !self.conf.bifrost.link_button.required || users.is_pressed(),
+            whitelist,
             ..ApiConfig::default()
         }
     }
@@ -0,0 +1,44 @@
+//! Timezone handling.
+//!
+//! Schedules and behaviors run in the timezone of the bridge (from the
+//! config file), not the timezone of the host bifrost happens to run on.
+
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::error::{ApiError, ApiResult};
+
+/// Parse an IANA timezone name, like "Europe/Copenhagen"
+pub fn parse(name: &str) -> ApiResult<Tz> {
+    name.parse()
+        .map_err(|_| ApiError::InvalidTimezone(name.to_string()))
+}
+
+/// The current time, in the given timezone
+#[must_use]
+pub fn now(tz: Tz) -> DateTime<Tz> {
+    Utc::now().with_timezone(&tz)
+}
+
+/// Convert a utc timestamp to local time
+#[must_use]
+pub fn to_local(tz: Tz, dt: &DateTime<Utc>) -> DateTime<Tz> {
+    dt.with_timezone(&tz)
+}
+
+/// Convert a local time to utc.
+///
+/// Local times that occur twice (when daylight saving time ends) resolve to
+/// the first occurrence. Local times that never occur (when daylight saving
+/// time starts) resolve to the same time, one hour later.
+#[must_use]
+pub fn to_utc(tz: Tz, local: &NaiveDateTime) -> Option<DateTime<Utc>> {
+    let dt = match tz.from_local_datetime(local) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt,
+        LocalResult::None => tz
+            .from_local_datetime(&(*local + Duration::hours(1)))
+            .earliest()?,
+    };
+
+    Some(dt.with_timezone(&Utc))
+}
@@ -1,11 +1,13 @@
 #![allow(clippy::struct_excessive_bools)]
 
-use std::{collections::HashMap, fmt::Debug};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
 
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 use crate::hue::api::MirekSchema;
+use crate::model::units;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
@@ -41,6 +43,12 @@ pub enum Message {
 
     #[serde(rename = "bridge/extensions")]
     BridgeExtensions(Value),
+
+    #[serde(rename = "bridge/response/networkmap")]
+    BridgeNetworkMap(BridgeResponse),
+
+    #[serde(rename = "bridge/response/install_code/add")]
+    BridgeInstallCode(BridgeResponse),
 }
 
 #[derive(Serialize, Deserialize, Clone, Hash)]
@@ -53,6 +61,33 @@ impl Debug for IeeeAddress {
     }
 }
 
+impl IeeeAddress {
+    /// Parse an address in the form returned by [`Self::topic`]
+    #[must_use]
+    pub fn from_topic(topic: &str) -> Option<Self> {
+        u64::from_str_radix(topic.strip_prefix("0x")?, 16)
+            .ok()
+            .map(Self)
+    }
+
+    /// The address as understood by z2m, in place of a friendly name
+    #[must_use]
+    pub fn topic(&self) -> String {
+        format!("0x{:016x}", self.0)
+    }
+
+    /// The address formatted as a mac address, like the hue api expects
+    #[must_use]
+    pub fn mac_address(&self) -> String {
+        self.0
+            .to_be_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
 fn ieee_address<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: Deserializer<'de>,
@@ -74,6 +109,7 @@ pub struct Other {
 #[serde(rename_all = "lowercase")]
 pub enum BridgeOnlineState {
     Online,
+    Offline,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -82,6 +118,17 @@ pub struct BridgeState {
     pub state: BridgeOnlineState,
 }
 
+/// Response to a `bridge/request/..` message. On failure, `data` is empty,
+/// and `error` explains why.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BridgeResponse {
+    pub status: String,
+    #[serde(default)]
+    pub data: Value,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct BridgeEvent {
@@ -92,6 +139,29 @@ pub struct BridgeEvent {
     pub event_type: String,
 }
 
+/// Data of the device events (`device_joined`, `device_announce`,
+/// `device_interview` and `device_leave`) of a [`BridgeEvent`]
+#[derive(Debug, Deserialize, Clone)]
+pub struct BridgeEventDevice {
+    pub friendly_name: String,
+    pub ieee_address: String,
+    /* interview status: "started", "successful" or "failed" */
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub supported: Option<bool>,
+    #[serde(default)]
+    pub definition: Option<BridgeEventDefinition>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BridgeEventDefinition {
+    pub model: String,
+    pub vendor: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct BridgeLogging {
@@ -339,6 +409,131 @@ impl Device {
         })
     }
 
+    /// All lights exposed by the device. Devices with more than one light
+    /// (e.g., dual-channel dimmers) expose one for each endpoint.
+    #[must_use]
+    pub fn expose_lights(&self) -> Vec<&ExposeLight> {
+        let lights: Vec<&ExposeLight> = self
+            .exposes()
+            .iter()
+            .filter_map(|exp| match exp {
+                Expose::Light(light) => Some(light),
+                _ => None,
+            })
+            .collect();
+
+        /* lights without an endpoint cannot be told apart */
+        if lights.iter().any(|light| light.endpoint.is_none()) {
+            return lights.into_iter().take(1).collect();
+        }
+        lights
+    }
+
+    /// True if the device has a native implementation of the named effect
+    #[must_use]
+    pub fn has_effect(&self, effect: &str) -> bool {
+        self.exposes().iter().any(|exp| match exp {
+            Expose::Enum(ExposeEnum { name, values, .. }) if name == "effect" => {
+                values.iter().any(|value| value == effect)
+            }
+            _ => false,
+        })
+    }
+
+    /// Number of buttons on a Zigbee Green Power switch (e.g. "Friends of Hue"
+    /// switches), derived from the `press_N` actions it exposes.
+    #[must_use]
+    pub fn green_power_buttons(&self) -> Option<u32> {
+        if self.device_type != "GreenPower" {
+            return None;
+        }
+
+        self.exposes()
+            .iter()
+            .find_map(|exp| match exp {
+                Expose::Enum(ExposeEnum { name, values, .. }) if name == "action" => Some(values),
+                _ => None,
+            })?
+            .iter()
+            .filter_map(|value| value.strip_prefix("press_")?.parse().ok())
+            .max()
+    }
+
+    /// True for Hue dimmer switches (and compatible 4-button remotes), which
+    /// report `on`, `up`, `down` and `off` (or `hue`) button actions.
+    #[must_use]
+    pub fn is_dimmer_switch(&self) -> bool {
+        let Some(values) = self.exposes().iter().find_map(|exp| match exp {
+            Expose::Enum(ExposeEnum { name, values, .. }) if name == "action" => Some(values),
+            _ => None,
+        }) else {
+            return false;
+        };
+
+        let has = |action: &str| values.iter().any(|value| value == action);
+
+        has("on_press")
+            && has("up_press")
+            && has("down_press")
+            && (has("off_press") || has("hue_press"))
+    }
+
+    #[must_use]
+    pub fn expose_motion(&self) -> bool {
+        self.exposes().iter().any(|exp| {
+            if let Expose::Binary(ExposeBinary { name, .. }) = exp {
+                name == "occupancy"
+            } else {
+                false
+            }
+        })
+    }
+
+    #[must_use]
+    pub fn expose_illuminance(&self) -> bool {
+        self.exposes().iter().any(|exp| {
+            if let Expose::Numeric(ExposeNumeric { name, .. }) = exp {
+                name == "illuminance" || name == "illuminance_lux"
+            } else {
+                false
+            }
+        })
+    }
+
+    #[must_use]
+    pub fn expose_temperature(&self) -> bool {
+        self.exposes().iter().any(|exp| {
+            if let Expose::Numeric(ExposeNumeric { name, .. }) = exp {
+                name == "temperature"
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Ids of the scenes stored on any endpoint of this device. The scene
+    /// entries are kept as raw json, so only the id is extracted here.
+    #[must_use]
+    pub fn scene_ids(&self) -> HashSet<u32> {
+        self.endpoints
+            .values()
+            .flat_map(|ep| &ep.scenes)
+            .filter_map(|scn| scn.get("id")?.as_u64())
+            .filter_map(|id| u32::try_from(id).ok())
+            .collect()
+    }
+
+    /// Possible values of `motion_sensitivity`, if the device supports it
+    #[must_use]
+    pub fn motion_sensitivity_values(&self) -> Option<&[String]> {
+        self.exposes().iter().find_map(|exp| match exp {
+            Expose::Enum(ExposeEnum { name, values, .. }) if name == "motion_sensitivity" => {
+                Some(values.as_slice())
+            }
+            _ => None,
+        })
+    }
+
     #[must_use]
     pub fn expose_action(&self) -> bool {
         self.exposes().iter().any(|exp| {
@@ -447,6 +642,9 @@ pub struct ExposeEnum {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExposeLight {
     pub features: Vec<Expose>,
+    /* set for devices with several lights (e.g., "l1", "l2") */
+    #[serde(default)]
+    pub endpoint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -490,10 +688,7 @@ impl ExposeNumeric {
     pub fn extract_mirek_schema(&self) -> Option<MirekSchema> {
         if self.unit.as_deref() == Some("mired") {
             if let (Some(min), Some(max)) = (self.value_min, self.value_max) {
-                return Some(MirekSchema {
-                    mirek_minimum: min as u32,
-                    mirek_maximum: max as u32,
-                });
+                return Some(units::mirek_schema(min, max));
             }
         }
         None
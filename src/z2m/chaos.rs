@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::ChaosConfig;
+
+/// Fault injection for a z2m connection (see [`ChaosConfig`]), to exercise
+/// reconnecting, coalescing and retrying, and to reproduce timing problems
+/// that only show up on busy or flaky networks.
+#[derive(Debug)]
+pub struct Chaos {
+    conf: ChaosConfig,
+    /* the next message has already been held back */
+    held: bool,
+}
+
+impl Chaos {
+    #[must_use]
+    pub const fn new(conf: ChaosConfig) -> Self {
+        Self { conf, held: false }
+    }
+
+    fn chance(share: f64) -> bool {
+        share > 0.0 && rand::random::<f64>() < share
+    }
+
+    /// Extra time to hold back the next outgoing message. Returns `None` once
+    /// the message has been held, so it can be sent.
+    pub fn hold(&mut self) -> Option<Duration> {
+        if self.conf.delay <= 0.0 || std::mem::take(&mut self.held) {
+            return None;
+        }
+        self.held = true;
+        Some(Duration::from_secs_f64(
+            rand::random::<f64>() * self.conf.delay,
+        ))
+    }
+
+    /// Whether to silently drop an outgoing command
+    #[must_use]
+    pub fn drop_outgoing(&self) -> bool {
+        Self::chance(self.conf.drop)
+    }
+
+    /// Whether to silently drop an incoming device message
+    #[must_use]
+    pub fn drop_incoming(&self) -> bool {
+        Self::chance(self.conf.drop_incoming)
+    }
+
+    /// The time to force a disconnect of a connection made now, if enabled.
+    /// Disconnects are spread between half and one and a half times the
+    /// configured interval.
+    #[must_use]
+    pub fn disconnect_at(&self) -> Option<Instant> {
+        let mean = self.conf.reconnect.filter(|secs| *secs > 0.0)?;
+        let secs = mean * (0.5 + rand::random::<f64>());
+        Some(Instant::now() + Duration::from_secs_f64(secs))
+    }
+}
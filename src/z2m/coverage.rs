@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::z2m::api::{Device, Expose};
+
+/// Names of exposed features that are mapped to hue resources
+const MAPPED: &[&str] = &[
+    "action",
+    "brightness",
+    "color_hs",
+    "color_temp",
+    "color_xy",
+    "effect",
+    "illuminance",
+    "illuminance_lux",
+    "linkquality",
+    "motion_sensitivity",
+    "occupancy",
+    "state",
+    "temperature",
+];
+
+/// An exposed feature that is not mapped to any hue resource, and the
+/// devices exposing it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UnmappedExpose {
+    #[serde(rename = "type")]
+    pub expose_type: String,
+    pub name: String,
+    pub devices: BTreeSet<String>,
+}
+
+/// The type and name of an expose, for exposes that are not modelled (yet)
+fn raw_expose(expose_type: &str, value: &Value) -> (String, String) {
+    let name = value["name"]
+        .as_str()
+        .or_else(|| value["property"].as_str())
+        .unwrap_or(expose_type);
+    (expose_type.to_string(), name.to_string())
+}
+
+/// Collect the unmapped (type, name) pairs of `exposes`. Lights, switches
+/// and locks are only containers, so their features are checked instead.
+fn collect(exposes: &[Expose], unmapped: &mut Vec<(String, String)>) {
+    for expose in exposes {
+        let (expose_type, name) = match expose {
+            Expose::Light(obj) => {
+                collect(&obj.features, unmapped);
+                continue;
+            }
+            Expose::Switch(obj) => {
+                collect(&obj.features, unmapped);
+                continue;
+            }
+            Expose::Lock(obj) => {
+                collect(&obj.features, unmapped);
+                continue;
+            }
+            Expose::Binary(obj) => ("binary", &obj.name),
+            Expose::Composite(obj) => ("composite", &obj.name),
+            Expose::Enum(obj) => ("enum", &obj.name),
+            Expose::Numeric(obj) => ("numeric", &obj.name),
+            Expose::List(value) => {
+                unmapped.push(raw_expose("list", value));
+                continue;
+            }
+            Expose::Text(value) => {
+                unmapped.push(raw_expose("text", value));
+                continue;
+            }
+            Expose::Cover(value) => {
+                unmapped.push(raw_expose("cover", value));
+                continue;
+            }
+            Expose::Fan(value) => {
+                unmapped.push(raw_expose("fan", value));
+                continue;
+            }
+            Expose::Climate(value) => {
+                unmapped.push(raw_expose("climate", value));
+                continue;
+            }
+        };
+
+        if !MAPPED.contains(&name.as_str()) {
+            unmapped.push((expose_type.to_string(), name.clone()));
+        }
+    }
+}
+
+/// The exposes of `devices` that are not mapped to any hue resource, the most
+/// common first
+#[must_use]
+pub fn unmapped_exposes(devices: &[Device]) -> Vec<UnmappedExpose> {
+    let mut found: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+
+    for dev in devices {
+        let mut unmapped = vec![];
+        collect(dev.exposes(), &mut unmapped);
+        for key in unmapped {
+            found
+                .entry(key)
+                .or_default()
+                .insert(dev.friendly_name.clone());
+        }
+    }
+
+    let mut res: Vec<UnmappedExpose> = found
+        .into_iter()
+        .map(|((expose_type, name), devices)| UnmappedExpose {
+            expose_type,
+            name,
+            devices,
+        })
+        .collect();
+
+    res.sort_by_key(|exp| std::cmp::Reverse(exp.devices.len()));
+    res
+}
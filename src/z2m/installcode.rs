@@ -0,0 +1,62 @@
+use crate::error::{ApiError, ApiResult};
+use crate::z2m::api::IeeeAddress;
+
+/// Valid install code lengths (in hex digits): 6, 8, 12 or 16 bytes of code,
+/// followed by a 2 byte crc
+const CODE_LENGTHS: [usize; 4] = [16, 20, 28, 36];
+
+fn parse_code(code: &str) -> ApiResult<String> {
+    let code: String = code
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | ':'))
+        .collect();
+
+    if !CODE_LENGTHS.contains(&code.len()) || !code.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::InvalidInstallCode(code));
+    }
+
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Parse the payload of a hue qr code ("HUE:Z:<code> M:<mac> D:.. A:..")
+fn parse_hue_qr(payload: &str) -> ApiResult<(String, String)> {
+    let mut code = None;
+    let mut mac = None;
+
+    for field in payload.split_whitespace() {
+        if let Some(value) = field.strip_prefix("Z:") {
+            code = Some(parse_code(value)?);
+        } else if let Some(value) = field.strip_prefix("M:") {
+            mac = Some(value.to_ascii_lowercase());
+        }
+    }
+
+    match (code, mac) {
+        (Some(code), Some(mac)) if mac.chars().all(|c| c.is_ascii_hexdigit()) => Ok((mac, code)),
+        _ => Err(ApiError::InvalidInstallCode(payload.to_string())),
+    }
+}
+
+/// Convert an install code, or the payload of a device qr code, to the format
+/// expected by zigbee2mqtt.
+///
+/// Plain install codes are bound to a device, so `ieee` is required for
+/// those. Qr codes already contain the device address.
+pub fn normalize(code: &str, ieee: Option<&IeeeAddress>) -> ApiResult<String> {
+    let code = code.trim();
+
+    /* formats understood by z2m as-is (aqara qr codes, and "<ieee>|<code>") */
+    if code.starts_with("G$M:") || code.contains('|') {
+        return Ok(code.to_string());
+    }
+
+    if let Some(payload) = code.strip_prefix("HUE:") {
+        let (mac, code) = parse_hue_qr(payload)?;
+        return Ok(format!("0x{mac}|{code}"));
+    }
+
+    let code = parse_code(code)?;
+    let ieee =
+        ieee.ok_or_else(|| ApiError::InvalidInstallCode(format!("{code} (ieee address needed)")))?;
+    Ok(format!("{}|{code}", ieee.topic()))
+}
@@ -1,5 +1,10 @@
 pub mod api;
+pub mod chaos;
+pub mod coverage;
+pub mod installcode;
+pub mod queue;
 pub mod request;
+pub mod supervisor;
 pub mod update;
 
 use std::collections::{HashMap, HashSet};
@@ -13,7 +18,7 @@ use tokio::net::TcpStream;
 use tokio::select;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::Mutex;
-use tokio::time::sleep;
+use tokio::time::{interval, sleep, sleep_until, Instant};
 use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
 use uuid::Uuid;
 
@@ -22,18 +27,96 @@ use crate::hue;
 use crate::hue::api::{
     Button, ButtonData, ButtonMetadata, ButtonReport, ColorTemperature, ColorTemperatureUpdate,
     ColorUpdate, Device, DeviceArchetype, DeviceProductData, Dimming, DimmingUpdate, GroupedLight,
-    Light, LightColor, LightUpdate, Metadata, RType, Resource, ResourceLink, Room, RoomArchetype,
-    RoomMetadata, Scene, SceneAction, SceneActionElement, SceneMetadata, SceneStatus,
-    ZigbeeConnectivity, ZigbeeConnectivityStatus,
+    Light, LightColor, LightEffect, LightEffects, LightLevel, LightLevelReport, LightUpdate,
+    Metadata, Motion, MotionReport, MotionSensitivity, RType, Resource, ResourceLink, Room,
+    RoomArchetype, RoomMetadata, Scene, SceneAction, SceneActionElement, SceneMetadata,
+    SceneStatus, Temperature, TemperatureReport, ZigbeeConnectivity, ZigbeeConnectivityStatus,
+    Zone,
 };
 
 use crate::error::{ApiError, ApiResult};
+use crate::hue::locale::Locale;
 use crate::hue::scene_icons;
-use crate::resource::AuxData;
+use crate::logging;
+use crate::model::layout;
+use crate::model::linkquality::LinkQualitySample;
+use crate::model::motion::MotionTiming;
+use crate::model::networkmap::NetworkMap;
+use crate::model::nightmode::NightMode;
+use crate::model::pairing::{PairingProgress, PairingStage};
+use crate::model::state::ColorMode;
+use crate::model::units;
+use crate::otlp;
 use crate::resource::Resources;
-use crate::z2m::api::{ExposeLight, Message, Other, RawMessage};
-use crate::z2m::request::{ClientRequest, Z2mRequest};
-use crate::z2m::update::DeviceUpdate;
+use crate::server::{interval_secs, optional_tick};
+use crate::timezone;
+use crate::z2m::api::{BridgeEvent, BridgeEventDevice, ExposeLight, Message, Other, RawMessage};
+use crate::z2m::chaos::Chaos;
+use crate::z2m::queue::{CommandQueue, Priority, DEFAULT_MAX_RATE};
+use crate::z2m::request::{ClientRequest, TaggedRequest, Z2mRequest};
+use crate::z2m::update::{DeviceUpdate, SensorUpdate};
+
+/// Requests without a state update after this time are counted as timeouts
+const LATENCY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Round trips slower than this are logged
+const LATENCY_WARNING: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Interval of `repeat` events for held buttons (same as real hue dimmers)
+const BUTTON_REPEAT: std::time::Duration = std::time::Duration::from_millis(800);
+
+/// Held buttons are assumed released after this time, even without a release
+const BUTTON_HOLD_MAX: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Changes in last seen time smaller than this are not reported as events
+const LAST_SEEN_RESOLUTION: Duration = Duration::seconds(60);
+
+/// How often devices are checked against the `unreachable_after` threshold
+const CONNECTIVITY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A button being held down
+#[derive(Debug)]
+struct HeldButton {
+    pub since: Instant,
+    pub last: Instant,
+}
+
+/// Prefix of the (hidden) z2m groups created for zones
+const ZONE_GROUP_PREFIX: &str = "bifrost_zone_";
+
+/// A z2m group mirroring the lights of a zone
+#[derive(Debug)]
+struct ZoneGroup {
+    pub glight: Uuid,
+    pub members: HashSet<Uuid>,
+}
+
+/// Differences between the devices reported by z2m, and the persisted state
+#[derive(Debug, Default)]
+struct DeviceReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub merged: Vec<String>,
+}
+
+impl DeviceReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.merged.is_empty()
+    }
+}
+
+/// A device that disappeared from z2m, remembered so it can be merged back
+/// into its rooms and zones when it is paired again
+#[derive(Debug)]
+struct RemovedDevice {
+    name: String,
+    /* rooms and zones, with the children (device or services) they had */
+    groups: Vec<(ResourceLink, Vec<ResourceLink>)>,
+}
 
 #[derive(Debug)]
 struct LearnScene {
@@ -42,6 +125,18 @@ struct LearnScene {
     pub known: HashMap<Uuid, SceneAction>,
 }
 
+/// A command sent to a resource, still waiting for the state update that
+/// confirms it
+#[derive(Debug)]
+struct PendingCommand {
+    /* first unanswered send, for latency tracking */
+    since: Instant,
+    /* latest send, for retrying */
+    sent: Instant,
+    msg: String,
+    attempts: u32,
+}
+
 pub struct Client {
     name: String,
     server: Z2mServer,
@@ -49,8 +144,32 @@ pub struct Client {
     state: Arc<Mutex<Resources>>,
     map: HashMap<String, Uuid>,
     rmap: HashMap<Uuid, String>,
+    /* friendly name -> z2m address (ieee address or group id) it belongs to */
+    claims: HashMap<String, String>,
     learn: HashMap<Uuid, LearnScene>,
+    light_scenes: HashMap<Uuid, HashSet<u32>>,
+    min_brightness: HashMap<Uuid, f64>,
     ignore: HashSet<String>,
+    sensitivity: HashMap<Uuid, Vec<String>>,
+    options_applied: HashSet<String>,
+    coordinator: Option<ResourceLink>,
+    zone_groups: HashMap<Uuid, ZoneGroup>,
+    zone_sync: HashSet<Uuid>,
+    availability: bool,
+    online: bool,
+    offline_status: HashMap<Uuid, ZigbeeConnectivityStatus>,
+    reconciled: bool,
+    removed: HashMap<Uuid, RemovedDevice>,
+    /* lights of multi-endpoint devices, by the light their topic maps to */
+    endpoints: HashMap<Uuid, Vec<(String, Uuid)>>,
+    pending: HashMap<Uuid, PendingCommand>,
+    held: HashMap<Uuid, HeldButton>,
+    /* number of z2m groups (exposed as rooms) each device is a member of */
+    room_count: HashMap<Uuid, usize>,
+    night: Option<NightMode>,
+    /* rooms, zones, grouped lights and lights under quiet hours right now */
+    night_targets: HashSet<Uuid>,
+    chaos: Option<Chaos>,
 }
 
 impl Client {
@@ -64,92 +183,338 @@ impl Client {
         let rmap = HashMap::new();
         let learn = HashMap::new();
         let ignore = HashSet::new();
+        let sensitivity = HashMap::new();
+        let night = match &config.bifrost.night_mode {
+            Some(conf) => Some(NightMode::new(
+                conf,
+                timezone::parse(&config.bridge.timezone)?,
+                config.bridge.location.as_ref(),
+            )?),
+            None => None,
+        };
+        if server.chaos.is_some() {
+            log::warn!("[{name}] Chaos mode enabled: injecting faults into z2m connection");
+        }
         Ok(Self {
             name,
+            chaos: server.chaos.clone().map(Chaos::new),
             server,
             config,
             state,
             map,
             rmap,
+            claims: HashMap::new(),
             learn,
+            light_scenes: HashMap::new(),
+            min_brightness: HashMap::new(),
             ignore,
+            sensitivity,
+            options_applied: HashSet::new(),
+            coordinator: None,
+            zone_groups: HashMap::new(),
+            zone_sync: HashSet::new(),
+            availability: false,
+            online: true,
+            offline_status: HashMap::new(),
+            reconciled: false,
+            removed: HashMap::new(),
+            endpoints: HashMap::new(),
+            pending: HashMap::new(),
+            held: HashMap::new(),
+            room_count: HashMap::new(),
+            night,
+            night_targets: HashSet::new(),
         })
     }
 
-    pub async fn add_light(&mut self, dev: &api::Device, expose: &ExposeLight) -> ApiResult<()> {
+    /// Claim the friendly `name` for the z2m entity at `address` (ieee address
+    /// or group id). Returns false, if the name is already used by another
+    /// entity.
+    ///
+    /// Friendly names should be unique, but nothing stops duplicates from
+    /// being configured. Messages for a duplicated name are ambiguous, so they
+    /// are only mapped to the first entity claiming it.
+    fn claim_name(&mut self, name: &str, address: &str) -> bool {
+        match self.claims.get(name) {
+            None => {
+                self.claims.insert(name.to_string(), address.to_string());
+                true
+            }
+            Some(owner) if owner == address => true,
+            Some(owner) => {
+                log::error!(
+                    "[{}] Duplicate friendly name [{name}], used by both {owner} and {address}!",
+                    self.name
+                );
+                log::error!(
+                    "[{}] Updates for [{name}] will only be applied to {owner}. Please rename one of them in zigbee2mqtt.",
+                    self.name
+                );
+                false
+            }
+        }
+    }
+
+    /// Map messages for `name` to `rid` (see [`Self::claim_name`]), and
+    /// requests for `rid` to `address`. Requests are always sent by address,
+    /// so renaming a device can never break control of it.
+    fn map_topic(&mut self, name: &str, address: &str, rid: Uuid) -> String {
+        if self.claim_name(name, address) {
+            self.map.insert(name.to_string(), rid);
+        }
+        self.rmap.insert(rid, address.to_string());
+        address.to_string()
+    }
+
+    /// Add a light device. Devices with several light endpoints (e.g.,
+    /// dual-channel dimmers) get one light service for each endpoint. The
+    /// first light keeps the id a single light would have, so rooms and
+    /// scenes from before endpoints were supported still refer to it.
+    #[allow(clippy::too_many_lines)]
+    pub async fn add_light(
+        &mut self,
+        dev: &api::Device,
+        exposes: &[&ExposeLight],
+    ) -> ApiResult<()> {
         let name = &dev.friendly_name;
+        let address = dev.ieee_address.topic();
+
+        let endpoints: Vec<Option<&str>> = match exposes {
+            [_] => vec![None],
+            _ => exposes.iter().map(|exp| exp.endpoint.as_deref()).collect(),
+        };
 
         let link_device = RType::Device.deterministic(&dev.ieee_address);
-        let link_light = RType::Light.deterministic(&dev.ieee_address);
+        let link_zbc = RType::ZigbeeConnectivity.deterministic(&dev.ieee_address);
+        let links_light: Vec<ResourceLink> = endpoints
+            .iter()
+            .enumerate()
+            .map(|(index, ep)| match ep {
+                Some(ep) if index > 0 => RType::Light.deterministic((&dev.ieee_address, ep)),
+                _ => RType::Light.deterministic(&dev.ieee_address),
+            })
+            .collect();
+
+        self.map_topic(name, &address, links_light[0].rid);
+        if endpoints.len() > 1 {
+            /* state updates for all endpoints arrive on the device topic */
+            self.endpoints.insert(
+                links_light[0].rid,
+                endpoints
+                    .iter()
+                    .zip(&links_light)
+                    .filter_map(|(ep, link)| Some(((*ep)?.to_string(), link.rid)))
+                    .collect(),
+            );
+            self.rmap.insert(link_device.rid, address.clone());
+        } else {
+            self.endpoints.remove(&links_light[0].rid);
+        }
 
         let product_data = DeviceProductData::guess_from_device(dev);
         let metadata = Metadata::new(DeviceArchetype::SpotBulb, name);
+        let native_effects = dev.has_effect("candle") || dev.has_effect("fireplace");
+
+        let zbc = ZigbeeConnectivity {
+            owner: link_device,
+            mac_address: dev.ieee_address.mac_address(),
+            status: ZigbeeConnectivityStatus::Connected,
+            channel: None,
+            extended_pan_id: String::from("0123456789abcdef"),
+            last_seen: None,
+            linkquality: None,
+            rssi: None,
+        };
 
-        let dev = hue::api::Device {
+        let services: Vec<ResourceLink> = links_light.iter().copied().chain([link_zbc]).collect();
+        let hue_dev = hue::api::Device {
             product_data,
             metadata: metadata.clone(),
-            services: vec![link_light],
+            services: services.clone(),
         };
 
-        self.map.insert(name.to_string(), link_light.rid);
-        self.rmap.insert(link_light.rid, name.to_string());
-
         let mut res = self.state.lock().await;
-        let mut light = Light::new(link_device, metadata);
-
-        light.dimming = expose
-            .feature("brightness")
-            .and_then(Dimming::extract_from_expose);
-        log::trace!("Detected dimming: {:?}", &light.dimming);
-
-        light.color_temperature = expose
-            .feature("color_temp")
-            .and_then(ColorTemperature::extract_from_expose);
-        log::trace!("Detected color temperature: {:?}", &light.color_temperature);
-
-        light.color = expose
-            .feature("color_xy")
-            .and_then(LightColor::extract_from_expose);
-        log::trace!("Detected color: {:?}", &light.color);
-
-        res.aux_set(&link_light, AuxData::new().with_topic(name));
-        res.add(&link_device, Resource::Device(dev))?;
-        res.add(&link_light, Resource::Light(light))?;
+        let aux = res.aux_get(&link_device).cloned().unwrap_or_default();
+        res.aux_set(&link_device, aux.with_server(&self.name));
+        res.add(&link_device, Resource::Device(hue_dev))?;
+
+        let light_conf = self.config.lights.get(name);
+
+        for ((expose, ep), link_light) in exposes.iter().zip(&endpoints).zip(&links_light) {
+            /* endpoints are addressed as "<device>/<endpoint>" by z2m */
+            let topic = ep.map_or_else(|| address.clone(), |ep| format!("{address}/{ep}"));
+            self.rmap.insert(link_light.rid, topic.clone());
+            self.light_scenes.insert(link_light.rid, dev.scene_ids());
+
+            let mut metadata = metadata.clone();
+            if let Some(ep) = ep {
+                metadata.name = format!("{name} ({ep})");
+            }
+            let mut light = Light::new(link_device, metadata);
+
+            light.dimming = expose
+                .feature("brightness")
+                .and_then(Dimming::extract_from_expose);
+            log::trace!("Detected dimming: {:?}", &light.dimming);
+
+            light.color_temperature = expose
+                .feature("color_temp")
+                .and_then(ColorTemperature::extract_from_expose);
+            log::trace!("Detected color temperature: {:?}", &light.color_temperature);
+
+            light.color = expose
+                .feature("color_xy")
+                .and_then(LightColor::extract_from_expose);
+            log::trace!("Detected color: {:?}", &light.color);
+
+            /* effects are emulated by streaming updates, so need brightness control */
+            if self.config.bifrost.effect_emulation && light.dimming.is_some() && !native_effects {
+                light.effects = Some(LightEffects::new(&[
+                    LightEffect::NoEffect,
+                    LightEffect::Candle,
+                    LightEffect::Fire,
+                ]));
+            }
+
+            /* keep the transition time and preferences set through the api */
+            let aux = res.aux_get(link_light).cloned().unwrap_or_default();
+            res.aux_set(link_light, aux.with_topic(&topic));
+            res.set_transition_default(link_light, light_conf.and_then(|conf| conf.transition));
+            match light_conf.and_then(|conf| conf.min_brightness) {
+                Some(min) => self
+                    .min_brightness
+                    .insert(link_light.rid, min.clamp(0.0, 99.0)),
+                None => self.min_brightness.remove(&link_light.rid),
+            };
+            res.add(link_light, Resource::Light(light))?;
+        }
+
+        let zbc = zbc.with_diagnostics(res.get::<ZigbeeConnectivity>(&link_zbc).ok());
+        res.add(&link_zbc, Resource::ZigbeeConnectivity(zbc))?;
+
+        /* lights from older state files have no zigbee connectivity service,
+         * or only a single light service */
+        res.update::<Device>(&link_device.rid, |dev| {
+            for svc in &services {
+                if !dev.services.contains(svc) {
+                    dev.services.push(*svc);
+                }
+            }
+        })?;
         drop(res);
 
         Ok(())
     }
 
-    pub async fn add_switch(&mut self, dev: &api::Device) -> ApiResult<()> {
+    pub async fn add_switch(&mut self, dev: &api::Device, buttons: u32) -> ApiResult<()> {
         let name = &dev.friendly_name;
 
         let link_device = RType::Device.deterministic(&dev.ieee_address);
-        let link_button = RType::Button.deterministic(&dev.ieee_address);
         let link_zbc = RType::ZigbeeConnectivity.deterministic(&dev.ieee_address);
+        let link_buttons: Vec<ResourceLink> = (1..=buttons)
+            .map(|control_id| RType::Button.deterministic((&dev.ieee_address, control_id)))
+            .collect();
+
+        let mut product_data = DeviceProductData::guess_from_device(dev);
+        product_data.product_archetype = DeviceArchetype::UnknownArchetype;
+
+        let mut services = link_buttons.clone();
+        services.push(link_zbc);
+
+        let repeat_interval = dev
+            .is_dimmer_switch()
+            .then(|| u32::try_from(BUTTON_REPEAT.as_millis()).unwrap_or(u32::MAX));
+
+        let event_values = if dev.is_dimmer_switch() {
+            json!(["initial_press", "repeat", "short_release", "long_release"])
+        } else {
+            json!(["initial_press", "short_release"])
+        };
 
-        let dev = hue::api::Device {
-            product_data: DeviceProductData::guess_from_device(dev),
-            metadata: Metadata::new(DeviceArchetype::UnknownArchetype, "foo"),
-            services: vec![link_button, link_zbc],
+        let hue_dev = hue::api::Device {
+            product_data,
+            metadata: Metadata::new(DeviceArchetype::UnknownArchetype, name),
+            services,
         };
 
-        self.map.insert(name.to_string(), link_button.rid);
-        self.rmap.insert(link_button.rid, name.to_string());
+        let topic = self.map_topic(name, &dev.ieee_address.topic(), link_device.rid);
 
         let mut res = self.state.lock().await;
-        let button = Button {
+
+        let zbc = ZigbeeConnectivity {
             owner: link_device,
-            metadata: ButtonMetadata { control_id: 0 },
-            button: ButtonData {
-                button_report: Some(ButtonReport {
-                    updated: Utc::now(),
-                    event: String::from("initial_press"),
-                }),
-                repeat_interval: Some(100),
-                event_values: Some(json!(["initial_press", "repeat"])),
-            },
+            mac_address: String::from("11:22:33:44:55:66:77:89"),
+            status: ZigbeeConnectivityStatus::ConnectivityIssue,
+            channel: Some(json!({
+                "status": "set",
+                "value": "channel_25",
+            })),
+            extended_pan_id: String::from("0123456789abcdef"),
+            last_seen: None,
+            linkquality: None,
+            rssi: None,
+        };
+
+        let aux = res.aux_get(&link_device).cloned().unwrap_or_default();
+        res.aux_set(&link_device, aux.with_topic(&topic).with_server(&self.name));
+        res.add(&link_device, Resource::Device(hue_dev))?;
+
+        for (link_button, control_id) in link_buttons.iter().zip(1..) {
+            let button = Button {
+                owner: link_device,
+                metadata: ButtonMetadata { control_id },
+                button: ButtonData {
+                    last_event: None,
+                    button_report: None,
+                    repeat_interval,
+                    event_values: Some(event_values.clone()),
+                },
+            };
+            res.add(link_button, Resource::Button(button))?;
+        }
+
+        let zbc = zbc.with_diagnostics(res.get::<ZigbeeConnectivity>(&link_zbc).ok());
+        res.add(&link_zbc, Resource::ZigbeeConnectivity(zbc))?;
+        drop(res);
+
+        Ok(())
+    }
+
+    pub async fn add_sensor(&mut self, dev: &api::Device) -> ApiResult<()> {
+        let name = &dev.friendly_name;
+
+        let link_device = RType::Device.deterministic(&dev.ieee_address);
+        let link_motion = RType::Motion.deterministic(&dev.ieee_address);
+        let link_light_level = RType::LightLevel.deterministic(&dev.ieee_address);
+        let link_temperature = RType::Temperature.deterministic(&dev.ieee_address);
+        let link_zbc = RType::ZigbeeConnectivity.deterministic(&dev.ieee_address);
+
+        let mut product_data = DeviceProductData::guess_from_device(dev);
+        product_data.product_archetype = DeviceArchetype::UnknownArchetype;
+
+        let mut services = vec![link_motion];
+        if dev.expose_illuminance() {
+            services.push(link_light_level);
+        }
+        if dev.expose_temperature() {
+            services.push(link_temperature);
+        }
+        services.push(link_zbc);
+
+        let hue_dev = hue::api::Device {
+            product_data,
+            metadata: Metadata::new(DeviceArchetype::UnknownArchetype, name),
+            services,
         };
 
+        if let Some(values) = dev.motion_sensitivity_values() {
+            self.sensitivity.insert(link_motion.rid, values.to_vec());
+        }
+
+        let topic = self.map_topic(name, &dev.ieee_address.topic(), link_device.rid);
+
+        let mut res = self.state.lock().await;
+
         let zbc = ZigbeeConnectivity {
             owner: link_device,
             mac_address: String::from("11:22:33:44:55:66:77:89"),
@@ -159,18 +524,243 @@ impl Client {
                 "value": "channel_25",
             })),
             extended_pan_id: String::from("0123456789abcdef"),
+            last_seen: None,
+            linkquality: None,
+            rssi: None,
+        };
+
+        let aux = res.aux_get(&link_device).cloned().unwrap_or_default();
+        res.aux_set(&link_device, aux.with_topic(&topic).with_server(&self.name));
+        res.add(&link_device, Resource::Device(hue_dev))?;
+        res.add(&link_motion, Resource::Motion(Motion::new(link_device)))?;
+
+        let sensor_conf = self.config.sensors.get(name).cloned().unwrap_or_default();
+        let seconds =
+            |secs: Option<f64>| secs.and_then(|s| std::time::Duration::try_from_secs_f64(s).ok());
+        res.motion_configure(
+            &link_motion,
+            MotionTiming {
+                hold: seconds(sensor_conf.hold),
+                retrigger_delay: seconds(sensor_conf.retrigger_delay),
+            },
+        );
+        if dev.expose_illuminance() {
+            res.add(
+                &link_light_level,
+                Resource::LightLevel(LightLevel::new(link_device)),
+            )?;
+        }
+        if dev.expose_temperature() {
+            res.add(
+                &link_temperature,
+                Resource::Temperature(Temperature::new(link_device)),
+            )?;
+        }
+        let zbc = zbc.with_diagnostics(res.get::<ZigbeeConnectivity>(&link_zbc).ok());
+        res.add(&link_zbc, Resource::ZigbeeConnectivity(zbc))?;
+        drop(res);
+
+        Ok(())
+    }
+
+    /// Add (or refresh) the coordinator of this z2m server, as a device with
+    /// zigbee connectivity.
+    pub async fn add_coordinator(&mut self, info: &api::BridgeInfo) -> ApiResult<()> {
+        let ieee = &info.coordinator.ieee_address;
+        let link_device = RType::Device.deterministic(ieee);
+        let link_zbc = RType::ZigbeeConnectivity.deterministic(ieee);
+
+        let product_data = DeviceProductData::from_coordinator(info);
+        let software_version = product_data.software_version.clone();
+
+        let hue_dev = hue::api::Device {
+            product_data,
+            metadata: Metadata::new(DeviceArchetype::UnknownArchetype, &self.name),
+            services: vec![link_zbc],
+        };
+
+        let extended_pan_id = match &info.network.extended_pan_id {
+            Value::String(id) => id.trim_start_matches("0x").to_string(),
+            other => other.to_string(),
         };
 
-        res.add(&link_device, Resource::Device(dev))?;
-        res.add(&link_button, Resource::Button(button))?;
+        let zbc = ZigbeeConnectivity {
+            owner: link_device,
+            mac_address: ieee.mac_address(),
+            status: ZigbeeConnectivityStatus::Connected,
+            channel: Some(json!({
+                "status": "set",
+                "value": format!("channel_{}", info.network.channel),
+            })),
+            extended_pan_id,
+            last_seen: None,
+            linkquality: None,
+            rssi: None,
+        };
+
+        self.coordinator = Some(link_device);
+
+        let mut res = self.state.lock().await;
+        /* the coordinator is found again by its server and ieee address (see
+         * crate::routes::bifrost::z2m), since its name can be changed */
+        let aux = res.aux_get(&link_device).cloned().unwrap_or_default();
+        res.aux_set(
+            &link_device,
+            aux.with_topic(&ieee.topic()).with_server(&self.name),
+        );
+        res.add(&link_device, Resource::Device(hue_dev))?;
         res.add(&link_zbc, Resource::ZigbeeConnectivity(zbc))?;
+
+        /* the coordinator is already known after a reconnect, so refresh it */
+        res.update::<Device>(&link_device.rid, |dev| {
+            dev.product_data.software_version = software_version;
+        })?;
+        res.update::<ZigbeeConnectivity>(&link_zbc.rid, |zbc| {
+            zbc.status = ZigbeeConnectivityStatus::Connected;
+        })?;
+        drop(res);
+
+        Ok(())
+    }
+
+    /// Mark the coordinator as disconnected, when the connection to z2m is lost
+    async fn coordinator_disconnected(&self) -> ApiResult<()> {
+        let Some(link_device) = self.coordinator else {
+            return Ok(());
+        };
+
+        let mut res = self.state.lock().await;
+        let zbc = res
+            .get::<Device>(&link_device)?
+            .services
+            .iter()
+            .find(|svc| svc.rtype == RType::ZigbeeConnectivity)
+            .copied();
+
+        if let Some(zbc) = zbc {
+            res.update::<ZigbeeConnectivity>(&zbc.rid, |zbc| {
+                zbc.status = ZigbeeConnectivityStatus::Disconnected;
+            })?;
+        }
+        drop(res);
+
+        Ok(())
+    }
+
+    /// Register a z2m group created for a zone. These groups are not shown as
+    /// rooms, but used to send zone updates as a single group cast.
+    async fn add_zone_group(&mut self, zone: &str, grp: &api::Group) -> ApiResult<()> {
+        let Ok(zone) = Uuid::parse_str(zone) else {
+            return Ok(());
+        };
+
+        let res = self.state.lock().await;
+        let glight = res
+            .get::<Zone>(&RType::Zone.link_to(zone))
+            .ok()
+            .and_then(|zone| {
+                zone.services
+                    .iter()
+                    .find(|svc| svc.rtype == RType::GroupedLight)
+                    .copied()
+            });
         drop(res);
 
+        let Some(glight) = glight else {
+            log::debug!("[{}] Ignoring group for unknown zone {zone}", self.name);
+            return Ok(());
+        };
+
+        let members = grp
+            .members
+            .iter()
+            .map(|m| RType::Light.deterministic(&m.ieee_address).rid)
+            .collect();
+
+        self.map.insert(grp.friendly_name.clone(), glight.rid);
+        self.rmap.insert(glight.rid, grp.id.to_string());
+        self.zone_groups.insert(
+            zone,
+            ZoneGroup {
+                glight: glight.rid,
+                members,
+            },
+        );
+        self.zone_sync.remove(&zone);
+
         Ok(())
     }
 
+    /// How a z2m group is presented (as a room or a zone), or `None` if it is
+    /// not presented at all
+    fn group_rtype(&self, grp: &api::Group) -> Option<RType> {
+        if grp.friendly_name.starts_with(ZONE_GROUP_PREFIX) {
+            return None;
+        }
+        if let Some(prefix) = &self.server.group_prefix {
+            if !grp.friendly_name.starts_with(prefix) {
+                return None;
+            }
+        }
+
+        self.config
+            .rooms
+            .get(&grp.friendly_name)
+            .map(|conf| conf.kind)
+            .unwrap_or_default()
+            .rtype()
+    }
+
+    fn count_room_memberships(&mut self, groups: &[api::Group]) {
+        let mut count: HashMap<Uuid, usize> = HashMap::new();
+
+        for grp in groups {
+            if self.group_rtype(grp) != Some(RType::Room) {
+                continue;
+            }
+            let devices: HashSet<Uuid> = grp
+                .members
+                .iter()
+                .map(|m| RType::Device.deterministic(&m.ieee_address).rid)
+                .collect();
+            for dev in devices {
+                *count.entry(dev).or_default() += 1;
+            }
+        }
+
+        self.room_count = count;
+    }
+
+    /// The devices to assign to the room for a z2m group. A device can only
+    /// be in one room, so devices in several groups are only kept in rooms
+    /// they were already assigned to.
+    fn room_children(
+        &self,
+        members: Vec<ResourceLink>,
+        current: &[ResourceLink],
+    ) -> Vec<ResourceLink> {
+        members
+            .into_iter()
+            .filter(|dev| {
+                let count = self.room_count.get(&dev.rid).copied().unwrap_or_default();
+                let keep = count <= 1 || current.contains(dev);
+                if !keep {
+                    log::debug!(
+                        "[{}] {dev:?} is in {count} groups, not assigning it to a room",
+                        self.name
+                    );
+                }
+                keep
+            })
+            .collect()
+    }
+
     #[allow(clippy::too_many_lines)]
     pub async fn add_group(&mut self, grp: &crate::z2m::api::Group) -> ApiResult<()> {
+        if let Some(zone) = grp.friendly_name.strip_prefix(ZONE_GROUP_PREFIX) {
+            return self.add_zone_group(zone, grp).await;
+        }
+
         let room_name;
 
         if let Some(ref prefix) = self.server.group_prefix {
@@ -188,20 +778,70 @@ impl Client {
             room_name = &grp.friendly_name;
         }
 
-        let link_room = RType::Room.deterministic(&grp.friendly_name);
+        let address = grp.id.to_string();
+        let unique = self.claim_name(&grp.friendly_name, &address);
+        let topic = grp.friendly_name.clone();
+        let room_conf = self.config.rooms.get(&topic);
+        let kind = room_conf.map(|conf| conf.kind).unwrap_or_default();
+
+        /* groups are identified by name, so duplicates also need the group id */
+        let mut room_name = room_name.to_string();
+        let link_for = |rtype: RType| {
+            if unique {
+                rtype.deterministic(&grp.friendly_name)
+            } else {
+                rtype.deterministic((&grp.friendly_name, grp.id))
+            }
+        };
+        if !unique {
+            room_name = format!("{room_name} ({})", grp.id);
+        }
+
+        let mut res = self.state.lock().await;
+
+        /* the group may have been exposed differently before */
+        for rtype in [RType::Room, RType::Zone] {
+            let link = link_for(rtype);
+            if kind.rtype() != Some(rtype) && res.get_resource_by_id(&link.rid).is_ok() {
+                log::info!("[{}] Removing {link:?} for group {topic}", self.name);
+                res.transaction(|res| res.delete_group(&link))?;
+            }
+        }
+
+        let Some(rtype) = kind.rtype() else {
+            log::debug!("[{}] Skipping group {topic}, by config", self.name);
+            return Ok(());
+        };
+
+        let link_room = link_for(rtype);
         let link_glight = RType::GroupedLight.deterministic((link_room.rid, grp.id));
 
-        let children = grp
-            .members
-            .iter()
-            .map(|f| RType::Device.deterministic(&f.ieee_address))
-            .collect();
+        /* rooms contain devices, while zones contain lights */
+        let child_rtype = match rtype {
+            RType::Zone => RType::Light,
+            _ => RType::Device,
+        };
+        let mut members: Vec<ResourceLink> = vec![];
+        for member in &grp.members {
+            let link = child_rtype.deterministic(&member.ieee_address);
+            if !members.contains(&link) {
+                members.push(link);
+            }
+        }
 
-        let topic = grp.friendly_name.to_string();
+        let current = match res.get_resource_by_id(&link_room.rid).map(|rr| rr.obj) {
+            Ok(Resource::Room(room)) => Some(room.children),
+            Ok(Resource::Zone(zone)) => Some(zone.children),
+            _ => None,
+        };
 
-        let mut res = self.state.lock().await;
+        let children = match rtype {
+            RType::Zone => members,
+            _ => self.room_children(members, current.as_deref().unwrap_or_default()),
+        };
 
         let mut scenes_new = HashSet::new();
+        let mut imported = 0;
 
         for scn in &grp.scenes {
             let scene = Scene {
@@ -210,7 +850,7 @@ impl Client {
                 group: link_room,
                 metadata: SceneMetadata {
                     appdata: None,
-                    image: guess_scene_icon(&scn.name),
+                    image: guess_scene_icon(&scn.name, self.config.bridge.locale),
                     name: scn.name.to_string(),
                 },
                 palette: json!({
@@ -225,24 +865,44 @@ impl Client {
 
             let link_scene = RType::Scene.deterministic((link_room.rid, scn.id));
 
-            res.aux_set(
-                &link_scene,
-                AuxData::new().with_topic(&topic).with_index(scn.id),
-            );
+            let aux = res.aux_get(&link_scene).cloned().unwrap_or_default();
+            res.aux_set(&link_scene, aux.with_topic(&address).with_index(scn.id));
 
             scenes_new.insert(link_scene.rid);
+            if res.get::<Scene>(&link_scene).is_err() {
+                log::debug!(
+                    "[{}] Importing scene {} ({}) from {topic}",
+                    self.name,
+                    scn.id,
+                    scn.name
+                );
+                imported += 1;
+            }
             res.add(&link_scene, Resource::Scene(scene))?;
         }
 
-        if let Ok(room) = res.get::<Room>(&link_room) {
+        if imported > 0 {
             log::info!(
-                "[{}] {link_room:?} ({}) known, updating..",
-                self.name,
-                room.metadata.name
+                "[{}] Imported {imported} scenes from group {topic}",
+                self.name
+            );
+        }
+
+        if res.get_resource_by_id(&link_room.rid).is_ok() {
+            log::info!(
+                "[{}] {link_room:?} ({room_name}) known, updating..",
+                self.name
             );
 
-            let scenes_old: HashSet<Uuid> =
-                HashSet::from_iter(res.get_scenes_for_room(&link_room.rid));
+            /* scenes never stored in z2m (e.g., copies) cannot be orphaned */
+            let scenes_old: HashSet<Uuid> = res
+                .get_scenes_for_room(&link_room.rid)
+                .into_iter()
+                .filter(|uuid| {
+                    res.aux_get(&RType::Scene.link_to(*uuid))
+                        .is_ok_and(|aux| aux.index.is_some())
+                })
+                .collect();
 
             log::trace!("[{}] old scenes: {scenes_old:?}", self.name);
             log::trace!("[{}] new scenes: {scenes_new:?}", self.name);
@@ -263,51 +923,93 @@ impl Client {
             );
         }
 
-        let mut metadata = RoomMetadata::new(RoomArchetype::Home, room_name);
-        if let Some(room_conf) = self.config.rooms.get(&topic) {
+        let mut metadata = RoomMetadata::new(RoomArchetype::Home, &room_name);
+        if let Some(room_conf) = room_conf {
             if let Some(name) = &room_conf.name {
                 metadata.name = name.to_string();
             }
             if let Some(icon) = &room_conf.icon {
                 metadata.archetype = *icon;
             }
-        };
+            res.set_transition_default(&link_room, room_conf.transition);
+        }
 
-        let room = Room {
-            children,
-            metadata,
-            services: vec![link_glight],
+        /* keep known rooms and zones in sync with group membership */
+        let changed = current.is_some_and(|current| {
+            let old: HashSet<&ResourceLink> = current.iter().collect();
+            old != children.iter().collect()
+        });
+        if changed {
+            log::info!(
+                "[{}] Members of group {topic} changed, updating {link_room:?}",
+                self.name
+            );
+        }
+        let new_children = changed.then(|| children.clone());
+
+        let room = match rtype {
+            RType::Zone => Resource::Zone(Zone {
+                children,
+                metadata,
+                services: vec![link_glight],
+            }),
+            _ => Resource::Room(Room {
+                children,
+                metadata,
+                services: vec![link_glight],
+            }),
         };
 
-        self.map.insert(topic.clone(), link_glight.rid);
-        self.rmap.insert(link_glight.rid, topic.clone());
-        self.rmap.insert(link_room.rid, topic.clone());
-
-        res.add(&link_room, Resource::Room(room))?;
+        if unique {
+            self.map.insert(topic.clone(), link_glight.rid);
+        }
+        self.rmap.insert(link_glight.rid, address.clone());
+        self.rmap.insert(link_room.rid, address);
+
+        res.transaction(|res| {
+            res.add(&link_room, room)?;
+
+            if let Some(children) = new_children {
+                match rtype {
+                    RType::Zone => res.update::<Zone>(&link_room.rid, |zone| {
+                        zone.children = children;
+                    })?,
+                    _ => res.update::<Room>(&link_room.rid, |room| room.children = children)?,
+                }
+            }
 
-        let glight = GroupedLight::new(link_room);
+            let glight = GroupedLight::new(link_room);
 
-        res.add(&link_glight, Resource::GroupedLight(glight))?;
+            res.add(&link_glight, Resource::GroupedLight(glight))
+        })?;
         drop(res);
 
         Ok(())
     }
 
     pub async fn handle_update(&mut self, rid: &Uuid, payload: &Value) -> ApiResult<()> {
-        let upd = DeviceUpdate::deserialize(payload)?;
+        self.track_latency(rid).await;
 
         let obj = self.state.lock().await.get_resource_by_id(rid)?.obj;
         match obj {
             Resource::Light(_) => {
+                let upd = DeviceUpdate::deserialize(payload)?;
                 if let Err(e) = self.handle_update_light(rid, &upd).await {
                     log::error!("FAIL: {e:?} in {upd:?}");
                 }
             }
             Resource::GroupedLight(_) => {
+                let upd = DeviceUpdate::deserialize(payload)?;
                 if let Err(e) = self.handle_update_grouped_light(rid, &upd).await {
                     log::error!("FAIL: {e:?} in {upd:?}");
                 }
             }
+            Resource::Device(_) => {
+                let upd = SensorUpdate::deserialize(payload)?;
+                if let Err(e) = self.handle_update_device(rid, &upd).await {
+                    log::error!("FAIL: {e:?} in {upd:?}");
+                }
+            }
             _ => {}
         }
 
@@ -315,17 +1017,50 @@ impl Client {
     }
 
     async fn handle_update_light(&mut self, uuid: &Uuid, devupd: &DeviceUpdate) -> ApiResult<()> {
+        /* report brightness on the hue scale, for lights with a minimum brightness */
+        let scaled;
+        let devupd = match (self.min_brightness.get(uuid), devupd.brightness) {
+            (Some(min), Some(bri)) => {
+                scaled = devupd
+                    .clone()
+                    .with_brightness(Some(units::brightness_from_device(bri, *min)));
+                &scaled
+            }
+            _ => devupd,
+        };
+
         let mut res = self.state.lock().await;
+        res.update_light_state(uuid, |state| state.apply_device_update(devupd));
+
+        /* z2m reports both color and color temperature, so only apply the one
+         * matching the active color mode (or both, if the mode is unknown) */
+        let mode = res
+            .light_states()
+            .get(uuid)
+            .and_then(|state| state.color_mode);
         res.update::<Light>(uuid, move |light| {
             let upd = LightUpdate::new()
                 .with_on(devupd.state.map(Into::into))
-                .with_brightness(devupd.brightness.map(|b| b / 254.0 * 100.0))
-                .with_color_temperature(devupd.color_temp)
-                .with_color_xy(devupd.color.map(|col| col.xy));
+                .with_brightness(devupd.brightness.map(units::device_to_percent))
+                .with_color_temperature(devupd.color_temp.filter(|_| mode != Some(ColorMode::Xy)))
+                .with_color_xy(
+                    devupd
+                        .color
+                        .map(|col| col.xy)
+                        .filter(|_| mode != Some(ColorMode::ColorTemperature)),
+                );
 
             *light += upd;
         })?;
 
+        /* lights changed outside of bifrost (e.g. by a remote) end active scenes */
+        let rlink = RType::Light.link_to(*uuid);
+        let light = res.get::<Light>(&rlink)?.clone();
+        res.deactivate_scenes(&rlink, Some(&light))?;
+        if !light.on.on {
+            res.effect_stop(&rlink)?;
+        }
+
         for learn in self.learn.values_mut() {
             if learn.missing.remove(uuid) {
                 let upd = devupd;
@@ -384,33 +1119,478 @@ impl Client {
 
             if let Some(b) = upd.brightness {
                 glight.dimming = Some(DimmingUpdate {
-                    brightness: b / 254.0 * 100.0,
+                    brightness: units::device_to_percent(b),
                 });
             }
         })
     }
 
+    async fn handle_update_device(&mut self, uuid: &Uuid, upd: &SensorUpdate) -> ApiResult<()> {
+        let state = self.state.clone();
+        let mut res = state.lock().await;
+        let dev = res.get::<Device>(&RType::Device.link_to(*uuid))?.clone();
+
+        if let Some((control_id, event)) = upd.action.as_deref().and_then(parse_button_action) {
+            self.update_button(&mut res, uuid, &dev, control_id, event)?;
+        }
+
+        if let Some(motion) = dev.services.iter().find(|rl| rl.rtype == RType::Motion) {
+            self.update_motion(&mut res, motion, upd)?;
+        }
+
+        if let Some(lux) = upd.lux() {
+            if let Some(light_level) = dev.services.iter().find(|rl| rl.rtype == RType::LightLevel)
+            {
+                Self::update_light_level(&mut res, light_level, lux)?;
+            }
+        }
+
+        if let Some(temp) = upd.temperature {
+            if let Some(temperature) = dev
+                .services
+                .iter()
+                .find(|rl| rl.rtype == RType::Temperature)
+            {
+                Self::update_temperature(&mut res, temperature, temp)?;
+            }
+        }
+
+        drop(res);
+
+        Ok(())
+    }
+
+    fn update_motion(
+        &self,
+        res: &mut Resources,
+        motion: &ResourceLink,
+        upd: &SensorUpdate,
+    ) -> ApiResult<()> {
+        /* translate z2m sensitivity (e.g., "medium") to hue sensitivity (index) */
+        let sensitivity = upd.motion_sensitivity.as_ref().and_then(|value| {
+            let values = self.sensitivity.get(&motion.rid)?;
+            let index = values.iter().position(|v| v == value)?;
+            Some(MotionSensitivity::new(
+                u32::try_from(index).ok()?,
+                u32::try_from(values.len() - 1).ok()?,
+            ))
+        });
+
+        let occupancy = upd
+            .occupancy
+            .and_then(|occupancy| res.motion_filter(motion, occupancy));
+
+        let known = res.get::<Motion>(motion)?;
+        let changed = occupancy.filter(|occupancy| {
+            known.enabled
+                && (known.motion.motion_report.is_none() || known.motion.motion != *occupancy)
+        });
+
+        if changed.is_none() && sensitivity.is_none() {
+            return Ok(());
+        }
+
+        res.update::<Motion>(&motion.rid, |motion| {
+            if let Some(sens) = sensitivity {
+                motion.sensitivity = Some(sens);
+            }
+
+            if let Some(occupancy) = changed {
+                motion.motion.motion = occupancy;
+                motion.motion.motion_report = Some(MotionReport {
+                    changed: Utc::now(),
+                    motion: occupancy,
+                });
+            }
+        })
+    }
+
+    fn update_light_level(res: &mut Resources, link: &ResourceLink, lux: f64) -> ApiResult<()> {
+        /* hue reports light level as 10000 * log10(lux) + 1 */
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let level = if lux > 0.0 {
+            lux.log10().mul_add(10000.0, 1.0).max(0.0).round() as u32
+        } else {
+            0
+        };
+
+        /* disabled sensors keep their last state, and emit no events */
+        let known = res.get::<LightLevel>(link)?;
+        if !known.enabled
+            || (known.light.light_level_report.is_some() && known.light.light_level == level)
+        {
+            return Ok(());
+        }
+
+        res.update::<LightLevel>(&link.rid, |ll| {
+            ll.light.light_level = level;
+            ll.light.light_level_report = Some(LightLevelReport {
+                changed: Utc::now(),
+                light_level: level,
+            });
+        })
+    }
+
+    fn update_temperature(res: &mut Resources, link: &ResourceLink, temp: f64) -> ApiResult<()> {
+        /* disabled sensors keep their last state, and emit no events */
+        let known = res.get::<Temperature>(link)?;
+        if !known.enabled
+            || (known.temperature.temperature_report.is_some()
+                && (known.temperature.temperature - temp).abs() < f64::EPSILON)
+        {
+            return Ok(());
+        }
+
+        res.update::<Temperature>(&link.rid, |tmp| {
+            tmp.temperature.temperature = temp;
+            tmp.temperature.temperature_report = Some(TemperatureReport {
+                changed: Utc::now(),
+                temperature: temp,
+            });
+        })
+    }
+
+    fn update_button(
+        &mut self,
+        res: &mut Resources,
+        uuid: &Uuid,
+        dev: &Device,
+        control_id: u32,
+        event: &str,
+    ) -> ApiResult<()> {
+        let Some(button) = dev.services.iter().copied().find(|rl| {
+            rl.rtype == RType::Button
+                && res
+                    .get::<Button>(rl)
+                    .is_ok_and(|btn| btn.metadata.control_id == control_id)
+        }) else {
+            log::debug!(
+                "[{}] Action for unknown button {control_id} on {uuid}",
+                self.name
+            );
+            return Ok(());
+        };
+
+        /* a held button repeats, until released. Repeats reported by z2m
+         * are limited to the hue cadence, and missing repeats are synthesized
+         * by `button_repeat` */
+        let now = Instant::now();
+        if event == "repeat" {
+            if let Some(held) = self.held.get_mut(&button.rid) {
+                if now - held.last < BUTTON_REPEAT {
+                    return Ok(());
+                }
+                held.last = now;
+            } else {
+                self.held.insert(
+                    button.rid,
+                    HeldButton {
+                        since: now,
+                        last: now,
+                    },
+                );
+            }
+        } else {
+            self.held.remove(&button.rid);
+        }
+
+        Self::button_event(res, &button, event)
+    }
+
+    fn button_event(res: &mut Resources, button: &ResourceLink, event: &str) -> ApiResult<()> {
+        res.update::<Button>(&button.rid, |btn| {
+            btn.button.last_event = Some(event.to_string());
+            btn.button.button_report = Some(ButtonReport {
+                updated: Utc::now(),
+                event: event.to_string(),
+            });
+        })
+    }
+
+    /// Time of the next synthesized `repeat` event, if any buttons are held
+    fn next_button_repeat(&self) -> Option<Instant> {
+        self.held
+            .values()
+            .map(|held| held.last + BUTTON_REPEAT)
+            .min()
+    }
+
+    /// Send `repeat` events for held buttons, for remotes that only report the
+    /// start of a hold, and its release.
+    async fn button_repeat(&mut self) -> ApiResult<()> {
+        let now = Instant::now();
+        let mut res = self.state.lock().await;
+
+        let name = &self.name;
+        let mut result = Ok(());
+        self.held.retain(|rid, held| {
+            if now - held.since > BUTTON_HOLD_MAX {
+                log::warn!("[{name}] Button {rid} held too long, assuming release");
+                return false;
+            }
+            if now - held.last >= BUTTON_REPEAT {
+                held.last = now;
+                if let Err(err) =
+                    Self::button_event(&mut res, &RType::Button.link_to(*rid), "repeat")
+                {
+                    result = Err(err);
+                    return false;
+                }
+            }
+            true
+        });
+        drop(res);
+
+        result
+    }
+
+    async fn handle_bridge_info(&mut self, info: &api::BridgeInfo) -> ApiResult<()> {
+        self.availability = match &info.config.availability {
+            Value::Bool(enabled) => *enabled,
+            Value::Object(map) => map.get("enabled").and_then(Value::as_bool) != Some(false),
+            _ => false,
+        };
+
+        if !self.availability && self.server.poll_interval.is_none() {
+            log::warn!(
+                "[{}] Availability is disabled in zigbee2mqtt, and no poll_interval is configured. Light state might drift.",
+                self.name
+            );
+        }
+
+        self.add_coordinator(info).await?;
+
+        Ok(())
+    }
+
+    fn handle_install_code(&self, resp: &api::BridgeResponse) {
+        if let Some(err) = &resp.error {
+            log::error!("[{}] Adding install code failed: {err}", self.name);
+        } else {
+            log::info!("[{}] Install code added, device can now join", self.name);
+        }
+    }
+
+    /// Store a network map requested through the admin api, to be served
+    /// from there once available.
+    async fn handle_network_map(&self, resp: &api::BridgeResponse) -> ApiResult<()> {
+        if let Some(err) = &resp.error {
+            log::error!("[{}] Network map request failed: {err}", self.name);
+            return Ok(());
+        }
+
+        let map = NetworkMap {
+            time: Utc::now(),
+            map_type: serde_json::from_value(resp.data["type"].clone())?,
+            routes: resp.data["routes"].as_bool().unwrap_or_default(),
+            value: resp.data["value"].clone(),
+        };
+
+        log::info!("[{}] Received {:?} network map", self.name, map.map_type);
+
+        self.state.lock().await.set_network_map(&self.name, map);
+
+        Ok(())
+    }
+
+    /// Track the online state of z2m. While z2m is offline, all devices on
+    /// this server are reported as unreachable, and commands are held back
+    /// (see [`Z2mServer::max_command_age`]).
+    async fn handle_bridge_state(&mut self, state: &api::BridgeOnlineState) -> ApiResult<()> {
+        let online = matches!(state, api::BridgeOnlineState::Online);
+        if online == self.online {
+            return Ok(());
+        }
+        self.online = online;
+
+        let mut res = self.state.lock().await;
+
+        if online {
+            log::info!("[{}] zigbee2mqtt is online, resuming commands", self.name);
+            for (id, status) in self.offline_status.drain() {
+                res.update::<ZigbeeConnectivity>(&id, |zbc| zbc.status = status)?;
+            }
+            return Ok(());
+        }
+
+        log::warn!("[{}] zigbee2mqtt is offline, holding commands", self.name);
+
+        let zbcs: Vec<(Uuid, ResourceLink)> = res
+            .get_resources_by_type(RType::ZigbeeConnectivity)
+            .into_iter()
+            .filter_map(|rr| Some((rr.id, ZigbeeConnectivity::try_from(rr.obj).ok()?.owner)))
+            .filter(|(_, owner)| {
+                self.coordinator.as_ref() == Some(owner) || self.device_topic(&res, owner).is_some()
+            })
+            .collect();
+
+        for (id, owner) in zbcs {
+            let status = if self.coordinator == Some(owner) {
+                ZigbeeConnectivityStatus::Disconnected
+            } else {
+                ZigbeeConnectivityStatus::ConnectivityIssue
+            };
+            res.update::<ZigbeeConnectivity>(&id, |zbc| {
+                self.offline_status.entry(id).or_insert(zbc.status);
+                zbc.status = status;
+            })?;
+        }
+        drop(res);
+
+        Ok(())
+    }
+
+    /// Mark the devices of this server, as loaded from the state file, as
+    /// unreachable. This is used when zigbee2mqtt cannot be reached at
+    /// startup, and undone once it reports being online.
+    async fn mark_unreachable(&mut self) -> ApiResult<()> {
+        if !self.online {
+            return Ok(());
+        }
+        self.online = false;
+
+        let state = self.state.clone();
+        let mut res = state.lock().await;
+
+        let devices: Vec<(ResourceLink, bool)> = res
+            .get_resources_by_type(RType::Device)
+            .into_iter()
+            .filter_map(|rr| {
+                let link = RType::Device.link_to(rr.id);
+                let dev = <&Device>::try_from(&rr.obj).ok()?;
+                let owned = res
+                    .aux_get(&link)
+                    .is_ok_and(|aux| aux.server.as_deref() == Some(self.name.as_str()));
+                let coordinator = owned && dev.product_data.is_coordinator();
+                let zbc = dev
+                    .services
+                    .iter()
+                    .find(|svc| svc.rtype == RType::ZigbeeConnectivity)?;
+                (coordinator || owned).then_some((*zbc, coordinator))
+            })
+            .collect();
+
+        log::warn!(
+            "[{}] zigbee2mqtt is unreachable, marking {} devices as unreachable until it is back",
+            self.name,
+            devices.len()
+        );
+
+        for (zbc, coordinator) in devices {
+            let status = if coordinator {
+                ZigbeeConnectivityStatus::Disconnected
+            } else {
+                ZigbeeConnectivityStatus::ConnectivityIssue
+            };
+            res.update::<ZigbeeConnectivity>(&zbc.rid, |obj| {
+                self.offline_status.entry(zbc.rid).or_insert(obj.status);
+                obj.status = status;
+            })?;
+        }
+        drop(res);
+
+        Ok(())
+    }
+
+    /// Report the progress of devices joining the network (see
+    /// [`PairingProgress`]). Announcements of devices that are already known
+    /// (e.g., after a power cycle) are not part of pairing, so they are
+    /// skipped.
+    async fn handle_bridge_event(&self, evt: &BridgeEvent) -> ApiResult<()> {
+        let Ok(data) = serde_json::from_value::<BridgeEventDevice>(evt.data.clone()) else {
+            return Ok(());
+        };
+
+        let stage = match (evt.event_type.as_str(), data.status.as_deref()) {
+            ("device_joined", _) => PairingStage::Joined,
+            ("device_announce", _) if !self.map.contains_key(&data.friendly_name) => {
+                PairingStage::Announced
+            }
+            ("device_interview", Some("started")) => PairingStage::InterviewStarted,
+            ("device_interview", Some("successful")) => PairingStage::InterviewSuccessful,
+            ("device_interview", Some("failed")) => PairingStage::InterviewFailed,
+            ("device_leave", _) => PairingStage::Left,
+            _ => return Ok(()),
+        };
+
+        log::info!(
+            "[{}] Pairing {} ({}): {stage:?}",
+            self.name,
+            data.friendly_name,
+            data.ieee_address
+        );
+
+        let definition = data.definition;
+        let progress = PairingProgress {
+            server: self.name.clone(),
+            ieee_address: data.ieee_address,
+            friendly_name: data.friendly_name,
+            stage,
+            model: definition.as_ref().map(|def| def.model.clone()),
+            vendor: definition.as_ref().map(|def| def.vendor.clone()),
+            description: definition.and_then(|def| def.description),
+            supported: data.supported,
+            updated: Utc::now(),
+        };
+
+        self.state.lock().await.pairing_record(progress)
+    }
+
     async fn handle_bridge_message(&mut self, msg: Message) -> ApiResult<()> {
         #[allow(unused_variables)]
         match msg {
-            Message::BridgeInfo(ref obj) => { /* println!("{obj:#?}"); */ }
+            Message::BridgeInfo(ref obj) => self.handle_bridge_info(obj).await?,
             Message::BridgeLogging(ref obj) => { /* println!("{obj:#?}"); */ }
             Message::BridgeExtensions(ref obj) => { /* println!("{obj:#?}"); */ }
-            Message::BridgeEvent(ref obj) => { /* println!("{obj:#?}"); */ }
+            Message::BridgeEvent(ref obj) => self.handle_bridge_event(obj).await?,
             Message::BridgeDefinitions(ref obj) => { /* println!("{obj:#?}"); */ }
-            Message::BridgeState(ref obj) => { /* println!("{obj:#?}"); */ }
+            Message::BridgeNetworkMap(ref obj) => self.handle_network_map(obj).await?,
+            Message::BridgeInstallCode(ref obj) => self.handle_install_code(obj),
+            Message::BridgeState(ref obj) => self.handle_bridge_state(&obj.state).await?,
 
             Message::BridgeDevices(ref obj) => {
+                /* device names are claimed again, to pick up renames */
+                self.claims.retain(|_, addr| !addr.starts_with("0x"));
+                let known = self.known_device_names(obj).await;
                 for dev in obj {
-                    if let Some(exp) = dev.expose_light() {
+                    let lights = dev.expose_lights();
+                    if !lights.is_empty() {
+                        log::info!(
+                            "[{}] Adding light {:?}: [{}] ({}, {} endpoints)",
+                            self.name,
+                            dev.ieee_address,
+                            dev.friendly_name,
+                            dev.model_id.as_deref().unwrap_or("<unknown model>"),
+                            lights.len(),
+                        );
+                        self.add_light(dev, &lights).await?;
+                    } else if dev.expose_motion() && self.config.bifrost.features.sensors {
+                        log::info!(
+                            "[{}] Adding motion sensor {:?}: [{}] ({})",
+                            self.name,
+                            dev.ieee_address,
+                            dev.friendly_name,
+                            dev.model_id.as_deref().unwrap_or("<unknown model>")
+                        );
+                        self.add_sensor(dev).await?;
+                    } else if let Some(buttons) = dev.green_power_buttons() {
                         log::info!(
-                            "[{}] Adding light {:?}: [{}] ({})",
+                            "[{}] Adding green power switch {:?}: [{}] ({} buttons)",
+                            self.name,
+                            dev.ieee_address,
+                            dev.friendly_name,
+                            buttons,
+                        );
+                        self.add_switch(dev, buttons).await?;
+                    } else if dev.is_dimmer_switch() {
+                        log::info!(
+                            "[{}] Adding dimmer switch {:?}: [{}] ({})",
                             self.name,
                             dev.ieee_address,
                             dev.friendly_name,
                             dev.model_id.as_deref().unwrap_or("<unknown model>")
                         );
-                        self.add_light(dev, exp).await?;
+                        self.add_switch(dev, 4).await?;
                     } else {
                         log::debug!(
                             "[{}] Ignoring unsupported device {}",
@@ -428,22 +1608,415 @@ impl Client {
                             dev.friendly_name,
                             dev.model_id.as_deref().unwrap_or("<unknown model>")
                         );
-                        self.add_switch(dev).await?;
+                        self.add_switch(dev, 1).await?;
                     }
                     */
                 }
+
+                self.reconcile_devices(obj, &known).await?;
+                self.report_unmapped_exposes(obj).await;
+                self.apply_device_options().await?;
+
+                /* rooms, zones and scenes in the config may name new devices */
+                let mut lock = self.state.lock().await;
+                layout::reconcile(&mut lock, &self.config.layout, self.config.bridge.locale)?;
+                drop(lock);
+            }
+
+            Message::BridgeGroups(ref obj) => {
+                /* println!("{obj:#?}"); */
+                self.claims.retain(|_, addr| addr.starts_with("0x"));
+                self.count_room_memberships(obj);
+                for grp in obj {
+                    self.add_group(grp).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the devices in `devices` that are already known in the
+    /// (persisted) state, before the device list is processed.
+    async fn known_device_names(&self, devices: &[api::Device]) -> HashMap<Uuid, String> {
+        let lock = self.state.lock().await;
+        devices
+            .iter()
+            .map(|dev| RType::Device.deterministic(&dev.ieee_address))
+            .filter_map(|link| {
+                let dev: &Device = lock.get(&link).ok()?;
+                Some((link.rid, dev.metadata.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Compare the device list reported by z2m with the persisted state.
+    /// Renamed devices are updated, and devices that were previously reported
+    /// by this server (but are no longer present) are removed.
+    ///
+    /// A summary is always logged for the first device list after startup,
+    /// and afterwards whenever something has changed.
+    async fn reconcile_devices(
+        &mut self,
+        devices: &[api::Device],
+        known: &HashMap<Uuid, String>,
+    ) -> ApiResult<()> {
+        let mut report = DeviceReport::default();
+        let mut reported = HashSet::new();
+
+        let mut lock = self.state.lock().await;
+
+        for dev in devices {
+            let link = RType::Device.deterministic(&dev.ieee_address);
+            reported.insert(link);
+
+            let name = &dev.friendly_name;
+            if self.ignore.contains(name) {
+                continue;
+            }
+
+            /* z2m names freshly paired devices after their address */
+            let default_name = *name == dev.ieee_address.topic();
+
+            match known.get(&link.rid) {
+                None => match self.removed.remove(&link.rid) {
+                    Some(removed) => {
+                        Self::merge_device(&mut lock, dev, &removed, default_name)?;
+                        report.merged.push(removed.name);
+                    }
+                    None => report.added.push(name.clone()),
+                },
+                Some(_) if default_name => {}
+                Some(old) if old != name => {
+                    lock.update::<Device>(&link.rid, |dev| dev.metadata.name.clone_from(name))?;
+
+                    let link_light = RType::Light.deterministic(&dev.ieee_address);
+                    if lock.get::<Light>(&link_light).is_ok() {
+                        lock.update::<Light>(&link_light.rid, |light| {
+                            light.metadata.name.clone_from(name);
+                        })?;
+                    }
+
+                    /* the old name is free for other devices to claim */
+                    self.map.remove(old);
+                    if self.claims.get(old) == Some(&dev.ieee_address.topic()) {
+                        self.claims.remove(old);
+                    }
+                    report.renamed.push((old.clone(), name.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for link in lock.get_devices_by_server(&self.name) {
+            if reported.contains(&link) {
+                continue;
+            }
+            let dev: &Device = lock.get(&link)?;
+            let name = dev.metadata.name.clone();
+            let services: Vec<ResourceLink> = dev.services.iter().chain([&link]).copied().collect();
+            for svc in &services {
+                if let Some(address) = self.rmap.remove(&svc.rid) {
+                    self.claims.retain(|_, owner| *owner != address);
+                }
+                self.endpoints.remove(&svc.rid);
+            }
+            self.map
+                .retain(|_, rid| !services.iter().any(|svc| svc.rid == *rid));
+
+            let mut groups = vec![];
+            for group in lock.device_groups(&link)? {
+                let children = match lock.get_resource_by_id(&group.rid)?.obj {
+                    Resource::Room(room) => room.children,
+                    Resource::Zone(zone) => zone.children,
+                    _ => continue,
+                };
+                let children = children.into_iter().filter(|c| services.contains(c));
+                groups.push((group, children.collect()));
+            }
+
+            lock.delete_device(&link)?;
+            report.removed.push(name.clone());
+            self.removed
+                .insert(link.rid, RemovedDevice { name, groups });
+        }
+        drop(lock);
+
+        if self.reconciled && report.is_empty() {
+            return Ok(());
+        }
+        self.reconciled = true;
+
+        log::info!(
+            "[{}] Device reconciliation: {} added, {} removed, {} renamed",
+            self.name,
+            report.added.len(),
+            report.removed.len(),
+            report.renamed.len(),
+        );
+        for name in &report.added {
+            log::info!("[{}]   added: [{name}]", self.name);
+        }
+        for name in &report.removed {
+            log::info!("[{}]   removed: [{name}]", self.name);
+        }
+        for (old, new) in &report.renamed {
+            log::info!("[{}]   renamed: [{old}] -> [{new}]", self.name);
+        }
+        for name in &report.merged {
+            log::info!("[{}]   re-paired: [{name}]", self.name);
+        }
+
+        Ok(())
+    }
+
+    /// Merge a re-paired device back into the rooms and zones it was removed
+    /// from. Its resources are recreated with the same ids (which derive from
+    /// the ieee address), so scenes still refer to them. If z2m only knows
+    /// the device by its address, the previous name is kept as well.
+    fn merge_device(
+        res: &mut Resources,
+        dev: &api::Device,
+        removed: &RemovedDevice,
+        default_name: bool,
+    ) -> ApiResult<()> {
+        let link_device = RType::Device.deterministic(&dev.ieee_address);
+
+        for (group, children) in &removed.groups {
+            let restore = |current: &mut Vec<ResourceLink>| {
+                for child in children {
+                    if !current.contains(child) {
+                        current.push(*child);
+                    }
+                }
+            };
+            match group.rtype {
+                RType::Room if res.get::<Room>(group).is_ok() => {
+                    res.update::<Room>(&group.rid, |room| restore(&mut room.children))?;
+                    /* rooms for z2m groups follow the group members */
+                    res.z2m_request(ClientRequest::group_member_add(*group, link_device))?;
+                }
+                RType::Zone if res.get::<Zone>(group).is_ok() => {
+                    res.update::<Zone>(&group.rid, |zone| restore(&mut zone.children))?;
+                }
+                _ => {}
+            }
+        }
+
+        if default_name {
+            res.update::<Device>(&link_device.rid, |dev| {
+                dev.metadata.name.clone_from(&removed.name);
+            })?;
+            let link_light = RType::Light.deterministic(&dev.ieee_address);
+            if res.get::<Light>(&link_light).is_ok() {
+                res.update::<Light>(&link_light.rid, |light| {
+                    light.metadata.name.clone_from(&removed.name);
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the exposes that are not mapped to hue resources for the admin
+    /// api, and log them when they change (so always at startup)
+    async fn report_unmapped_exposes(&self, devices: &[api::Device]) {
+        let unmapped = coverage::unmapped_exposes(devices);
+
+        let mut lock = self.state.lock().await;
+        let changed = lock.unmapped_exposes(&self.name) != Some(unmapped.as_slice());
+
+        if changed && !unmapped.is_empty() {
+            log::warn!(
+                "[{}] {} exposed features are not mapped to hue resources (see /bifrost/z2m/{}/unmapped):",
+                self.name,
+                unmapped.len(),
+                self.name,
+            );
+            for exp in &unmapped {
+                log::info!(
+                    "[{}]   {} {}: {} device(s)",
+                    self.name,
+                    exp.expose_type,
+                    exp.name,
+                    exp.devices.len()
+                );
+            }
+        }
+
+        lock.set_unmapped_exposes(&self.name, unmapped);
+    }
+
+    /// Request the device options from the config file, for all devices known
+    /// on this connection. Each device is only configured once, since z2m
+    /// republishes the device list after every change.
+    async fn apply_device_options(&mut self) -> ApiResult<()> {
+        let lock = self.state.lock().await;
+        for (name, options) in &self.server.device_options {
+            if self.options_applied.contains(name) {
+                continue;
+            }
+            let Some(uuid) = self.map.get(name) else {
+                continue;
+            };
+            let Ok(obj) = lock.get_resource_by_id(uuid) else {
+                continue;
+            };
+
+            log::info!("[{}] Setting device options for [{name}]", self.name);
+            let link = ResourceLink::new(*uuid, obj.obj.rtype());
+            lock.z2m_request(ClientRequest::device_options(link, options.clone()))?;
+            self.options_applied.insert(name.clone());
+        }
+        drop(lock);
+
+        Ok(())
+    }
+
+    /// The zigbee connectivity service of the device owning `rid` (a light,
+    /// or a device)
+    fn connectivity_service(res: &Resources, rid: &Uuid) -> Option<ResourceLink> {
+        let device = match res.get_resource_by_id(rid).ok()?.obj {
+            Resource::Light(light) => light.owner,
+            Resource::Device(_) => RType::Device.link_to(*rid),
+            _ => return None,
+        };
+
+        res.get::<Device>(&device)
+            .ok()?
+            .services
+            .iter()
+            .find(|svc| svc.rtype == RType::ZigbeeConnectivity)
+            .copied()
+    }
+
+    /// Connectivity status of a device, judged by the thresholds configured
+    /// for this server
+    fn connectivity_status(&self, zbc: &ZigbeeConnectivity) -> ZigbeeConnectivityStatus {
+        let stale = match (self.server.unreachable_after, zbc.last_seen) {
+            (Some(secs), Some(seen)) => {
+                u64::try_from((Utc::now() - seen).num_seconds()).is_ok_and(|age| age > secs)
             }
+            _ => false,
+        };
+        let weak = match (self.server.min_linkquality, zbc.linkquality) {
+            (Some(min), Some(lq)) => lq < min,
+            _ => false,
+        };
+
+        if stale {
+            ZigbeeConnectivityStatus::Disconnected
+        } else if weak {
+            ZigbeeConnectivityStatus::ConnectivityIssue
+        } else {
+            ZigbeeConnectivityStatus::Connected
+        }
+    }
+
+    /// Record link quality, signal strength and last seen time reported with
+    /// any device message
+    async fn handle_diagnostics(&self, rid: &Uuid, payload: &Value) -> ApiResult<()> {
+        let mut res = self.state.lock().await;
+        let Some(link_zbc) = Self::connectivity_service(&res, rid) else {
+            return Ok(());
+        };
+
+        let linkquality = payload["linkquality"]
+            .as_u64()
+            .and_then(|lq| u8::try_from(lq).ok());
+        let rssi = payload["rssi"]
+            .as_i64()
+            .and_then(|rssi| i32::try_from(rssi).ok());
+        let last_seen = parse_last_seen(&payload["last_seen"]).unwrap_or_else(Utc::now);
+
+        let owner = res.get::<ZigbeeConnectivity>(&link_zbc)?.owner;
+        if let Some(linkquality) = linkquality {
+            let sample = LinkQualitySample {
+                time: last_seen,
+                linkquality,
+                rssi,
+            };
+            res.linkquality_record(&owner, sample);
+        }
+
+        /* avoid an event for every message, if only the timestamp moved a bit */
+        let zbc = res.get::<ZigbeeConnectivity>(&link_zbc)?;
+        let mut new = zbc.clone();
+        new.last_seen = Some(last_seen);
+        new.linkquality = linkquality.or(zbc.linkquality);
+        new.rssi = rssi.or(zbc.rssi);
+        new.status = self.connectivity_status(&new);
+
+        let recent = zbc
+            .last_seen
+            .is_some_and(|seen| last_seen - seen < LAST_SEEN_RESOLUTION);
+        if recent
+            && new.linkquality == zbc.linkquality
+            && new.rssi == zbc.rssi
+            && new.status == zbc.status
+        {
+            return Ok(());
+        }
+
+        res.update::<ZigbeeConnectivity>(&link_zbc.rid, |zbc| *zbc = new)?;
+        drop(res);
+
+        Ok(())
+    }
 
-            Message::BridgeGroups(ref obj) => {
-                /* println!("{obj:#?}"); */
-                for grp in obj {
-                    self.add_group(grp).await?;
-                }
-            }
+    /// Mark devices that have not been seen for too long as disconnected
+    async fn check_connectivity(&self) -> ApiResult<()> {
+        let mut res = self.state.lock().await;
+
+        let changed: Vec<(Uuid, ZigbeeConnectivityStatus)> = res
+            .get_resources_by_type(RType::ZigbeeConnectivity)
+            .into_iter()
+            .filter_map(|rr| Some((rr.id, ZigbeeConnectivity::try_from(rr.obj).ok()?)))
+            .filter(|(_, zbc)| {
+                zbc.last_seen.is_some() && self.device_topic(&res, &zbc.owner).is_some()
+            })
+            .filter_map(|(id, zbc)| {
+                let status = self.connectivity_status(&zbc);
+                (status != zbc.status).then_some((id, status))
+            })
+            .collect();
+
+        for (id, status) in changed {
+            log::info!("[{}] Connectivity of {id} is now {status:?}", self.name);
+            res.update::<ZigbeeConnectivity>(&id, |zbc| zbc.status = status)?;
         }
+        drop(res);
+
         Ok(())
     }
 
+    /// The state of a single endpoint, from a device message reporting all
+    /// endpoints. Properties of the endpoint (e.g., `state_l1`) are renamed to
+    /// their plain names, properties of other endpoints are left out, and
+    /// shared properties (e.g., `linkquality`) are kept.
+    fn endpoint_payload(payload: &Value, endpoint: &str, endpoints: &[(String, Uuid)]) -> Value {
+        let Some(obj) = payload.as_object() else {
+            return payload.clone();
+        };
+
+        let suffix = format!("_{endpoint}");
+        let mut res = serde_json::Map::new();
+        for (key, value) in obj {
+            if endpoints
+                .iter()
+                .any(|(ep, _)| key.ends_with(&format!("_{ep}")))
+            {
+                continue;
+            }
+            res.insert(key.clone(), value.clone());
+        }
+        for (key, value) in obj {
+            if let Some(key) = key.strip_suffix(&suffix) {
+                res.insert(key.to_string(), value.clone());
+            }
+        }
+        Value::Object(res)
+    }
+
     async fn handle_device_message(&mut self, msg: RawMessage) -> ApiResult<()> {
         if msg.topic.contains('/') {
             return Ok(());
@@ -460,12 +2033,26 @@ impl Client {
             return Ok(());
         };
 
-        let res = self.handle_update(val, &msg.payload).await;
-        if let Err(ref err) = res {
-            log::error!(
-                "Cannot parse update: {err}\n{}",
-                serde_json::to_string_pretty(&msg.payload)?
-            );
+        if let Err(err) = self.handle_diagnostics(val, &msg.payload).await {
+            log::warn!("[{}] Cannot update diagnostics: {err}", self.name);
+        }
+
+        let updates = match self.endpoints.get(val) {
+            Some(endpoints) => endpoints
+                .iter()
+                .map(|(ep, rid)| (*rid, Self::endpoint_payload(&msg.payload, ep, endpoints)))
+                .collect(),
+            None => vec![(*val, msg.payload)],
+        };
+
+        for (rid, payload) in updates {
+            let res = self.handle_update(&rid, &payload).await;
+            if let Err(ref err) = res {
+                log::error!(
+                    "Cannot parse update: {err}\n{}",
+                    serde_json::to_string_pretty(&payload)?
+                );
+            }
         }
 
         /* return Ok here, since we do not want to break the event loop */
@@ -510,6 +2097,9 @@ impl Client {
                             }
                         }
                     }
+                } else if self.chaos.as_ref().is_some_and(Chaos::drop_incoming) {
+                    log::debug!("[{}] Chaos: dropping message on {}", self.name, msg.topic);
+                    Ok(())
                 } else {
                     self.handle_device_message(msg).await
                 }
@@ -547,13 +2137,21 @@ impl Client {
 
         if scene.actions.is_empty() {
             let room: &Room = lock.get(&scene.group)?;
+            let index = lock.aux_get(lscene)?.index;
 
+            /* scenes imported from z2m do not necessarily include all lights
+             * in the room, so only wait for the lights that have the scene
+             * stored (if known) */
             let lights: Vec<Uuid> = room
                 .children
                 .iter()
                 .filter_map(|rl| lock.get(rl).ok())
                 .filter_map(Device::light_service)
                 .map(|rl| rl.rid)
+                .filter(|rid| match (index, self.light_scenes.get(rid)) {
+                    (Some(index), Some(ids)) if !ids.is_empty() => ids.contains(&index),
+                    _ => true,
+                })
                 .collect();
 
             drop(lock);
@@ -570,22 +2168,127 @@ impl Client {
         Ok(())
     }
 
-    async fn websocket_send<'a>(
+    /// Recall a scene by replaying its actions for the lights on this z2m
+    /// connection. Lights on other connections are handled by their own
+    /// client, so scenes spanning several coordinators are recalled in full.
+    fn scene_recall_actions(
         &self,
-        socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
-        topic: &str,
-        payload: Z2mRequest<'a>,
+        queue: &mut CommandQueue,
+        scene: &ResourceLink,
+        actions: &[SceneActionElement],
+        transition: Option<f64>,
     ) -> ApiResult<()> {
-        let Some(uuid) = self.map.get(topic) else {
-            log::trace!(
-                "[{}] Topic [{topic}] unknown on this z2m connection",
-                self.name
+        if actions.is_empty() {
+            log::warn!(
+                "[{}] Scene {} has no z2m index, and no actions to replay",
+                self.name,
+                scene.rid
             );
             return Ok(());
+        }
+
+        let local: Vec<&SceneActionElement> = actions
+            .iter()
+            .filter(|sae| self.rmap.contains_key(&sae.target.rid))
+            .collect();
+        if local.is_empty() {
+            return Ok(());
+        }
+
+        log::info!(
+            "[{}] Recall scene {} by replaying {} light actions",
+            self.name,
+            scene.rid,
+            local.len()
+        );
+
+        for sae in local {
+            let act = &sae.action;
+            let upd = DeviceUpdate::default()
+                .with_state(act.on.map(|on| on.on))
+                .with_brightness(
+                    act.dimming
+                        .as_ref()
+                        .map(|dim| units::percent_to_device(dim.brightness)),
+                )
+                .with_color_temp(act.color_temperature.as_ref().map(|ct| ct.mirek))
+                .with_color_xy(act.color.as_ref().map(|col| col.xy))
+                .with_transition(transition);
+
+            self.websocket_send(queue, &sae.target.rid, Z2mRequest::Update(&upd))?;
+        }
+
+        Ok(())
+    }
+
+    /// Brightness changes cannot be sent as a group command, if any of the
+    /// lights has a minimum brightness (since each light is scaled on its
+    /// own). Neither can updates to a group with only some of its lights
+    /// under quiet hours.
+    fn needs_per_light(&self, group: &Uuid, lights: &[ResourceLink], upd: &DeviceUpdate) -> bool {
+        let scaled = upd.brightness.is_some()
+            && lights
+                .iter()
+                .any(|light| self.min_brightness.contains_key(&light.rid));
+
+        let quiet = !self.night_targets.contains(group)
+            && lights
+                .iter()
+                .any(|light| self.night_targets.contains(&light.rid));
+
+        scaled || quiet
+    }
+
+    /// Send a request to the z2m device (or group) backing `uuid`, addressed
+    /// by its ieee address (or group id), never by its friendly name.
+    fn websocket_send(
+        &self,
+        queue: &mut CommandQueue,
+        uuid: &Uuid,
+        payload: Z2mRequest<'_>,
+    ) -> ApiResult<()> {
+        self.websocket_send_prio(queue, Priority::Interactive, uuid, payload)
+    }
+
+    fn websocket_send_prio(
+        &self,
+        queue: &mut CommandQueue,
+        prio: Priority,
+        uuid: &Uuid,
+        payload: Z2mRequest<'_>,
+    ) -> ApiResult<()> {
+        let Some(topic) = self.rmap.get(uuid) else {
+            log::trace!("[{}] {uuid} unknown on this z2m connection", self.name);
+            return Ok(());
+        };
+
+        /* quiet hours keep the lights of some rooms dim and warm */
+        let night_upd = match (&payload, &self.night) {
+            (Z2mRequest::Update(upd), Some(night)) if self.night_targets.contains(uuid) => {
+                night.apply(upd)
+            }
+            _ => None,
+        };
+        if night_upd.is_some() {
+            log::debug!("[{}] Quiet hours, limiting update for {uuid}", self.name);
+        }
+        let payload = night_upd.as_ref().map_or(payload, Z2mRequest::Update);
+
+        /* lights with a minimum brightness are dimmed within their usable range */
+        let scaled;
+        let payload = match (payload, self.min_brightness.get(uuid)) {
+            (Z2mRequest::Update(upd), Some(min)) if upd.brightness.is_some() => {
+                let bri = upd
+                    .brightness
+                    .map(|bri| units::brightness_to_device(bri, *min));
+                scaled = upd.clone().with_brightness(bri);
+                Z2mRequest::Update(&scaled)
+            }
+            (payload, _) => payload,
         };
 
         log::trace!(
-            "[{}] Topic [{topic}] known as {uuid} on this z2m connection, sending event..",
+            "[{}] {uuid} known as [{topic}] on this z2m connection, sending event..",
             self.name
         );
         let api_req = Other {
@@ -593,58 +2296,213 @@ impl Client {
             topic: format!("{topic}/set"),
         };
         let json = serde_json::to_string(&api_req)?;
-        log::debug!("[{}] Sending {json}", self.name);
-        let msg = tungstenite::Message::Text(json);
-        Ok(socket.send(msg).await?)
+        if prio == Priority::Entertainment {
+            /* stream frames are superseded by the next one, so they are
+             * neither tracked, nor retried */
+            log::trace!("[{}] Queueing {json}", self.name);
+            queue.push(prio, json);
+        } else {
+            log::debug!("[{}] Queueing {json}", self.name);
+            queue.push_for(prio, *uuid, json);
+        }
+        Ok(())
+    }
+
+    fn websocket_bridge_request(
+        &self,
+        queue: &mut CommandQueue,
+        prio: Priority,
+        request: &str,
+        payload: Value,
+    ) -> ApiResult<()> {
+        let api_req = Other {
+            payload,
+            topic: format!("bridge/request/{request}"),
+        };
+        let json = serde_json::to_string(&api_req)?;
+        log::debug!("[{}] Queueing {json}", self.name);
+        queue.push(prio, json);
+        Ok(())
+    }
+
+    /// Send an update to the lights of a zone. This uses the z2m group for
+    /// the zone if it is in sync, or updates each light otherwise.
+    fn websocket_zone_update(
+        &mut self,
+        queue: &mut CommandQueue,
+        zone: &ResourceLink,
+        lights: &[ResourceLink],
+        upd: &DeviceUpdate,
+    ) -> ApiResult<()> {
+        let members: HashSet<Uuid> = lights.iter().map(|light| light.rid).collect();
+
+        if let Some(group) = self.zone_groups.get(&zone.rid) {
+            if group.members == members && !self.needs_per_light(&zone.rid, lights, upd) {
+                let z2mreq = Z2mRequest::Update(upd);
+                return self.websocket_send(queue, &group.glight, z2mreq);
+            }
+        }
+
+        for light in lights {
+            self.websocket_send(queue, &light.rid, Z2mRequest::Update(upd))?;
+        }
+
+        /* a group can only be used, if all lights are on this coordinator */
+        let local = lights
+            .iter()
+            .all(|light| self.rmap.contains_key(&light.rid));
+        if self.server.zone_groups && local && !lights.is_empty() {
+            self.websocket_zone_group_sync(queue, zone, &members)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create (or update) the z2m group for a zone, to match its lights. The
+    /// group is used once z2m reports it back with the right members.
+    fn websocket_zone_group_sync(
+        &mut self,
+        queue: &mut CommandQueue,
+        zone: &ResourceLink,
+        members: &HashSet<Uuid>,
+    ) -> ApiResult<()> {
+        if !self.zone_sync.insert(zone.rid) {
+            return Ok(());
+        }
+
+        let name = format!("{ZONE_GROUP_PREFIX}{}", zone.rid.simple());
+        let current = if let Some(group) = self.zone_groups.get(&zone.rid) {
+            group.members.clone()
+        } else {
+            log::info!("[{}] Creating group [{name}] for zone", self.name);
+            let payload = json!({"friendly_name": name});
+            self.websocket_bridge_request(queue, Priority::Background, "group/add", payload)?;
+            HashSet::new()
+        };
+
+        for light in members.difference(&current) {
+            if let Some(device) = self.rmap.get(light) {
+                let payload = json!({"group": name, "device": device});
+                self.websocket_bridge_request(
+                    queue,
+                    Priority::Background,
+                    "group/members/add",
+                    payload,
+                )?;
+            }
+        }
+
+        for light in current.difference(members) {
+            if let Some(device) = self.rmap.get(light) {
+                let payload = json!({"group": name, "device": device});
+                self.websocket_bridge_request(
+                    queue,
+                    Priority::Background,
+                    "group/members/remove",
+                    payload,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the z2m topic of a device. Devices are known by their light
+    /// service, if they have one.
+    fn device_topic(&self, res: &Resources, device: &ResourceLink) -> Option<&String> {
+        self.rmap.get(&device.rid).or_else(|| {
+            res.get::<Device>(device)
+                .ok()?
+                .services
+                .iter()
+                .find_map(|svc| self.rmap.get(&svc.rid))
+        })
     }
 
+    #[allow(clippy::too_many_lines)]
     async fn websocket_write(
         &mut self,
-        socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
-        req: Arc<ClientRequest>,
+        queue: &mut CommandQueue,
+        req: &ClientRequest,
     ) -> ApiResult<()> {
         self.learn_cleanup();
 
         let lock = self.state.lock().await;
+        self.night_targets = self
+            .night
+            .as_ref()
+            .map(|night| night.targets(&lock))
+            .unwrap_or_default();
 
-        match &*req {
+        match req {
             ClientRequest::LightUpdate { device, upd } => {
                 drop(lock);
-                if let Some(topic) = self.rmap.get(&device.rid) {
-                    let z2mreq = Z2mRequest::Update(upd);
-                    self.websocket_send(socket, topic, z2mreq).await?;
-                };
+                self.websocket_send(queue, &device.rid, Z2mRequest::Update(upd))?;
+            }
+
+            ClientRequest::StreamUpdate { device, upd } => {
+                drop(lock);
+                self.websocket_send_prio(
+                    queue,
+                    Priority::Entertainment,
+                    &device.rid,
+                    Z2mRequest::Update(upd),
+                )?;
             }
 
             ClientRequest::GroupUpdate { device, upd } => {
-                let room = lock.get::<GroupedLight>(device)?.owner.rid;
+                let owner = lock.get::<GroupedLight>(device)?.owner;
+
+                let lights = lock.get_lights_for_group(&owner);
                 drop(lock);
 
-                if let Some(topic) = self.rmap.get(&room) {
-                    let z2mreq = Z2mRequest::Update(upd);
-                    self.websocket_send(socket, topic, z2mreq).await?;
+                /* zones are not z2m groups (unless configured as one), so
+                 * they need special handling */
+                if owner.rtype == RType::Zone && !self.rmap.contains_key(&owner.rid) {
+                    self.websocket_zone_update(queue, &owner, &lights, upd)?;
+                } else if self.needs_per_light(&owner.rid, &lights, upd) {
+                    for light in &lights {
+                        self.websocket_send(queue, &light.rid, Z2mRequest::Update(upd))?;
+                    }
+                } else {
+                    self.websocket_send(queue, &owner.rid, Z2mRequest::Update(upd))?;
                 }
             }
 
             ClientRequest::SceneStore { room, id, name } => {
                 drop(lock);
-                if let Some(topic) = self.rmap.get(&room.rid) {
-                    let z2mreq = Z2mRequest::SceneStore { name, id: *id };
-                    self.websocket_send(socket, topic, z2mreq).await?;
-                }
+                let z2mreq = Z2mRequest::SceneStore { name, id: *id };
+                self.websocket_send(queue, &room.rid, z2mreq)?;
             }
 
-            ClientRequest::SceneRecall { scene } => {
-                let room = lock.get::<Scene>(scene)?.group.rid;
-                let index = lock
-                    .aux_get(scene)?
-                    .index
-                    .ok_or(ApiError::NotFound(scene.rid))?;
+            ClientRequest::SceneRecall { scene, transition } => {
+                let scn = lock.get::<Scene>(scene)?;
+                let room = scn.group.rid;
+                let index = lock.aux_get(scene).ok().and_then(|aux| aux.index);
+                let actions = scn.actions.clone();
                 drop(lock);
-                if let Some(topic) = self.rmap.get(&room).cloned() {
-                    self.learn_scene_recall(scene).await?;
-                    let z2mreq = Z2mRequest::SceneRecall(index);
-                    self.websocket_send(socket, &topic, z2mreq).await?;
+
+                /* stored scenes are recalled by the lights themselves, so
+                 * they cannot be limited during quiet hours */
+                let quiet = self.night_targets.contains(&room);
+
+                if let Some(index) = index.filter(|_| !quiet) {
+                    if self.rmap.contains_key(&room) {
+                        self.learn_scene_recall(scene).await?;
+                        let z2mreq = Z2mRequest::SceneRecall {
+                            scene_recall: index,
+                            transition: *transition,
+                        };
+                        self.websocket_send(queue, &room, z2mreq)?;
+                    } else if !actions.is_empty() {
+                        /* the scene index is only stored on the coordinator of
+                         * the room, so lights on this coordinator replay
+                         * their actions instead */
+                        self.scene_recall_actions(queue, scene, &actions, *transition)?;
+                    }
+                } else {
+                    /* Scene is not stored in z2m (yet), so replay its light actions instead */
+                    self.scene_recall_actions(queue, scene, &actions, *transition)?;
                 }
             }
 
@@ -656,9 +2514,113 @@ impl Client {
                     .ok_or(ApiError::NotFound(scene.rid))?;
                 drop(lock);
 
-                if let Some(topic) = self.rmap.get(&room).cloned() {
-                    let z2mreq = Z2mRequest::SceneRemove(index);
-                    self.websocket_send(socket, &topic, z2mreq).await?;
+                let z2mreq = Z2mRequest::SceneRemove(index);
+                self.websocket_send(queue, &room, z2mreq)?;
+            }
+
+            ClientRequest::MotionSensitivity {
+                motion,
+                sensitivity,
+            } => {
+                let dev = lock.get::<Motion>(motion)?.owner.rid;
+                drop(lock);
+
+                let value = self
+                    .sensitivity
+                    .get(&motion.rid)
+                    .zip(usize::try_from(*sensitivity).ok())
+                    .and_then(|(values, index)| values.get(index));
+
+                if let Some(value) = value {
+                    let upd = DeviceUpdate::default().with_motion_sensitivity(Some(value.clone()));
+                    self.websocket_send(queue, &dev, Z2mRequest::Update(&upd))?;
+                }
+            }
+
+            ClientRequest::PermitJoin { coordinator, time } => {
+                drop(lock);
+                if coordinator.is_none() || *coordinator == self.coordinator {
+                    let payload = json!({"value": *time > 0, "time": time});
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Interactive,
+                        "permit_join",
+                        payload,
+                    )?;
+                }
+            }
+
+            ClientRequest::InstallCode { coordinator, code } => {
+                drop(lock);
+                if coordinator.is_none() || *coordinator == self.coordinator {
+                    let payload = json!({"value": code});
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Interactive,
+                        "install_code/add",
+                        payload,
+                    )?;
+                }
+            }
+
+            ClientRequest::NetworkMap {
+                coordinator,
+                map_type,
+                routes,
+            } => {
+                drop(lock);
+                if self.coordinator.as_ref() == Some(coordinator) {
+                    log::info!("[{}] Requesting {map_type:?} network map", self.name);
+                    let payload = json!({"type": map_type, "routes": routes});
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Background,
+                        "networkmap",
+                        payload,
+                    )?;
+                }
+            }
+
+            ClientRequest::BridgeRestart { device } => {
+                drop(lock);
+                if self.coordinator.as_ref() == Some(device) {
+                    log::warn!("[{}] Restarting zigbee2mqtt", self.name);
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Interactive,
+                        "restart",
+                        json!(""),
+                    )?;
+                }
+            }
+
+            ClientRequest::GroupMemberAdd { room, device } => {
+                let topic = self.device_topic(&lock, device).cloned();
+                drop(lock);
+
+                if let (Some(group), Some(topic)) = (self.rmap.get(&room.rid), topic) {
+                    let payload = json!({"group": group, "device": topic});
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Background,
+                        "group/members/add",
+                        payload,
+                    )?;
+                }
+            }
+
+            ClientRequest::DeviceOptions { device, options } => {
+                let topic = self.device_topic(&lock, device);
+                drop(lock);
+
+                if let Some(topic) = topic {
+                    let payload = json!({"id": topic, "options": options});
+                    self.websocket_bridge_request(
+                        queue,
+                        Priority::Background,
+                        "device/options",
+                        payload,
+                    )?;
                 }
             }
         }
@@ -666,17 +2628,241 @@ impl Client {
         Ok(())
     }
 
+    async fn websocket_poll(&self, queue: &mut CommandQueue) -> ApiResult<()> {
+        /* z2m keeps device state current by itself, when availability is enabled */
+        if self.availability {
+            return Ok(());
+        }
+
+        let lock = self.state.lock().await;
+        let topics: Vec<&String> = self
+            .rmap
+            .iter()
+            .filter(|(uuid, _)| lock.get_resource(RType::Light, uuid).is_ok())
+            .map(|(_, topic)| topic)
+            .collect();
+        drop(lock);
+
+        log::debug!("[{}] Polling state of {} lights", self.name, topics.len());
+
+        for topic in topics {
+            let api_req = Other {
+                payload: json!({"state": ""}),
+                topic: format!("{topic}/get"),
+            };
+            queue.push(Priority::Background, serde_json::to_string(&api_req)?);
+        }
+
+        Ok(())
+    }
+
+    /// Measure the time since the oldest unanswered request to `rid`, now that
+    /// its state update has arrived. Requests that are never answered are
+    /// counted as timeouts.
+    async fn track_latency(&mut self, rid: &Uuid) {
+        let now = Instant::now();
+        let sent = self.pending.remove(rid).map(|cmd| cmd.since);
+
+        /* with retries enabled, unanswered commands are expired by [`Self::retry`] */
+        let expired: Vec<Uuid> = if self.server.retries == 0 {
+            self.pending
+                .iter()
+                .filter(|(_, cmd)| now - cmd.since > LATENCY_TIMEOUT)
+                .map(|(uuid, _)| *uuid)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        if sent.is_none() && expired.is_empty() {
+            return;
+        }
+
+        let mut lock = self.state.lock().await;
+        if let Some(sent) = sent {
+            let latency = now - sent;
+            if latency > LATENCY_WARNING {
+                log::warn!(
+                    "[{}] Slow response from {rid}: {}ms",
+                    self.name,
+                    latency.as_millis()
+                );
+            }
+            lock.record_latency(rid, Some(latency));
+            otlp::record_roundtrip(&self.name, &rid.to_string(), Some(latency));
+        }
+        for uuid in expired {
+            self.pending.remove(&uuid);
+            log::warn!("[{}] No response from {uuid}", self.name);
+            lock.record_latency(&uuid, None);
+            otlp::record_roundtrip(&self.name, &uuid.to_string(), None);
+        }
+        drop(lock);
+    }
+
+    /// Remember a command sent to `target`, until its state update arrives.
+    /// A different command replaces the one waiting for confirmation.
+    fn track_command(&mut self, target: Uuid, msg: &str) {
+        let now = Instant::now();
+        let cmd = self
+            .pending
+            .entry(target)
+            .or_insert_with(|| PendingCommand {
+                since: now,
+                sent: now,
+                msg: msg.to_string(),
+                attempts: 0,
+            });
+        if cmd.msg != msg {
+            cmd.msg = msg.to_string();
+            cmd.attempts = 0;
+        }
+        cmd.sent = now;
+    }
+
+    /// The time the next unconfirmed command is due for a retry, if retries
+    /// are enabled
+    fn next_retry(&self) -> Option<Instant> {
+        if self.server.retries == 0 {
+            return None;
+        }
+        let timeout = self.server.retry_timeout();
+        self.pending.values().map(|cmd| cmd.sent + timeout).min()
+    }
+
+    /// Send unconfirmed commands again, until the configured number of
+    /// retries is used up. Commands that are never confirmed are reported as
+    /// failed, and their target is marked as having connectivity issues,
+    /// until it is heard from again.
+    async fn retry(&mut self, queue: &mut CommandQueue) -> ApiResult<()> {
+        let now = Instant::now();
+        let timeout = self.server.retry_timeout();
+
+        let due: Vec<Uuid> = self
+            .pending
+            .iter()
+            .filter(|(_, cmd)| now - cmd.sent >= timeout)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+
+        let mut lock = self.state.lock().await;
+        for uuid in due {
+            let Some(cmd) = self.pending.get_mut(&uuid) else {
+                continue;
+            };
+
+            if cmd.attempts < self.server.retries {
+                cmd.attempts += 1;
+                /* not due again until sent (and the timeout has passed) */
+                cmd.sent = now + timeout;
+                log::info!(
+                    "[{}] No response from {uuid}, retrying ({}/{})",
+                    self.name,
+                    cmd.attempts,
+                    self.server.retries
+                );
+                queue.push_for(Priority::Interactive, uuid, cmd.msg.clone());
+                lock.record_retry(&uuid);
+                otlp::record_retry();
+                continue;
+            }
+
+            self.pending.remove(&uuid);
+            log::warn!(
+                "[{}] No response from {uuid} after {} retries",
+                self.name,
+                self.server.retries
+            );
+            lock.record_latency(&uuid, None);
+            lock.record_delivery_failure(&uuid);
+            otlp::record_roundtrip(&self.name, &uuid.to_string(), None);
+            otlp::record_delivery_failure();
+
+            let Some(link_zbc) = Self::connectivity_service(&lock, &uuid) else {
+                continue;
+            };
+            if lock.get::<ZigbeeConnectivity>(&link_zbc)?.status
+                == ZigbeeConnectivityStatus::Connected
+            {
+                lock.update::<ZigbeeConnectivity>(&link_zbc.rid, |zbc| {
+                    zbc.status = ZigbeeConnectivityStatus::ConnectivityIssue;
+                })?;
+            }
+        }
+        drop(lock);
+
+        Ok(())
+    }
+
+    async fn deadline_tick(ready: Option<Instant>) {
+        match ready {
+            Some(at) => sleep_until(at).await,
+            None => std::future::pending().await,
+        }
+    }
+
     pub async fn event_loop(
         &mut self,
-        chan: &mut Receiver<Arc<ClientRequest>>,
+        chan: &mut Receiver<Arc<TaggedRequest>>,
         mut socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
     ) -> ApiResult<()> {
+        let mut poll = self.server.poll_interval.map(interval_secs);
+
+        let mut connectivity = self
+            .server
+            .unreachable_after
+            .map(|_| interval(CONNECTIVITY_CHECK_INTERVAL));
+
+        let mut queue = CommandQueue::new(
+            self.server.max_rate.unwrap_or(DEFAULT_MAX_RATE),
+            self.server.max_command_age(),
+        );
+        self.pending.clear();
+        self.held.clear();
+
+        let disconnect = self.chaos.as_ref().and_then(Chaos::disconnect_at);
+
         loop {
             select! {
+                () = Self::deadline_tick(queue.ready_at().filter(|_| self.online)) => {
+                    if let Some(delay) = self.chaos.as_mut().and_then(Chaos::hold) {
+                        queue.hold(Instant::now() + delay);
+                    } else if let Some((json, target)) = queue.pop() {
+                        log::trace!("[{}] Sending {json} ({} queued)", self.name, queue.len());
+                        if let Some(target) = target {
+                            self.track_command(target, &json);
+                            if self.chaos.as_ref().is_some_and(Chaos::drop_outgoing) {
+                                log::debug!("[{}] Chaos: dropping {json}", self.name);
+                                continue;
+                            }
+                        }
+                        socket.send(tungstenite::Message::Text(json)).await?;
+                    }
+                },
+                () = Self::deadline_tick(disconnect) => {
+                    log::warn!("[{}] Chaos: dropping connection", self.name);
+                    return Ok(());
+                },
+                () = Self::deadline_tick(self.next_retry().filter(|_| self.online)) => {
+                    self.retry(&mut queue).await?;
+                },
+                () = Self::deadline_tick(self.next_button_repeat()) => {
+                    self.button_repeat().await?;
+                },
+                () = optional_tick(&mut poll) => {
+                    self.websocket_poll(&mut queue).await?;
+                },
+                () = optional_tick(&mut connectivity) => {
+                    self.check_connectivity().await?;
+                },
                 pkt = chan.recv() => {
                     let api_req = pkt?;
-                    self.websocket_write(&mut socket, api_req).await?;
-                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    let request_id = api_req.request_id.clone();
+                    logging::with_request_id(
+                        request_id,
+                        self.websocket_write(&mut queue, &api_req.req),
+                    )
+                    .await?;
                 },
                 pkt = socket.next() => {
                     self.websocket_read(pkt.ok_or(ApiError::UnexpectedZ2mEof)??).await?;
@@ -685,19 +2871,33 @@ impl Client {
         }
     }
 
+    /// Connect to zigbee2mqtt, and keep reconnecting when the connection is
+    /// lost. If zigbee2mqtt cannot be reached at startup, the devices of this
+    /// server are served from the state file, marked as unreachable, until
+    /// the first connection is made.
     pub async fn run_forever(mut self) -> ApiResult<()> {
         let mut chan = self.state.lock().await.z2m_channel();
+        let mut connected = false;
         loop {
             log::info!("[{}] Connecting to {}", self.name, self.server.url);
             match connect_async(&self.server.url).await {
                 Ok((socket, _)) => {
+                    connected = true;
                     let res = self.event_loop(&mut chan, socket).await;
                     if let Err(err) = res {
                         log::error!("[{}] Event loop broke: {err}", self.name);
                     }
+                    if let Err(err) = self.coordinator_disconnected().await {
+                        log::error!("[{}] Failed to update coordinator: {err}", self.name);
+                    }
                 }
                 Err(err) => {
                     log::error!("[{}] Connect failed: {err:?}", self.name);
+                    if !connected {
+                        if let Err(err) = self.mark_unreachable().await {
+                            log::error!("[{}] Failed to mark devices: {err}", self.name);
+                        }
+                    }
                 }
             }
             sleep(std::time::Duration::from_millis(2000)).await;
@@ -705,26 +2905,69 @@ impl Client {
     }
 }
 
-#[allow(clippy::match_same_arms)]
-fn guess_scene_icon(name: &str) -> Option<ResourceLink> {
-    let icon = match name {
-        /* Built-in names */
-        "Bright" => scene_icons::BRIGHT,
-        "Relax" => scene_icons::RELAX,
-        "Night Light" => scene_icons::NIGHT_LIGHT,
-        "Rest" => scene_icons::REST,
-        "Concentrate" => scene_icons::CONCENTRATE,
-        "Dimmed" => scene_icons::DIMMED,
-        "Energize" => scene_icons::ENERGIZE,
-        "Read" => scene_icons::READ,
-        "Cool Bright" => scene_icons::COOL_BRIGHT,
+/// Parse the `last_seen` value of a device message. Depending on the z2m
+/// config, this is an iso 8601 timestamp, or milliseconds since the epoch.
+fn parse_last_seen(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+        Value::Number(n) => DateTime::from_timestamp_millis(n.as_i64()?),
+        _ => None,
+    }
+}
+
+/// Map a z2m action (e.g. `press_2` on a green power switch, or `up_hold` on a
+/// dimmer switch) to the control id of the button, and the corresponding hue
+/// button event.
+fn parse_button_action(action: &str) -> Option<(u32, &'static str)> {
+    if let Some(res) = parse_dimmer_action(action) {
+        return Some(res);
+    }
+
+    let (kind, button) = action.split_once('_')?;
+    let control_id = button.parse().ok()?;
+
+    match kind {
+        "press" => Some((control_id, "initial_press")),
+        "release" => Some((control_id, "short_release")),
+        _ => None,
+    }
+}
+
+fn parse_dimmer_action(action: &str) -> Option<(u32, &'static str)> {
+    let (button, kind) = action.split_once('_')?;
+
+    let control_id = match button {
+        "on" => 1,
+        "up" => 2,
+        "down" => 3,
+        "off" | "hue" => 4,
+        _ => return None,
+    };
+
+    let event = match kind {
+        "press" => "initial_press",
+        "press_release" => "short_release",
+        "hold" => "repeat",
+        "hold_release" => "long_release",
+        _ => return None,
+    };
 
+    Some((control_id, event))
+}
+
+pub(crate) fn guess_scene_icon(name: &str, locale: Locale) -> Option<ResourceLink> {
+    let icon = match name {
         /* Aliasas */
         "Night" => scene_icons::NIGHT_LIGHT,
         "Cool" => scene_icons::COOL_BRIGHT,
         "Dim" => scene_icons::DIMMED,
 
-        _ => return None,
+        /* Built-in names, in english or the configured language */
+        _ => Locale::En
+            .scene_icon(name)
+            .or_else(|| locale.scene_icon(name))?,
     };
 
     Some(ResourceLink {
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Priority of messages sent to zigbee2mqtt. Messages with a higher priority
+/// are always sent before any message with a lower priority.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// State polling, group synchronization, device configuration
+    Background = 0,
+    /// Commands caused by api requests
+    Interactive = 1,
+    /// Entertainment streaming
+    Entertainment = 2,
+}
+
+/// Messages per second sent to z2m, unless configured otherwise
+pub const DEFAULT_MAX_RATE: f64 = 20.0;
+
+/// Messages queued for each priority, at most. When full, the oldest
+/// message of that priority is dropped. Stream frames go stale quickly, so
+/// only a few are kept.
+const CAPACITY: [usize; 3] = [512, 512, 64];
+
+/// Stream frames older than this are dropped, regardless of the configured
+/// maximum age
+const MAX_FRAME_AGE: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Queued {
+    msg: String,
+    target: Option<Uuid>,
+    since: Instant,
+}
+
+/// Outgoing message queue for a single z2m connection.
+///
+/// Messages are sent highest priority first (and in order within each
+/// priority), at no more than the configured number of messages per second.
+/// Messages that have been queued for longer than `max_age` (e.g., while z2m
+/// is offline) are dropped.
+#[derive(Debug)]
+pub struct CommandQueue {
+    queues: [VecDeque<Queued>; 3],
+    interval: Option<Duration>,
+    max_age: Duration,
+    next_send: Instant,
+}
+
+impl CommandQueue {
+    /// A queue sending at most `max_rate` messages per second (unlimited,
+    /// if zero)
+    #[must_use]
+    pub fn new(max_rate: f64, max_age: Duration) -> Self {
+        let interval = (max_rate > 0.0).then(|| Duration::from_secs_f64(1.0 / max_rate));
+
+        Self {
+            queues: Default::default(),
+            interval,
+            max_age,
+            next_send: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, prio: Priority, msg: String) {
+        self.push_entry(prio, msg, None);
+    }
+
+    /// Queue a message targeting a specific resource, for latency tracking
+    pub fn push_for(&mut self, prio: Priority, target: Uuid, msg: String) {
+        self.push_entry(prio, msg, Some(target));
+    }
+
+    fn push_entry(&mut self, prio: Priority, msg: String, target: Option<Uuid>) {
+        let queue = &mut self.queues[prio as usize];
+        if queue.len() >= CAPACITY[prio as usize] {
+            if let Some(Queued { msg, .. }) = queue.pop_front() {
+                if prio == Priority::Entertainment {
+                    log::trace!("Queue full, dropping {msg}");
+                } else {
+                    log::warn!("{prio:?} queue full, dropping {msg}");
+                }
+            }
+        }
+        queue.push_back(Queued {
+            msg,
+            target,
+            since: Instant::now(),
+        });
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    /// The time the next message can be sent, or `None` if the queue is empty
+    #[must_use]
+    pub fn ready_at(&self) -> Option<Instant> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.next_send)
+        }
+    }
+
+    /// Send nothing before `until`
+    pub fn hold(&mut self, until: Instant) {
+        self.next_send = self.next_send.max(until);
+    }
+
+    /// Take the next message to send (and its target, if any), and start the
+    /// rate limit interval. Messages that are too old are dropped.
+    pub fn pop(&mut self) -> Option<(String, Option<Uuid>)> {
+        self.expire();
+        let Queued { msg, target, .. } =
+            self.queues.iter_mut().rev().find_map(VecDeque::pop_front)?;
+
+        if let Some(interval) = self.interval {
+            self.next_send = Instant::now() + interval;
+        }
+
+        Some((msg, target))
+    }
+
+    /// Drop messages that have been queued for too long. Messages are queued
+    /// in order, so only the front of each queue needs checking.
+    fn expire(&mut self) {
+        for (prio, queue) in self.queues.iter_mut().enumerate() {
+            let max_age = if prio == Priority::Entertainment as usize {
+                MAX_FRAME_AGE.min(self.max_age)
+            } else {
+                self.max_age
+            };
+
+            let mut dropped = 0;
+            while queue
+                .front()
+                .is_some_and(|queued| queued.since.elapsed() > max_age)
+            {
+                queue.pop_front();
+                dropped += 1;
+            }
+
+            if dropped > 0 && prio != Priority::Entertainment as usize {
+                log::warn!(
+                    "Dropping {dropped} command(s) queued for more than {}s",
+                    max_age.as_secs_f64()
+                );
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::config::DeviceOptions;
 use crate::hue::api::ResourceLink;
+use crate::model::networkmap::NetworkMapType;
 use crate::z2m::update::DeviceUpdate;
 
 #[derive(Clone, Debug, Deserialize)]
@@ -15,6 +17,12 @@ pub enum ClientRequest {
         upd: DeviceUpdate,
     },
 
+    /* a light update from an entertainment stream */
+    StreamUpdate {
+        device: ResourceLink,
+        upd: DeviceUpdate,
+    },
+
     SceneStore {
         room: ResourceLink,
         id: u32,
@@ -23,11 +31,52 @@ pub enum ClientRequest {
 
     SceneRecall {
         scene: ResourceLink,
+        /* transition time, in seconds */
+        transition: Option<f64>,
     },
 
     SceneRemove {
         scene: ResourceLink,
     },
+
+    MotionSensitivity {
+        motion: ResourceLink,
+        sensitivity: u32,
+    },
+
+    /* permit join on all coordinators, or only the given one */
+    PermitJoin {
+        coordinator: Option<ResourceLink>,
+        time: u32,
+    },
+
+    /* add an install code on all coordinators, or only the given one */
+    InstallCode {
+        coordinator: Option<ResourceLink>,
+        code: String,
+    },
+
+    DeviceOptions {
+        device: ResourceLink,
+        options: DeviceOptions,
+    },
+
+    BridgeRestart {
+        device: ResourceLink,
+    },
+
+    /* add a device to the z2m group behind a room */
+    GroupMemberAdd {
+        room: ResourceLink,
+        device: ResourceLink,
+    },
+
+    /* request a network map from the given coordinator */
+    NetworkMap {
+        coordinator: ResourceLink,
+        map_type: NetworkMapType,
+        routes: bool,
+    },
 }
 
 impl ClientRequest {
@@ -36,6 +85,11 @@ impl ClientRequest {
         Self::LightUpdate { device, upd }
     }
 
+    #[must_use]
+    pub const fn stream_update(device: ResourceLink, upd: DeviceUpdate) -> Self {
+        Self::StreamUpdate { device, upd }
+    }
+
     #[must_use]
     pub const fn group_update(device: ResourceLink, upd: DeviceUpdate) -> Self {
         Self::GroupUpdate { device, upd }
@@ -47,8 +101,76 @@ impl ClientRequest {
     }
 
     #[must_use]
-    pub const fn scene_recall(scene: ResourceLink) -> Self {
-        Self::SceneRecall { scene }
+    pub const fn scene_recall(scene: ResourceLink, transition: Option<f64>) -> Self {
+        Self::SceneRecall { scene, transition }
+    }
+
+    #[must_use]
+    pub const fn motion_sensitivity(motion: ResourceLink, sensitivity: u32) -> Self {
+        Self::MotionSensitivity {
+            motion,
+            sensitivity,
+        }
+    }
+
+    #[must_use]
+    pub const fn permit_join(time: u32) -> Self {
+        Self::PermitJoin {
+            coordinator: None,
+            time,
+        }
+    }
+
+    #[must_use]
+    pub const fn permit_join_on(coordinator: ResourceLink, time: u32) -> Self {
+        Self::PermitJoin {
+            coordinator: Some(coordinator),
+            time,
+        }
+    }
+
+    #[must_use]
+    pub const fn install_code(code: String) -> Self {
+        Self::InstallCode {
+            coordinator: None,
+            code,
+        }
+    }
+
+    #[must_use]
+    pub const fn install_code_on(coordinator: ResourceLink, code: String) -> Self {
+        Self::InstallCode {
+            coordinator: Some(coordinator),
+            code,
+        }
+    }
+
+    #[must_use]
+    pub const fn device_options(device: ResourceLink, options: DeviceOptions) -> Self {
+        Self::DeviceOptions { device, options }
+    }
+
+    #[must_use]
+    pub const fn bridge_restart(device: ResourceLink) -> Self {
+        Self::BridgeRestart { device }
+    }
+
+    #[must_use]
+    pub const fn group_member_add(room: ResourceLink, device: ResourceLink) -> Self {
+        Self::GroupMemberAdd { room, device }
+    }
+
+    #[must_use]
+    pub const fn network_map(
+        coordinator: ResourceLink,
+        map_type: NetworkMapType,
+        routes: bool,
+    ) -> Self {
+        Self::NetworkMap {
+            coordinator,
+            map_type,
+            routes,
+        }
     }
 
     #[must_use]
@@ -57,6 +179,48 @@ impl ClientRequest {
     }
 }
 
+/// A [`ClientRequest`], tagged with the id of the http request that caused it
+#[derive(Clone, Debug)]
+pub struct TaggedRequest {
+    pub request_id: Option<String>,
+    pub req: ClientRequest,
+}
+
+/// Combine light updates for the same light into one request. Updates are
+/// only combined across other light updates (which do not affect each
+/// other), so any other request in between keeps them apart.
+#[must_use]
+pub fn coalesce(requests: Vec<TaggedRequest>) -> Vec<TaggedRequest> {
+    let mut res: Vec<TaggedRequest> = Vec::with_capacity(requests.len());
+
+    for tagged in requests {
+        let ClientRequest::LightUpdate { device, upd } = tagged.req else {
+            res.push(tagged);
+            continue;
+        };
+
+        let earlier = res
+            .iter_mut()
+            .rev()
+            .map_while(|prev| match &mut prev.req {
+                ClientRequest::LightUpdate { device, upd } => Some((*device, upd)),
+                _ => None,
+            })
+            .find(|(prev, _)| *prev == device);
+
+        if let Some((_, prev)) = earlier {
+            *prev = std::mem::take(prev).merge(upd);
+        } else {
+            res.push(TaggedRequest {
+                request_id: tagged.request_id,
+                req: ClientRequest::LightUpdate { device, upd },
+            });
+        }
+    }
+
+    res
+}
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Z2mRequest<'a> {
@@ -66,10 +230,15 @@ pub enum Z2mRequest<'a> {
         id: u32,
     },
 
-    SceneRecall(u32),
-
     SceneRemove(u32),
 
+    #[serde(untagged)]
+    SceneRecall {
+        scene_recall: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transition: Option<f64>,
+    },
+
     #[serde(untagged)]
     Update(&'a DeviceUpdate),
 }
@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::task::{AbortHandle, JoinSet};
+
+use crate::config::{AppConfig, Z2mServer};
+use crate::error::ApiResult;
+use crate::resource::Resources;
+use crate::z2m::Client;
+
+/// Run a client for each z2m server, starting, stopping and restarting
+/// clients as servers are added, removed or changed through the admin api.
+/// Clients are stopped along with the supervisor.
+pub async fn supervisor(config: Arc<AppConfig>, res: Arc<Mutex<Resources>>) -> ApiResult<()> {
+    let notify = res.lock().await.z2m_server_notify();
+    let mut clients = JoinSet::new();
+    let mut running: BTreeMap<String, (Z2mServer, AbortHandle)> = BTreeMap::new();
+
+    loop {
+        let servers = res.lock().await.z2m_servers().effective(&config.z2m);
+
+        /* stop clients for servers that were removed or changed */
+        running.retain(|name, (server, handle)| {
+            if servers.get(name) == Some(server) && !handle.is_finished() {
+                return true;
+            }
+            log::info!("[{name}] Stopping z2m client");
+            handle.abort();
+            false
+        });
+
+        for (name, server) in servers {
+            if running.contains_key(&name) {
+                continue;
+            }
+            match Client::new(name.clone(), server.clone(), config.clone(), res.clone()) {
+                Ok(client) => {
+                    let handle = clients.spawn(client.run_forever());
+                    running.insert(name, (server, handle));
+                }
+                Err(err) => log::error!("[{name}] Failed to start z2m client: {err}"),
+            }
+        }
+
+        /* drop the results of finished clients (restarted on the next change) */
+        while clients.try_join_next().is_some() {}
+
+        notify.notified().await;
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::hue::api::On;
+use crate::hue::api::{AlertEffect, On};
 use crate::model::types::XY;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -21,6 +21,9 @@ pub struct DeviceUpdate {
     pub color: Option<DeviceColor>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub linkquality: Option<u8>,
+    /* only present if enabled in z2m; format depends on the z2m config */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color_options: Option<ColorOptions>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -42,6 +45,12 @@ pub struct DeviceUpdate {
     pub battery: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transition: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<DeviceEffect>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motion_sensitivity: Option<String>,
 }
 
 impl DeviceUpdate {
@@ -50,6 +59,28 @@ impl DeviceUpdate {
         Self::default()
     }
 
+    /// Combine two updates for the same device into one. Fields set in
+    /// `newer` take precedence, and a color (or color temperature) in
+    /// `newer` replaces both color modes of `self`.
+    #[must_use]
+    pub fn merge(self, newer: Self) -> Self {
+        let mut base = self;
+        if newer.color.is_some() || newer.color_temp.is_some() {
+            base.color = None;
+            base.color_temp = None;
+            base.color_mode = None;
+        }
+
+        let (Ok(Value::Object(mut old)), Ok(Value::Object(new))) =
+            (serde_json::to_value(&base), serde_json::to_value(&newer))
+        else {
+            return newer;
+        };
+        old.extend(new);
+
+        serde_json::from_value(Value::Object(old)).unwrap_or(newer)
+    }
+
     #[must_use]
     pub fn with_state(self, state: Option<bool>) -> Self {
         Self {
@@ -81,6 +112,60 @@ impl DeviceUpdate {
             ..self
         }
     }
+
+    /// Set color from hue (in degrees) and saturation (in percent)
+    #[must_use]
+    pub fn with_color_hs(self, hs: Option<(f64, f64)>) -> Self {
+        Self {
+            color: hs.map(|(h, s)| DeviceColor::hs(h, s)),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_motion_sensitivity(self, motion_sensitivity: Option<String>) -> Self {
+        Self {
+            motion_sensitivity,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_effect(self, effect: Option<DeviceEffect>) -> Self {
+        Self { effect, ..self }
+    }
+
+    #[must_use]
+    pub fn with_transition(self, transition: Option<f64>) -> Self {
+        Self { transition, ..self }
+    }
+}
+
+/// State reported by sensors and switches. These report a lot of values we
+/// have no use for (battery, voltage, etc), so unknown fields are ignored.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SensorUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupancy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motion_sensitivity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub illuminance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub illuminance_lux: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+impl SensorUpdate {
+    /// Measured illuminance in lux, preferring `illuminance_lux` when both are
+    /// reported (older z2m versions use `illuminance` for the raw value)
+    #[must_use]
+    pub fn lux(&self) -> Option<f64> {
+        self.illuminance_lux.or(self.illuminance)
+    }
 }
 
 #[derive(Copy, Debug, Serialize, Deserialize, Clone)]
@@ -181,6 +266,25 @@ pub enum DeviceColorMode {
     Xy,
 }
 
+#[derive(Copy, Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceEffect {
+    Blink,
+    Breathe,
+    Okay,
+    ChannelChange,
+    FinishEffect,
+    StopEffect,
+}
+
+impl From<AlertEffect> for DeviceEffect {
+    fn from(value: AlertEffect) -> Self {
+        match value {
+            AlertEffect::Breathe => Self::Breathe,
+        }
+    }
+}
+
 #[derive(Copy, Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum DeviceState {